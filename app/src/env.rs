@@ -2,27 +2,29 @@ use std::{
     mem,
 };
 
-use geo::{
-    algorithm::{
-        rotate::{
-            RotatePoint,
-        },
-    },
-};
-
 use piston_window::{
     Viewport,
 };
 
 use common::{
+    geom,
     problem,
     solver,
+    svg,
 };
 
 use crate::{
     draw,
 };
 
+/// Annealing iterations done per frame, long cooling steps are spread across frames to keep the gui responsive.
+const SIMULATED_ANNEALING_ITERATIONS_PER_FRAME: usize = 2048;
+/// Cooling steps `Env::project_to_valid` spends on every seed looking for a valid pose.
+const PROJECT_TO_VALID_MAX_STEPS: usize = 200;
+/// Seeds `Env::project_to_valid` tries one after another until a valid pose is found, fixed so the same pose
+/// is always repaired the same way.
+const PROJECT_TO_VALID_SEEDS: [u64; 4] = [0, 1, 2, 3];
+
 pub struct Env {
     screen_width: u32,
     screen_height: u32,
@@ -38,10 +40,21 @@ pub struct Env {
     mouse_cursor: Option<[f64; 2]>,
     score_state: ScoringState,
     drag_state: DragState,
+    geo_hole_quad_tree: problem::geo_hole_quad_tree::GeoHoleQuadTree,
     allowed_angles: Vec<f64>,
+    angles_stale: bool,
     selected_angle: Option<f64>,
+    rotation_pivot: RotationPivot,
+    articulation_points: Vec<usize>,
+    /// vertices placed by hand which simulated annealing keeps in place
+    locked_vertices: Vec<usize>,
     solver_mode: SolverMode,
     bonus_highlight: Option<problem::ProblemId>,
+    /// indices of `problem.bonuses` written into the exported pose, toggled with `toggle_pose_bonus`
+    pose_bonuses: Vec<usize>,
+    hole_mask_overlay: Option<Vec<problem::Point>>,
+    /// use the same scale for both axes in `translator`, so shapes and angles are not distorted
+    lock_aspect: bool,
 }
 
 enum SolverMode {
@@ -51,6 +64,13 @@ enum SolverMode {
     },
 }
 
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum RotationPivot {
+    Centroid,
+    WantVertex,
+    Vertex { vertex_index: usize, },
+}
+
 #[derive(Debug)]
 enum DragState {
     WantVertex,
@@ -74,6 +94,9 @@ pub struct ViewportTranslator {
     scale_y: f64,
     min_x: f64,
     min_y: f64,
+    /// shift centering the content along an axis with spare room when aspect is locked
+    offset_x: f64,
+    offset_y: f64,
 }
 
 #[derive(Debug)]
@@ -87,6 +110,8 @@ pub enum SimulatedAnnealingSolverError {
     SolverCreate(solver::CreateError),
     SimulatedAnnealingSolverCreate(solver::simulated_annealing::CreateError),
     SolverStep(solver::simulated_annealing::StepError),
+    PinVertex(solver::simulated_annealing::PinVertexError),
+    GenerateVertices(solver::simulated_annealing::GenerateVerticesError),
 }
 
 #[derive(Debug)]
@@ -97,10 +122,22 @@ pub enum DrawError {
     InvalidEdgeTargetIndex { edge: problem::Edge, index: usize, },
 }
 
+/// Outcome of `Env::project_to_valid`, dislikes of an invalid pose are counted as if it was valid.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Projection {
+    AlreadyValid,
+    Projected { dislikes_before: i64, dislikes_after: i64, },
+    NotFound,
+}
+
+#[derive(Debug)]
+pub enum ExportError {
+    BonusNotOccupied(problem::ProblemBonus),
+}
+
 #[derive(Debug)]
 pub enum RotateError {
     GeoExport(problem::GeoExportError),
-    GeoImport(problem::GeoImportError),
     NoCentroidBuilt,
 }
 
@@ -111,6 +148,7 @@ enum ScoringState {
     VerticeCountMismatch,
     BrokenEdgesFound(Vec<problem::Edge>),
     EdgesNotFitHole(Vec<problem::Edge>),
+    UnsupportedBonus(problem::PoseBonus),
 }
 
 
@@ -182,6 +220,9 @@ impl Env {
         let max_x = if max_x_hole < max_x_figure { max_x_figure } else { max_x_hole } as f64;
         let max_y = if max_y_hole < max_y_figure { max_y_figure } else { max_y_hole } as f64;
 
+        let geo_hole_quad_tree = problem::geo_hole_quad_tree::GeoHoleQuadTree::new(problem.hole_polygon_f64());
+        let allowed_angles = problem.possible_rotations_with(&geo_hole_quad_tree);
+
         Ok(Env {
             screen_width,
             screen_height,
@@ -189,8 +230,13 @@ impl Env {
             border_width,
             original_pose: problem.export_pose(),
             initial_problem: problem.clone(),
-            allowed_angles: problem.possible_rotations(),
+            geo_hole_quad_tree,
+            allowed_angles,
+            angles_stale: false,
             selected_angle: None,
+            rotation_pivot: RotationPivot::Centroid,
+            articulation_points: problem.figure.articulation_points(),
+            locked_vertices: Vec::new(),
             problem,
             min_x: min_x - ((max_x - min_x) / 2.0),
             min_y: min_y - ((max_y - min_y) / 2.0),
@@ -201,9 +247,19 @@ impl Env {
             drag_state: DragState::WantVertex,
             solver_mode: SolverMode::None,
             bonus_highlight: None,
+            pose_bonuses: Vec::new(),
+            hole_mask_overlay: None,
+            lock_aspect: false,
         })
     }
 
+    pub fn with_lock_aspect(self, lock_aspect: bool) -> Env {
+        Env {
+            lock_aspect,
+            ..self
+        }
+    }
+
     pub fn translator(&self, viewport: &Option<Viewport>) -> Option<ViewportTranslator> {
         let (w, h) = viewport
             .map(|v| (v.draw_size[0], v.draw_size[1]))
@@ -212,13 +268,24 @@ impl Env {
         if (w <= 2 * self.border_width) || (h <= self.border_width + self.console_height) {
             None
         } else {
+            let area_width = (w - (self.border_width * 2)) as f64;
+            let area_height = (h - (self.border_width + self.console_height)) as f64;
+            let mut scale_x = area_width / (self.max_x - self.min_x);
+            let mut scale_y = area_height / (self.max_y - self.min_y);
+            if self.lock_aspect {
+                let scale = scale_x.min(scale_y);
+                scale_x = scale;
+                scale_y = scale;
+            }
             Some(ViewportTranslator {
                 console_height: self.console_height,
                 border_width: self.border_width,
-                scale_x: (w - (self.border_width * 2)) as f64 / (self.max_x - self.min_x),
-                scale_y: (h - (self.border_width + self.console_height)) as f64 / (self.max_y - self.min_y),
+                scale_x,
+                scale_y,
                 min_x: self.min_x,
                 min_y: self.min_y,
+                offset_x: (area_width - (self.max_x - self.min_x) * scale_x) / 2.0,
+                offset_y: (area_height - (self.max_y - self.min_y) * scale_y) / 2.0,
             })
         }
     }
@@ -227,11 +294,31 @@ impl Env {
         match &self.solver_mode {
             SolverMode::None =>
                 format!(
-                    "move: W/A/S/D, rotate: Z/X, next/prev angle: C/V, export pose: E, drag: {}, {}, sel.angle: {}, angles: {:?}",
+                    "move: W/A/S/D, rotate: Z/X, flip: T, next/prev angle: C/V, refresh angles: G, pivot: O ({}), hole mask: H, project to valid: K, lock vertex: L ({} locked), bonus in pose: 1/2/3, export pose: E, copy vertices: J, drag: {}, {}, eps: {}%, {}, sel.angle: {}, angles{}: {:?}",
+                    match self.rotation_pivot {
+                        RotationPivot::Centroid =>
+                            "centroid".to_string(),
+                        RotationPivot::WantVertex =>
+                            "choose pivot vertex".to_string(),
+                        RotationPivot::Vertex { vertex_index, } =>
+                            format!("vertex {}", vertex_index),
+                    },
+                    self.locked_vertices.len(),
                     match self.drag_state {
-                        DragState::WantVertex |
-                        DragState::WantVertexHighlight { .. } =>
+                        DragState::WantVertex =>
                             "choose vertex".to_string(),
+                        DragState::WantVertexHighlight { vertex_index, } =>
+                            format!(
+                                "choose vertex (vertex {}: degree {}{})",
+                                vertex_index,
+                                self.problem.figure.vertex_degrees()[vertex_index],
+                                if self.articulation_points.contains(&vertex_index) { ", articulation" } else { "" },
+                            ),
+                        DragState::WantTargetHighlight { vertex_index, candidate: AllowedMove::FoldVertex { target, }, .. } =>
+                            format!(
+                                "choose new vertex position or edge (M to reset), {}",
+                                self.candidate_text(vertex_index, target),
+                            ),
                         DragState::WantTarget { .. } |
                         DragState::WantTargetHighlight { .. } =>
                             "choose new vertex position or edge (M to reset)".to_string(),
@@ -239,17 +326,14 @@ impl Env {
                         DragState::WantEdgeTargetHighlight { .. } =>
                             "choose new edge position (M to reset)".to_string(),
                     },
-                    match &self.score_state {
-                        ScoringState::Unscored => "<unscored>".to_string(),
-                        ScoringState::Ok(score) => format!("score: {}", score),
-                        ScoringState::VerticeCountMismatch => "score err: vertice count mismatch".to_string(),
-                        ScoringState::BrokenEdgesFound(edges) => format!("score err: {} broken edges found", edges.len()),
-                        ScoringState::EdgesNotFitHole(edges) => format!("score err: {} edges does fit hole", edges.len()),
-                    },
+                    self.score_text(),
+                    self.problem.epsilon_percent(),
+                    self.bonus_text(),
                     match self.selected_angle {
                         None => "<n/a>".to_string(),
                         Some(a) => format!("{}", a),
                     },
+                    if self.angles_stale { " (stale)" } else { "" },
                     self.allowed_angles,
                 ),
             SolverMode::SimulatedAnnealing { solver, } =>
@@ -257,6 +341,85 @@ impl Env {
         }
     }
 
+    pub fn score_text(&self) -> String {
+        match &self.score_state {
+            ScoringState::Unscored => "<unscored>".to_string(),
+            ScoringState::Ok(score) =>
+                match self.initial_problem.worst_hole_vertex(&self.problem.figure.vertices) {
+                    Some((corner, dislikes)) if dislikes > 0 =>
+                        format!("score: {}, worst corner ({},{}): {}", score, corner.0, corner.1, dislikes),
+                    Some(..) | None =>
+                        format!("score: {}", score),
+                },
+            ScoringState::VerticeCountMismatch => "score err: vertice count mismatch".to_string(),
+            ScoringState::BrokenEdgesFound(edges) => format!("score err: {} broken edges found", edges.len()),
+            ScoringState::EdgesNotFitHole(edges) => format!("score err: {} edges does fit hole", edges.len()),
+            ScoringState::UnsupportedBonus(bonus) => format!("score err: unsupported bonus {:?}", bonus),
+        }
+    }
+
+    /// Prospective score if `vertex_index` is moved to `target`, with the difference to the current score if it is valid.
+    fn candidate_text(&self, vertex_index: usize, target: problem::Point) -> String {
+        match (self.preview_move_score(vertex_index, target), &self.score_state) {
+            (Ok(score), ScoringState::Ok(current_score)) =>
+                format!("candidate -> score {} ({:+})", score, score - current_score),
+            (Ok(score), _) =>
+                format!("candidate -> score {}", score),
+            (Err(problem::PoseValidationError::VerticeCountMismatch), _) =>
+                "candidate -> vertice count mismatch".to_string(),
+            (Err(problem::PoseValidationError::BrokenEdgesFound { broken_edges, .. }), _) =>
+                format!("candidate -> {} broken edges", broken_edges.len()),
+            (Err(problem::PoseValidationError::EdgesNotFitHole(edges)), _) =>
+                format!("candidate -> {} edges do not fit hole", edges.len()),
+            (Err(problem::PoseValidationError::UnsupportedBonus(bonus)), _) =>
+                format!("candidate -> unsupported bonus {:?}", bonus),
+        }
+    }
+
+    /// Scores the figure as if `vertex_index` had been moved to `target` without touching the figure itself.
+    fn preview_move_score(&self, vertex_index: usize, target: problem::Point) -> Result<i64, problem::PoseValidationError> {
+        let mut vertices = self.problem.figure.vertices.clone();
+        vertices[vertex_index] = target;
+        self.initial_problem.score_vertices(&vertices, None)
+    }
+
+    /// Bonuses offered by the problem, each marked as claimed if some figure vertex occupies its position
+    /// and as in pose if it is toggled to be written into the exported pose.
+    pub fn bonus_text(&self) -> String {
+        let bonuses = match self.problem.bonuses.as_ref() {
+            Some(bonuses) if !bonuses.is_empty() =>
+                bonuses,
+            _ =>
+                return "bonuses: none".to_string(),
+        };
+        let items: Vec<_> = bonuses
+            .iter()
+            .enumerate()
+            .map(|(bonus_index, bonus)| format!(
+                "{} for {} ({}{})",
+                bonus_type_name(bonus.bonus),
+                bonus.problem.0,
+                if bonus.is_claimed_by(&self.problem.figure.vertices) { "claimed" } else { "available" },
+                if self.pose_bonuses.contains(&bonus_index) { ", in pose" } else { "" },
+            ))
+            .collect();
+        format!("bonuses: {}", items.join(", "))
+    }
+
+    /// Process exit code for `--validate-and-exit`: 0 if the current pose has been scored successfully, 1 otherwise.
+    pub fn validation_exit_code(&self) -> i32 {
+        match self.score_state {
+            ScoringState::Ok(..) =>
+                0,
+            ScoringState::Unscored |
+            ScoringState::VerticeCountMismatch |
+            ScoringState::BrokenEdgesFound(..) |
+            ScoringState::EdgesNotFitHole(..) |
+            ScoringState::UnsupportedBonus(..) =>
+                1,
+        }
+    }
+
     pub fn draw<DF>(&mut self, tr: &ViewportTranslator, mut draw_element: DF) -> Result<(), DrawError> where DF: FnMut(draw::DrawElement) {
         let mut points_iter = self.problem.hole.iter();
         let mut prev_point = points_iter.next()
@@ -273,6 +436,16 @@ impl Env {
             prev_point = point;
         }
 
+        for point in self.hole_mask_overlay.iter().flatten() {
+            draw_element(draw::DrawElement::Ellipse {
+                color: [0.0, 0.0, 1.0, 0.25],
+                x: point.0 as f64,
+                y: point.1 as f64,
+                width: 2.0,
+                height: 2.0,
+            });
+        }
+
         match &self.solver_mode {
             SolverMode::None => {
                 for &edge in &self.problem.figure.edges {
@@ -282,13 +455,34 @@ impl Env {
                         .ok_or(DrawError::InvalidEdgeTargetIndex { edge, index: edge.1, })?;
                     draw_element(draw::DrawElement::Line {
                         color: [1., 1., 0., 1.,],
-                        radius: 0.5,
+                        radius: self.edge_radius(&edge, &self.problem.figure.vertices),
                         source_x: source_point.0 as f64,
                         source_y: source_point.1 as f64,
                         target_x: target_point.0 as f64,
                         target_y: target_point.1 as f64,
                     });
                 }
+                for &vertex_index in &self.articulation_points {
+                    let vertex = self.problem.figure.vertices[vertex_index];
+                    draw_element(draw::DrawElement::Ellipse {
+                        color: [0.0, 1.0, 1.0, 1.0],
+                        x: vertex.0 as f64,
+                        y: vertex.1 as f64,
+                        width: 10.0,
+                        height: 10.0,
+                    });
+                }
+                self.draw_locked_vertices(&self.problem.figure.vertices, &mut draw_element);
+                if let RotationPivot::Vertex { vertex_index, } = self.rotation_pivot {
+                    let pivot = self.problem.figure.vertices[vertex_index];
+                    draw_element(draw::DrawElement::Ellipse {
+                        color: [1.0, 0.0, 1.0, 1.0],
+                        x: pivot.0 as f64,
+                        y: pivot.1 as f64,
+                        width: 12.0,
+                        height: 12.0,
+                    });
+                }
             },
             SolverMode::SimulatedAnnealing { solver, } => {
                 let solver_vertices = solver.vertices();
@@ -299,7 +493,7 @@ impl Env {
                         .ok_or(DrawError::InvalidEdgeTargetIndex { edge, index: edge.1, })?;
                     draw_element(draw::DrawElement::Line {
                         color: [1., 1., 0., 1.,],
-                        radius: 0.5,
+                        radius: self.edge_radius(&edge, solver_vertices),
                         source_x: source_point.0 as f64,
                         source_y: source_point.1 as f64,
                         target_x: target_point.0 as f64,
@@ -320,7 +514,7 @@ impl Env {
                         height: 16.0,
                     });
                 }
-
+                self.draw_locked_vertices(solver_vertices, &mut draw_element);
             },
         }
 
@@ -336,16 +530,7 @@ impl Env {
                 draw_element(draw::DrawElement::Text {
                     color: [1.0, 1.0, 1.0, 1.0],
                     size: 16,
-                    text: match bonus.bonus {
-                        problem::ProblemBonusType::BreakALeg =>
-                            "break_a_leg".to_string(),
-                        problem::ProblemBonusType::Globalist =>
-                            "globalist".to_string(),
-                        problem::ProblemBonusType::Wallhack =>
-                            "wallhack".to_string(),
-                        problem::ProblemBonusType::Superflex =>
-                            "superflex".to_string(),
-                    },
+                    text: bonus_type_name(bonus.bonus).to_string(),
                     x: bonus.position.0 as f64,
                     y: bonus.position.1 as f64,
                 });
@@ -512,30 +697,159 @@ impl Env {
     )
         -> Result<(), SimulatedAnnealingSolverError>
     {
-        let solver = solver::simulated_annealing::SimulatedAnnealingSolver::new(
+        let mut solver = solver::simulated_annealing::SimulatedAnnealingSolver::new(
             solver::Solver::with_bonus(&self.problem, Some(self.problem.export_pose()), Some(problem::ProblemBonusType::Wallhack))
                 .map_err(SimulatedAnnealingSolverError::SolverCreate)?,
             solver::simulated_annealing::Params {
                 max_temp: 100.0,
                 cooling_step_temp: 0.1,
                 minimum_temp: 2.0,
-                valid_edge_accept_prob: 0.5,
+                below_floor: solver::simulated_annealing::FloorBehavior::Stop,
+                edge_bias: solver::simulated_annealing::EdgeBias::ProbSkip(0.5),
                 frozen_swap_prob: 0.15,
+                frozen_swap_bias: 0.0,
+                vertex_swap_prob: 0.0,
+                reachable_moves: false,
+                wallhack_allowed_outside: problem::WALLHACK_ALLOWED_OUTSIDE,
+                coarsen_factor: 1,
+                min_move_radius: 1,
+                max_move_radius: 1,
+                dislikes_hint_weight: 0.0,
                 iterations_per_cooling_step: 512,
                 operating_mode,
             },
         ).map_err(SimulatedAnnealingSolverError::SimulatedAnnealingSolverCreate)?;
+        for &vertex_index in &self.locked_vertices {
+            solver.pin_vertex(vertex_index, self.problem.figure.vertices[vertex_index])
+                .map_err(SimulatedAnnealingSolverError::PinVertex)?;
+        }
         self.solver_mode = SolverMode::SimulatedAnnealing { solver, };
         Ok(())
     }
 
+    /// Repairs a slightly invalid pose (e.g. an imported one) with simulated annealing moving only endpoints of broken edges,
+    /// locked vertices stay in place. The first valid pose found is taken, the pose is left unchanged if there is none
+    /// within `PROJECT_TO_VALID_MAX_STEPS` cooling steps for any of `PROJECT_TO_VALID_SEEDS`.
+    pub fn project_to_valid(&mut self) -> Result<Projection, SimulatedAnnealingSolverError> {
+        // `problem` figure is the current pose, so it is scored against the initial one
+        let vertices = self.problem.figure.vertices.clone();
+        if self.initial_problem.score_vertices(&vertices, None).is_ok() {
+            return Ok(Projection::AlreadyValid);
+        }
+
+        let mut solver = solver::simulated_annealing::SimulatedAnnealingSolver::new(
+            solver::Solver::new(&self.initial_problem, Some(self.problem.export_pose()))
+                .map_err(SimulatedAnnealingSolverError::SolverCreate)?,
+            solver::simulated_annealing::Params {
+                // almost greedy: the repaired pose should stay close to the current one
+                max_temp: 0.01,
+                cooling_step_temp: 0.0005,
+                minimum_temp: 0.0001,
+                below_floor: solver::simulated_annealing::FloorBehavior::Greedy,
+                edge_bias: solver::simulated_annealing::EdgeBias::Off,
+                frozen_swap_prob: 0.0,
+                frozen_swap_bias: 0.0,
+                vertex_swap_prob: 0.0,
+                reachable_moves: false,
+                wallhack_allowed_outside: problem::WALLHACK_ALLOWED_OUTSIDE,
+                coarsen_factor: 1,
+                min_move_radius: 1,
+                max_move_radius: 1,
+                dislikes_hint_weight: 0.0,
+                iterations_per_cooling_step: 512,
+                operating_mode: solver::simulated_annealing::OperatingMode::EdgeRepair,
+            },
+        ).map_err(SimulatedAnnealingSolverError::SimulatedAnnealingSolverCreate)?;
+        for &vertex_index in &self.locked_vertices {
+            solver.pin_vertex(vertex_index, vertices[vertex_index])
+                .map_err(SimulatedAnnealingSolverError::PinVertex)?;
+        }
+
+        for &seed in &PROJECT_TO_VALID_SEEDS {
+            // every attempt starts over from the current pose, pins are kept
+            solver = solver.with_seed(seed)
+                .map_err(SimulatedAnnealingSolverError::GenerateVertices)?;
+            for _ in 0 .. PROJECT_TO_VALID_MAX_STEPS {
+                solver.step()
+                    .map_err(SimulatedAnnealingSolverError::SolverStep)?;
+                if let solver::simulated_annealing::Fitness::FigureScored { score: dislikes_after, } = solver.fitness() {
+                    if self.initial_problem.score_vertices(solver.vertices(), None) == Ok(dislikes_after) {
+                        let dislikes_before = self.initial_problem.dislikes_assignment(&vertices)
+                            .iter()
+                            .map(|&(_, sq_distance)| sq_distance)
+                            .sum();
+                        self.problem.figure.vertices = solver.vertices().to_vec();
+                        self.rescore_solution();
+                        self.mark_angles_stale();
+                        return Ok(Projection::Projected { dislikes_before, dislikes_after, });
+                    }
+                }
+            }
+        }
+        Ok(Projection::NotFound)
+    }
+
+    /// Locks or unlocks the vertex under the mouse cursor: locked vertices stay in place when simulated annealing starts.
+    pub fn toggle_vertex_lock(&mut self) {
+        if let DragState::WantVertexHighlight { vertex_index, } = self.drag_state {
+            match self.locked_vertices.iter().position(|&locked| locked == vertex_index) {
+                Some(position) => {
+                    self.locked_vertices.remove(position);
+                },
+                None =>
+                    self.locked_vertices.push(vertex_index),
+            }
+        }
+    }
+
+    /// Adds or removes the problem bonus number `bonus_index` to the bonuses written by `export_solution`.
+    /// Break a leg is never added, as the pose would also need the edge to break.
+    pub fn toggle_pose_bonus(&mut self, bonus_index: usize) {
+        let bonus = match self.problem.bonuses.as_ref().and_then(|bonuses| bonuses.get(bonus_index)) {
+            Some(bonus) =>
+                *bonus,
+            None => {
+                log::warn!("no bonus #{} in the problem", bonus_index + 1);
+                return;
+            },
+        };
+        match self.pose_bonuses.iter().position(|&index| index == bonus_index) {
+            Some(position) => {
+                self.pose_bonuses.remove(position);
+            },
+            None if pose_bonus(&bonus).is_none() =>
+                log::warn!("bonus {:?} cannot be written into the pose", bonus),
+            None =>
+                self.pose_bonuses.push(bonus_index),
+        }
+    }
+
+    /// Figure edges get thinner as their stretching approaches the epsilon limit, so fragile ones stand out.
+    fn edge_radius(&self, edge: &problem::Edge, vertices: &[problem::Point]) -> f64 {
+        let (_is_valid, ratio) = solver::is_edge_ratio_valid(edge, vertices, &self.initial_problem);
+        svg::headroom_width(ratio, self.initial_problem.epsilon_fraction(), 0.15, 0.5)
+    }
+
+    fn draw_locked_vertices<DF>(&self, vertices: &[problem::Point], draw_element: &mut DF) where DF: FnMut(draw::DrawElement) {
+        for &vertex_index in &self.locked_vertices {
+            let vertex = vertices[vertex_index];
+            draw_element(draw::DrawElement::Ellipse {
+                color: [1.0, 0.5, 0.0, 1.0],
+                x: vertex.0 as f64,
+                y: vertex.1 as f64,
+                width: 8.0,
+                height: 8.0,
+            });
+        }
+    }
+
     pub fn step_solver_simulated_annealing(&mut self) -> Result<(), SimulatedAnnealingSolverError> {
         match &mut self.solver_mode {
             SolverMode::None =>
                 Ok(()),
             SolverMode::SimulatedAnnealing { solver, } => {
-                match solver.step() {
-                    Ok(()) =>
+                match solver.step_budget(SIMULATED_ANNEALING_ITERATIONS_PER_FRAME) {
+                    Ok(_progress) =>
                         (),
                     Err(solver::simulated_annealing::StepError::TempTooLow) =>
                         solver.reheat(0.33),
@@ -546,17 +860,9 @@ impl Env {
                     Err(solver::simulated_annealing::StepError::ProbablyInfiniteLoopInFrozenIndex) =>
                         log::error!("probably infinite loop in frozen index, stopping"),
                     Err(solver::simulated_annealing::StepError::GenerateVertices(
-                        solver::simulated_annealing::GenerateVerticesError::ProbablyInfiniteLoopInFrozenIndexInBonusCollector,
-                    )) =>
-                        log::error!("probably infinite loop in generate vertices for bonus collector, stopping"),
-                    Err(solver::simulated_annealing::StepError::GenerateVertices(
-                        solver::simulated_annealing::GenerateVerticesError::ProbablyInfiniteLoopInFrozenIndexInBonusHunter,
+                        solver::simulated_annealing::GenerateVerticesError::MoreFrozenTargetsThanVertices { targets, vertices, },
                     )) =>
-                        log::error!("probably infinite loop in generate vertices for bonus hunter, stopping"),
-                    Err(solver::simulated_annealing::StepError::GenerateVertices(
-                        solver::simulated_annealing::GenerateVerticesError::ProbablyInfiniteLoopInFrozenIndexInZeroHunter,
-                    )) =>
-                        log::error!("probably infinite loop in generate vertices for zero hunter, stopping"),
+                        log::error!("{} frozen targets for {} vertices in generate vertices, stopping", targets, vertices),
                 }
                 Ok(())
             },
@@ -581,6 +887,10 @@ impl Env {
                 match mem::replace(&mut self.drag_state, DragState::WantVertex) {
                     DragState::WantVertex =>
                         (),
+                    DragState::WantVertexHighlight { vertex_index, } if self.rotation_pivot == RotationPivot::WantVertex => {
+                        self.rotation_pivot = RotationPivot::Vertex { vertex_index, };
+                        self.mark_angles_stale();
+                    },
                     DragState::WantVertexHighlight { vertex_index, } => {
                         let mut allowed = Vec::new();
                         let connected_edges: Vec<_> = self.problem
@@ -617,8 +927,8 @@ impl Env {
                                     let try_sq_dist = (try_vertex.0 - other_vertex.0) * (try_vertex.0 - other_vertex.0)
                                         + (try_vertex.1 - other_vertex.1) * (try_vertex.1 - other_vertex.1);
 
-                                    let ratio = ((try_sq_dist as f64 / orig_sq_dist as f64) - 1.0).abs();
-                                    if ratio > self.problem.epsilon as f64 / 1000000.0 {
+                                    let ratio = problem::stretch_ratio(orig_sq_dist, try_sq_dist);
+                                    if ratio > self.problem.epsilon_fraction() {
                                         is_ok = false;
                                         break;
                                     }
@@ -630,14 +940,14 @@ impl Env {
                         }
                         self.drag_state = DragState::WantTarget { vertex_index, allowed, };
                         self.rescore_solution();
-                        self.update_angles();
+                        self.mark_angles_stale();
                     },
                     DragState::WantTarget { vertex_index, allowed, } =>
                         self.drag_state = DragState::WantTarget { vertex_index, allowed, },
                     DragState::WantTargetHighlight { vertex_index, candidate: AllowedMove::FoldVertex { target, }, .. } => {
                         self.problem.figure.vertices[vertex_index] = target;
                         self.rescore_solution();
-                        self.update_angles();
+                        self.mark_angles_stale();
                     },
                     DragState::WantTargetHighlight { vertex_index, candidate: AllowedMove::ChooseEdge { other_index, }, .. } => {
                         let mut allowed = Vec::new();
@@ -701,8 +1011,8 @@ impl Env {
                                         + (sample_vertex_a.1 - sample_vertex_b.1) * (sample_vertex_a.1 - sample_vertex_b.1);
                                     let try_sq_dist = (px - qx) * (px - qx) + (py - qy) * (py - qy);
 
-                                    let ratio = ((try_sq_dist as f64 / orig_sq_dist as f64) - 1.0).abs();
-                                    if ratio > self.problem.epsilon as f64 / 1000000.0 {
+                                    let ratio = problem::stretch_ratio(orig_sq_dist, try_sq_dist);
+                                    if ratio > self.problem.epsilon_fraction() {
                                         is_ok = false;
                                         break;
                                     }
@@ -724,26 +1034,40 @@ impl Env {
                         self.problem.figure.vertices[edge.0] = p;
                         self.problem.figure.vertices[edge.1] = q;
                         self.rescore_solution();
-                        self.update_angles();
+                        self.mark_angles_stale();
                     },
                 },
             SolverMode::SimulatedAnnealing { .. } =>
                 if let Some(problem_id) = self.bonus_highlight {
                     if let Ok(solver) = solver::Solver::new(&self.problem, Some(self.problem.export_pose())) {
-                        if let Ok(solver) = solver::simulated_annealing::SimulatedAnnealingSolver::new(
+                        if let Ok(mut solver) = solver::simulated_annealing::SimulatedAnnealingSolver::new(
                             solver,
                             solver::simulated_annealing::Params {
                                 max_temp: 100.0,
                                 cooling_step_temp: 1.0,
                                 minimum_temp: 2.0,
-                                valid_edge_accept_prob: 0.5,
+                                below_floor: solver::simulated_annealing::FloorBehavior::Stop,
+                                edge_bias: solver::simulated_annealing::EdgeBias::ProbSkip(0.5),
                                 frozen_swap_prob: 0.15,
+                                frozen_swap_bias: 0.0,
+                                vertex_swap_prob: 0.0,
+                                reachable_moves: false,
+                                wallhack_allowed_outside: problem::WALLHACK_ALLOWED_OUTSIDE,
+                                coarsen_factor: 1,
+                                min_move_radius: 1,
+                                max_move_radius: 1,
+                                dislikes_hint_weight: 0.0,
                                 iterations_per_cooling_step: 10000,
                                 operating_mode: solver::simulated_annealing::OperatingMode::BonusCollector {
                                     target_problem: problem_id,
                                 },
                             },
                         ) {
+                            for &vertex_index in &self.locked_vertices {
+                                if let Err(error) = solver.pin_vertex(vertex_index, self.problem.figure.vertices[vertex_index]) {
+                                    log::warn!("failed to pin locked vertex {}: {:?}", vertex_index, error);
+                                }
+                            }
                             self.solver_mode = SolverMode::SimulatedAnnealing { solver, };
                         }
                     }
@@ -756,122 +1080,186 @@ impl Env {
     }
 
     pub fn move_figure_left(&mut self) {
-        for point in &self.problem.figure.vertices {
-            if point.0 - 1 < self.min_x as i64  {
-                return;
-            }
-        }
-        for point in &mut self.problem.figure.vertices {
-            point.0 -= 1;
-        }
-
-        self.rescore_solution();
-        self.update_angles();
+        self.transform_figure(&geom::Transform::translate(-1.0, 0.0));
     }
 
     pub fn move_figure_right(&mut self) {
-        for point in &self.problem.figure.vertices {
-            if point.0 + 1 > self.max_x as i64 {
-                return;
-            }
-        }
-        for point in &mut self.problem.figure.vertices {
-            point.0 += 1;
-        }
-
-        self.rescore_solution();
-        self.update_angles();
+        self.transform_figure(&geom::Transform::translate(1.0, 0.0));
     }
 
     pub fn move_figure_upper(&mut self) {
-        for point in &self.problem.figure.vertices {
-            if point.1 - 1 < self.min_x as i64 {
-                return;
-            }
-        }
-        for point in &mut self.problem.figure.vertices {
-            point.1 -= 1;
-        }
-
-        self.rescore_solution();
-        self.update_angles();
+        self.transform_figure(&geom::Transform::translate(0.0, -1.0));
     }
 
     pub fn move_figure_lower(&mut self) {
-        for point in &self.problem.figure.vertices {
-            if point.1 + 1 > self.max_y as i64 {
-                return;
-            }
-        }
-        for point in &mut self.problem.figure.vertices {
-            point.1 += 1;
-        }
+        self.transform_figure(&geom::Transform::translate(0.0, 1.0));
+    }
 
-        self.rescore_solution();
-        self.update_angles();
+    /// Mirrors the figure over the vertical line through the current pivot.
+    pub fn flip_figure(&mut self) -> Result<(), RotateError> {
+        let pivot = self.pivot_point()?;
+        self.transform_figure(&geom::Transform::mirror_around(pivot));
+        Ok(())
     }
 
-    pub fn rotate_figure_left(&mut self) -> Result<(), RotateError> {
-        if self.selected_angle.is_none() {
-            return Ok(());
+    /// Applies `transform` to the figure unless it leaves the field.
+    fn transform_figure(&mut self, transform: &geom::Transform) {
+        if let Some(vertices) = self.transformed_vertices(transform) {
+            self.problem.figure.vertices = vertices;
+            self.rescore_solution();
+            self.mark_angles_stale();
         }
+    }
 
-        let geo_figure = self.problem.figure.export_to_geo()
-            .map_err(RotateError::GeoExport)?;
-
-        let rotated_points: Vec<_> = geo_figure
-            .points
-            .iter()
-            .map(|p| p.rotate_around_point(-self.selected_angle.unwrap(), geo_figure.centroid))
-            .collect();
-
-        for point in &rotated_points {
-            if point.x() < self.min_x || point.x() > self.max_x || point.y() < self.min_y || point.y() > self.max_y {
-                return Ok(());
-            }
+    /// Figure vertices moved by `transform`, `None` if the result leaves the field.
+    fn transformed_vertices(&self, transform: &geom::Transform) -> Option<Vec<problem::Point>> {
+        let vertices = transform.apply(&self.problem.figure.vertices);
+        let inside_field = vertices.iter()
+            .all(|point| {
+                let (x, y) = (point.0 as f64, point.1 as f64);
+                x >= self.min_x && x <= self.max_x && y >= self.min_y && y <= self.max_y
+            });
+        if inside_field {
+            Some(vertices)
+        } else {
+            None
         }
+    }
 
-        self.problem.figure.import_from_geo(rotated_points)
-            .map_err(RotateError::GeoImport)?;
+    /// Point the figure is rotated and flipped about: the pivot vertex or the figure centroid.
+    fn pivot_point(&self) -> Result<(f64, f64), RotateError> {
+        match self.rotation_pivot {
+            RotationPivot::Centroid |
+            RotationPivot::WantVertex => {
+                let geo_figure = self.problem.figure.export_to_geo()
+                    .map_err(RotateError::GeoExport)?;
+                Ok((geo_figure.centroid.x(), geo_figure.centroid.y()))
+            },
+            RotationPivot::Vertex { vertex_index, } => {
+                let vertex = self.problem.figure.vertices[vertex_index];
+                Ok((vertex.0 as f64, vertex.1 as f64))
+            },
+        }
+    }
 
-        self.rescore_solution();
-        self.update_angles();
-        self.select_next_angle();
-        Ok(())
+    pub fn rotate_figure_left(&mut self) -> Result<(), RotateError> {
+        match self.selected_angle {
+            None =>
+                Ok(()),
+            Some(angle) =>
+                self.rotate_figure(-angle),
+        }
     }
 
     pub fn rotate_figure_right(&mut self) -> Result<(), RotateError> {
-        if self.selected_angle.is_none() {
-            return Ok(());
+        match self.selected_angle {
+            None =>
+                Ok(()),
+            Some(angle) =>
+                self.rotate_figure(angle),
         }
+    }
 
-        let geo_figure = self.problem.figure.export_to_geo()
-            .map_err(RotateError::GeoExport)?;
+    /// Rotates by the nearest angle which does not break edges after rounding (see `Problem::nearest_valid_rounded_angle`),
+    /// or by `angle` as is if there is none, e.g. for an already broken figure.
+    fn rotate_figure(&mut self, angle: f64) -> Result<(), RotateError> {
+        let pivot = self.pivot_point()?;
+        let angle = match self.initial_problem.nearest_valid_rounded_angle(&self.problem.figure.vertices, pivot, angle) {
+            Some(snapped_angle) => {
+                if snapped_angle != angle {
+                    log::info!("rotation by {} breaks edges after rounding, snapped to {:.1}", angle, snapped_angle);
+                }
+                snapped_angle
+            },
+            None => {
+                log::warn!("no rotation near {} keeps edges valid after rounding", angle);
+                angle
+            },
+        };
+        self.transform_figure(&geom::Transform::rotate_around(angle, pivot));
+        Ok(())
+    }
 
-        let rotated_points: Vec<_> = geo_figure
-            .points
-            .iter()
-            .map(|p| p.rotate_around_point(self.selected_angle.unwrap(), geo_figure.centroid))
-            .collect();
+    /// Figure vertices rotated by `angle` about the current pivot, `None` if the result leaves the field.
+    fn rotated_vertices(&self, angle: f64) -> Result<Option<Vec<problem::Point>>, RotateError> {
+        let pivot = self.pivot_point()?;
+        Ok(self.transformed_vertices(&geom::Transform::rotate_around(angle, pivot)))
+    }
+
+    /// Tries every allowed angle and applies the rotation giving a valid pose with the least dislikes,
+    /// returns the chosen angle with its score or `None` if no rotation gives a valid pose.
+    pub fn rotate_to_best_fit(&mut self) -> Result<Option<(f64, i64)>, RotateError> {
+        if self.angles_stale {
+            self.update_angles();
+        }
 
-        for point in &rotated_points {
-            if point.x() < self.min_x || point.x() > self.max_x || point.y() < self.min_y || point.y() > self.max_y {
-                return Ok(());
+        let mut best: Option<(f64, i64, Vec<problem::Point>)> = None;
+        for &angle in &self.allowed_angles {
+            let vertices = match self.rotated_vertices(angle)? {
+                Some(vertices) =>
+                    vertices,
+                None =>
+                    continue,
+            };
+            let score = match self.initial_problem.score_vertices_in(&self.geo_hole_quad_tree, &vertices, None) {
+                Ok(score) =>
+                    score,
+                Err(..) =>
+                    continue,
+            };
+            match best {
+                Some((_, best_score, _)) if best_score <= score =>
+                    (),
+                _ =>
+                    best = Some((angle, score, vertices)),
             }
         }
 
-        self.problem.figure.import_from_geo(rotated_points)
-            .map_err(RotateError::GeoImport)?;
-        self.rescore_solution();
-        self.update_angles();
-        self.select_next_angle();
-        Ok(())
+        match best {
+            Some((angle, score, vertices)) => {
+                log::info!("best fit rotation by {} degrees, score: {}", angle, score);
+                self.problem.figure.vertices = vertices;
+                self.rescore_solution();
+                self.mark_angles_stale();
+                Ok(Some((angle, score)))
+            },
+            None => {
+                log::info!("no rotation out of {} allowed angles gives a valid pose", self.allowed_angles.len());
+                Ok(None)
+            },
+        }
+    }
+
+    /// Switches between rotation about the centroid and rotation about a vertex chosen with the next click.
+    pub fn toggle_rotation_pivot(&mut self) {
+        self.rotation_pivot = match self.rotation_pivot {
+            RotationPivot::Centroid =>
+                RotationPivot::WantVertex,
+            RotationPivot::WantVertex |
+            RotationPivot::Vertex { .. } =>
+                RotationPivot::Centroid,
+        };
+        self.mark_angles_stale();
+    }
+
+    /// Shows or hides the rasterized hole mask used by solvers as faint dots on in-hole cells.
+    pub fn toggle_hole_mask(&mut self) {
+        if self.hole_mask_overlay.take().is_some() {
+            return;
+        }
+        match solver::Solver::new(&self.initial_problem, None) {
+            Ok(solver) =>
+                self.hole_mask_overlay = Some(solver.in_hole_points().collect()),
+            Err(error) =>
+                log::warn!("failed to rasterize hole mask: {:?}", error),
+        }
     }
 
     pub fn import_solution(&mut self, pose: problem::Pose) {
         self.problem = self.initial_problem.clone();
         let score = self.problem.import_pose(pose);
         self.update_score_state(score);
+        self.mark_angles_stale();
     }
 
     pub fn rescore_solution(&mut self) {
@@ -880,8 +1268,26 @@ impl Env {
     }
 
     pub fn update_angles(&mut self) {
-        self.allowed_angles = self.initial_problem.possible_rotations_for_vertices(&self.problem.figure.vertices);
-        log::debug!("possible rotations around centroid: {:?}", self.allowed_angles);
+        match self.rotation_pivot {
+            RotationPivot::Centroid |
+            RotationPivot::WantVertex => {
+                self.allowed_angles = self.initial_problem
+                    .possible_rotations_for_vertices_with(&self.problem.figure.vertices, &self.geo_hole_quad_tree);
+                log::debug!("possible rotations around centroid: {:?}", self.allowed_angles);
+            },
+            RotationPivot::Vertex { vertex_index, } => {
+                let pivot = self.problem.figure.vertices[vertex_index];
+                self.allowed_angles = self.initial_problem
+                    .possible_rotations_around_point_for_vertices_with(&self.problem.figure.vertices, &pivot, &self.geo_hole_quad_tree);
+                log::debug!("possible rotations around vertex {}: {:?}", vertex_index, self.allowed_angles);
+            },
+        }
+        self.angles_stale = false;
+    }
+
+    /// Figure has been changed: `allowed_angles` are kept as is until `update_angles` is requested explicitly.
+    pub fn mark_angles_stale(&mut self) {
+        self.angles_stale = true;
     }
 
     pub fn select_next_angle(&mut self) {
@@ -958,11 +1364,16 @@ impl Env {
                 log::debug!(" ;; pose load failure, edges not fitting hole found");
                 self.score_state = ScoringState::EdgesNotFitHole(edges);
             },
+            Err(problem::PoseValidationError::UnsupportedBonus(bonus)) => {
+                log::debug!(" ;; pose load failure, unsupported bonus: {:?}", bonus);
+                self.score_state = ScoringState::UnsupportedBonus(bonus);
+            },
         }
     }
 
-    pub fn export_solution(&self) -> problem::Pose {
-        match &self.solver_mode {
+    /// Current pose with the bonuses toggled by `toggle_pose_bonus`, each of them has to be occupied by some pose vertex.
+    pub fn export_solution(&self) -> Result<problem::Pose, ExportError> {
+        let mut pose = match &self.solver_mode {
             SolverMode::None =>
                 self.problem.export_pose(),
             SolverMode::SimulatedAnnealing { solver, } => {
@@ -973,21 +1384,44 @@ impl Env {
                 assert!(self.problem.score_vertices(&pose.vertices, None).is_ok());
                 pose
             },
+        };
+
+        let mut bonuses = Vec::new();
+        for &bonus_index in &self.pose_bonuses {
+            let bonus = self.problem.bonuses.as_ref().unwrap()[bonus_index];
+            if !bonus.is_claimed_by(&pose.vertices) {
+                return Err(ExportError::BonusNotOccupied(bonus));
+            }
+            bonuses.extend(pose_bonus(&bonus));
+        }
+        if !bonuses.is_empty() {
+            pose.bonuses = Some(bonuses);
         }
+        Ok(pose)
+    }
+
+    /// Current figure vertices as a json array of `[x, y]` pairs, the same as `vertices` field of a pose file.
+    pub fn vertices_json(&self) -> String {
+        let points: Vec<_> = self.problem.figure.vertices
+            .iter()
+            .map(|point| format!("[{},{}]", point.0, point.1))
+            .collect();
+        format!("[{}]", points.join(","))
     }
 
     pub fn figure_reset(&mut self) {
         self.problem.figure.vertices = self.original_pose.vertices.clone();
+        self.mark_angles_stale();
     }
 }
 
 impl ViewportTranslator {
     pub fn x(&self, x: f64) -> f64 {
-        (x - self.min_x) * self.scale_x + self.border_width as f64
+        (x - self.min_x) * self.scale_x + self.border_width as f64 + self.offset_x
     }
 
     pub fn y(&self, y: f64) -> f64 {
-        (y - self.min_y) * self.scale_y + self.console_height as f64
+        (y - self.min_y) * self.scale_y + self.console_height as f64 + self.offset_y
     }
 
     // pub fn back_x(&self, viewport_x: f64) -> f64 {
@@ -1004,3 +1438,325 @@ impl ViewportTranslator {
 //         (point_a.1 - point_b.1) * (point_a.1 - point_b.1);
 //     (sq as f64).sqrt() as i64
 // }
+
+/// Pose bonus entry for the problem bonus, `None` for break a leg which needs an edge as well.
+fn pose_bonus(bonus: &problem::ProblemBonus) -> Option<problem::PoseBonus> {
+    match bonus.bonus {
+        problem::ProblemBonusType::BreakALeg =>
+            None,
+        problem::ProblemBonusType::Globalist =>
+            Some(problem::PoseBonus::Globalist { problem: bonus.problem, }),
+        problem::ProblemBonusType::Wallhack =>
+            Some(problem::PoseBonus::Wallhack { problem: bonus.problem, }),
+        problem::ProblemBonusType::Superflex =>
+            Some(problem::PoseBonus::Superflex { problem: bonus.problem, }),
+    }
+}
+
+fn bonus_type_name(bonus_type: problem::ProblemBonusType) -> &'static str {
+    match bonus_type {
+        problem::ProblemBonusType::BreakALeg =>
+            "break_a_leg",
+        problem::ProblemBonusType::Globalist =>
+            "globalist",
+        problem::ProblemBonusType::Wallhack =>
+            "wallhack",
+        problem::ProblemBonusType::Superflex =>
+            "superflex",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn make_env() -> Env {
+        let problem = problem::Problem {
+            hole: vec![problem::Point(20, 0), problem::Point(40, 20), problem::Point(20, 40), problem::Point(0, 20)],
+            figure: problem::Figure {
+                edges: vec![problem::Edge(0, 1), problem::Edge(0, 2), problem::Edge(1, 3), problem::Edge(2, 3)],
+                vertices: vec![problem::Point(15, 21), problem::Point(34, 0), problem::Point(0, 45), problem::Point(19, 24)],
+            },
+            epsilon: 2494,
+            bonuses: None,
+        };
+        Env::new(problem, 640, 320, 32, 16).unwrap()
+    }
+
+    #[test]
+    fn select_next_angle_cycles_allowed_angles() {
+        let mut env = make_env();
+        env.allowed_angles = vec![90.0, 180.0, 270.0];
+        env.selected_angle = None;
+
+        env.select_next_angle();
+        assert_eq!(env.selected_angle, Some(90.0));
+        env.select_next_angle();
+        assert_eq!(env.selected_angle, Some(180.0));
+        env.select_next_angle();
+        assert_eq!(env.selected_angle, Some(270.0));
+        env.select_next_angle();
+        assert_eq!(env.selected_angle, Some(90.0));
+
+        env.select_prev_angle();
+        assert_eq!(env.selected_angle, Some(270.0));
+    }
+
+    #[test]
+    fn preview_move_score_keeps_figure() {
+        let mut env = make_env();
+        env.import_solution(problem::Pose {
+            vertices: vec![problem::Point(20, 0), problem::Point(40, 20), problem::Point(0, 20), problem::Point(20, 40)],
+            bonuses: None,
+        });
+        let vertices = env.problem.figure.vertices.clone();
+
+        assert_eq!(env.preview_move_score(3, problem::Point(20, 40)), Ok(0));
+        assert!(env.preview_move_score(3, problem::Point(20, 30)).is_err());
+        assert_eq!(env.candidate_text(3, problem::Point(20, 40)), "candidate -> score 0 (+0)");
+        assert_eq!(env.problem.figure.vertices, vertices);
+    }
+
+    #[test]
+    fn validation_exit_code() {
+        let mut env = make_env();
+        assert_eq!(env.validation_exit_code(), 1);
+
+        env.import_solution(problem::Pose {
+            vertices: vec![problem::Point(20, 0), problem::Point(40, 20), problem::Point(0, 20), problem::Point(20, 40)],
+            bonuses: None,
+        });
+        assert_eq!(env.validation_exit_code(), 0);
+
+        env.import_solution(problem::Pose {
+            vertices: vec![problem::Point(15, 21), problem::Point(34, 0), problem::Point(0, 45), problem::Point(19, 24)],
+            bonuses: None,
+        });
+        assert_eq!(env.validation_exit_code(), 1);
+    }
+
+    #[test]
+    fn bonus_text_marks_claimed() {
+        let mut env = make_env();
+        assert_eq!(env.bonus_text(), "bonuses: none");
+
+        env.problem.bonuses = Some(vec![
+            problem::ProblemBonus {
+                position: problem::Point(34, 0),
+                bonus: problem::ProblemBonusType::Globalist,
+                problem: problem::ProblemId(12),
+            },
+            problem::ProblemBonus {
+                position: problem::Point(30, 30),
+                bonus: problem::ProblemBonusType::Wallhack,
+                problem: problem::ProblemId(5),
+            },
+        ]);
+        assert_eq!(env.bonus_text(), "bonuses: globalist for 12 (claimed), wallhack for 5 (available)");
+    }
+
+    #[test]
+    fn export_with_pose_bonuses() {
+        let mut env = make_env();
+        env.problem.bonuses = Some(vec![
+            problem::ProblemBonus {
+                position: problem::Point(34, 0),
+                bonus: problem::ProblemBonusType::Globalist,
+                problem: problem::ProblemId(12),
+            },
+            problem::ProblemBonus {
+                position: problem::Point(30, 30),
+                bonus: problem::ProblemBonusType::Wallhack,
+                problem: problem::ProblemId(5),
+            },
+        ]);
+        assert_eq!(env.export_solution().unwrap().bonuses, None);
+
+        env.toggle_pose_bonus(0);
+        assert_eq!(env.bonus_text(), "bonuses: globalist for 12 (claimed, in pose), wallhack for 5 (available)");
+        assert_eq!(
+            env.export_solution().unwrap().bonuses,
+            Some(vec![problem::PoseBonus::Globalist { problem: problem::ProblemId(12), }]),
+        );
+
+        // the second bonus position is not occupied
+        env.toggle_pose_bonus(1);
+        assert!(matches!(env.export_solution(), Err(ExportError::BonusNotOccupied(bonus)) if bonus.problem == problem::ProblemId(5)));
+
+        env.toggle_pose_bonus(1);
+        env.toggle_pose_bonus(0);
+        env.toggle_pose_bonus(7);
+        assert_eq!(env.export_solution().unwrap().bonuses, None);
+    }
+
+    #[test]
+    fn lock_aspect_keeps_squares() {
+        let side = |tr: &ViewportTranslator| (tr.x(1.0) - tr.x(0.0), tr.y(1.0) - tr.y(0.0));
+
+        let env = make_env();
+        let tr = env.translator(&None).unwrap();
+        let (width, height) = side(&tr);
+        assert!((width - height).abs() > 1.0, "unit square is {} x {} without lock", width, height);
+        assert_eq!((tr.offset_x, tr.offset_y), (0.0, 0.0));
+
+        let env = make_env().with_lock_aspect(true);
+        let tr = env.translator(&None).unwrap();
+        let (width, height) = side(&tr);
+        assert!((width - height).abs() < 1e-9, "unit square is {} x {} with lock", width, height);
+        // the content is centered within the drawing area
+        let left = tr.x(env.min_x) - env.border_width as f64;
+        let right = env.screen_width as f64 - env.border_width as f64 - tr.x(env.max_x);
+        let top = tr.y(env.min_y) - env.console_height as f64;
+        let bottom = env.screen_height as f64 - env.border_width as f64 - tr.y(env.max_y);
+        assert!((left - right).abs() < 1e-9 && (top - bottom).abs() < 1e-9);
+        assert!(left.min(top).abs() < 1e-9 && left >= 0.0 && top >= 0.0);
+    }
+
+    #[test]
+    fn hole_mask_overlay_matches_mask() {
+        let mut env = make_env();
+        let tr = env.translator(&None).unwrap();
+        let count_dots = |env: &mut Env| {
+            let mut dots = 0;
+            env.draw(&tr, |element| if let draw::DrawElement::Ellipse { color: [0.0, 0.0, 1.0, 0.25], .. } = element { dots += 1; })
+                .unwrap();
+            dots
+        };
+        assert_eq!(count_dots(&mut env), 0);
+
+        env.toggle_hole_mask();
+        let solver = solver::Solver::new(&env.initial_problem, None).unwrap();
+        assert_eq!(count_dots(&mut env), solver.in_hole_points().count());
+
+        env.toggle_hole_mask();
+        assert_eq!(count_dots(&mut env), 0);
+    }
+
+    #[test]
+    fn vertices_json() {
+        let env = make_env();
+        assert_eq!(env.vertices_json(), "[[15,21],[34,0],[0,45],[19,24]]");
+    }
+
+    #[test]
+    fn move_marks_angles_stale() {
+        let mut env = make_env();
+        env.allowed_angles = vec![90.0];
+        assert!(!env.angles_stale);
+
+        env.move_figure_right();
+        assert!(env.angles_stale);
+        assert_eq!(env.allowed_angles, vec![90.0]);
+
+        env.update_angles();
+        assert!(!env.angles_stale);
+        assert_eq!(env.allowed_angles, env.initial_problem.possible_rotations_for_vertices(&env.problem.figure.vertices));
+    }
+
+    #[test]
+    fn locked_vertices_stay_in_place() {
+        let mut env = make_env();
+        for &vertex_index in &[0, 3, 1] {
+            env.drag_state = DragState::WantVertexHighlight { vertex_index, };
+            env.toggle_vertex_lock();
+        }
+        // second toggle unlocks
+        env.toggle_vertex_lock();
+        assert_eq!(env.locked_vertices, vec![0, 3]);
+
+        env.enter_solver_simulated_annealing(solver::simulated_annealing::OperatingMode::ScoreMaximizer).unwrap();
+        for _ in 0 .. 5 {
+            env.step_solver_simulated_annealing().unwrap();
+            let solver_vertices = match &env.solver_mode {
+                SolverMode::SimulatedAnnealing { solver, } =>
+                    solver.vertices().to_vec(),
+                SolverMode::None =>
+                    panic!("solver is not running"),
+            };
+            assert_eq!(solver_vertices[0], env.problem.figure.vertices[0]);
+            assert_eq!(solver_vertices[3], env.problem.figure.vertices[3]);
+        }
+    }
+
+    #[test]
+    fn rotate_to_best_fit() {
+        let problem = problem::Problem {
+            hole: vec![problem::Point(0, 0), problem::Point(30, 0), problem::Point(30, 30), problem::Point(0, 30)],
+            figure: problem::Figure {
+                edges: vec![problem::Edge(0, 1), problem::Edge(1, 2), problem::Edge(2, 0)],
+                vertices: vec![problem::Point(5, 5), problem::Point(15, 5), problem::Point(5, 15)],
+            },
+            epsilon: 20000,
+            bonuses: None,
+        };
+        let mut env = Env::new(problem, 640, 320, 32, 16).unwrap();
+        env.allowed_angles = vec![30.0, 90.0, 180.0, 270.0];
+        env.angles_stale = false;
+        let candidates: Vec<_> = env.allowed_angles
+            .iter()
+            .flat_map(|&angle| env.rotated_vertices(angle).unwrap().map(|vertices| (angle, vertices)))
+            .flat_map(|(angle, vertices)| env.initial_problem.score_vertices(&vertices, None).ok().map(|score| (angle, score)))
+            .collect();
+        assert!(candidates.len() > 1);
+        let best_score = candidates.iter().map(|&(_, score)| score).min().unwrap();
+
+        let (angle, score) = env.rotate_to_best_fit().unwrap().unwrap();
+        assert_eq!(score, best_score);
+        assert!(candidates.contains(&(angle, score)));
+        assert!(matches!(env.score_state, ScoringState::Ok(state_score) if state_score == score));
+        assert!(env.angles_stale);
+    }
+
+    #[test]
+    fn rotate_about_pivot_keeps_pivot_fixed() {
+        let mut env = make_env();
+        env.rotation_pivot = RotationPivot::Vertex { vertex_index: 3, };
+        env.selected_angle = Some(90.0);
+        let vertices = env.problem.figure.vertices.clone();
+
+        env.rotate_figure_right().unwrap();
+        assert_eq!(env.problem.figure.vertices[3], vertices[3]);
+        assert_eq!(env.problem.figure.vertices[0], problem::Point(22, 20));
+
+        env.rotate_figure_left().unwrap();
+        assert_eq!(env.problem.figure.vertices, vertices);
+    }
+
+    #[test]
+    fn project_marginally_broken_pose() {
+        let mut env = make_env();
+        env.import_solution(problem::Pose {
+            vertices: vec![problem::Point(20, 0), problem::Point(40, 20), problem::Point(0, 20), problem::Point(20, 40)],
+            bonuses: None,
+        });
+        assert_eq!(env.project_to_valid().unwrap(), Projection::AlreadyValid);
+
+        // one vertex a cell off breaks its edges
+        env.import_solution(problem::Pose {
+            vertices: vec![problem::Point(20, 0), problem::Point(40, 20), problem::Point(0, 20), problem::Point(20, 37)],
+            bonuses: None,
+        });
+        assert_eq!(env.validation_exit_code(), 1);
+        match env.project_to_valid().unwrap() {
+            Projection::Projected { dislikes_after, .. } =>
+                assert_eq!(env.initial_problem.score_vertices(&env.problem.figure.vertices, None), Ok(dislikes_after)),
+            projection =>
+                panic!("unexpected projection {:?}", projection),
+        }
+        assert_eq!(env.validation_exit_code(), 0);
+    }
+
+    #[test]
+    fn flip_about_pivot_twice_restores_figure() {
+        let mut env = make_env();
+        env.rotation_pivot = RotationPivot::Vertex { vertex_index: 3, };
+        let vertices = env.problem.figure.vertices.clone();
+
+        env.flip_figure().unwrap();
+        assert_eq!(env.problem.figure.vertices[3], vertices[3]);
+        assert!(env.problem.figure.vertices.iter().zip(&vertices).all(|(flipped, vertex)| flipped.1 == vertex.1 && flipped.0 + vertex.0 == 2 * vertices[3].0));
+
+        env.flip_figure().unwrap();
+        assert_eq!(env.problem.figure.vertices, vertices);
+    }
+}
@@ -1,5 +1,6 @@
 use std::{
     io,
+    process,
     path::PathBuf,
     path::Path,
 };
@@ -34,6 +35,7 @@ use common::{
 
 mod env;
 mod draw;
+mod tasks;
 
 #[derive(Clone, StructOpt, Debug)]
 #[structopt(setting = AppSettings::DeriveDisplayOrder)]
@@ -57,9 +59,24 @@ pub struct CliArgs {
     /// window initial screen height in pixels
     #[structopt(long = "screen-height", default_value = "320")]
     pub screen_height: u32,
+    /// tasks directory for next/previous problem navigation
+    #[structopt(long = "tasks-directory", default_value = "./tasks")]
+    pub tasks_directory: PathBuf,
+    /// poses directory for next/previous problem navigation
+    #[structopt(long = "poses-directory", default_value = "./poses")]
+    pub poses_directory: PathBuf,
     /// do not load pose
     #[structopt(long = "no-pose-load")]
     pub no_pose_load: bool,
+    /// score loaded pose, print the result and exit with code 0 if it is valid or 1 otherwise (no window is opened)
+    #[structopt(long = "validate-and-exit")]
+    pub validate_and_exit: bool,
+    /// replace problem epsilon for experiments (results are not submittable)
+    #[structopt(long = "epsilon-override")]
+    pub epsilon_override: Option<u64>,
+    /// draw with the same scale on both axes (centered), so shapes and angles are not distorted by the window proportions
+    #[structopt(long = "lock-aspect")]
+    pub lock_aspect: bool,
 }
 
 #[derive(Debug)]
@@ -81,9 +98,19 @@ fn main() -> Result<(), Error> {
     let cli_args = CliArgs::from_args();
     log::info!("program starts as: {:?}", cli_args);
 
-    let problem = problem::Problem::from_file(&cli_args.common.problem_file)
-        .map_err(Error::ProblemLoad)?;
-    log::debug!(" ;; problem loaded: {:?}", problem);
+    let mut problem_file = cli_args.common.problem_file.clone();
+    let mut pose_file = cli_args.common.pose_file.clone();
+    let mut env = load_env(&cli_args, &problem_file, &pose_file)?;
+
+    if cli_args.validate_and_exit {
+        match cli_args.epsilon_override {
+            None =>
+                println!("{}", env.score_text()),
+            Some(epsilon) =>
+                println!("{} (epsilon override {}, NOT SUBMITTABLE)", env.score_text(), epsilon),
+        }
+        process::exit(env.validation_exit_code());
+    }
 
     let opengl = OpenGL::V3_2;
     let mut window: PistonWindow =
@@ -96,29 +123,11 @@ fn main() -> Result<(), Error> {
         .build()
         .map_err(Error::PistonWindowCreate)?;
 
-    let mut font_path = cli_args.assets_directory;
+    let mut font_path = cli_args.assets_directory.clone();
     font_path.push("FiraSans-Regular.ttf");
     let mut glyphs = window.load_font(&font_path)
         .map_err(Error::GlyphsCreate)?;
 
-    let mut env =
-        env::Env::new(
-            problem,
-            cli_args.screen_width,
-            cli_args.screen_height,
-            cli_args.console_height,
-            cli_args.border_width,
-        )
-        .map_err(Error::EnvCreate)?;
-
-    if !cli_args.no_pose_load && Path::exists(&cli_args.common.pose_file) {
-        let pose = problem::Pose::from_file(&cli_args.common.pose_file)
-            .map_err(Error::ProblemLoad)?;
-
-        env.import_solution(pose)
-    }
-
-
     while let Some(event) = window.next() {
         let maybe_result = window.draw_2d(&event, |context, g2d, device| {
             use piston_window::{clear, text, line, ellipse, Transformed};
@@ -192,19 +201,61 @@ fn main() -> Result<(), Error> {
                 env.rotate_figure_left().map_err(Error::EnvRotate)?,
             Event::Input(Input::Button(ButtonArgs { button: Button::Keyboard(Key::X), state: ButtonState::Release, .. }), _timestamp) =>
                 env.rotate_figure_right().map_err(Error::EnvRotate)?,
+            Event::Input(Input::Button(ButtonArgs { button: Button::Keyboard(Key::T), state: ButtonState::Release, .. }), _timestamp) =>
+                env.flip_figure().map_err(Error::EnvRotate)?,
             Event::Input(Input::Button(ButtonArgs { button: Button::Keyboard(Key::C), state: ButtonState::Release, .. }), _timestamp) =>
                 env.select_next_angle(),
             Event::Input(Input::Button(ButtonArgs { button: Button::Keyboard(Key::V), state: ButtonState::Release, .. }), _timestamp) =>
                 env.select_prev_angle(),
-
-            Event::Input(Input::Button(ButtonArgs { button: Button::Keyboard(Key::E), state: ButtonState::Release, .. }), _timestamp) => {
-                let pose = env.export_solution();
-                pose.write_to_file(&cli_args.common.pose_file)
-                    .map_err(Error::PoseExport)?;
-                log::info!("pose {:?} has been written to {:?}", pose, cli_args.common.pose_file);
+            Event::Input(Input::Button(ButtonArgs { button: Button::Keyboard(Key::G), state: ButtonState::Release, .. }), _timestamp) =>
+                env.update_angles(),
+            Event::Input(Input::Button(ButtonArgs { button: Button::Keyboard(Key::F), state: ButtonState::Release, .. }), _timestamp) => {
+                env.rotate_to_best_fit().map_err(Error::EnvRotate)?;
             },
+            Event::Input(Input::Button(ButtonArgs { button: Button::Keyboard(Key::O), state: ButtonState::Release, .. }), _timestamp) =>
+                env.toggle_rotation_pivot(),
+            Event::Input(Input::Button(ButtonArgs { button: Button::Keyboard(Key::H), state: ButtonState::Release, .. }), _timestamp) =>
+                env.toggle_hole_mask(),
+            Event::Input(Input::Button(ButtonArgs { button: Button::Keyboard(Key::L), state: ButtonState::Release, .. }), _timestamp) =>
+                env.toggle_vertex_lock(),
+            Event::Input(Input::Button(ButtonArgs { button: Button::Keyboard(Key::K), state: ButtonState::Release, .. }), _timestamp) =>
+                match env.project_to_valid().map_err(Error::SimulatedAnnealingSolver)? {
+                    env::Projection::AlreadyValid =>
+                        log::info!("pose is already valid"),
+                    env::Projection::Projected { dislikes_before, dislikes_after, } =>
+                        log::info!("pose projected to a valid one, dislikes: {} -> {}", dislikes_before, dislikes_after),
+                    env::Projection::NotFound =>
+                        log::warn!("no valid pose found near the current one, pose is left unchanged"),
+                },
+
+            Event::Input(Input::Button(ButtonArgs { button: Button::Keyboard(Key::D1), state: ButtonState::Release, .. }), _timestamp) =>
+                env.toggle_pose_bonus(0),
+            Event::Input(Input::Button(ButtonArgs { button: Button::Keyboard(Key::D2), state: ButtonState::Release, .. }), _timestamp) =>
+                env.toggle_pose_bonus(1),
+            Event::Input(Input::Button(ButtonArgs { button: Button::Keyboard(Key::D3), state: ButtonState::Release, .. }), _timestamp) =>
+                env.toggle_pose_bonus(2),
+
+            Event::Input(Input::Button(ButtonArgs { button: Button::Keyboard(Key::E), state: ButtonState::Release, .. }), _timestamp) =>
+                match env.export_solution() {
+                    Ok(pose) => {
+                        if let Some(epsilon) = cli_args.epsilon_override {
+                            log::warn!("pose is scored with epsilon override {} and may be not submittable", epsilon);
+                        }
+                        pose.write_to_file(&pose_file)
+                            .map_err(Error::PoseExport)?;
+                        log::info!("pose {:?} has been written to {:?}", pose, pose_file);
+                    },
+                    Err(error) =>
+                        log::warn!("pose has not been exported: {:?}", error),
+                },
             Event::Input(Input::Button(ButtonArgs { button: Button::Keyboard(Key::R), state: ButtonState::Release, .. }), _timestamp) =>
                 env.figure_reset(),
+            Event::Input(Input::Button(ButtonArgs { button: Button::Keyboard(Key::J), state: ButtonState::Release, .. }), _timestamp) =>
+                copy_to_clipboard(env.vertices_json()),
+            Event::Input(Input::Button(ButtonArgs { button: Button::Keyboard(Key::N), state: ButtonState::Release, .. }), _timestamp) =>
+                switch_task(&cli_args, &mut env, &mut problem_file, &mut pose_file, tasks::Direction::Next),
+            Event::Input(Input::Button(ButtonArgs { button: Button::Keyboard(Key::B), state: ButtonState::Release, .. }), _timestamp) =>
+                switch_task(&cli_args, &mut env, &mut problem_file, &mut pose_file, tasks::Direction::Prev),
 
             Event::Input(Input::Button(ButtonArgs { button: Button::Keyboard(Key::U), state: ButtonState::Release, .. }), _timestamp) => {
                 env.enter_solver_simulated_annealing(solver::simulated_annealing::OperatingMode::ScoreMaximizer)
@@ -228,3 +279,96 @@ fn main() -> Result<(), Error> {
 
     Ok(())
 }
+
+fn load_env(cli_args: &CliArgs, problem_file: &Path, pose_file: &Path) -> Result<env::Env, Error> {
+    let mut problem = problem::Problem::from_file(problem_file)
+        .map_err(Error::ProblemLoad)?;
+    problem.validate_structure();
+    if let Some(epsilon) = cli_args.epsilon_override {
+        let overridden = problem.with_epsilon(epsilon);
+        log::warn!(
+            "overriding problem epsilon {}% with {}%: scores are not submittable",
+            problem.epsilon_percent(),
+            overridden.epsilon_percent(),
+        );
+        problem = overridden;
+    }
+    log::debug!(" ;; problem loaded: {:?}", problem);
+
+    let mut env =
+        env::Env::new(
+            problem,
+            cli_args.screen_width,
+            cli_args.screen_height,
+            cli_args.console_height,
+            cli_args.border_width,
+        )
+        .map_err(Error::EnvCreate)?
+        .with_lock_aspect(cli_args.lock_aspect);
+
+    if !cli_args.no_pose_load && Path::exists(pose_file) {
+        let pose = problem::Pose::from_file(pose_file)
+            .map_err(Error::ProblemLoad)?;
+
+        env.import_solution(pose)
+    } else if cli_args.validate_and_exit {
+        env.rescore_solution();
+    }
+
+    Ok(env)
+}
+
+/// Replaces `env` with the next or previous task from tasks directory, keeping the current one if it cannot be loaded.
+fn switch_task(
+    cli_args: &CliArgs,
+    env: &mut env::Env,
+    problem_file: &mut PathBuf,
+    pose_file: &mut PathBuf,
+    direction: tasks::Direction,
+)
+{
+    let task_ids = match tasks::task_ids(&cli_args.tasks_directory) {
+        Ok(task_ids) =>
+            task_ids,
+        Err(error) => {
+            log::error!("failed to list tasks directory {:?}: {:?}", cli_args.tasks_directory, error);
+            return;
+        },
+    };
+    let task_id = match tasks::neighbour_task_id(&task_ids, tasks::task_id(problem_file), direction) {
+        Some(task_id) =>
+            task_id,
+        None => {
+            log::info!("no {:?} task in {:?}", direction, cli_args.tasks_directory);
+            return;
+        },
+    };
+
+    let next_problem_file = cli_args.tasks_directory.join(format!("{}.problem", task_id));
+    let next_pose_file = cli_args.poses_directory.join(format!("{}.pose", task_id));
+    match load_env(cli_args, &next_problem_file, &next_pose_file) {
+        Ok(next_env) => {
+            log::info!("switched to task {} from {:?}", task_id, next_problem_file);
+            *env = next_env;
+            *problem_file = next_problem_file;
+            *pose_file = next_pose_file;
+        },
+        Err(error) =>
+            log::error!("failed to load task {} from {:?}: {:?}", task_id, next_problem_file, error),
+    }
+}
+
+#[cfg(feature = "clipboard")]
+fn copy_to_clipboard(text: String) {
+    match arboard::Clipboard::new().and_then(|mut clipboard| clipboard.set_text(text.clone())) {
+        Ok(()) =>
+            log::info!("copied to clipboard: {}", text),
+        Err(error) =>
+            log::error!("failed to copy to clipboard: {:?}", error),
+    }
+}
+
+#[cfg(not(feature = "clipboard"))]
+fn copy_to_clipboard(text: String) {
+    log::warn!("built without \"clipboard\" feature, vertices: {}", text);
+}
@@ -0,0 +1,73 @@
+use std::{
+    fs,
+    io,
+    ffi::OsStr,
+    path::Path,
+};
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Direction {
+    Next,
+    Prev,
+}
+
+/// Numeric task id from problem file name, e.g. `./tasks/12.problem` -> `12`.
+pub fn task_id(problem_file: &Path) -> Option<u64> {
+    problem_file.file_stem()
+        .and_then(|stem| stem.to_str())
+        .and_then(|stem| stem.parse().ok())
+}
+
+/// Sorted ids of all `.problem` files with numeric names in `tasks_directory`.
+pub fn task_ids<P>(tasks_directory: P) -> io::Result<Vec<u64>> where P: AsRef<Path> {
+    let mut ids = Vec::new();
+    for entry in fs::read_dir(tasks_directory)? {
+        let path = entry?.path();
+        if path.extension() != Some(OsStr::new("problem")) {
+            continue;
+        }
+        if let Some(id) = task_id(&path) {
+            ids.push(id);
+        }
+    }
+    ids.sort_unstable();
+    Ok(ids)
+}
+
+/// Task id following (or preceding) `current` in `task_ids`, `None` at the ends of the list. Unknown `current`
+/// (e.g. a problem loaded from elsewhere) starts from the first task going forward or from the last one going back.
+pub fn neighbour_task_id(task_ids: &[u64], current: Option<u64>, direction: Direction) -> Option<u64> {
+    match (current, direction) {
+        (None, Direction::Next) =>
+            task_ids.first().cloned(),
+        (None, Direction::Prev) =>
+            task_ids.last().cloned(),
+        (Some(current), Direction::Next) =>
+            task_ids.iter().cloned().find(|&id| id > current),
+        (Some(current), Direction::Prev) =>
+            task_ids.iter().cloned().rev().find(|&id| id < current),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn next_and_prev_task_id() {
+        let temp_directory = tempfile::tempdir().unwrap();
+        let directory = temp_directory.path();
+        for name in &["1.problem", "10.problem", "2.problem", "2.pose", "notes.problem"] {
+            fs::write(directory.join(name), "{}").unwrap();
+        }
+        let ids = task_ids(directory).unwrap();
+
+        assert_eq!(ids, vec![1, 2, 10]);
+        assert_eq!(neighbour_task_id(&ids, task_id(Path::new("./tasks/2.problem")), Direction::Next), Some(10));
+        assert_eq!(neighbour_task_id(&ids, Some(2), Direction::Prev), Some(1));
+        assert_eq!(neighbour_task_id(&ids, Some(10), Direction::Next), None);
+        assert_eq!(neighbour_task_id(&ids, Some(1), Direction::Prev), None);
+        assert_eq!(neighbour_task_id(&ids, Some(5), Direction::Next), Some(10));
+        assert_eq!(neighbour_task_id(&ids, None, Direction::Prev), Some(10));
+    }
+}
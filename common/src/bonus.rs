@@ -0,0 +1,127 @@
+use std::collections::{
+    BTreeMap,
+    VecDeque,
+};
+
+use crate::{
+    problem,
+};
+
+/// Bonus point on `source` task: a pose of `source` touching it unlocks `bonus` on `target` task.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct BonusEdge {
+    pub source: u64,
+    pub target: u64,
+    pub bonus: problem::ProblemBonusType,
+}
+
+#[derive(Clone, PartialEq, Default, Debug)]
+pub struct BonusGraph {
+    pub edges: Vec<BonusEdge>,
+    /// Tasks known to be solvable only with the given bonus, so it has to be unlocked for them first.
+    pub requires: BTreeMap<u64, problem::ProblemBonusType>,
+}
+
+impl BonusGraph {
+    /// Graph of all bonus points of `problems` keyed by task id, with no requirements.
+    pub fn from_problems(problems: &BTreeMap<u64, problem::Problem>) -> BonusGraph {
+        let mut edges = Vec::new();
+        for (&source, problem) in problems {
+            for problem_bonus in problem.bonuses.iter().flatten() {
+                edges.push(BonusEdge {
+                    source,
+                    target: problem_bonus.problem.0 as u64,
+                    bonus: problem_bonus.bonus,
+                });
+            }
+        }
+        BonusGraph { edges, requires: BTreeMap::new(), }
+    }
+}
+
+/// Task together with the bonus which has to be unlocked on it.
+type State = (u64, problem::ProblemBonusType);
+
+/// Shortest list of tasks to solve, in order, with bonus collecting poses so that `target_bonus` gets unlocked on `target_task`.
+/// The last task gives the bonus to the target directly, every previous one unlocks the bonus required by the next one.
+/// A plan of a single task means the bonus is directly available. Empty plan means the bonus cannot be unlocked at all.
+pub fn plan_unlock(graph: &BonusGraph, target_task: u64, target_bonus: problem::ProblemBonusType) -> Vec<u64> {
+    // reverse bfs over (task, bonus needed on it) states, each state remembers the state it was reached from and via which task
+    let mut states: Vec<(State, Option<(usize, u64)>)> = vec![((target_task, target_bonus), None)];
+    let mut queue = VecDeque::new();
+    queue.push_back(0);
+    while let Some(state_index) = queue.pop_front() {
+        let ((task, bonus), _) = states[state_index];
+        let mut sources: Vec<_> = graph.edges.iter()
+            .filter(|edge| edge.target == task && edge.bonus == bonus)
+            .map(|edge| edge.source)
+            .collect();
+        sources.sort_unstable();
+        sources.dedup();
+
+        for source in sources {
+            match graph.requires.get(&source) {
+                None => {
+                    let mut plan = vec![source];
+                    let mut current = state_index;
+                    while let Some((parent, via)) = states[current].1 {
+                        plan.push(via);
+                        current = parent;
+                    }
+                    return plan;
+                },
+                Some(&required_bonus) => {
+                    let next_state = (source, required_bonus);
+                    if states.iter().all(|&(state, _)| state != next_state) {
+                        states.push((next_state, Some((state_index, source))));
+                        queue.push_back(states.len() - 1);
+                    }
+                },
+            }
+        }
+    }
+    Vec::new()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn edge(source: u64, target: u64, bonus: problem::ProblemBonusType) -> BonusEdge {
+        BonusEdge { source, target, bonus, }
+    }
+
+    #[test]
+    fn plan_on_synthetic_graph() {
+        let mut graph = BonusGraph {
+            edges: vec![
+                edge(1, 2, problem::ProblemBonusType::Wallhack),
+                edge(3, 1, problem::ProblemBonusType::Globalist),
+                edge(4, 3, problem::ProblemBonusType::Superflex),
+                edge(5, 1, problem::ProblemBonusType::Globalist),
+                edge(2, 5, problem::ProblemBonusType::Superflex),
+                edge(6, 2, problem::ProblemBonusType::Globalist),
+            ],
+            requires: BTreeMap::new(),
+        };
+
+        // directly available
+        assert_eq!(plan_unlock(&graph, 2, problem::ProblemBonusType::Wallhack), vec![1]);
+        // unreachable
+        assert_eq!(plan_unlock(&graph, 2, problem::ProblemBonusType::Superflex), Vec::<u64>::new());
+
+        graph.requires.insert(1, problem::ProblemBonusType::Globalist);
+        graph.requires.insert(3, problem::ProblemBonusType::Superflex);
+        assert_eq!(plan_unlock(&graph, 2, problem::ProblemBonusType::Wallhack), vec![5, 1]);
+
+        // nobody gives wallhack to 5
+        graph.requires.insert(5, problem::ProblemBonusType::Wallhack);
+        assert_eq!(plan_unlock(&graph, 2, problem::ProblemBonusType::Wallhack), vec![4, 3, 1]);
+
+        // 4 is blocked, 5 needs superflex from 2, which in turn needs globalist from 6
+        graph.requires.insert(5, problem::ProblemBonusType::Superflex);
+        graph.requires.insert(4, problem::ProblemBonusType::Wallhack);
+        graph.requires.insert(2, problem::ProblemBonusType::Globalist);
+        assert_eq!(plan_unlock(&graph, 2, problem::ProblemBonusType::Wallhack), vec![6, 2, 5, 1]);
+    }
+}
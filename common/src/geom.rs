@@ -0,0 +1,120 @@
+use crate::problem;
+
+/// Rigid motion of the plane for figure manipulation: `x` is mirrored to `-x` first (if `mirror` is set),
+/// then points are rotated about the origin by `rotation_deg` counterclockwise (the same way as `geo` rotations)
+/// and translated by `translation`.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Transform {
+    pub rotation_deg: f64,
+    pub translation: (f64, f64),
+    pub mirror: bool,
+}
+
+impl Default for Transform {
+    fn default() -> Transform {
+        Transform::identity()
+    }
+}
+
+impl Transform {
+    pub fn identity() -> Transform {
+        Transform { rotation_deg: 0.0, translation: (0.0, 0.0), mirror: false, }
+    }
+
+    pub fn translate(dx: f64, dy: f64) -> Transform {
+        Transform { translation: (dx, dy), ..Transform::identity() }
+    }
+
+    pub fn rotate(rotation_deg: f64) -> Transform {
+        Transform { rotation_deg, ..Transform::identity() }
+    }
+
+    /// Rotation by `rotation_deg` keeping `pivot` in place.
+    pub fn rotate_around(rotation_deg: f64, pivot: (f64, f64)) -> Transform {
+        Transform::translate(-pivot.0, -pivot.1)
+            .then(&Transform::rotate(rotation_deg))
+            .then(&Transform::translate(pivot.0, pivot.1))
+    }
+
+    /// Mirroring over the vertical line through `pivot`.
+    pub fn mirror_around(pivot: (f64, f64)) -> Transform {
+        Transform::translate(-pivot.0, -pivot.1)
+            .then(&Transform { mirror: true, ..Transform::identity() })
+            .then(&Transform::translate(pivot.0, pivot.1))
+    }
+
+    /// Composition applying `self` first and `next` after it.
+    pub fn then(&self, next: &Transform) -> Transform {
+        // mirroring after a rotation is the same as mirroring before the opposite rotation
+        let rotation_deg = if next.mirror {
+            next.rotation_deg - self.rotation_deg
+        } else {
+            next.rotation_deg + self.rotation_deg
+        };
+        Transform {
+            rotation_deg,
+            translation: next.apply_f64(self.translation),
+            mirror: self.mirror != next.mirror,
+        }
+    }
+
+    pub fn apply_f64(&self, point: (f64, f64)) -> (f64, f64) {
+        let (x, y) = if self.mirror { (-point.0, point.1) } else { point };
+        let (sin, cos) = self.rotation_deg.to_radians().sin_cos();
+        (
+            x * cos - y * sin + self.translation.0,
+            x * sin + y * cos + self.translation.1,
+        )
+    }
+
+    /// Transformed points rounded to the nearest integer ones.
+    pub fn apply(&self, points: &[problem::Point]) -> Vec<problem::Point> {
+        points.iter()
+            .map(|point| {
+                let (x, y) = self.apply_f64((point.0 as f64, point.1 as f64));
+                problem::Point(x.round() as i64, y.round() as i64)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use geo::algorithm::rotate::RotatePoint;
+
+    #[test]
+    fn rotation_then_translation() {
+        let points = vec![problem::Point(0, 0), problem::Point(10, 0), problem::Point(0, 5)];
+        let transform = Transform::rotate(90.0).then(&Transform::translate(3.0, 4.0));
+        assert_eq!(transform.apply(&points), vec![problem::Point(3, 4), problem::Point(3, 14), problem::Point(-2, 4)]);
+        assert_eq!(transform.apply(&points), Transform::translate(3.0, 4.0).apply(&Transform::rotate(90.0).apply(&points)));
+
+        // the other order moves the origin as well
+        let transform = Transform::translate(3.0, 4.0).then(&Transform::rotate(90.0));
+        assert_eq!(transform.apply(&points)[0], problem::Point(-4, 3));
+
+        // same direction as geo rotations used by the editor before
+        let pivot = geo::Point::new(2.0, 3.0);
+        let rotated = geo::Point::new(10.0, 0.0).rotate_around_point(30.0, pivot);
+        let (x, y) = Transform::rotate_around(30.0, (2.0, 3.0)).apply_f64((10.0, 0.0));
+        assert!((x - rotated.x()).abs() < 1e-9 && (y - rotated.y()).abs() < 1e-9, "({}, {}) vs {:?}", x, y, rotated);
+    }
+
+    #[test]
+    fn mirror_twice_is_identity() {
+        let points = vec![problem::Point(1, 2), problem::Point(-7, 4), problem::Point(13, -6)];
+        let mirror = Transform::mirror_around((5.0, 1.0));
+        assert_eq!(mirror.apply(&points), vec![problem::Point(9, 2), problem::Point(17, 4), problem::Point(-3, -6)]);
+        assert_eq!(mirror.apply(&mirror.apply(&points)), points);
+
+        let twice = mirror.then(&mirror);
+        assert!(!twice.mirror);
+        assert_eq!(twice.apply(&points), points);
+
+        // mirroring swaps the rotation direction of a composed transform
+        let rotate_mirrored = Transform::rotate(90.0).then(&mirror);
+        assert_eq!(rotate_mirrored.apply(&points), mirror.apply(&Transform::rotate(90.0).apply(&points)));
+    }
+}
@@ -1,3 +1,14 @@
 pub mod cli;
+pub mod geom;
+pub mod math;
 pub mod solver;
 pub mod problem;
+pub mod svg;
+pub mod submission;
+pub mod partial_pose;
+pub mod bonus;
+pub mod pose;
+pub mod progress;
+pub mod standings;
+
+pub use partial_pose::PartialPose;
@@ -0,0 +1,68 @@
+/// Exact integer square root: the largest `r` with `r * r <= n`, zero for non-positive `n`.
+pub fn isqrt(n: i64) -> i64 {
+    if n <= 0 {
+        return 0;
+    }
+    // f64 estimate is off by at most a few units for huge `n`, so it is corrected in i128 to avoid overflow
+    let n = n as i128;
+    let mut root = (n as f64).sqrt() as i128;
+    while root * root > n {
+        root -= 1;
+    }
+    while (root + 1) * (root + 1) <= n {
+        root += 1;
+    }
+    root as i64
+}
+
+/// Smallest `r` with `r * r >= n`, zero for non-positive `n`.
+pub fn isqrt_ceil(n: i64) -> i64 {
+    let root = isqrt(n);
+    if (root as i128) * (root as i128) < n as i128 {
+        root + 1
+    } else {
+        root
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn isqrt_boundaries() {
+        assert_eq!(isqrt(-5), 0);
+        assert_eq!(isqrt(0), 0);
+        assert_eq!(isqrt(1), 1);
+        assert_eq!(isqrt(2), 1);
+        assert_eq!(isqrt(3), 1);
+        assert_eq!(isqrt(4), 2);
+        assert_eq!(isqrt_ceil(3), 2);
+        assert_eq!(isqrt_ceil(4), 2);
+        assert_eq!(isqrt_ceil(5), 3);
+
+        // small values agree with the f64 approach
+        for n in 0 .. 100_000 {
+            assert_eq!(isqrt(n), (n as f64).sqrt() as i64, "n = {}", n);
+        }
+
+        // perfect squares and their neighbours, up to the largest root fitting i64
+        for &root in &[1_000_i64, 46_341, 3_037_000_499, 94_906_265, 1 << 31] {
+            let square = root * root;
+            assert_eq!(isqrt(square), root);
+            assert_eq!(isqrt(square - 1), root - 1);
+            assert_eq!(isqrt(square + 1), root);
+            assert_eq!(isqrt_ceil(square), root);
+            assert_eq!(isqrt_ceil(square - 1), root);
+            assert_eq!(isqrt_ceil(square + 1), root + 1);
+        }
+
+        // here f64 rounding gives a root whose square overflows the value
+        let n = (3_037_000_499_i64 * 3_037_000_499) - 1;
+        assert_ne!(isqrt(n), (n as f64).sqrt() as i64);
+        assert_eq!(isqrt(n), 3_037_000_498);
+
+        assert_eq!(isqrt(i64::MAX), 3_037_000_499);
+        assert_eq!(isqrt_ceil(i64::MAX), 3_037_000_500);
+    }
+}
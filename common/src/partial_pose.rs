@@ -0,0 +1,95 @@
+use std::ops::Index;
+
+use crate::{
+    problem,
+};
+
+/// Pose under construction: every vertex has a position, but only `placed` ones are decided by the solver yet.
+/// Unplacing a vertex keeps its last position, so reading unplaced vertices gives their initial or previous position.
+#[derive(Clone, PartialEq, Debug)]
+pub struct PartialPose {
+    pub vertices: Vec<problem::Point>,
+    pub placed: bit_vec::BitVec,
+}
+
+impl PartialPose {
+    /// Starts with all vertices unplaced at the given initial positions (usually the original figure).
+    pub fn new(vertices: Vec<problem::Point>) -> PartialPose {
+        let placed = bit_vec::BitVec::from_elem(vertices.len(), false);
+        PartialPose { vertices, placed, }
+    }
+
+    pub fn len(&self) -> usize {
+        self.vertices.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.vertices.is_empty()
+    }
+
+    pub fn place(&mut self, index: usize, point: problem::Point) {
+        self.vertices[index] = point;
+        self.placed.set(index, true);
+    }
+
+    pub fn unplace(&mut self, index: usize) {
+        self.placed.set(index, false);
+    }
+
+    pub fn is_placed(&self, index: usize) -> bool {
+        self.placed.get(index).unwrap_or(false)
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.placed.all()
+    }
+
+    /// Complete pose with the given bonuses, `None` while some vertex is still unplaced.
+    pub fn to_pose(&self, bonuses: Option<Vec<problem::PoseBonus>>) -> Option<problem::Pose> {
+        if !self.is_complete() {
+            return None;
+        }
+        Some(problem::Pose {
+            vertices: self.vertices.clone(),
+            bonuses,
+        })
+    }
+}
+
+impl Index<usize> for PartialPose {
+    type Output = problem::Point;
+
+    fn index(&self, index: usize) -> &problem::Point {
+        &self.vertices[index]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn place_and_unplace() {
+        let mut partial = PartialPose::new(vec![problem::Point(0, 0), problem::Point(1, 1)]);
+        assert!(!partial.is_complete());
+        assert_eq!(partial.to_pose(None), None);
+
+        partial.place(1, problem::Point(5, 5));
+        assert!(partial.is_placed(1));
+        assert!(!partial.is_placed(0));
+        assert_eq!(partial[1], problem::Point(5, 5));
+
+        partial.place(0, problem::Point(3, 3));
+        assert!(partial.is_complete());
+        assert_eq!(
+            partial.to_pose(None),
+            Some(problem::Pose { vertices: vec![problem::Point(3, 3), problem::Point(5, 5)], bonuses: None, }),
+        );
+
+        partial.unplace(1);
+        assert!(!partial.is_complete());
+        assert!(!partial.is_placed(1));
+        assert_eq!(partial[1], problem::Point(5, 5));
+        assert_eq!(partial.to_pose(None), None);
+    }
+}
@@ -0,0 +1,141 @@
+use std::{
+    fs,
+    io::{
+        self,
+        BufRead,
+    },
+    ffi::OsStr,
+    path::Path,
+    collections::HashMap,
+};
+
+use crate::{
+    problem,
+};
+
+pub type TaskId = u64;
+
+#[derive(Debug)]
+pub enum ImportError {
+    OpenFile(io::Error),
+    Read(io::Error),
+    Deserialize(serde_json::Error),
+    InvalidTaskId(String),
+    CsvLine { line: usize, content: String, },
+}
+
+/// Reads a collection of poses keyed by task id from `collection_file`.
+///
+/// Files with `.csv` extension are lines of `task_id,x0,y0,x1,y1,...` (a non numeric header line is skipped),
+/// anything else is a json object like `{ "12": { "vertices": ... }, ... }`.
+/// If `tasks_directory` is provided every pose is validated against its `<task_id>.problem` and invalid ones are dropped.
+pub fn import_collection<P>(collection_file: P, tasks_directory: Option<&Path>) -> Result<HashMap<TaskId, problem::Pose>, ImportError> where P: AsRef<Path> {
+    let collection_file = collection_file.as_ref();
+    let file = fs::File::open(collection_file)
+        .map_err(ImportError::OpenFile)?;
+    let reader = io::BufReader::new(file);
+    let mut poses = if collection_file.extension() == Some(OsStr::new("csv")) {
+        read_csv(reader)?
+    } else {
+        read_json(reader)?
+    };
+
+    if let Some(tasks_directory) = tasks_directory {
+        poses.retain(|&task_id, pose| {
+            let problem_file = tasks_directory.join(format!("{}.problem", task_id));
+            let problem = match problem::Problem::from_file(&problem_file) {
+                Ok(problem) =>
+                    problem,
+                Err(error) => {
+                    log::warn!("dropping imported pose for task {}: failed to load {:?}: {:?}", task_id, problem_file, error);
+                    return false;
+                },
+            };
+            match problem.score_pose(pose) {
+                Ok(..) =>
+                    true,
+                Err(error) => {
+                    log::warn!("dropping imported pose for task {}: {:?}", task_id, error);
+                    false
+                },
+            }
+        });
+    }
+    Ok(poses)
+}
+
+fn read_json<R>(reader: R) -> Result<HashMap<TaskId, problem::Pose>, ImportError> where R: io::Read {
+    let collection: HashMap<String, problem::Pose> = serde_json::from_reader(reader)
+        .map_err(ImportError::Deserialize)?;
+    let mut poses = HashMap::with_capacity(collection.len());
+    for (task_id, pose) in collection {
+        let task_id = task_id.trim().parse()
+            .map_err(|_| ImportError::InvalidTaskId(task_id))?;
+        poses.insert(task_id, pose);
+    }
+    Ok(poses)
+}
+
+fn read_csv<R>(reader: R) -> Result<HashMap<TaskId, problem::Pose>, ImportError> where R: BufRead {
+    let mut poses = HashMap::new();
+    for (index, line) in reader.lines().enumerate() {
+        let line = line.map_err(ImportError::Read)?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let mut fields = line.split(',').map(str::trim);
+        let task_id = match fields.next().and_then(|task_id| task_id.parse().ok()) {
+            Some(task_id) =>
+                task_id,
+            None if index == 0 =>
+                continue,
+            None =>
+                return Err(ImportError::CsvLine { line: index + 1, content: line, }),
+        };
+        let coords: Result<Vec<i64>, _> = fields.map(str::parse).collect();
+        let coords = match coords {
+            Ok(ref coords) if !coords.is_empty() && coords.len() % 2 == 0 =>
+                coords,
+            Ok(..) | Err(..) =>
+                return Err(ImportError::CsvLine { line: index + 1, content: line, }),
+        };
+        let vertices = coords.chunks(2)
+            .map(|coord| problem::Point(coord[0], coord[1]))
+            .collect();
+        poses.insert(task_id, problem::Pose { vertices, bonuses: None, });
+    }
+    Ok(poses)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn import_json_collection() {
+        let temp_directory = tempfile::tempdir().unwrap();
+        let directory = temp_directory.path();
+        let collection_file = directory.join("collection.json");
+        fs::write(
+            &collection_file,
+            r#"{"12":{"vertices":[[20,0],[40,20],[0,20],[20,40]]},"7":{"vertices":[[1,2]],"bonuses":[{"bonus":"GLOBALIST","problem":3}]}}"#,
+        ).unwrap();
+
+        let poses = import_collection(&collection_file, None).unwrap();
+        assert_eq!(poses.len(), 2);
+        assert_eq!(poses[&12].vertices, vec![problem::Point(20, 0), problem::Point(40, 20), problem::Point(0, 20), problem::Point(20, 40)]);
+        assert_eq!(poses[&7].bonus(), Some(problem::PoseBonus::Globalist { problem: problem::ProblemId(3), }));
+
+        // only task 12 fits its problem, there is no problem for task 7 at all
+        fs::write(
+            directory.join("12.problem"),
+            r#"{"bonuses":[],"hole":[[20,0],[40,20],[20,40],[0,20]],"epsilon":2494,"figure":{"edges":[[0,1],[0,2],[1,3],[2,3]],"vertices":[[15,21],[34,0],[0,45],[19,24]]}}"#,
+        ).unwrap();
+        let poses = import_collection(&collection_file, Some(directory)).unwrap();
+        assert_eq!(poses.keys().collect::<Vec<_>>(), vec![&12]);
+
+        let csv_file = directory.join("collection.csv");
+        fs::write(&csv_file, "task,vertices\n12,20,0,40,20,0,20,20,40\n").unwrap();
+        assert_eq!(import_collection(&csv_file, None).unwrap()[&12].vertices, poses[&12].vertices);
+    }
+}
@@ -1,9 +1,20 @@
 use std::{
     fs,
-    io,
+    io::{
+        self,
+        Write,
+    },
+    fmt,
+    str,
+    ffi::OsStr,
     path::Path,
     cmp,
-    collections::HashSet,
+    hash::Hasher,
+    time::Instant,
+    collections::{
+        HashSet,
+        HashMap,
+    },
 };
 
 use geo::{
@@ -25,6 +36,19 @@ use serde_derive::{
     Deserialize,
 };
 
+use crate::{
+    math,
+    geom,
+};
+
+use flate2::{
+    Compression,
+    read::GzDecoder,
+    write::GzEncoder,
+};
+
+pub mod geo_hole_quad_tree;
+
 #[derive(Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, Debug)]
 pub struct Point(pub i64, pub i64);
 
@@ -45,6 +69,16 @@ pub struct Figure {
     pub vertices: Vec<Point>,
 }
 
+/// Rotation and translation invariant fingerprint of a figure: equal figures have equal signatures
+/// (the opposite is not guaranteed, but is good enough to find recurring figures).
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub struct FigureSignature {
+    /// sorted squared edge lengths
+    pub edge_lengths: Vec<i64>,
+    /// sorted vertex degrees
+    pub degrees: Vec<usize>,
+}
+
 #[derive(Clone, PartialEq, Eq, Serialize, Deserialize, Debug)]
 pub struct Pose {
     pub vertices: Vec<Point>,
@@ -96,16 +130,117 @@ pub enum PoseBonus {
 }
 
 
+/// Number of vertices the wallhack bonus lets stay outside of the hole under the contest rules.
+pub const WALLHACK_ALLOWED_OUTSIDE: usize = 1;
+
+/// Angle step in degrees `Problem::nearest_valid_rounded_angle` searches with.
+pub const ROUNDED_ANGLE_SEARCH_STEP: f64 = 0.1;
+/// The largest deviation in degrees from the requested angle `Problem::nearest_valid_rounded_angle` accepts.
+pub const ROUNDED_ANGLE_SEARCH_MAX: f64 = 5.0;
+
+/// Vertex assignments `Pose::reorder_to_match` tries before giving up, the search is exponential on loosely connected figures.
+pub const REORDER_SEARCH_NODES_MAX: usize = 1_000_000;
+
+/// Hole containment backend used by scoring: either plain `geo::Polygon<f64>` or an accelerated structure built on top of it.
+pub trait InvalidEdge {
+    /// Edge is invalid if any part of it lies outside of the hole (touching the hole boundary is fine).
+    fn is_edge_invalid(&self, edge_from: Point, edge_to: Point) -> bool;
+    /// Point is outside if it lies neither inside of the hole nor on its boundary.
+    fn is_point_outside(&self, point: Point) -> bool;
+    /// Query counters for backends keeping them (only `GeoHoleQuadTree` for now).
+    fn quad_tree_stats(&self) -> Option<geo_hole_quad_tree::QuadTreeStats> {
+        None
+    }
+}
+
+/// `InvalidEdge` backend to build for scoring, selectable with `--edge-checker` in solver binaries.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum EdgeChecker {
+    Polygon,
+    QuadTree,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct UnknownEdgeChecker(pub String);
+
+impl fmt::Display for UnknownEdgeChecker {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "unknown edge checker {:?}, expected one of: polygon, quadtree", self.0)
+    }
+}
+
+impl str::FromStr for EdgeChecker {
+    type Err = UnknownEdgeChecker;
+
+    fn from_str(s: &str) -> Result<EdgeChecker, UnknownEdgeChecker> {
+        match s {
+            "polygon" =>
+                Ok(EdgeChecker::Polygon),
+            "quadtree" =>
+                Ok(EdgeChecker::QuadTree),
+            _ =>
+                Err(UnknownEdgeChecker(s.to_string())),
+        }
+    }
+}
+
+impl EdgeChecker {
+    pub fn build(&self, problem: &Problem) -> Box<dyn InvalidEdge + Send + Sync> {
+        match self {
+            EdgeChecker::Polygon =>
+                Box::new(problem.hole_polygon_f64()),
+            EdgeChecker::QuadTree =>
+                Box::new(geo_hole_quad_tree::GeoHoleQuadTree::new(problem.hole_polygon_f64())),
+        }
+    }
+}
+
 #[derive(Debug)]
 pub enum FromFileError {
     OpenFile(io::Error),
     Deserialize(serde_json::Error),
+    ReadBinary(ReadBinaryError),
 }
 
 #[derive(Debug)]
 pub enum WriteFileError {
+    CreateDirectory(io::Error),
     CreateFile(io::Error),
     Serialize(serde_json::Error),
+    Compress(io::Error),
+    WriteBinary(io::Error),
+}
+
+/// Extension for poses stored with `Pose::write_binary`, json `.pose` files stay the canonical format.
+pub const POSE_BINARY_EXTENSION: &str = "posebin";
+/// First bytes of every binary pose, followed by `POSE_BINARY_VERSION`.
+pub const POSE_BINARY_MAGIC: [u8; 4] = *b"POSB";
+pub const POSE_BINARY_VERSION: u8 = 1;
+
+#[derive(Debug)]
+pub enum ReadBinaryError {
+    OpenFile(io::Error),
+    Read(io::Error),
+    NotBinaryPose,
+    UnsupportedVersion(u8),
+    UnknownBonusTag(u8),
+}
+
+#[derive(Debug)]
+pub enum WriteBinaryError {
+    CreateDirectory(io::Error),
+    CreateFile(io::Error),
+    Write(io::Error),
+}
+
+#[derive(Debug, PartialEq)]
+pub enum ReorderError {
+    VerticesCountMismatch { expected: usize, provided: usize, },
+    NoMatchingOrder,
+    /// several orders fit edge lengths but give different edges
+    Ambiguous,
+    /// `REORDER_SEARCH_NODES_MAX` assignments are tried without a conclusion
+    SearchBudgetExhausted,
 }
 
 #[derive(Debug, PartialEq)]
@@ -113,17 +248,73 @@ pub enum PoseValidationError {
     VerticeCountMismatch,
     BrokenEdgesFound { ratio_sum: f64, broken_edges: Vec<Edge>, },
     EdgesNotFitHole(Vec<Edge>),
+    UnsupportedBonus(PoseBonus),
+}
+
+/// Nanoseconds spent in every phase of `Problem::score_pose_timed`, phases skipped after a failed check stay zero.
+#[derive(Clone, Copy, PartialEq, Default, Debug)]
+pub struct ScoreTimings {
+    pub count_ns: u64,
+    pub stretching_ns: u64,
+    pub hole_ns: u64,
+    pub dislikes_ns: u64,
+}
+
+impl ScoreTimings {
+    pub fn total_ns(&self) -> u64 {
+        self.count_ns + self.stretching_ns + self.hole_ns + self.dislikes_ns
+    }
 }
 
 impl Problem {
+    /// Files ending with `.gz` are decompressed on the fly.
     pub fn from_file<P>(filename: P) -> Result<Problem, FromFileError> where P: AsRef<Path> {
-        let file = fs::File::open(filename)
+        let reader = open_reader(filename.as_ref())
             .map_err(FromFileError::OpenFile)?;
-        let reader = io::BufReader::new(file);
         serde_json::from_reader(reader)
             .map_err(FromFileError::Deserialize)
     }
 
+    /// Stable seahash fingerprint of the hole, the figure and epsilon (bonuses are not taken into account).
+    pub fn fingerprint(&self) -> u64 {
+        let mut hasher = seahash::SeaHasher::new();
+        hasher.write_u64(self.hole.len() as u64);
+        for point in &self.hole {
+            hasher.write_i64(point.0);
+            hasher.write_i64(point.1);
+        }
+        hasher.write_u64(self.figure.vertices.len() as u64);
+        for vertex in &self.figure.vertices {
+            hasher.write_i64(vertex.0);
+            hasher.write_i64(vertex.1);
+        }
+        hasher.write_u64(self.figure.edges.len() as u64);
+        for edge in &self.figure.edges {
+            hasher.write_u64(edge.0 as u64);
+            hasher.write_u64(edge.1 as u64);
+        }
+        hasher.write_u64(self.epsilon);
+        hasher.finish()
+    }
+
+    /// Allowed relative edge stretching, e.g. `0.002494` for epsilon 2494.
+    pub fn epsilon_fraction(&self) -> f64 {
+        self.epsilon as f64 / 1000000.0
+    }
+
+    /// Allowed relative edge stretching in percent for user facing output, e.g. `0.2494` for epsilon 2494.
+    pub fn epsilon_percent(&self) -> f64 {
+        self.epsilon as f64 / 10000.0
+    }
+
+    /// Copy of the problem with a different `epsilon`, for experiments only: scores obtained with it are not submittable.
+    pub fn with_epsilon(&self, epsilon: u64) -> Problem {
+        Problem {
+            epsilon,
+            ..self.clone()
+        }
+    }
+
     pub fn export_pose(&self) -> Pose {
         Pose {
             vertices: self.figure.vertices.clone(),
@@ -145,13 +336,166 @@ impl Problem {
         geo::Polygon::new(self.hole.clone().into(), vec![])
     }
 
+    /// Hole vertices where the hole touches itself: repeated vertices or vertices lying on a non adjacent hole edge.
+    /// Such holes are not simple polygons and containment checks near these points are less trustworthy.
+    pub fn hole_pinch_points(&self) -> Vec<Point> {
+        let mut pinch_points = Vec::new();
+        for (vertex_index, vertex) in self.hole.iter().enumerate() {
+            let touches = (0 .. self.hole.len()).any(|edge_index| {
+                let edge_next = (edge_index + 1) % self.hole.len();
+                edge_index != vertex_index && edge_next != vertex_index
+                    && is_on_segment(*vertex, self.hole[edge_index], self.hole[edge_next])
+            });
+            if touches && !pinch_points.contains(vertex) {
+                pinch_points.push(*vertex);
+            }
+        }
+        pinch_points
+    }
+
+    /// Checks problem data for structural oddities scoring should be aware of, logging a warning for each one.
+    /// Returns `false` if anything was found.
+    pub fn validate_structure(&self) -> bool {
+        let pinch_points = self.hole_pinch_points();
+        for pinch_point in &pinch_points {
+            log::warn!("hole touches itself at {:?}", pinch_point);
+        }
+        pinch_points.is_empty()
+    }
+
+    /// Conservative check that the figure can be placed into the hole at all (bonuses are not taken into account).
+    /// Figure may fold around its vertices, so only single edges are compared with the hole diameter:
+    /// `false` means that some edge cannot be squeezed enough to fit between any two hole points.
+    pub fn figure_might_fit(&self) -> bool {
+        let hole_sq_diameter = self.hole.iter()
+            .flat_map(|p| self.hole.iter().map(move |q| distance(p, q)))
+            .max()
+            .unwrap_or(0) as f64;
+
+        let (_center, radius) = self.figure.bounding_circle();
+        if 4.0 * radius * radius <= hole_sq_diameter {
+            // no edge is longer than the bounding circle diameter
+            return true;
+        }
+
+        let max_squeeze = 1.0 - self.epsilon_fraction();
+        self.figure.edges.iter().all(|edge| {
+            let sq_length = distance(&self.figure.vertices[edge.0], &self.figure.vertices[edge.1]) as f64;
+            sq_length * max_squeeze <= hole_sq_diameter
+        })
+    }
+
+    /// Fast conservative check that a zero dislikes pose is possible at all (bonuses are not taken into account):
+    /// every distinct hole vertex needs its own figure vertex, and any two hole vertices need figure vertices
+    /// which can be moved apart that far with all edges stretched to the maximum. `false` means definitely impossible.
+    pub fn zero_dislikes_plausible(&self) -> bool {
+        let mut hole_vertices = self.hole.clone();
+        hole_vertices.sort_unstable_by_key(|point| (point.0, point.1));
+        hole_vertices.dedup();
+        if hole_vertices.len() > self.figure.vertices.len() {
+            return false;
+        }
+
+        // figure diameter: the largest of the shortest paths along the figure edges between two vertices, all edges
+        // stretched by epsilon (a vertex can be moved at most that far from another one)
+        let vertices_count = self.figure.vertices.len();
+        let max_stretch = 1.0 + self.epsilon_fraction();
+        let mut reach = vec![vec![f64::INFINITY; vertices_count]; vertices_count];
+        for (index, row) in reach.iter_mut().enumerate() {
+            row[index] = 0.0;
+        }
+        for edge in &self.figure.edges {
+            let length = (distance(&self.figure.vertices[edge.0], &self.figure.vertices[edge.1]) as f64 * max_stretch).sqrt();
+            reach[edge.0][edge.1] = reach[edge.0][edge.1].min(length);
+            reach[edge.1][edge.0] = reach[edge.1][edge.0].min(length);
+        }
+        for via in 0 .. vertices_count {
+            for from in 0 .. vertices_count {
+                for to in 0 .. vertices_count {
+                    let through = reach[from][via] + reach[via][to];
+                    if through < reach[from][to] {
+                        reach[from][to] = through;
+                    }
+                }
+            }
+        }
+        let figure_reach = reach.iter()
+            .flatten()
+            .cloned()
+            .fold(0.0, f64::max);
+
+        let hole_sq_diameter = hole_vertices.iter()
+            .flat_map(|p| hole_vertices.iter().map(move |q| distance(p, q)))
+            .max()
+            .unwrap_or(0) as f64;
+        hole_sq_diameter.sqrt() <= figure_reach + 1e-9
+    }
+
+    /// Conservative check of hole vertices no figure vertex can ever be placed on (bonuses are not taken into account):
+    /// a figure vertex on a hole vertex needs every incident edge to end at some in-hole point within the allowed stretching,
+    /// with the edge itself fitting the hole. Returned vertices keep positive dislikes in any valid pose,
+    /// the rest are not guaranteed to be reachable.
+    pub fn unreachable_hole_vertices(&self) -> Vec<Point> {
+        let mut incident_lengths = vec![Vec::new(); self.figure.vertices.len()];
+        for edge in &self.figure.edges {
+            let d_before = distance(&self.figure.vertices[edge.0], &self.figure.vertices[edge.1]);
+            incident_lengths[edge.0].push(d_before);
+            incident_lengths[edge.1].push(d_before);
+        }
+        if incident_lengths.iter().any(Vec::is_empty) {
+            // a vertex without edges is not constrained at all
+            return Vec::new();
+        }
+        for lengths in &mut incident_lengths {
+            lengths.sort_unstable();
+            lengths.dedup();
+        }
+
+        let geo_hole = self.hole_polygon_f64();
+        let mut unreachable = Vec::new();
+        for (hole_index, hole_vertex) in self.hole.iter().enumerate() {
+            if self.hole[.. hole_index].contains(hole_vertex) {
+                continue;
+            }
+            let mut edge_fits = HashMap::new();
+            let reachable = incident_lengths.iter().any(|lengths| {
+                lengths.iter().all(|&d_before| {
+                    *edge_fits.entry(d_before)
+                        .or_insert_with(|| self.edge_fits_from(&geo_hole, *hole_vertex, d_before))
+                })
+            });
+            if !reachable {
+                unreachable.push(*hole_vertex);
+            }
+        }
+        unreachable
+    }
+
+    /// Whether an edge of the original squared length `d_before` can start at `from` and end at some in-hole point.
+    fn edge_fits_from(&self, geo_hole: &dyn InvalidEdge, from: Point, d_before: i64) -> bool {
+        let d_max = (d_before as f64 * (1.0 + self.epsilon_fraction())).ceil() as i64;
+        let radius = math::isqrt_ceil(d_max) + 1;
+        for dx in -radius ..= radius {
+            for dy in -radius ..= radius {
+                let to = Point(from.0 + dx, from.1 + dy);
+                if stretch_ratio(d_before, distance(&from, &to)) <= self.epsilon_fraction()
+                    && !geo_hole.is_point_outside(to)
+                    && !geo_hole.is_edge_invalid(from, to)
+                {
+                    return true;
+                }
+            }
+        }
+        false
+    }
+
     pub fn score_vertices_check_count(&self,
                                       pose_vertices: &[Point],
                                       bonus: Option<PoseBonus>) -> Result<(), PoseValidationError> {
         // Check (a): connectivity. As our app does not change include edges in Pose,
         // we just check that the new Pose inclues the same number of vertices as the original
-        if let Some(PoseBonus::BreakALeg { .. }) = bonus {
-            unimplemented!("BREAK_A_LEG is not supported yet");
+        if let Some(bonus @ PoseBonus::BreakALeg { .. }) = bonus {
+            return Err(PoseValidationError::UnsupportedBonus(bonus));
         }
 
         if self.figure.vertices.len() != pose_vertices.len() {
@@ -160,29 +504,33 @@ impl Problem {
         Ok(())
     }
 
+    /// Sum of `stretch_ratio` over all figure edges, the same `ratio_sum` stretching check reports. Unlike scoring
+    /// it is defined for infeasible poses too, so it can serve as an objective for studying near misses under a tight epsilon.
+    pub fn total_stretch(&self, pose_vertices: &[Point]) -> f64 {
+        self.figure.edges.iter()
+            .map(|&Edge(from_idx, to_idx)| {
+                let d_before = distance(&self.figure.vertices[from_idx], &self.figure.vertices[to_idx]);
+                let d_after = distance(&pose_vertices[from_idx], &pose_vertices[to_idx]);
+                stretch_ratio(d_before, d_after)
+            })
+            .sum()
+    }
+
     pub fn score_vertices_check_stretching(&self,
                                            pose_vertices: &[Point],
                                            bonus: Option<PoseBonus>) -> Result<f64, PoseValidationError> {
         match bonus {
             Some(PoseBonus::Globalist { .. }) => {
                 // Check stretching
-                let mut ratio_sum = 0.0;
-                for &Edge(from_idx, to_idx) in &self.figure.edges {
-                    let d_before = distance(&self.figure.vertices[from_idx], &self.figure.vertices[to_idx]);
-                    let d_after = distance(&pose_vertices[from_idx], &pose_vertices[to_idx]);
-
-                    let ratio = ((d_after as f64) / (d_before as f64) - 1_f64).abs();
-                    ratio_sum += ratio;
-                }
-
+                let ratio_sum = self.total_stretch(pose_vertices);
                 if ratio_sum > (self.figure.edges.len() as f64 * self.epsilon as f64) / 1000000_f64 {
                     return Err(PoseValidationError::BrokenEdgesFound { ratio_sum, broken_edges: vec![], });
                 }
 
                 Ok(ratio_sum)
             }
-            Some(PoseBonus::BreakALeg { .. }) => {
-                unimplemented!("BREAK_A_LEG is not supported yet");
+            Some(bonus @ PoseBonus::BreakALeg { .. }) => {
+                Err(PoseValidationError::UnsupportedBonus(bonus))
             }
             _ => {
                 // Check stretching
@@ -196,9 +544,9 @@ impl Problem {
                     let d_before = distance(&self.figure.vertices[from_idx], &self.figure.vertices[to_idx]);
                     let d_after = distance(&pose_vertices[from_idx], &pose_vertices[to_idx]);
 
-                    let ratio = ((d_after as f64) / (d_before as f64) - 1_f64).abs();
+                    let ratio = stretch_ratio(d_before, d_after);
                     ratio_sum += ratio;
-                    if ratio > self.epsilon as f64 / 1000000_f64 {
+                    if ratio > self.epsilon_fraction() {
                         if allow_broken > 0 {
                             allow_broken -= 1;
                             continue;
@@ -219,41 +567,47 @@ impl Problem {
     pub fn score_vertices_check_hole(&self,
                                      pose_vertices: &[Point],
                                      bonus: Option<PoseBonus>) -> Result<(), PoseValidationError> {
-        let geo_hole = self.hole_polygon_f64();
+        self.score_vertices_check_hole_in(&self.hole_polygon_f64(), pose_vertices, bonus)
+    }
+
+    pub fn score_vertices_check_hole_in(&self,
+                                        geo_hole: &dyn InvalidEdge,
+                                        pose_vertices: &[Point],
+                                        bonus: Option<PoseBonus>) -> Result<(), PoseValidationError> {
+        self.score_vertices_check_hole_allowing(geo_hole, pose_vertices, bonus, WALLHACK_ALLOWED_OUTSIDE)
+    }
+
+    /// Same as `score_vertices_check_hole_in` but wallhack lets up to `allowed_outside` vertices be outside of the hole.
+    pub fn score_vertices_check_hole_allowing(&self,
+                                              geo_hole: &dyn InvalidEdge,
+                                              pose_vertices: &[Point],
+                                              bonus: Option<PoseBonus>,
+                                              allowed_outside: usize) -> Result<(), PoseValidationError> {
         let mut edges_out_of_hole = Vec::new();
-        let mut outer_vertex: Option<usize> = None;
+        let mut outer_vertices: Vec<usize> = Vec::new();
         for &Edge(from_idx, to_idx) in &self.figure.edges {
-            let geo_start = geo::Coordinate::from(pose_vertices[from_idx]);
-            let geo_end = geo::Coordinate::from(pose_vertices[to_idx]);
-            let geo_edge = geo::Line {
-                start: geo_start,
-                end: geo_end,
-            };
-            if geo_hole.contains(&geo_edge) || geo_hole.exterior().contains(&geo_edge) {
+            let start = pose_vertices[from_idx];
+            let end = pose_vertices[to_idx];
+            if !geo_hole.is_edge_invalid(start, end) {
                 // ok
             }
             else {
                 if let Some(PoseBonus::Wallhack { .. }) = bonus {
-                    /* probably we can allow that for one vertice */
-                    match outer_vertex {
-                        None => {
-                            let contains_start = geo_hole.contains(&geo_start) || geo_hole.exterior().contains(&geo_start);
-                            let contains_end = geo_hole.contains(&geo_end) || geo_hole.exterior().contains(&geo_end);
-                            if !contains_start && contains_end {
-                                outer_vertex = Some(from_idx);
-                                continue; // Ok, that's edge belongs to outer-point
-                            }
-                            else if contains_start && !contains_end {
-                                outer_vertex = Some(to_idx);
-                                continue; // Ok, that's edge belongs to outer-point
-                            }
-                        },
-                        Some(idx) => {
-                            if (idx == from_idx) || (idx == to_idx) {
-                                continue; // Ok, that's edge belongs to outer-point
+                    /* probably we can allow that for a few vertices */
+                    // every end outside of the hole has to be an outer-point, edge crossing the hole border with both ends inside is never ok
+                    let outside_ends: Vec<_> = [(from_idx, start), (to_idx, end)].iter()
+                        .filter(|&&(_, point)| geo_hole.is_point_outside(point))
+                        .map(|&(idx, _)| idx)
+                        .collect();
+                    let new_outer_count = outside_ends.iter().filter(|idx| !outer_vertices.contains(idx)).count();
+                    if !outside_ends.is_empty() && outer_vertices.len() + new_outer_count <= allowed_outside {
+                        for idx in outside_ends {
+                            if !outer_vertices.contains(&idx) {
+                                outer_vertices.push(idx);
                             }
-                        },
-                    };
+                        }
+                        continue; // Ok, that's edge belongs to outer-point
+                    }
                 }
 
                 edges_out_of_hole.push(Edge(from_idx, to_idx));
@@ -270,33 +624,168 @@ impl Problem {
     pub fn score_vertices(&self,
                           pose_vertices: &[Point],
                           bonus: Option<PoseBonus>) -> Result<i64, PoseValidationError> {
+        self.score_vertices_in(&self.hole_polygon_f64(), pose_vertices, bonus)
+    }
+
+    pub fn score_vertices_in(&self,
+                             geo_hole: &dyn InvalidEdge,
+                             pose_vertices: &[Point],
+                             bonus: Option<PoseBonus>) -> Result<i64, PoseValidationError> {
+        self.score_vertices_in_allowing(geo_hole, pose_vertices, bonus, WALLHACK_ALLOWED_OUTSIDE)
+    }
+
+    /// Same as `score_vertices_in` but wallhack lets up to `allowed_outside` vertices be outside of the hole.
+    pub fn score_vertices_in_allowing(&self,
+                                      geo_hole: &dyn InvalidEdge,
+                                      pose_vertices: &[Point],
+                                      bonus: Option<PoseBonus>,
+                                      allowed_outside: usize) -> Result<i64, PoseValidationError> {
         self.score_vertices_check_count(pose_vertices, bonus)?;
         self.score_vertices_check_stretching(pose_vertices, bonus)?;
-        self.score_vertices_check_hole(pose_vertices, bonus)?;
+        self.score_vertices_check_hole_allowing(geo_hole, pose_vertices, bonus, allowed_outside)?;
 
 
-        let dislikes = self.hole.iter().map(|hole_vert| {
-            pose_vertices.iter().map(|pose_vert| distance(hole_vert, pose_vert)).min().unwrap()
-        }).sum();
+        let dislikes = self.nearest_distances(pose_vertices).sum();
 
         Ok(dislikes)
     }
 
+    /// Squared distance from every hole vertex to the nearest pose vertex, i.e. per corner dislikes.
+    fn nearest_distances<'a>(&'a self, pose_vertices: &'a [Point]) -> impl Iterator<Item = i64> + 'a {
+        self.hole.iter().map(move |hole_vert| nearest_pose_vertex(hole_vert, pose_vertices).unwrap().1)
+    }
+
+    /// For every hole vertex: index of the nearest pose vertex and squared distance to it. Ties are resolved
+    /// in favor of the lowest pose vertex index, so the assignment is stable. Empty if there are no pose vertices.
+    pub fn dislikes_assignment(&self, pose_vertices: &[Point]) -> Vec<(usize, i64)> {
+        self.hole.iter()
+            .flat_map(|hole_vert| nearest_pose_vertex(hole_vert, pose_vertices))
+            .collect()
+    }
+
+    /// Every hole vertex with its dislikes contribution (squared distance to the nearest pose vertex), largest first,
+    /// hole order is kept on ties. Contributions sum up to pose dislikes.
+    pub fn dislikes_contributions(&self, pose_vertices: &[Point]) -> Vec<(Point, i64)> {
+        let mut contributions: Vec<_> = self.hole.iter()
+            .zip(self.dislikes_assignment(pose_vertices))
+            .map(|(&hole_vert, (_, sq_dist))| (hole_vert, sq_dist))
+            .collect();
+        contributions.sort_by_key(|&(_, sq_dist)| cmp::Reverse(sq_dist));
+        contributions
+    }
+
+    /// Hole vertex contributing most dislikes (the farthest from any pose vertex) with its squared distance,
+    /// the first one in hole order on ties. `None` if there are no pose or hole vertices.
+    pub fn worst_hole_vertex(&self, pose_vertices: &[Point]) -> Option<(Point, i64)> {
+        self.hole.iter()
+            .flat_map(|hole_vert| nearest_pose_vertex(hole_vert, pose_vertices).map(|(_, sq_dist)| (*hole_vert, sq_dist)))
+            .fold(None, |worst, (hole_vert, sq_dist)| match worst {
+                Some((_, worst_sq_dist)) if worst_sq_dist >= sq_dist =>
+                    worst,
+                _ =>
+                    Some((hole_vert, sq_dist)),
+            })
+    }
+
+    /// True if every hole vertex has a pose vertex within `tolerance` squared distance, so the pose
+    /// is only a few snaps away from zero dislikes. Pose validity is not checked.
+    pub fn near_perfect(&self, pose_vertices: &[Point], tolerance: i64) -> bool {
+        !pose_vertices.is_empty() && self.nearest_distances(pose_vertices).all(|nearest| nearest <= tolerance)
+    }
+
+
+    /// Bonuses placed exactly on a hole vertex: any zero dislikes pose claims them for free.
+    pub fn bonus_on_hole_vertices(&self) -> Vec<ProblemBonus> {
+        self.bonuses.iter()
+            .flatten()
+            .filter(|bonus| bonus.is_claimed_by(&self.hole))
+            .cloned()
+            .collect()
+    }
 
     pub fn score_pose(&self, pose: &Pose) -> Result<i64, PoseValidationError> {
-        self.score_vertices(&pose.vertices, pose.bonuses.as_ref().and_then(|bonuses| bonuses.first().cloned()))
+        self.score_pose_in(&self.hole_polygon_f64(), pose)
+    }
+
+    /// Same as `score_pose` but checks the hole with the given backend, like `score_vertices_in`.
+    pub fn score_pose_in(&self, geo_hole: &dyn InvalidEdge, pose: &Pose) -> Result<i64, PoseValidationError> {
+        self.score_vertices_in(geo_hole, &pose.vertices, pose.bonuses.as_ref().and_then(|bonuses| bonuses.first().cloned()))
+    }
+
+    /// Same as `score_pose_in` but also measures time spent in each check, to find the scoring bottleneck on large problems.
+    pub fn score_pose_timed(&self, geo_hole: &dyn InvalidEdge, pose: &Pose) -> (Result<i64, PoseValidationError>, ScoreTimings) {
+        let bonus = pose.bonuses.as_ref().and_then(|bonuses| bonuses.first().cloned());
+        let mut timings = ScoreTimings::default();
+        let result = self.score_vertices_timed(geo_hole, &pose.vertices, bonus, &mut timings);
+        (result, timings)
+    }
+
+    fn score_vertices_timed(&self,
+                            geo_hole: &dyn InvalidEdge,
+                            pose_vertices: &[Point],
+                            bonus: Option<PoseBonus>,
+                            timings: &mut ScoreTimings) -> Result<i64, PoseValidationError> {
+        let start = Instant::now();
+        let result = self.score_vertices_check_count(pose_vertices, bonus);
+        timings.count_ns = elapsed_ns(start);
+        result?;
+
+        let start = Instant::now();
+        let result = self.score_vertices_check_stretching(pose_vertices, bonus);
+        timings.stretching_ns = elapsed_ns(start);
+        result?;
+
+        let start = Instant::now();
+        let result = self.score_vertices_check_hole_in(geo_hole, pose_vertices, bonus);
+        timings.hole_ns = elapsed_ns(start);
+        result?;
+
+        let start = Instant::now();
+        let dislikes = self.nearest_distances(pose_vertices).sum();
+        timings.dislikes_ns = elapsed_ns(start);
+
+        Ok(dislikes)
+    }
+
+    /// Angle closest to `target_angle` rotating `vertices` about `pivot` without broken edges after rounding to integer points:
+    /// the exact angle may be fine in theory but round to a broken figure. Only stretching is checked, not the hole.
+    /// `None` if there is no such angle within `ROUNDED_ANGLE_SEARCH_MAX` degrees.
+    pub fn nearest_valid_rounded_angle(&self, vertices: &[Point], pivot: (f64, f64), target_angle: f64) -> Option<f64> {
+        let max_steps = (ROUNDED_ANGLE_SEARCH_MAX / ROUNDED_ANGLE_SEARCH_STEP).round() as i64;
+        (0 ..= max_steps)
+            .flat_map(|steps| if steps == 0 { vec![0] } else { vec![-steps, steps] })
+            .map(|steps| target_angle + steps as f64 * ROUNDED_ANGLE_SEARCH_STEP)
+            .find(|&angle| {
+                let rotated = geom::Transform::rotate_around(angle, pivot).apply(vertices);
+                self.score_vertices_check_stretching(&rotated, None).is_ok()
+            })
     }
 
     pub fn possible_rotations(&self) -> Vec<f64> {
+        self.possible_rotations_with(&self.hole_polygon_f64())
+    }
+
+    /// Same as `possible_rotations` but checks the hole with the given backend, e.g. a prebuilt `GeoHoleQuadTree`.
+    pub fn possible_rotations_with(&self, geo_hole: &dyn InvalidEdge) -> Vec<f64> {
         let mut angles = vec![];
-        let geo_figure = self.figure.export_to_geo().unwrap();
+        let geo_figure = match self.figure.export_to_geo() {
+            Ok(geo_figure) =>
+                geo_figure,
+            Err(error) => {
+                log::warn!("cannot compute possible rotations: {:?}", error);
+                return angles;
+            },
+        };
         for angle in 1..360 {
             // log::debug!("checking angle {}", angle);
             let mut new_geo_figure = geo_figure.clone();
             new_geo_figure.rotate_around_centroid_mut(angle as f64);
             let mut new_figure = self.figure.clone();
-            new_figure.import_from_geo(new_geo_figure.points).unwrap();
-            match self.score_vertices(&new_figure.vertices, None) {
+            if let Err(error) = new_figure.import_from_geo(new_geo_figure.points) {
+                log::warn!("cannot import rotated figure for angle {}: {:?}", angle, error);
+                continue;
+            }
+            match self.score_vertices_in(geo_hole, &new_figure.vertices, None) {
                 Err(PoseValidationError::BrokenEdgesFound { .. }) |
                 Err(PoseValidationError::VerticeCountMismatch) => continue,
                 _ => { angles.push(angle as f64); }
@@ -306,16 +795,36 @@ impl Problem {
     }
 
     pub fn possible_rotations_around_point(&self, point: &Point) -> Vec<f64> {
+        self.possible_rotations_around_point_with(point, &self.hole_polygon_f64())
+    }
+
+    pub fn possible_rotations_around_point_with(&self, point: &Point, geo_hole: &dyn InvalidEdge) -> Vec<f64> {
+        self.possible_rotations_around_point_for_vertices_with(&self.figure.vertices, point, geo_hole)
+    }
+
+    pub fn possible_rotations_around_point_for_vertices_with(&self, vertices: &Vec<Point>, point: &Point, geo_hole: &dyn InvalidEdge) -> Vec<f64> {
         let mut angles = vec![];
         let geo_point = geo::Point::from(point);
-        let geo_figure = self.figure.export_to_geo().unwrap();
+        let mut figure = self.figure.clone();
+        figure.vertices = vertices.clone();
+        let geo_figure = match figure.export_to_geo() {
+            Ok(geo_figure) =>
+                geo_figure,
+            Err(error) => {
+                log::warn!("cannot compute possible rotations: {:?}", error);
+                return angles;
+            },
+        };
         for angle in 1..360 {
             // log::debug!("(for point) checking angle {}", angle);
             let mut new_geo_figure = geo_figure.clone();
             new_geo_figure.rotate_around_point_mut(angle as f64, geo_point);
             let mut new_figure = self.figure.clone();
-            new_figure.import_from_geo(new_geo_figure.points).unwrap();
-            match self.score_vertices(&new_figure.vertices, None) {
+            if let Err(error) = new_figure.import_from_geo(new_geo_figure.points) {
+                log::warn!("cannot import rotated figure for angle {}: {:?}", angle, error);
+                continue;
+            }
+            match self.score_vertices_in(geo_hole, &new_figure.vertices, None) {
                 Err(PoseValidationError::BrokenEdgesFound { .. }) |
                 Err(PoseValidationError::VerticeCountMismatch) => continue,
                 _ => { angles.push(angle as f64); }
@@ -325,17 +834,31 @@ impl Problem {
     }
 
     pub fn possible_rotations_for_vertices(&self, vertices: &Vec<Point>) -> Vec<f64> {
+        self.possible_rotations_for_vertices_with(vertices, &self.hole_polygon_f64())
+    }
+
+    pub fn possible_rotations_for_vertices_with(&self, vertices: &Vec<Point>, geo_hole: &dyn InvalidEdge) -> Vec<f64> {
         let mut angles = vec![];
         let mut figure = self.figure.clone();
         figure.vertices = vertices.clone();
-        let geo_figure = figure.export_to_geo().unwrap();
+        let geo_figure = match figure.export_to_geo() {
+            Ok(geo_figure) =>
+                geo_figure,
+            Err(error) => {
+                log::warn!("cannot compute possible rotations: {:?}", error);
+                return angles;
+            },
+        };
         for angle in 1..360 {
             // log::debug!("checking angle {}", angle);
             let mut new_geo_figure = geo_figure.clone();
             new_geo_figure.rotate_around_centroid_mut(angle as f64);
             let mut new_figure = self.figure.clone();
-            new_figure.import_from_geo(new_geo_figure.points).unwrap();
-            match self.score_vertices(&new_figure.vertices, None) {
+            if let Err(error) = new_figure.import_from_geo(new_geo_figure.points) {
+                log::warn!("cannot import rotated figure for angle {}: {:?}", angle, error);
+                continue;
+            }
+            match self.score_vertices_in(geo_hole, &new_figure.vertices, None) {
                 Err(PoseValidationError::BrokenEdgesFound { .. }) |
                 Err(PoseValidationError::VerticeCountMismatch) => continue,
                 _ => { angles.push(angle as f64); }
@@ -357,6 +880,13 @@ impl Problem {
     }
 }
 
+impl ProblemBonus {
+    /// Bonus is claimed when some pose vertex occupies its position.
+    pub fn is_claimed_by(&self, pose_vertices: &[Point]) -> bool {
+        pose_vertices.iter().any(|vertex| vertex == &self.position)
+    }
+}
+
 #[derive(Debug)]
 pub enum GeoExportError {
     NoCentroidBuilt,
@@ -409,6 +939,258 @@ impl Figure {
         Ok(())
     }
 
+    /// Count of edges incident to every vertex.
+    pub fn vertex_degrees(&self) -> Vec<usize> {
+        let mut degrees = vec![0; self.vertices.len()];
+        for edge in &self.edges {
+            degrees[edge.0] += 1;
+            degrees[edge.1] += 1;
+        }
+        degrees
+    }
+
+    pub fn signature(&self) -> FigureSignature {
+        let mut edge_lengths: Vec<_> = self.edges.iter()
+            .map(|edge| distance(&self.vertices[edge.0], &self.vertices[edge.1]))
+            .collect();
+        edge_lengths.sort_unstable();
+        let mut degrees = self.vertex_degrees();
+        degrees.sort_unstable();
+        FigureSignature { edge_lengths, degrees, }
+    }
+
+    /// Cut vertices of the figure graph (removing any of them disconnects the figure), sorted by index:
+    /// exactly the vertices shared by several biconnected components.
+    pub fn articulation_points(&self) -> Vec<usize> {
+        let mut components_count = vec![0; self.vertices.len()];
+        for component in self.biconnected_components() {
+            let mut component_vertices: Vec<_> = component.iter()
+                .flat_map(|edge| vec![edge.0, edge.1])
+                .collect();
+            component_vertices.sort_unstable();
+            component_vertices.dedup();
+            for vertex in component_vertices {
+                components_count[vertex] += 1;
+            }
+        }
+        (0 .. self.vertices.len()).filter(|&vertex| components_count[vertex] > 1).collect()
+    }
+
+    /// Edges of the figure split into biconnected components (no single vertex removal disconnects a component),
+    /// components share articulation points only. A bridge edge is a component on its own.
+    /// Components are ordered by their first edge index and keep edges in figure order.
+    pub fn biconnected_components(&self) -> Vec<Vec<Edge>> {
+        let vertices_count = self.vertices.len();
+        let mut adjacency = vec![Vec::new(); vertices_count];
+        for (edge_index, edge) in self.edges.iter().enumerate() {
+            adjacency[edge.0].push((edge.1, edge_index));
+            adjacency[edge.1].push((edge.0, edge_index));
+        }
+
+        let mut discovered = vec![usize::MAX; vertices_count];
+        let mut low = vec![0; vertices_count];
+        let mut parent_edge = vec![usize::MAX; vertices_count];
+        let mut edge_stack = Vec::new();
+        let mut components = Vec::new();
+        let mut timer = 0;
+        for root in 0 .. vertices_count {
+            if discovered[root] != usize::MAX {
+                continue;
+            }
+            discovered[root] = timer;
+            low[root] = timer;
+            timer += 1;
+            // iterative dfs: (vertex, next adjacency index to visit)
+            let mut stack = vec![(root, 0)];
+            while let Some(top) = stack.last_mut() {
+                let vertex = top.0;
+                if top.1 < adjacency[vertex].len() {
+                    let (next, edge_index) = adjacency[vertex][top.1];
+                    top.1 += 1;
+                    if discovered[next] == usize::MAX {
+                        parent_edge[next] = edge_index;
+                        discovered[next] = timer;
+                        low[next] = timer;
+                        timer += 1;
+                        edge_stack.push(edge_index);
+                        stack.push((next, 0));
+                    } else if edge_index != parent_edge[vertex] && discovered[next] < discovered[vertex] {
+                        // back edge to an ancestor, seen once from the lower end
+                        low[vertex] = low[vertex].min(discovered[next]);
+                        edge_stack.push(edge_index);
+                    }
+                } else {
+                    stack.pop();
+                    if let Some(&(up, _)) = stack.last() {
+                        low[up] = low[up].min(low[vertex]);
+                        if low[vertex] >= discovered[up] {
+                            // `up` separates the subtree of `vertex`: its edges form a component
+                            let mut component = Vec::new();
+                            while let Some(edge_index) = edge_stack.pop() {
+                                component.push(edge_index);
+                                if edge_index == parent_edge[vertex] {
+                                    break;
+                                }
+                            }
+                            component.sort_unstable();
+                            components.push(component);
+                        }
+                    }
+                }
+            }
+        }
+
+        components.sort_unstable();
+        components.into_iter()
+            .map(|component| component.into_iter().map(|edge_index| self.edges[edge_index]).collect())
+            .collect()
+    }
+
+    /// True if the figure graph is generically rigid in 2D (Laman condition checked with the (2, 3) pebble game):
+    /// it has `2n - 3` independent edges, so a pose may only rotate, reflect or translate the figure.
+    /// Degenerate placements (like collinear vertices) are not taken into account.
+    pub fn is_rigid(&self) -> bool {
+        let vertices_count = self.vertices.len();
+        if vertices_count < 2 {
+            return true;
+        }
+
+        // tries to bring a free pebble to `from` reversing the path it travels, without touching `other`
+        fn collect_pebble(from: usize, other: usize, pebbles: &mut [usize], directed: &mut [Vec<usize>]) -> bool {
+            let mut parent = vec![usize::MAX; pebbles.len()];
+            parent[from] = from;
+            parent[other] = other;
+            let mut stack = vec![from];
+            while let Some(vertex) = stack.pop() {
+                for &next in &directed[vertex] {
+                    if parent[next] != usize::MAX {
+                        continue;
+                    }
+                    parent[next] = vertex;
+                    if pebbles[next] == 0 {
+                        stack.push(next);
+                        continue;
+                    }
+                    pebbles[next] -= 1;
+                    pebbles[from] += 1;
+                    let mut head = next;
+                    while head != from {
+                        let tail = parent[head];
+                        let position = directed[tail].iter().position(|&target| target == head).unwrap();
+                        directed[tail].swap_remove(position);
+                        directed[head].push(tail);
+                        head = tail;
+                    }
+                    return true;
+                }
+            }
+            false
+        }
+
+        let mut pebbles = vec![2; vertices_count];
+        let mut directed = vec![Vec::new(); vertices_count];
+        let mut independent_edges = 0;
+        for edge in &self.edges {
+            let (a, b) = (edge.0, edge.1);
+            if a == b {
+                continue;
+            }
+            while pebbles[a] + pebbles[b] < 4 {
+                let collected = (pebbles[a] < 2 && collect_pebble(a, b, &mut pebbles, &mut directed))
+                    || (pebbles[b] < 2 && collect_pebble(b, a, &mut pebbles, &mut directed));
+                if !collected {
+                    break;
+                }
+            }
+            if pebbles[a] + pebbles[b] == 4 {
+                pebbles[a] -= 1;
+                directed[a].push(b);
+                independent_edges += 1;
+            }
+        }
+        independent_edges == 2 * vertices_count - 3
+    }
+
+    /// Minimum enclosing circle of the figure vertices: center rounded to the grid and radius covering all vertices from it.
+    pub fn bounding_circle(&self) -> (Point, f64) {
+        let points: Vec<_> = self.vertices.iter()
+            .map(|vertex| (vertex.0 as f64, vertex.1 as f64))
+            .collect();
+        let mut circle = match points.first() {
+            None =>
+                return (Point(0, 0), 0.0),
+            Some(&point) =>
+                (point, 0.0),
+        };
+        for i in 1 .. points.len() {
+            if circle_contains(circle, points[i]) {
+                continue;
+            }
+            circle = (points[i], 0.0);
+            for j in 0 .. i {
+                if circle_contains(circle, points[j]) {
+                    continue;
+                }
+                circle = circle_from_two(points[i], points[j]);
+                for k in 0 .. j {
+                    if !circle_contains(circle, points[k]) {
+                        circle = circle_from_three(points[i], points[j], points[k]);
+                    }
+                }
+            }
+        }
+
+        let center = Point(circle.0.0.round() as i64, circle.0.1.round() as i64);
+        let radius = self.vertices.iter()
+            .map(|vertex| (distance(&center, vertex) as f64).sqrt())
+            .fold(0.0, f64::max);
+        (center, radius)
+    }
+}
+
+fn read_u64<R>(reader: &mut R) -> Result<u64, ReadBinaryError> where R: io::Read {
+    let mut bytes = [0; 8];
+    reader.read_exact(&mut bytes)
+        .map_err(ReadBinaryError::Read)?;
+    Ok(u64::from_le_bytes(bytes))
+}
+
+fn read_u8<R>(reader: &mut R) -> Result<u8, ReadBinaryError> where R: io::Read {
+    let mut bytes = [0; 1];
+    reader.read_exact(&mut bytes)
+        .map_err(ReadBinaryError::Read)?;
+    Ok(bytes[0])
+}
+
+type Circle = ((f64, f64), f64);
+
+fn circle_contains(circle: Circle, point: (f64, f64)) -> bool {
+    let ((cx, cy), radius) = circle;
+    ((point.0 - cx).powi(2) + (point.1 - cy).powi(2)).sqrt() <= radius + 1e-9
+}
+
+fn circle_from_two(a: (f64, f64), b: (f64, f64)) -> Circle {
+    let center = ((a.0 + b.0) / 2.0, (a.1 + b.1) / 2.0);
+    (center, ((a.0 - b.0).powi(2) + (a.1 - b.1).powi(2)).sqrt() / 2.0)
+}
+
+fn circle_from_three(a: (f64, f64), b: (f64, f64), c: (f64, f64)) -> Circle {
+    let d = 2.0 * (a.0 * (b.1 - c.1) + b.0 * (c.1 - a.1) + c.0 * (a.1 - b.1));
+    if d.abs() < 1e-12 {
+        // collinear points: the farthest pair spans the circle
+        return [circle_from_two(a, b), circle_from_two(a, c), circle_from_two(b, c)]
+            .iter()
+            .cloned()
+            .fold(((0.0, 0.0), 0.0), |best, circle| if circle.1 > best.1 { circle } else { best });
+    }
+    let sq_a = a.0 * a.0 + a.1 * a.1;
+    let sq_b = b.0 * b.0 + b.1 * b.1;
+    let sq_c = c.0 * c.0 + c.1 * c.1;
+    let center = (
+        (sq_a * (b.1 - c.1) + sq_b * (c.1 - a.1) + sq_c * (a.1 - b.1)) / d,
+        (sq_a * (c.0 - b.0) + sq_b * (a.0 - c.0) + sq_c * (b.0 - a.0)) / d,
+    );
+    (center, ((a.0 - center.0).powi(2) + (a.1 - center.1).powi(2)).sqrt())
 }
 
 impl GeoFigure {
@@ -438,30 +1220,244 @@ impl GeoFigure {
 }
 
 impl Pose {
+    /// Reorders vertices of an imported pose listing them in some other order, so that every figure edge connects
+    /// pose vertices at the right distance (within `problem` epsilon, bonuses are not taken into account).
+    ///
+    /// The order is searched by backtracking over the figure vertices in breadth first order. Orders differing by a figure
+    /// symmetry (same edges up to vertex numbering) are equivalent and the first one found is kept, other different
+    /// matching orders make the result `ReorderError::Ambiguous`. A pose already in a matching order is left untouched.
+    /// The search gives up with `ReorderError::SearchBudgetExhausted` after `REORDER_SEARCH_NODES_MAX` assignments.
+    pub fn reorder_to_match(&mut self, problem: &Problem) -> Result<(), ReorderError> {
+        let figure = &problem.figure;
+        if self.vertices.len() != figure.vertices.len() {
+            return Err(ReorderError::VerticesCountMismatch { expected: figure.vertices.len(), provided: self.vertices.len(), });
+        }
+        let identity: Vec<_> = (0 .. self.vertices.len()).collect();
+        let mut matcher = OrderMatcher::new(problem, &self.vertices);
+        if matcher.fits(&identity) {
+            return Ok(());
+        }
+
+        let order = matcher.search_order();
+        matcher.search(&order, 0);
+        if matcher.exhausted {
+            return Err(ReorderError::SearchBudgetExhausted);
+        }
+        if matcher.ambiguous {
+            return Err(ReorderError::Ambiguous);
+        }
+        let assignment = matcher.found
+            .ok_or(ReorderError::NoMatchingOrder)?;
+        self.vertices = assignment.iter()
+            .map(|&pose_index| self.vertices[pose_index])
+            .collect();
+        Ok(())
+    }
+
+    /// Files ending with `.gz` are decompressed on the fly, `.posebin` files are read with `Pose::read_binary`.
     pub fn from_file<P>(filename: P) -> Result<Pose, FromFileError> where P: AsRef<Path> {
-        let file = fs::File::open(filename)
+        if is_pose_binary(filename.as_ref()) {
+            let file = fs::File::open(filename)
+                .map_err(FromFileError::OpenFile)?;
+            return Pose::decode_binary(&mut io::BufReader::new(file))
+                .map_err(FromFileError::ReadBinary);
+        }
+        let reader = open_reader(filename.as_ref())
             .map_err(FromFileError::OpenFile)?;
-        let reader = io::BufReader::new(file);
         serde_json::from_reader(reader)
             .map_err(FromFileError::Deserialize)
     }
 
+    /// Files ending with `.gz` (like `12.pose.gz`) are written gzipped, `.posebin` files like with `Pose::write_binary`.
     pub fn write_to_file<P>(&self, filename: P) -> Result<(), WriteFileError> where P: AsRef<Path> {
+        create_parent_directory(filename.as_ref())
+            .map_err(WriteFileError::CreateDirectory)?;
+        let gzipped = is_gzipped(filename.as_ref());
+        let binary = is_pose_binary(filename.as_ref());
         let file = fs::File::create(filename)
             .map_err(WriteFileError::CreateFile)?;
+        if binary {
+            let mut file = file;
+            return file.write_all(&self.encode_binary())
+                .map_err(WriteFileError::WriteBinary);
+        }
         let writer = io::BufWriter::new(file);
-        serde_json::to_writer(writer, self)
-            .map_err(WriteFileError::Serialize)
+        if gzipped {
+            let mut encoder = GzEncoder::new(writer, Compression::default());
+            serde_json::to_writer(&mut encoder, self)
+                .map_err(WriteFileError::Serialize)?;
+            encoder.finish()
+                .map_err(WriteFileError::Compress)?;
+            Ok(())
+        } else {
+            serde_json::to_writer(writer, self)
+                .map_err(WriteFileError::Serialize)
+        }
     }
 
-    pub fn bonus(&self) -> Option<PoseBonus> {
-        self.bonuses.as_ref()
-            .map_or(None, |bonus_vec| Some(bonus_vec[0]))
+    /// Compact binary form: `POSE_BINARY_MAGIC` and `POSE_BINARY_VERSION`, little endian `u64` vertices count followed
+    /// by `i64` coordinates, then `u8` bonuses presence flag, `u64` bonuses count and a `u8` tag with `u64` fields for every bonus.
+    pub fn write_binary<P>(&self, filename: P) -> Result<(), WriteBinaryError> where P: AsRef<Path> {
+        create_parent_directory(filename.as_ref())
+            .map_err(WriteBinaryError::CreateDirectory)?;
+        let mut file = fs::File::create(filename)
+            .map_err(WriteBinaryError::CreateFile)?;
+        file.write_all(&self.encode_binary())
+            .map_err(WriteBinaryError::Write)
     }
-}
 
-impl From<Point> for geo::Point<i64> {
-    fn from(point: Point) -> Self {
+    fn encode_binary(&self) -> Vec<u8> {
+        let mut buffer = Vec::with_capacity(POSE_BINARY_MAGIC.len() + 1 + 8 + self.vertices.len() * 16 + 9);
+        buffer.extend_from_slice(&POSE_BINARY_MAGIC);
+        buffer.push(POSE_BINARY_VERSION);
+        buffer.extend_from_slice(&(self.vertices.len() as u64).to_le_bytes());
+        for vertex in &self.vertices {
+            buffer.extend_from_slice(&vertex.0.to_le_bytes());
+            buffer.extend_from_slice(&vertex.1.to_le_bytes());
+        }
+        match &self.bonuses {
+            None =>
+                buffer.push(0),
+            Some(bonuses) => {
+                buffer.push(1);
+                buffer.extend_from_slice(&(bonuses.len() as u64).to_le_bytes());
+                for bonus in bonuses {
+                    let (tag, problem) = match *bonus {
+                        PoseBonus::BreakALeg { problem, .. } =>
+                            (0, problem),
+                        PoseBonus::Globalist { problem, } =>
+                            (1, problem),
+                        PoseBonus::Wallhack { problem, } =>
+                            (2, problem),
+                        PoseBonus::Superflex { problem, } =>
+                            (3, problem),
+                    };
+                    buffer.push(tag);
+                    buffer.extend_from_slice(&(problem.0 as u64).to_le_bytes());
+                    if let PoseBonus::BreakALeg { edge, .. } = bonus {
+                        buffer.extend_from_slice(&(edge.0 as u64).to_le_bytes());
+                        buffer.extend_from_slice(&(edge.1 as u64).to_le_bytes());
+                    }
+                }
+            },
+        }
+        buffer
+    }
+
+    pub fn read_binary<P>(filename: P) -> Result<Pose, ReadBinaryError> where P: AsRef<Path> {
+        let file = fs::File::open(filename)
+            .map_err(ReadBinaryError::OpenFile)?;
+        Pose::decode_binary(&mut io::BufReader::new(file))
+    }
+
+    fn decode_binary<R>(reader: &mut R) -> Result<Pose, ReadBinaryError> where R: io::Read {
+        let mut magic = [0; POSE_BINARY_MAGIC.len()];
+        reader.read_exact(&mut magic)
+            .map_err(ReadBinaryError::Read)?;
+        if magic != POSE_BINARY_MAGIC {
+            return Err(ReadBinaryError::NotBinaryPose);
+        }
+        match read_u8(reader)? {
+            POSE_BINARY_VERSION =>
+                (),
+            version =>
+                return Err(ReadBinaryError::UnsupportedVersion(version)),
+        }
+
+        let vertices_count = read_u64(reader)?;
+        let mut vertices = Vec::new();
+        for _ in 0 .. vertices_count {
+            let x = read_u64(reader)? as i64;
+            let y = read_u64(reader)? as i64;
+            vertices.push(Point(x, y));
+        }
+
+        let bonuses = match read_u8(reader)? {
+            0 =>
+                None,
+            _ => {
+                let bonuses_count = read_u64(reader)?;
+                let mut bonuses = Vec::new();
+                for _ in 0 .. bonuses_count {
+                    let tag = read_u8(reader)?;
+                    let problem = ProblemId(read_u64(reader)? as usize);
+                    bonuses.push(match tag {
+                        0 => {
+                            let edge = Edge(read_u64(reader)? as usize, read_u64(reader)? as usize);
+                            PoseBonus::BreakALeg { problem, edge, }
+                        },
+                        1 =>
+                            PoseBonus::Globalist { problem, },
+                        2 =>
+                            PoseBonus::Wallhack { problem, },
+                        3 =>
+                            PoseBonus::Superflex { problem, },
+                        tag =>
+                            return Err(ReadBinaryError::UnknownBonusTag(tag)),
+                    });
+                }
+                Some(bonuses)
+            },
+        };
+
+        Ok(Pose { vertices, bonuses, })
+    }
+
+    /// Bonus used for scoring (the first entry), `None` for a missing or empty bonuses list.
+    pub fn bonus(&self) -> Option<PoseBonus> {
+        self.bonuses_slice().first().copied()
+    }
+
+    /// All bonus entries, empty for `None`.
+    pub fn bonuses_slice(&self) -> &[PoseBonus] {
+        self.bonuses.as_deref().unwrap_or(&[])
+    }
+
+    /// Drops bonus entries scoring cannot use: a pose may use a single bonus, and scoring looks at the first entry only,
+    /// so duplicates of the same bonus and any other entries are removed. An empty list becomes `None`.
+    pub fn canonicalize_bonuses(&mut self) {
+        self.bonuses = self.bonuses.take()
+            .and_then(|bonuses| bonuses.first().cloned())
+            .map(|bonus| vec![bonus]);
+    }
+
+    /// Stable seahash fingerprint of the pose: bonuses are compared as a set, so their order
+    /// (as well as `None` versus an empty list) does not change it.
+    pub fn fingerprint(&self) -> u64 {
+        let mut bonuses: Vec<_> = self.bonuses.iter()
+            .flatten()
+            .map(|bonus| match *bonus {
+                PoseBonus::BreakALeg { problem, edge, } =>
+                    (0, problem.0, edge.0, edge.1),
+                PoseBonus::Globalist { problem, } =>
+                    (1, problem.0, 0, 0),
+                PoseBonus::Wallhack { problem, } =>
+                    (2, problem.0, 0, 0),
+                PoseBonus::Superflex { problem, } =>
+                    (3, problem.0, 0, 0),
+            })
+            .collect();
+        bonuses.sort_unstable();
+
+        let mut hasher = seahash::SeaHasher::new();
+        hasher.write_u64(self.vertices.len() as u64);
+        for vertex in &self.vertices {
+            hasher.write_i64(vertex.0);
+            hasher.write_i64(vertex.1);
+        }
+        hasher.write_u64(bonuses.len() as u64);
+        for (kind, problem, edge_from, edge_to) in bonuses {
+            hasher.write_u8(kind);
+            hasher.write_u64(problem as u64);
+            hasher.write_u64(edge_from as u64);
+            hasher.write_u64(edge_to as u64);
+        }
+        hasher.finish()
+    }
+}
+
+impl From<Point> for geo::Point<i64> {
+    fn from(point: Point) -> Self {
         geo::Point(geo::Coordinate::<i64> { x: point.0, y: point.1 })
     }
 }
@@ -496,6 +1492,21 @@ impl From<&Point> for geo::Coordinate<i64> {
     }
 }
 
+impl InvalidEdge for geo::Polygon<f64> {
+    fn is_edge_invalid(&self, edge_from: Point, edge_to: Point) -> bool {
+        let geo_edge = geo::Line {
+            start: geo::Coordinate::from(edge_from),
+            end: geo::Coordinate::from(edge_to),
+        };
+        !(self.contains(&geo_edge) || self.exterior().contains(&geo_edge))
+    }
+
+    fn is_point_outside(&self, point: Point) -> bool {
+        let geo_point = geo::Coordinate::from(point);
+        !(self.contains(&geo_point) || self.exterior().contains(&geo_point))
+    }
+}
+
 impl geo::algorithm::contains::Contains<Point> for geo::Polygon<i64> {
     fn contains(&self, point: &Point) -> bool {
         let geo_point = geo::Coordinate::from(point);
@@ -505,8 +1516,170 @@ impl geo::algorithm::contains::Contains<Point> for geo::Polygon<i64> {
     }
 }
 
+/// Squared distance between points, computed in `i128` and saturated to `i64::MAX` for far apart points.
 pub fn distance(p: &Point, q: &Point) -> i64 {
-    (p.0 - q.0) * (p.0 - q.0) + (p.1 - q.1) * (p.1 - q.1)
+    let dx = p.0 as i128 - q.0 as i128;
+    let dy = p.1 as i128 - q.1 as i128;
+    (dx * dx + dy * dy).min(i64::MAX as i128) as i64
+}
+
+/// True if `point` lies on the closed segment `[start, end]`.
+fn is_on_segment(point: Point, start: Point, end: Point) -> bool {
+    let cross = (end.0 - start.0) as i128 * (point.1 - start.1) as i128
+        - (end.1 - start.1) as i128 * (point.0 - start.0) as i128;
+    cross == 0
+        && point.0 >= start.0.min(end.0) && point.0 <= start.0.max(end.0)
+        && point.1 >= start.1.min(end.1) && point.1 <= start.1.max(end.1)
+}
+
+fn nearest_pose_vertex(hole_vert: &Point, pose_vertices: &[Point]) -> Option<(usize, i64)> {
+    pose_vertices.iter()
+        .enumerate()
+        .map(|(index, pose_vert)| (index, distance(hole_vert, pose_vert)))
+        .min_by_key(|&(index, sq_dist)| (sq_dist, index))
+}
+
+fn elapsed_ns(start: Instant) -> u64 {
+    start.elapsed().as_nanos() as u64
+}
+
+/// Backtracking state of `Pose::reorder_to_match`: `assignment[figure_index]` is the pose vertex put in its place.
+struct OrderMatcher<'a> {
+    problem: &'a Problem,
+    pose_vertices: &'a [Point],
+    adjacency: Vec<Vec<usize>>,
+    assignment: Vec<usize>,
+    used: Vec<bool>,
+    found: Option<Vec<usize>>,
+    ambiguous: bool,
+    /// assignments the search may still try
+    nodes_left: usize,
+    /// set when the search stops for lack of `nodes_left`
+    exhausted: bool,
+}
+
+impl<'a> OrderMatcher<'a> {
+    fn new(problem: &'a Problem, pose_vertices: &'a [Point]) -> OrderMatcher<'a> {
+        let mut adjacency = vec![Vec::new(); pose_vertices.len()];
+        for edge in &problem.figure.edges {
+            adjacency[edge.0].push(edge.1);
+            adjacency[edge.1].push(edge.0);
+        }
+        OrderMatcher {
+            problem,
+            pose_vertices,
+            adjacency,
+            assignment: vec![usize::MAX; pose_vertices.len()],
+            used: vec![false; pose_vertices.len()],
+            found: None,
+            ambiguous: false,
+            nodes_left: REORDER_SEARCH_NODES_MAX,
+            exhausted: false,
+        }
+    }
+
+    /// Figure vertices in breadth first order, so every vertex except component roots has an earlier neighbour.
+    fn search_order(&self) -> Vec<usize> {
+        let mut order = Vec::with_capacity(self.adjacency.len());
+        let mut visited = vec![false; self.adjacency.len()];
+        for root in 0 .. self.adjacency.len() {
+            if visited[root] {
+                continue;
+            }
+            visited[root] = true;
+            let start = order.len();
+            order.push(root);
+            let mut cursor = start;
+            while cursor < order.len() {
+                let vertex = order[cursor];
+                cursor += 1;
+                for &next in &self.adjacency[vertex] {
+                    if !visited[next] {
+                        visited[next] = true;
+                        order.push(next);
+                    }
+                }
+            }
+        }
+        order
+    }
+
+    fn edge_fits(&self, figure_from: usize, figure_to: usize, pose_from: usize, pose_to: usize) -> bool {
+        let d_before = distance(&self.problem.figure.vertices[figure_from], &self.problem.figure.vertices[figure_to]);
+        let d_after = distance(&self.pose_vertices[pose_from], &self.pose_vertices[pose_to]);
+        stretch_ratio(d_before, d_after) <= self.problem.epsilon_fraction()
+    }
+
+    fn fits(&self, assignment: &[usize]) -> bool {
+        self.problem.figure.edges.iter()
+            .all(|edge| self.edge_fits(edge.0, edge.1, assignment[edge.0], assignment[edge.1]))
+    }
+
+    /// Pose edges produced by `assignment` as sorted segments, equal for orders differing by a figure symmetry.
+    fn segments(&self, assignment: &[usize]) -> Vec<((i64, i64), (i64, i64))> {
+        let mut segments: Vec<_> = self.problem.figure.edges.iter()
+            .map(|edge| {
+                let from = self.pose_vertices[assignment[edge.0]];
+                let to = self.pose_vertices[assignment[edge.1]];
+                let (from, to) = ((from.0, from.1), (to.0, to.1));
+                if from <= to { (from, to) } else { (to, from) }
+            })
+            .collect();
+        segments.sort_unstable();
+        segments
+    }
+
+    fn search(&mut self, order: &[usize], depth: usize) {
+        if self.ambiguous || self.exhausted {
+            return;
+        }
+        if depth == order.len() {
+            match &self.found {
+                None =>
+                    self.found = Some(self.assignment.clone()),
+                Some(found) =>
+                    if self.segments(found) != self.segments(&self.assignment) {
+                        self.ambiguous = true;
+                    },
+            }
+            return;
+        }
+
+        let figure_index = order[depth];
+        for pose_index in 0 .. self.pose_vertices.len() {
+            if self.used[pose_index] {
+                continue;
+            }
+            let fits = self.adjacency[figure_index].iter()
+                .filter(|&&neighbour| self.assignment[neighbour] != usize::MAX)
+                .all(|&neighbour| self.edge_fits(figure_index, neighbour, pose_index, self.assignment[neighbour]));
+            if !fits {
+                continue;
+            }
+            if self.nodes_left == 0 {
+                self.exhausted = true;
+                return;
+            }
+            self.nodes_left -= 1;
+            self.assignment[figure_index] = pose_index;
+            self.used[pose_index] = true;
+            self.search(order, depth + 1);
+            self.assignment[figure_index] = usize::MAX;
+            self.used[pose_index] = false;
+            if self.ambiguous || self.exhausted {
+                return;
+            }
+        }
+    }
+}
+
+/// Relative stretching `|d_after / d_before - 1|` of an edge given its squared lengths. A zero-length original edge
+/// may only stay zero-length: the ratio is 0 for it and infinite otherwise, so it is never accepted for any epsilon.
+pub fn stretch_ratio(d_before: i64, d_after: i64) -> f64 {
+    if d_before == 0 {
+        return if d_after == 0 { 0.0 } else { f64::INFINITY };
+    }
+    ((d_after as f64) / (d_before as f64) - 1_f64).abs()
 }
 
 #[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
@@ -520,7 +1693,7 @@ impl SquareRing {
         let outer_box = &self.0;
         let inner_box = &self.1;
 
-        let capacity = ((outer_box.0.0 - outer_box.1.0).abs() * (outer_box.0.1 - outer_box.1.1).abs()) as usize;
+        let capacity = (outer_box.0.0 - outer_box.1.0).abs().saturating_mul((outer_box.0.1 - outer_box.1.1).abs()) as usize;
         let mut set = HashSet::with_capacity(capacity);
 
         for x in cmp::max(0, cmp::min(outer_box.0.0, outer_box.1.0))..=cmp::max(outer_box.0.0, outer_box.1.0) {
@@ -538,13 +1711,30 @@ impl SquareRing {
         set
     }
 
+    /// Same points as `point_set` yielded lazily, so callers filtering the ring do not have to allocate all of it.
+    pub fn points_iter(&self) -> impl Iterator<Item = Point> {
+        let outer_box = self.0;
+        let inner_box = self.1;
+        let inner_x = cmp::min(inner_box.0.0, inner_box.1.0) + 1 .. cmp::max(inner_box.0.0, inner_box.1.0);
+        let inner_y = cmp::min(inner_box.0.1, inner_box.1.1) + 1 .. cmp::max(inner_box.0.1, inner_box.1.1);
+
+        (cmp::max(0, cmp::min(outer_box.0.0, outer_box.1.0)) ..= cmp::max(outer_box.0.0, outer_box.1.0))
+            .flat_map(move |x| {
+                let inner_y = inner_y.clone();
+                let skip_inner = inner_x.contains(&x);
+                (cmp::max(0, cmp::min(outer_box.0.1, outer_box.1.1)) ..= cmp::max(outer_box.0.1, outer_box.1.1))
+                    .filter(move |y| !(skip_inner && inner_y.contains(y)))
+                    .map(move |y| Point(x, y))
+            })
+    }
+
     pub fn point_set_within_hole(&self, hole: &Vec<Point>) -> HashSet<Point> {
         let geo_hole = geo::Polygon::new(hole.clone().into(), vec![]);
 
         let outer_box = &self.0;
         let inner_box = &self.1;
 
-        let capacity = ((outer_box.0.0 - outer_box.1.0).abs() * (outer_box.0.1 - outer_box.1.1).abs()) as usize;
+        let capacity = (outer_box.0.0 - outer_box.1.0).abs().saturating_mul((outer_box.0.1 - outer_box.1.1).abs()) as usize;
         let mut set = HashSet::with_capacity(capacity);
 
         for x in cmp::max(0, cmp::min(outer_box.0.0, outer_box.1.0))..=cmp::max(outer_box.0.0, outer_box.1.0) {
@@ -572,6 +1762,36 @@ impl SquareRing {
 
 
 
+/// True for `.gz` files, which are read and written gzip compressed.
+fn is_gzipped(filename: &Path) -> bool {
+    filename.extension() == Some(OsStr::new("gz"))
+}
+
+/// True for `.posebin` files, which are read and written in the binary pose format.
+fn is_pose_binary(filename: &Path) -> bool {
+    filename.extension() == Some(OsStr::new(POSE_BINARY_EXTENSION))
+}
+
+/// Buffered reader of `filename`, gzip decoding is added for `.gz` files.
+fn open_reader(filename: &Path) -> Result<Box<dyn io::Read>, io::Error> {
+    let file = fs::File::open(filename)?;
+    if is_gzipped(filename) {
+        Ok(Box::new(io::BufReader::new(GzDecoder::new(file))))
+    } else {
+        Ok(Box::new(io::BufReader::new(file)))
+    }
+}
+
+/// Creates missing parent directories of `filename`, so a pose can be written on the first run without `./poses`.
+pub fn create_parent_directory(filename: &Path) -> Result<(), io::Error> {
+    match filename.parent() {
+        Some(directory) if !directory.as_os_str().is_empty() =>
+            fs::create_dir_all(directory),
+        Some(..) | None =>
+            Ok(()),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -581,12 +1801,12 @@ mod tests {
     #[test]
     fn geo_type_conversion_test() {
         let problem = Problem {
-            epsilon: 0,
             hole: vec![ Point(0, 0), Point(10, 0), Point(10, 10), Point(0, 10) ],
             figure: Figure {
                 edges: vec![],
                 vertices: vec![],
             },
+            epsilon: 0,
             bonuses: None,
         };
 
@@ -600,12 +1820,12 @@ mod tests {
     #[test]
     fn polygon_contains_test() {
         let problem1 = Problem {
-            epsilon: 0,
             hole: vec![ Point(0, 0), Point(10, 0), Point(10, 10), Point(0, 10) ],
             figure: Figure {
                 edges: vec![],
                 vertices: vec![],
             },
+            epsilon: 0,
             bonuses: None,
         };
 
@@ -698,6 +1918,52 @@ mod tests {
         // TODO: add more tests
     }
 
+    #[test]
+    fn malformed_inputs_do_not_panic() {
+        let problem: Problem = serde_json::from_str(PROBLEM_13_JSON).unwrap();
+        let vertices = vec![Point(20, 0), Point(40, 20), Point(0, 20), Point(20, 40)];
+        let pose: Pose = serde_json::from_str(r#"{"vertices":[[20,0],[40,20],[0,20],[20,40]],"bonuses":[]}"#).unwrap();
+        assert_eq!(pose.bonus(), None);
+        assert_eq!(problem.score_pose(&pose), Ok(0));
+
+        let break_a_leg = PoseBonus::BreakALeg { problem: ProblemId(1), edge: Edge(0, 1), };
+        assert_eq!(problem.score_vertices(&vertices, Some(break_a_leg)), Err(PoseValidationError::UnsupportedBonus(break_a_leg)));
+
+        let mut empty_figure = problem.clone();
+        empty_figure.figure = Figure { edges: vec![], vertices: vec![], };
+        assert_eq!(empty_figure.possible_rotations(), Vec::<f64>::new());
+    }
+
+    #[test]
+    fn pose_bonus_of_empty_list() {
+        let pose = Pose { vertices: vec![Point(1, 2)], bonuses: Some(vec![]), };
+        assert_eq!(pose.bonus(), None);
+        assert!(pose.bonuses_slice().is_empty());
+
+        let wallhack = PoseBonus::Wallhack { problem: ProblemId(5), };
+        let pose = Pose { vertices: vec![Point(1, 2)], bonuses: Some(vec![wallhack]), };
+        assert_eq!(pose.bonus(), Some(wallhack));
+        assert_eq!(pose.bonuses_slice(), &[wallhack]);
+        assert!(Pose { vertices: vec![], bonuses: None, }.bonuses_slice().is_empty());
+    }
+
+    #[test]
+    fn canonicalize_bonuses() {
+        let globalist = PoseBonus::Globalist { problem: ProblemId(3), };
+        let mut pose = Pose {
+            vertices: vec![Point(1, 2)],
+            bonuses: Some(vec![globalist, globalist, PoseBonus::Wallhack { problem: ProblemId(5), }]),
+        };
+        let score_bonus = pose.bonus();
+        pose.canonicalize_bonuses();
+        assert_eq!(pose.bonuses, Some(vec![globalist]));
+        assert_eq!(pose.bonus(), score_bonus);
+
+        let mut pose = Pose { vertices: vec![Point(1, 2)], bonuses: Some(vec![]), };
+        pose.canonicalize_bonuses();
+        assert_eq!(pose.bonuses, None);
+    }
+
     #[test]
     fn score_vertices_check_hole() {
         let problem: Problem = serde_json::from_str(PROBLEM_13_JSON).unwrap();
@@ -716,6 +1982,41 @@ mod tests {
         // TODO: add more tests
     }
 
+    #[test]
+    fn possible_rotations_prebuilt_hole() {
+        let problem: Problem = serde_json::from_str(PROBLEM_13_JSON).unwrap();
+        let pose: Pose = serde_json::from_str(POSE_13_SCORE_0_JSON).unwrap();
+
+        let per_angle_hole_rotations = |vertices: &Vec<Point>| {
+            let mut figure = problem.figure.clone();
+            figure.vertices = vertices.clone();
+            let geo_figure = figure.export_to_geo().unwrap();
+            let mut angles = vec![];
+            for angle in 1..360 {
+                let mut new_geo_figure = geo_figure.clone();
+                new_geo_figure.rotate_around_centroid_mut(angle as f64);
+                let mut new_figure = problem.figure.clone();
+                new_figure.import_from_geo(new_geo_figure.points).unwrap();
+                match problem.score_vertices_in(&problem.hole_polygon_f64(), &new_figure.vertices, None) {
+                    Err(PoseValidationError::BrokenEdgesFound { .. }) |
+                    Err(PoseValidationError::VerticeCountMismatch) => continue,
+                    _ => { angles.push(angle as f64); }
+                }
+            }
+            angles
+        };
+
+        let expected_figure = per_angle_hole_rotations(&problem.figure.vertices);
+        let expected_pose = per_angle_hole_rotations(&pose.vertices);
+
+        let provided_figure = problem.possible_rotations();
+        let provided_pose = problem.possible_rotations_for_vertices(&pose.vertices);
+
+        assert_eq!(provided_figure, expected_figure);
+        assert_eq!(provided_pose, expected_pose);
+        assert!(!provided_pose.is_empty());
+    }
+
     #[test]
     fn score_vertices_check_stretching_broken_pose_task_50() {
         let problem: Problem = serde_json::from_str(
@@ -759,6 +2060,582 @@ mod tests {
         assert!(problem.score_vertices_check_hole(&pose_vertices, Some(PoseBonus::Wallhack { problem: ProblemId(0), })).is_err());
     }
 
+    #[test]
+    fn wallhack_allowed_outside() {
+        let problem: Problem = serde_json::from_str(
+            r#"{"bonuses":[],"hole":[[21,35],[0,22],[26,14],[0,8],[15,0],[45,0],[52,12],[52,48],[51,60],[49,86],[38,73],[17,85],[31,92],[24,103],[2,103],[0,85],[0,52],[8,41]],"epsilon":17065,"figure":{"edges":[[0,1],[0,2],[1,2]],"vertices":[[0,75],[7,0],[10,26]]}}"#,
+        ).unwrap();
+        let geo_hole = problem.hole_polygon_f64();
+        let wallhack = Some(PoseBonus::Wallhack { problem: ProblemId(0), });
+        let pose_vertices = vec![
+            Point(0, 48), Point(34, 47), Point(1000, 48),
+        ];
+        assert!(problem.score_vertices_check_hole_allowing(&geo_hole, &pose_vertices, wallhack, 1).is_err());
+        assert_eq!(problem.score_vertices_check_hole_allowing(&geo_hole, &pose_vertices, wallhack, 2), Ok(()));
+        assert!(problem.score_vertices_check_hole_allowing(&geo_hole, &pose_vertices, None, 2).is_err());
+    }
+
+    #[test]
+    fn pose_write_creates_directory() {
+        let temp_directory = tempfile::tempdir().unwrap();
+        let directory = temp_directory.path();
+        let pose_file = directory.join("nested").join("poses").join("12.pose");
+        let pose = Pose { vertices: vec![Point(1, 2), Point(3, 4)], bonuses: None, };
+        pose.write_to_file(&pose_file).unwrap();
+        assert_eq!(Pose::from_file(&pose_file).unwrap().vertices, pose.vertices);
+
+        let binary_file = directory.join("binary").join("12.posebin");
+        pose.write_binary(&binary_file).unwrap();
+        assert!(binary_file.exists());
+    }
+
+    #[test]
+    fn pose_gzip_round_trip() {
+        let temp_directory = tempfile::tempdir().unwrap();
+        let directory = temp_directory.path();
+        let pose_file = directory.join("12.pose.gz");
+        let pose = Pose {
+            vertices: vec![Point(1, 2), Point(3, 4), Point(5, 6)],
+            bonuses: Some(vec![PoseBonus::Globalist { problem: ProblemId(7), }]),
+        };
+        pose.write_to_file(&pose_file).unwrap();
+        // gzip magic bytes
+        assert_eq!(&fs::read(&pose_file).unwrap()[.. 2], &[0x1f, 0x8b]);
+        assert_eq!(Pose::from_file(&pose_file).unwrap(), pose);
+
+        let plain_file = directory.join("12.pose");
+        pose.write_to_file(&plain_file).unwrap();
+        assert_eq!(fs::read(&plain_file).unwrap()[0], b'{');
+        assert_eq!(Pose::from_file(&plain_file).unwrap(), pose);
+    }
+
+    #[test]
+    fn pose_binary_round_trip() {
+        let temp_directory = tempfile::tempdir().unwrap();
+        let json = r#"{"vertices":[[21,28],[31,28],[-5,7]],"bonuses":[{"bonus":"GLOBALIST","problem":3},{"bonus":"BREAK_A_LEG","problem":5,"edge":[0,2]}]}"#;
+        for json in &[json, r#"{"vertices":[[1,2]],"bonuses":null}"#] {
+            let pose: Pose = serde_json::from_str(json).unwrap();
+            let binary_file = temp_directory.path().join(format!("12.{}", POSE_BINARY_EXTENSION));
+            pose.write_binary(&binary_file).unwrap();
+            let read_pose = Pose::read_binary(&binary_file).unwrap();
+            assert_eq!(read_pose, pose);
+            assert_eq!(&serde_json::to_string(&read_pose).unwrap(), json);
+        }
+    }
+
+    #[test]
+    fn pose_binary_by_extension() {
+        let temp_directory = tempfile::tempdir().unwrap();
+        let directory = temp_directory.path();
+        let binary_file = directory.join(format!("12.{}", POSE_BINARY_EXTENSION));
+        let pose = Pose {
+            vertices: vec![Point(1, 2), Point(3, 4)],
+            bonuses: Some(vec![PoseBonus::Wallhack { problem: ProblemId(7), }]),
+        };
+        pose.write_to_file(&binary_file).unwrap();
+        let bytes = fs::read(&binary_file).unwrap();
+        assert_eq!(&bytes[.. 4], &POSE_BINARY_MAGIC);
+        assert_eq!(bytes[4], POSE_BINARY_VERSION);
+        assert_eq!(Pose::from_file(&binary_file).unwrap(), pose);
+        assert_eq!(Pose::read_binary(&binary_file).unwrap(), pose);
+
+        // json under the binary extension, a truncated file and a newer version are reported
+        fs::write(&binary_file, serde_json::to_vec(&pose).unwrap()).unwrap();
+        assert!(matches!(Pose::from_file(&binary_file), Err(FromFileError::ReadBinary(ReadBinaryError::NotBinaryPose))));
+        fs::write(&binary_file, &bytes[.. bytes.len() - 3]).unwrap();
+        assert!(matches!(Pose::from_file(&binary_file), Err(FromFileError::ReadBinary(ReadBinaryError::Read(..)))));
+        let mut newer = bytes.clone();
+        newer[4] = POSE_BINARY_VERSION + 1;
+        fs::write(&binary_file, &newer).unwrap();
+        assert!(matches!(Pose::read_binary(&binary_file), Err(ReadBinaryError::UnsupportedVersion(version)) if version == POSE_BINARY_VERSION + 1));
+    }
+
+    #[test]
+    fn figure_signature() {
+        let figure = Figure {
+            edges: vec![ Edge(0, 1), Edge(1, 2), Edge(2, 0), Edge(2, 3) ],
+            vertices: vec![ Point(0, 0), Point(3, 0), Point(0, 4), Point(0, 9) ],
+        };
+        let translated = Figure {
+            edges: figure.edges.clone(),
+            vertices: figure.vertices.iter().map(|point| Point(point.0 + 17, point.1 - 5)).collect(),
+        };
+        assert_eq!(figure.signature(), translated.signature());
+
+        let rotated = Figure {
+            edges: vec![ Edge(3, 2), Edge(2, 1), Edge(1, 3), Edge(1, 0) ],
+            vertices: vec![ Point(-9, 0), Point(-4, 0), Point(0, 3), Point(0, 0) ],
+        };
+        assert_eq!(figure.signature(), rotated.signature());
+
+        let stretched = Figure {
+            edges: figure.edges.clone(),
+            vertices: vec![ Point(0, 0), Point(3, 0), Point(0, 4), Point(0, 10) ],
+        };
+        assert_ne!(figure.signature(), stretched.signature());
+    }
+
+    #[test]
+    fn articulation_points() {
+        let two_triangles = Figure {
+            edges: vec![ Edge(0, 1), Edge(1, 2), Edge(2, 0), Edge(2, 3), Edge(3, 4), Edge(4, 2) ],
+            vertices: vec![ Point(0, 0), Point(2, 0), Point(1, 1), Point(0, 2), Point(2, 2) ],
+        };
+        assert_eq!(two_triangles.articulation_points(), vec![2]);
+        assert_eq!(two_triangles.vertex_degrees(), vec![2, 2, 4, 2, 2]);
+
+        let path = Figure {
+            edges: vec![ Edge(2, 1), Edge(1, 0), Edge(2, 3) ],
+            vertices: vec![ Point(0, 0), Point(1, 0), Point(2, 0), Point(3, 0) ],
+        };
+        assert_eq!(path.articulation_points(), vec![1, 2]);
+    }
+
+    #[test]
+    fn biconnected_components() {
+        let two_triangles = Figure {
+            edges: vec![ Edge(0, 1), Edge(1, 2), Edge(2, 0), Edge(2, 3), Edge(3, 4), Edge(4, 2) ],
+            vertices: vec![ Point(0, 0), Point(2, 0), Point(1, 1), Point(0, 2), Point(2, 2) ],
+        };
+        assert_eq!(
+            two_triangles.biconnected_components(),
+            vec![
+                vec![ Edge(0, 1), Edge(1, 2), Edge(2, 0) ],
+                vec![ Edge(2, 3), Edge(3, 4), Edge(4, 2) ],
+            ],
+        );
+
+        // square with a diagonal and a tail: the tail edges are bridges
+        let square_with_tail = Figure {
+            edges: vec![ Edge(0, 1), Edge(1, 2), Edge(2, 3), Edge(3, 0), Edge(0, 2), Edge(2, 4), Edge(4, 5) ],
+            vertices: vec![ Point(0, 0), Point(1, 0), Point(1, 1), Point(0, 1), Point(2, 2), Point(3, 3) ],
+        };
+        assert_eq!(
+            square_with_tail.biconnected_components(),
+            vec![
+                vec![ Edge(0, 1), Edge(1, 2), Edge(2, 3), Edge(3, 0), Edge(0, 2) ],
+                vec![ Edge(2, 4) ],
+                vec![ Edge(4, 5) ],
+            ],
+        );
+        assert_eq!(square_with_tail.articulation_points(), vec![2, 4]);
+    }
+
+    #[test]
+    fn figure_rigidity() {
+        let triangle = Figure {
+            edges: vec![ Edge(0, 1), Edge(1, 2), Edge(2, 0) ],
+            vertices: vec![ Point(0, 0), Point(2, 0), Point(1, 1) ],
+        };
+        assert!(triangle.is_rigid());
+
+        let mut square = Figure {
+            edges: vec![ Edge(0, 1), Edge(1, 2), Edge(2, 3), Edge(3, 0) ],
+            vertices: vec![ Point(0, 0), Point(2, 0), Point(2, 2), Point(0, 2) ],
+        };
+        assert!(!square.is_rigid());
+        square.edges.push(Edge(0, 2));
+        assert!(square.is_rigid());
+        // redundant edge keeps it rigid
+        square.edges.push(Edge(1, 3));
+        assert!(square.is_rigid());
+
+        // two triangles hinged at a single vertex
+        let two_triangles = Figure {
+            edges: vec![ Edge(0, 1), Edge(1, 2), Edge(2, 0), Edge(2, 3), Edge(3, 4), Edge(4, 2) ],
+            vertices: vec![ Point(0, 0), Point(2, 0), Point(1, 1), Point(0, 2), Point(2, 2) ],
+        };
+        assert!(!two_triangles.is_rigid());
+
+        // enough edges in total, but all of them are spent on the K4 part while the last vertex dangles
+        let dangling = Figure {
+            edges: vec![ Edge(0, 1), Edge(0, 2), Edge(0, 3), Edge(1, 2), Edge(1, 3), Edge(2, 3), Edge(3, 4) ],
+            vertices: vec![ Point(0, 0), Point(2, 0), Point(2, 2), Point(0, 2), Point(4, 4) ],
+        };
+        assert!(!dangling.is_rigid());
+    }
+
+    #[test]
+    fn figure_might_fit() {
+        let hole = vec![ Point(0, 0), Point(10, 0), Point(10, 10), Point(0, 10) ];
+        let square = Figure {
+            edges: vec![ Edge(0, 1), Edge(1, 2), Edge(2, 3), Edge(3, 0) ],
+            vertices: vec![ Point(0, 0), Point(6, 0), Point(6, 6), Point(0, 6) ],
+        };
+        assert_eq!(square.bounding_circle(), (Point(3, 3), (18.0_f64).sqrt()));
+        assert!(Problem { hole: hole.clone(), figure: square, epsilon: 0, bonuses: None, }.figure_might_fit());
+
+        // folded zigzag is much wider than the hole but still fits
+        let zigzag = Figure {
+            edges: vec![ Edge(0, 1), Edge(1, 2), Edge(2, 3) ],
+            vertices: vec![ Point(0, 0), Point(8, 0), Point(16, 0), Point(24, 0) ],
+        };
+        assert!(Problem { hole: hole.clone(), figure: zigzag, epsilon: 0, bonuses: None, }.figure_might_fit());
+
+        let long_edge = Figure {
+            edges: vec![ Edge(0, 1) ],
+            vertices: vec![ Point(0, 0), Point(20, 0) ],
+        };
+        assert!(!Problem { hole: hole.clone(), figure: long_edge.clone(), epsilon: 0, bonuses: None, }.figure_might_fit());
+        assert!(Problem { hole, figure: long_edge, epsilon: 750000, bonuses: None, }.figure_might_fit());
+    }
+
+    #[test]
+    fn zero_dislikes_plausible() {
+        let hole = vec![ Point(0, 0), Point(10, 0), Point(10, 10), Point(0, 10) ];
+        let square = Figure {
+            edges: vec![ Edge(0, 1), Edge(1, 2), Edge(2, 3), Edge(3, 0) ],
+            vertices: vec![ Point(0, 0), Point(10, 0), Point(10, 10), Point(0, 10) ],
+        };
+        assert!(Problem { hole: hole.clone(), figure: square, epsilon: 0, bonuses: None, }.zero_dislikes_plausible());
+
+        // folded chain reaches the far corner when unfolded
+        let chain = Figure {
+            edges: vec![ Edge(0, 1), Edge(1, 2), Edge(2, 3) ],
+            vertices: vec![ Point(0, 0), Point(6, 0), Point(6, 6), Point(12, 6) ],
+        };
+        assert!(Problem { hole: hole.clone(), figure: chain, epsilon: 0, bonuses: None, }.zero_dislikes_plausible());
+
+        // too small to span the hole diagonal
+        let small = Figure {
+            edges: vec![ Edge(0, 1), Edge(1, 2), Edge(2, 3), Edge(3, 0) ],
+            vertices: vec![ Point(0, 0), Point(3, 0), Point(3, 3), Point(0, 3) ],
+        };
+        assert!(!Problem { hole: hole.clone(), figure: small, epsilon: 0, bonuses: None, }.zero_dislikes_plausible());
+
+        let triangle = Figure {
+            edges: vec![ Edge(0, 1), Edge(1, 2), Edge(2, 0) ],
+            vertices: vec![ Point(0, 0), Point(20, 0), Point(0, 20) ],
+        };
+        assert!(!Problem { hole, figure: triangle, epsilon: 0, bonuses: None, }.zero_dislikes_plausible());
+    }
+
+    #[test]
+    fn unreachable_hole_vertices() {
+        // square with a thin slanted spike: no lattice point at distance 5 from the spike tip lies inside of the hole
+        let hole = vec![ Point(0, 0), Point(20, 0), Point(20, 9), Point(60, 13), Point(20, 11), Point(20, 20), Point(0, 20) ];
+        let square = Figure {
+            edges: vec![ Edge(0, 1), Edge(1, 2), Edge(2, 3), Edge(3, 0) ],
+            vertices: vec![ Point(0, 0), Point(5, 0), Point(5, 5), Point(0, 5) ],
+        };
+        assert_eq!(Problem { hole: hole.clone(), figure: square.clone(), epsilon: 0, bonuses: None, }.unreachable_hole_vertices(), vec![Point(60, 13)]);
+
+        // a bit stretched longer edge reaches (48, 12) inside of the spike
+        let long_square = Figure {
+            edges: square.edges.clone(),
+            vertices: vec![ Point(0, 0), Point(12, 0), Point(12, 12), Point(0, 12) ],
+        };
+        assert_eq!(Problem { hole: hole.clone(), figure: long_square.clone(), epsilon: 0, bonuses: None, }.unreachable_hole_vertices(), vec![Point(60, 13)]);
+        assert_eq!(Problem { hole: hole.clone(), figure: long_square, epsilon: 10000, bonuses: None, }.unreachable_hole_vertices(), vec![]);
+
+        // a vertex without edges could be anywhere
+        let mut loose = square;
+        loose.vertices.push(Point(3, 3));
+        assert_eq!(Problem { hole, figure: loose, epsilon: 0, bonuses: None, }.unreachable_hole_vertices(), vec![]);
+    }
+
+    #[test]
+    fn pose_fingerprint() {
+        let globalist = PoseBonus::Globalist { problem: ProblemId(3), };
+        let break_a_leg = PoseBonus::BreakALeg { problem: ProblemId(5), edge: Edge(0, 1), };
+        let pose = |bonuses| Pose { vertices: vec![ Point(1, 2), Point(3, 4) ], bonuses, };
+
+        assert_eq!(pose(Some(vec![globalist, break_a_leg])).fingerprint(), pose(Some(vec![break_a_leg, globalist])).fingerprint());
+        assert_eq!(pose(None).fingerprint(), pose(Some(vec![])).fingerprint());
+        assert_ne!(pose(None).fingerprint(), pose(Some(vec![globalist])).fingerprint());
+
+        let moved = Pose { vertices: vec![ Point(1, 2), Point(3, 5) ], bonuses: None, };
+        assert_ne!(pose(None).fingerprint(), moved.fingerprint());
+    }
+
+    #[test]
+    fn problem_fingerprint() {
+        let problem = |epsilon| Problem {
+            hole: vec![ Point(0, 0), Point(10, 0), Point(10, 10) ],
+            figure: Figure { edges: vec![ Edge(0, 1) ], vertices: vec![ Point(1, 2), Point(3, 4) ], },
+            epsilon,
+            bonuses: None,
+        };
+        let with_bonuses = Problem {
+            hole: problem(0).hole,
+            figure: problem(0).figure,
+            epsilon: 0,
+            bonuses: Some(vec![ ProblemBonus { position: Point(0, 0), bonus: ProblemBonusType::Globalist, problem: ProblemId(2), } ]),
+        };
+
+        assert_eq!(problem(0).fingerprint(), problem(0).fingerprint());
+        assert_eq!(problem(0).fingerprint(), with_bonuses.fingerprint());
+        assert_ne!(problem(0).fingerprint(), problem(1).fingerprint());
+    }
+
+    #[test]
+    fn score_pose_with_explicit_hole() {
+        let problem = Problem {
+            hole: vec![ Point(0, 0), Point(10, 0), Point(10, 10), Point(0, 10) ],
+            figure: Figure {
+                edges: vec![ Edge(0, 1), Edge(1, 2) ],
+                vertices: vec![ Point(0, 0), Point(5, 0), Point(5, 5) ],
+            },
+            epsilon: 0,
+            bonuses: None,
+        };
+        let geo_hole = problem.hole_polygon_f64();
+        let quad_tree = geo_hole_quad_tree::GeoHoleQuadTree::new(problem.hole_polygon_f64());
+
+        let inside = Pose { vertices: vec![ Point(1, 1), Point(6, 1), Point(6, 6) ], bonuses: None, };
+        assert_eq!(problem.score_pose(&inside), problem.score_pose_in(&geo_hole, &inside));
+        assert_eq!(problem.score_pose(&inside), problem.score_pose_in(&quad_tree, &inside));
+
+        let outside = Pose { vertices: vec![ Point(8, 8), Point(13, 8), Point(13, 13) ], bonuses: None, };
+        assert!(problem.score_pose(&outside).is_err());
+        assert_eq!(problem.score_pose(&outside), problem.score_pose_in(&geo_hole, &outside));
+    }
+
+    #[test]
+    fn score_pose_timed() {
+        // a long zigzag, so every phase does some work
+        let vertices: Vec<_> = (0 .. 150).map(|index| Point(index, (index % 2) * 2 + 1)).collect();
+        let problem = Problem {
+            hole: (0 .. 100).map(|index| Point(index * 2, 0)).chain(vec![ Point(200, 10), Point(0, 10) ]).collect(),
+            figure: Figure {
+                edges: (1 .. vertices.len()).map(|index| Edge(index - 1, index)).collect(),
+                vertices: vertices.clone(),
+            },
+            epsilon: 0,
+            bonuses: None,
+        };
+        let pose = Pose { vertices, bonuses: None, };
+        let (result, timings) = problem.score_pose_timed(&problem.hole_polygon_f64(), &pose);
+        assert_eq!(result, problem.score_pose(&pose));
+        assert!(result.is_ok());
+        assert!(timings.total_ns() > 0);
+        assert!(timings.hole_ns > 0 && timings.dislikes_ns > 0, "{:?}", timings);
+        assert_eq!(timings.total_ns(), timings.count_ns + timings.stretching_ns + timings.hole_ns + timings.dislikes_ns);
+
+        // phases after a failed check are skipped
+        let broken = Pose { vertices: pose.vertices.iter().map(|vertex| Point(vertex.0, vertex.1 * 3)).collect(), bonuses: None, };
+        let (result, timings) = problem.score_pose_timed(&problem.hole_polygon_f64(), &broken);
+        assert!(matches!(result, Err(PoseValidationError::BrokenEdgesFound { .. })));
+        assert_eq!((timings.hole_ns, timings.dislikes_ns), (0, 0));
+    }
+
+    #[test]
+    fn total_stretch_matches_ratio_sum() {
+        let problem = Problem {
+            hole: vec![ Point(0, 0), Point(20, 0), Point(20, 20), Point(0, 20) ],
+            figure: Figure {
+                edges: vec![ Edge(0, 1), Edge(1, 2), Edge(2, 0) ],
+                vertices: vec![ Point(0, 0), Point(10, 0), Point(0, 10) ],
+            },
+            epsilon: 1000,
+            bonuses: None,
+        };
+        assert_eq!(problem.total_stretch(&problem.figure.vertices), 0.0);
+
+        let stretched = vec![ Point(0, 0), Point(12, 0), Point(0, 9) ];
+        let total_stretch = problem.total_stretch(&stretched);
+        assert!(total_stretch > 0.0);
+        for &bonus in &[None, Some(PoseBonus::Superflex { problem: ProblemId(1), })] {
+            match problem.score_vertices_check_stretching(&stretched, bonus) {
+                Err(PoseValidationError::BrokenEdgesFound { ratio_sum, .. }) =>
+                    assert_eq!(ratio_sum, total_stretch),
+                other =>
+                    panic!("unexpected stretching check result {:?}", other),
+            }
+        }
+        match problem.score_vertices_check_stretching(&stretched, Some(PoseBonus::Globalist { problem: ProblemId(1), })) {
+            Err(PoseValidationError::BrokenEdgesFound { ratio_sum, .. }) =>
+                assert_eq!(ratio_sum, total_stretch),
+            other =>
+                panic!("unexpected globalist stretching check result {:?}", other),
+        }
+    }
+
+    #[test]
+    fn reorder_pose_to_match_figure() {
+        // a "flag": triangle with a pole, no symmetry
+        let problem = Problem {
+            hole: vec![ Point(0, 0), Point(30, 0), Point(30, 30), Point(0, 30) ],
+            figure: Figure {
+                edges: vec![ Edge(0, 1), Edge(1, 2), Edge(2, 0), Edge(2, 3) ],
+                vertices: vec![ Point(0, 0), Point(6, 0), Point(0, 8), Point(0, 20) ],
+            },
+            epsilon: 0,
+            bonuses: None,
+        };
+        let correct = vec![ Point(5, 5), Point(11, 5), Point(5, 13), Point(5, 25) ];
+        let permutation = [2, 0, 3, 1];
+        let mut pose = Pose { vertices: permutation.iter().map(|&index| correct[index]).collect(), bonuses: None, };
+        assert!(problem.score_pose(&pose).is_err());
+        pose.reorder_to_match(&problem).unwrap();
+        assert_eq!(pose.vertices, correct);
+        assert!(problem.score_pose(&pose).is_ok());
+
+        // already matching order is kept
+        pose.reorder_to_match(&problem).unwrap();
+        assert_eq!(pose.vertices, correct);
+
+        let mut wrong_count = Pose { vertices: correct[.. 3].to_vec(), bonuses: None, };
+        assert_eq!(
+            wrong_count.reorder_to_match(&problem),
+            Err(ReorderError::VerticesCountMismatch { expected: 4, provided: 3, }),
+        );
+        let mut stretched = Pose { vertices: vec![ Point(5, 5), Point(12, 5), Point(5, 13), Point(25, 5) ], bonuses: None, };
+        assert_eq!(stretched.reorder_to_match(&problem), Err(ReorderError::NoMatchingOrder));
+
+        // a square fits a path of three unit edges in several different ways
+        let path = Problem {
+            hole: vec![ Point(0, 0), Point(10, 0), Point(10, 10), Point(0, 10) ],
+            figure: Figure {
+                edges: vec![ Edge(0, 1), Edge(1, 2), Edge(2, 3) ],
+                vertices: vec![ Point(0, 0), Point(1, 0), Point(2, 0), Point(3, 0) ],
+            },
+            epsilon: 0,
+            bonuses: None,
+        };
+        let mut square = Pose { vertices: vec![ Point(1, 1), Point(2, 2), Point(1, 2), Point(2, 1) ], bonuses: None, };
+        assert_eq!(square.reorder_to_match(&path), Err(ReorderError::Ambiguous));
+
+        // loose vertices may take any of 12! places, all giving the same (single) edge
+        let loose = Problem {
+            hole: vec![ Point(0, 0), Point(100, 0), Point(100, 100), Point(0, 100) ],
+            figure: Figure {
+                edges: vec![ Edge(0, 1) ],
+                vertices: (0 .. 14).map(|index| Point(index * 5, 0)).collect(),
+            },
+            epsilon: 0,
+            bonuses: None,
+        };
+        let mut scattered = Pose { vertices: (0 .. 14).map(|index| Point(index * 5, 50)).collect(), bonuses: None, };
+        scattered.vertices.swap(1, 13);
+        assert_eq!(scattered.reorder_to_match(&loose), Err(ReorderError::SearchBudgetExhausted));
+    }
+
+    #[test]
+    fn epsilon_override() {
+        let problem = Problem {
+            hole: vec![ Point(0, 0), Point(20, 0), Point(20, 20), Point(0, 20) ],
+            figure: Figure {
+                edges: vec![ Edge(0, 1) ],
+                vertices: vec![ Point(0, 0), Point(10, 0) ],
+            },
+            epsilon: 1000,
+            bonuses: None,
+        };
+        let pose = Pose { vertices: vec![ Point(0, 0), Point(11, 0) ], bonuses: None, };
+        assert!(problem.score_pose(&pose).is_err());
+
+        let relaxed = problem.with_epsilon(250000);
+        assert!(relaxed.score_pose(&pose).is_ok());
+        assert_eq!(problem.epsilon, 1000);
+    }
+
+    #[test]
+    fn dislikes_assignment_ties() {
+        let problem = Problem {
+            hole: vec![ Point(0, 0), Point(10, 0), Point(5, 10) ],
+            figure: Figure {
+                edges: vec![ Edge(0, 1) ],
+                vertices: vec![ Point(0, 0), Point(10, 0) ],
+            },
+            epsilon: 0,
+            bonuses: None,
+        };
+
+        // hole vertex (5, 10) is equidistant to both pose vertices
+        assert_eq!(problem.dislikes_assignment(&[ Point(2, 0), Point(8, 0) ]), vec![ (0, 4), (1, 4), (0, 109) ]);
+        assert_eq!(problem.dislikes_assignment(&[ Point(8, 0), Point(2, 0) ]), vec![ (1, 4), (0, 4), (0, 109) ]);
+        assert!(problem.dislikes_assignment(&[]).is_empty());
+    }
+
+    #[test]
+    fn epsilon_conversions() {
+        let problem = Problem { hole: vec![ Point(0, 0) ], figure: Figure { edges: vec![], vertices: vec![ Point(0, 0) ], }, epsilon: 2494, bonuses: None, };
+        assert!((problem.epsilon_fraction() - 0.002494).abs() < 1e-12);
+        assert!((problem.epsilon_percent() - 0.2494).abs() < 1e-12);
+
+        let problem = problem.with_epsilon(150000);
+        assert!((problem.epsilon_fraction() - 0.15).abs() < 1e-12);
+        assert!((problem.epsilon_percent() - 15.0).abs() < 1e-12);
+
+        assert_eq!(problem.with_epsilon(0).epsilon_percent(), 0.0);
+    }
+
+    #[test]
+    fn bonus_on_hole_vertices() {
+        let problem_data = r#"{"bonuses":[{"bonus":"GLOBALIST","problem":3,"position":[10,0]},{"bonus":"WALLHACK","problem":5,"position":[5,5]}],"hole":[[0,0],[10,0],[10,10],[0,10]],"epsilon":0,"figure":{"edges":[[0,1]],"vertices":[[0,0],[10,0]]}}"#;
+        let problem: Problem = serde_json::from_str(problem_data).unwrap();
+        let lucky = problem.bonus_on_hole_vertices();
+        assert_eq!(lucky.len(), 1);
+        assert_eq!(lucky[0].problem, ProblemId(3));
+        assert_eq!(lucky[0].position, Point(10, 0));
+
+        let problem = Problem { hole: problem.hole.clone(), figure: problem.figure.clone(), epsilon: 0, bonuses: None, };
+        assert!(problem.bonus_on_hole_vertices().is_empty());
+    }
+
+    #[test]
+    fn worst_hole_vertex() {
+        let problem = Problem {
+            hole: vec![ Point(0, 0), Point(10, 0), Point(10, 10), Point(0, 10) ],
+            figure: Figure {
+                edges: vec![ Edge(0, 1) ],
+                vertices: vec![ Point(0, 0), Point(10, 0) ],
+            },
+            epsilon: 0,
+            bonuses: None,
+        };
+
+        // top corners are 10 away from the bottom edge, (10, 10) is farther from (1, 0)
+        assert_eq!(problem.worst_hole_vertex(&[ Point(0, 0), Point(1, 0) ]), Some((Point(10, 10), 181)));
+        // tie between top corners resolves to the first one in hole order
+        assert_eq!(problem.worst_hole_vertex(&[ Point(0, 0), Point(10, 0) ]), Some((Point(10, 10), 100)));
+        assert_eq!(problem.worst_hole_vertex(&[]), None);
+    }
+
+    #[test]
+    fn near_perfect() {
+        let problem = Problem {
+            hole: vec![ Point(0, 0), Point(10, 0), Point(10, 10), Point(0, 10) ],
+            figure: Figure {
+                edges: vec![ Edge(0, 1), Edge(1, 2), Edge(2, 3), Edge(3, 0) ],
+                vertices: vec![ Point(0, 0), Point(10, 0), Point(10, 10), Point(0, 10) ],
+            },
+            epsilon: 0,
+            bonuses: None,
+        };
+
+        let exact = vec![ Point(0, 0), Point(10, 0), Point(10, 10), Point(0, 10) ];
+        assert!(problem.near_perfect(&exact, 0));
+
+        let one_off = vec![ Point(0, 0), Point(9, 1), Point(10, 10), Point(0, 10) ];
+        assert!(problem.near_perfect(&one_off, 2));
+        assert!(!problem.near_perfect(&one_off, 0));
+        assert!(!problem.near_perfect(&[], 2));
+    }
+
+    #[test]
+    fn score_vertices_degenerate_edge() {
+        let problem = Problem {
+            hole: vec![ Point(0, 0), Point(10, 0), Point(10, 10), Point(0, 10) ],
+            figure: Figure {
+                edges: vec![ Edge(0, 1), Edge(1, 2) ],
+                vertices: vec![ Point(5, 5), Point(5, 5), Point(5, 8) ],
+            },
+            epsilon: 1000000,
+            bonuses: None,
+        };
+
+        assert_eq!(stretch_ratio(0, 0), 0.0);
+        assert_eq!(stretch_ratio(0, 1), f64::INFINITY);
+        assert!(problem.score_vertices_check_stretching(&vec![ Point(2, 2), Point(2, 2), Point(2, 5) ], None).is_ok());
+        assert!(matches!(
+            problem.score_vertices_check_stretching(&vec![ Point(2, 2), Point(2, 3), Point(2, 6) ], None),
+            Err(PoseValidationError::BrokenEdgesFound { broken_edges, .. }) if broken_edges == vec![Edge(0, 1)],
+        ));
+        assert!(problem.score_vertices_check_stretching(&vec![ Point(2, 2), Point(2, 3), Point(2, 6) ], Some(PoseBonus::Globalist { problem: ProblemId(0), })).is_err());
+    }
+
     #[test]
     fn bounding_ring_box() {
         let outer = BoundingBox(Point(0,0), Point(4,4));
@@ -776,6 +2653,21 @@ mod tests {
         assert_eq!(ring.point_set(), right.iter().cloned().collect());
     }
 
+    #[test]
+    fn ring_points_iter() {
+        let rings = [
+            SquareRing(BoundingBox(Point(0, 0), Point(4, 4)), BoundingBox(Point(1, 1), Point(3, 3))),
+            SquareRing(BoundingBox(Point(-3, 2), Point(7, 12)), BoundingBox(Point(0, 5), Point(4, 9))),
+            SquareRing(BoundingBox(Point(10, 10), Point(2, 2)), BoundingBox(Point(6, 6), Point(6, 6))),
+        ];
+        for ring in &rings {
+            let points: Vec<_> = ring.points_iter().collect();
+            let set = ring.point_set();
+            assert_eq!(points.len(), set.len(), "duplicates in {:?}", ring);
+            assert_eq!(points.into_iter().collect::<HashSet<_>>(), set);
+        }
+    }
+
     #[test]
     fn score_vertice_broken_wallhack_tasks() {
         let problem_47: Problem = serde_json::from_str(
@@ -834,4 +2726,28 @@ mod tests {
         ).unwrap();
         assert!(problem_2_outer_same.score_pose(&pose_2_outer_same_wallhack ).is_err());
     }
+
+    #[test]
+    fn rotation_snaps_to_angle_rounding_to_valid() {
+        let problem = Problem {
+            hole: vec![Point(-10, -10), Point(10, -10), Point(10, 10), Point(-10, 10)],
+            figure: Figure { edges: vec![Edge(0, 1)], vertices: vec![Point(0, 0), Point(5, 0)], },
+            epsilon: 0,
+            bonuses: None,
+        };
+        // 44.8 degrees puts the edge end to (3.55, 3.52), which rounds to (4, 4) and breaks the edge
+        let exact = geom::Transform::rotate(44.8).apply(&problem.figure.vertices);
+        assert_eq!(exact[1], Point(4, 4));
+        assert!(problem.score_vertices_check_stretching(&exact, None).is_err());
+
+        // up to 44.4 degrees it rounds to (4, 3) and from 45.6 degrees to (3, 4), the former is closer
+        let angle = problem.nearest_valid_rounded_angle(&problem.figure.vertices, (0.0, 0.0), 44.8).unwrap();
+        assert!((angle - 44.4).abs() < 1e-9, "{}", angle);
+        assert_eq!(geom::Transform::rotate(angle).apply(&problem.figure.vertices)[1], Point(4, 3));
+
+        // a valid exact angle is kept as is
+        assert_eq!(problem.nearest_valid_rounded_angle(&problem.figure.vertices, (0.0, 0.0), 90.0), Some(90.0));
+        // nothing within reach: every angle from 7 to 17 degrees rounds the edge end to (5, 1)
+        assert_eq!(problem.nearest_valid_rounded_angle(&problem.figure.vertices, (0.0, 0.0), 12.0), None);
+    }
 }
@@ -0,0 +1,282 @@
+use std::sync::atomic::{
+    AtomicUsize,
+    Ordering,
+};
+
+use geo::{
+    algorithm::{
+        contains::{
+            Contains,
+        },
+        intersects::{
+            Intersects,
+        },
+    },
+};
+
+use super::{
+    Point,
+    InvalidEdge,
+};
+
+const MAX_DEPTH: usize = 8;
+const MIN_NODE_SIZE: f64 = 1.0;
+
+/// Hole polygon with a quad tree over its bounding box: cells classified as completely inside or completely
+/// outside of the hole answer edge queries without touching the polygon, the rest falls back to `geo::Polygon<f64>`.
+pub struct GeoHoleQuadTree {
+    geo_hole: geo::Polygon<f64>,
+    root: Node,
+    hits_total: AtomicUsize,
+    hits_slow: AtomicUsize,
+}
+
+/// Snapshot of `GeoHoleQuadTree` query counters: `hits_slow` queries out of `hits_total` fell back to the polygon.
+#[derive(Clone, Copy, PartialEq, Default, Debug)]
+pub struct QuadTreeStats {
+    pub hits_total: usize,
+    pub hits_slow: usize,
+}
+
+impl QuadTreeStats {
+    /// Share of queries answered by the polygon instead of the tree, 0.0 if there were no queries.
+    pub fn slow_ratio(&self) -> f64 {
+        if self.hits_total == 0 {
+            0.0
+        } else {
+            self.hits_slow as f64 / self.hits_total as f64
+        }
+    }
+}
+
+struct Node {
+    min: geo::Coordinate<f64>,
+    max: geo::Coordinate<f64>,
+    kind: NodeKind,
+}
+
+enum NodeKind {
+    Inside,
+    Outside,
+    Boundary { children: Vec<Node>, },
+}
+
+impl GeoHoleQuadTree {
+    pub fn new(geo_hole: geo::Polygon<f64>) -> GeoHoleQuadTree {
+        let mut min = geo::Coordinate { x: f64::MAX, y: f64::MAX, };
+        let mut max = geo::Coordinate { x: f64::MIN, y: f64::MIN, };
+        for coord in geo_hole.exterior().0.iter() {
+            min.x = min.x.min(coord.x);
+            min.y = min.y.min(coord.y);
+            max.x = max.x.max(coord.x);
+            max.y = max.y.max(coord.y);
+        }
+        let root = Node::build(&geo_hole, min, max, 0);
+        GeoHoleQuadTree { geo_hole, root, hits_total: AtomicUsize::new(0), hits_slow: AtomicUsize::new(0), }
+    }
+
+    pub fn stats(&self) -> QuadTreeStats {
+        QuadTreeStats {
+            hits_total: self.hits_total.load(Ordering::Relaxed),
+            hits_slow: self.hits_slow.load(Ordering::Relaxed),
+        }
+    }
+
+    pub fn reset_stats(&self) {
+        self.hits_total.store(0, Ordering::Relaxed);
+        self.hits_slow.store(0, Ordering::Relaxed);
+    }
+
+    pub fn geo_hole(&self) -> &geo::Polygon<f64> {
+        &self.geo_hole
+    }
+
+    /// Classifies the box `[min, max]`: `Some(true)` if it is completely outside of the hole,
+    /// `Some(false)` if completely inside, `None` if the tree cannot tell.
+    fn lookup(&self, min: geo::Coordinate<f64>, max: geo::Coordinate<f64>) -> Option<bool> {
+        if !self.root.covers(min, max) {
+            // some point is beyond the hole bounding box
+            return Some(true);
+        }
+        let mut node = &self.root;
+        loop {
+            match &node.kind {
+                NodeKind::Inside =>
+                    return Some(false),
+                NodeKind::Outside =>
+                    return Some(true),
+                NodeKind::Boundary { children, } =>
+                    node = children.iter().find(|child| child.covers(min, max))?,
+            }
+        }
+    }
+}
+
+impl InvalidEdge for GeoHoleQuadTree {
+    fn is_edge_invalid(&self, edge_from: Point, edge_to: Point) -> bool {
+        let min = geo::Coordinate {
+            x: edge_from.0.min(edge_to.0) as f64,
+            y: edge_from.1.min(edge_to.1) as f64,
+        };
+        let max = geo::Coordinate {
+            x: edge_from.0.max(edge_to.0) as f64,
+            y: edge_from.1.max(edge_to.1) as f64,
+        };
+        self.hits_total.fetch_add(1, Ordering::Relaxed);
+        match self.lookup(min, max) {
+            Some(is_outside) =>
+                is_outside,
+            None => {
+                self.hits_slow.fetch_add(1, Ordering::Relaxed);
+                self.geo_hole.is_edge_invalid(edge_from, edge_to)
+            },
+        }
+    }
+
+    fn is_point_outside(&self, point: Point) -> bool {
+        let coord = geo::Coordinate::from(point);
+        self.hits_total.fetch_add(1, Ordering::Relaxed);
+        match self.lookup(coord, coord) {
+            Some(is_outside) =>
+                is_outside,
+            None => {
+                self.hits_slow.fetch_add(1, Ordering::Relaxed);
+                self.geo_hole.is_point_outside(point)
+            },
+        }
+    }
+
+    fn quad_tree_stats(&self) -> Option<QuadTreeStats> {
+        Some(self.stats())
+    }
+}
+
+impl Node {
+    fn build(geo_hole: &geo::Polygon<f64>, min: geo::Coordinate<f64>, max: geo::Coordinate<f64>, depth: usize) -> Node {
+        let sides = [
+            geo::Line { start: geo::Coordinate { x: min.x, y: min.y, }, end: geo::Coordinate { x: max.x, y: min.y, }, },
+            geo::Line { start: geo::Coordinate { x: max.x, y: min.y, }, end: geo::Coordinate { x: max.x, y: max.y, }, },
+            geo::Line { start: geo::Coordinate { x: max.x, y: max.y, }, end: geo::Coordinate { x: min.x, y: max.y, }, },
+            geo::Line { start: geo::Coordinate { x: min.x, y: max.y, }, end: geo::Coordinate { x: min.x, y: min.y, }, },
+        ];
+
+        let kind = if sides.iter().all(|side| geo_hole.contains(side)) {
+            // hole has no inner rings, so the whole box is inside as soon as its border is
+            NodeKind::Inside
+        } else if !sides.iter().any(|side| geo_hole.intersects(side)) && !Node::covers_hole(geo_hole, min, max) {
+            NodeKind::Outside
+        } else if depth >= MAX_DEPTH || (max.x - min.x) < MIN_NODE_SIZE || (max.y - min.y) < MIN_NODE_SIZE {
+            NodeKind::Boundary { children: Vec::new(), }
+        } else {
+            let mid = geo::Coordinate { x: (min.x + max.x) / 2.0, y: (min.y + max.y) / 2.0, };
+            NodeKind::Boundary {
+                children: vec![
+                    Node::build(geo_hole, min, mid, depth + 1),
+                    Node::build(geo_hole, geo::Coordinate { x: mid.x, y: min.y, }, geo::Coordinate { x: max.x, y: mid.y, }, depth + 1),
+                    Node::build(geo_hole, geo::Coordinate { x: min.x, y: mid.y, }, geo::Coordinate { x: mid.x, y: max.y, }, depth + 1),
+                    Node::build(geo_hole, mid, max, depth + 1),
+                ],
+            }
+        };
+
+        Node { min, max, kind, }
+    }
+
+    fn covers_hole(geo_hole: &geo::Polygon<f64>, min: geo::Coordinate<f64>, max: geo::Coordinate<f64>) -> bool {
+        geo_hole.exterior().0.first()
+            .map_or(false, |coord| coord.x >= min.x && coord.x <= max.x && coord.y >= min.y && coord.y <= max.y)
+    }
+
+    fn covers(&self, min: geo::Coordinate<f64>, max: geo::Coordinate<f64>) -> bool {
+        min.x >= self.min.x && max.x <= self.max.x && min.y >= self.min.y && max.y <= self.max.y
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{
+        Rng,
+        SeedableRng,
+    };
+
+    use super::*;
+    use super::super::Problem;
+
+    const PROBLEM_50_JSON: &str = r#"{"bonuses":[{"bonus":"GLOBALIST","problem":9,"position":[0,8]},{"bonus":"GLOBALIST","problem":70,"position":[33,48]},{"bonus":"WALLHACK","problem":91,"position":[42,33]}],"hole":[[21,35],[0,22],[26,14],[0,8],[15,0],[45,0],[52,12],[52,48],[51,60],[49,86],[38,73],[17,85],[31,92],[24,103],[2,103],[0,85],[0,52],[8,41]],"epsilon":17065,"figure":{"edges":[[0,3],[0,4],[1,2],[1,5],[2,3],[2,5],[3,6],[3,7],[4,6],[5,8],[6,7],[7,8]],"vertices":[[0,75],[7,0],[10,26],[20,55],[20,95],[29,13],[40,75],[46,52],[48,28]]}}"#;
+
+    #[test]
+    fn random_edges_match_polygon() {
+        let problem: Problem = serde_json::from_str(PROBLEM_50_JSON).unwrap();
+        let geo_hole = problem.hole_polygon_f64();
+        let quad_tree = GeoHoleQuadTree::new(geo_hole.clone());
+
+        let mut rng = rand::rngs::StdRng::seed_from_u64(50);
+        for _ in 0 .. 10000 {
+            let from = Point(rng.gen_range(-5 ..= 60), rng.gen_range(-5 ..= 110));
+            let to = Point(rng.gen_range(-5 ..= 60), rng.gen_range(-5 ..= 110));
+            assert_eq!(quad_tree.is_edge_invalid(from, to), geo_hole.is_edge_invalid(from, to), "edge {:?} -> {:?}", from, to);
+            assert_eq!(quad_tree.is_point_outside(from), geo_hole.is_point_outside(from), "point {:?}", from);
+        }
+    }
+
+    #[test]
+    fn pinched_hole_matches_polygon() {
+        // two squares touching at the corner (10, 10)
+        let problem = Problem {
+            hole: vec![Point(0, 0), Point(10, 0), Point(10, 10), Point(20, 10), Point(20, 20), Point(10, 20), Point(10, 10), Point(0, 10)],
+            figure: super::super::Figure { edges: vec![], vertices: vec![], },
+            epsilon: 0,
+            bonuses: None,
+        };
+        assert_eq!(problem.hole_pinch_points(), vec![Point(10, 10)]);
+        let geo_hole = problem.hole_polygon_f64();
+        let quad_tree = GeoHoleQuadTree::new(geo_hole.clone());
+
+        let mut rng = rand::rngs::StdRng::seed_from_u64(10);
+        for _ in 0 .. 10000 {
+            let from = Point(rng.gen_range(-2 ..= 22), rng.gen_range(-2 ..= 22));
+            let to = Point(rng.gen_range(-2 ..= 22), rng.gen_range(-2 ..= 22));
+            assert_eq!(quad_tree.is_edge_invalid(from, to), geo_hole.is_edge_invalid(from, to), "edge {:?} -> {:?}", from, to);
+            assert_eq!(quad_tree.is_point_outside(from), geo_hole.is_point_outside(from), "point {:?}", from);
+        }
+        for &(from, to) in &[(Point(5, 5), Point(15, 15)), (Point(5, 5), Point(10, 10)), (Point(10, 10), Point(15, 15))] {
+            assert_eq!(quad_tree.is_edge_invalid(from, to), geo_hole.is_edge_invalid(from, to), "edge {:?} -> {:?}", from, to);
+        }
+        assert!(!geo_hole.is_point_outside(Point(10, 10)));
+    }
+
+    #[test]
+    fn stats_count_queries() {
+        let problem: Problem = serde_json::from_str(PROBLEM_50_JSON).unwrap();
+        let quad_tree = GeoHoleQuadTree::new(problem.hole_polygon_f64());
+        assert_eq!(quad_tree.stats(), QuadTreeStats::default());
+
+        for x in 0 .. 10 {
+            quad_tree.is_edge_invalid(Point(x, 30), Point(40, 30));
+        }
+        let stats = quad_tree.stats();
+        assert_eq!(stats.hits_total, 10);
+        assert!(stats.hits_slow <= stats.hits_total);
+        assert_eq!(quad_tree.quad_tree_stats(), Some(stats));
+
+        quad_tree.reset_stats();
+        assert_eq!(quad_tree.stats(), QuadTreeStats::default());
+        assert_eq!(quad_tree.stats().slow_ratio(), 0.0);
+    }
+
+    #[test]
+    fn possible_rotations_match_polygon() {
+        let problem: Problem = serde_json::from_str(PROBLEM_50_JSON).unwrap();
+        let quad_tree = GeoHoleQuadTree::new(problem.hole_polygon_f64());
+
+        let vertices = vec![
+            Point(18, 81), Point(40, 46), Point(28, 23), Point(22, 53), Point(38, 61), Point(51, 23), Point(42, 33), Point(48, 56), Point(32, 38),
+        ];
+        assert_eq!(problem.possible_rotations_with(&quad_tree), problem.possible_rotations());
+        assert_eq!(problem.possible_rotations_for_vertices_with(&vertices, &quad_tree), problem.possible_rotations_for_vertices(&vertices));
+        assert_eq!(
+            problem.possible_rotations_around_point_with(&vertices[0], &quad_tree),
+            problem.possible_rotations_around_point(&vertices[0]),
+        );
+    }
+}
@@ -0,0 +1,339 @@
+use std::{
+    fs,
+    io::{
+        self,
+        Write,
+    },
+    path::Path,
+    collections::BTreeMap,
+};
+
+use serde_derive::{
+    Serialize,
+    Deserialize,
+};
+
+/// Best scores reached by the autonomous solver per task, kept on disk between restarts.
+#[derive(Clone, PartialEq, Default, Serialize, Deserialize, Debug)]
+pub struct BestIndex {
+    pub tasks: BTreeMap<String, TaskProgress>,
+}
+
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize, Debug)]
+pub struct TaskProgress {
+    pub best_score: i64,
+    /// runs in a row which have not improved `best_score`
+    pub stale_runs: usize,
+}
+
+/// Outcome of one autonomous solver pass over the whole problems directory.
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize, Debug)]
+pub struct PassSummary {
+    /// pass number, starting from 1
+    pub pass: usize,
+    pub tasks_done: usize,
+    /// tasks which got a new best score during the pass
+    pub tasks_improved: usize,
+    /// sum of best scores over all tasks of the index after the pass
+    pub total_dislikes: i64,
+}
+
+/// Dislikes thresholds worth reporting for a task (e.g. 100, 50, 10, 0), so progress logs only meaningful milestones
+/// instead of every improvement. A threshold is crossed by a score equal to or below it.
+#[derive(Clone, PartialEq, Debug)]
+pub struct DislikesLadder {
+    /// sorted from the highest one
+    thresholds: Vec<i64>,
+    last_crossed: Option<i64>,
+}
+
+/// One line of a solver progress stream for external monitoring: the annealing state after a cooling step or a new best pose.
+#[derive(Clone, PartialEq, Serialize, Deserialize, Debug)]
+pub struct ProgressRecord {
+    pub task: String,
+    /// cooling steps done so far
+    pub step: usize,
+    pub temp: f64,
+    /// dislikes of the current pose, `None` while it is not valid
+    pub score: Option<i64>,
+    pub energy: f64,
+}
+
+/// Appends `ProgressRecord` json lines to a file another process may tail. Every line is flushed as soon as it is complete,
+/// and goes to the file whole, so several streams may append to the same file.
+pub struct ProgressStream {
+    writer: io::LineWriter<fs::File>,
+}
+
+#[derive(Debug)]
+pub enum LoadError {
+    OpenFile(io::Error),
+    Deserialize(serde_json::Error),
+}
+
+#[derive(Debug)]
+pub enum SaveError {
+    CreateFile(io::Error),
+    Serialize(serde_json::Error),
+    Rename(io::Error),
+}
+
+#[derive(Debug)]
+pub enum StreamError {
+    OpenFile(io::Error),
+    Serialize(serde_json::Error),
+    Write(io::Error),
+}
+
+#[derive(Debug)]
+pub enum AppendError {
+    OpenFile(io::Error),
+    Serialize(serde_json::Error),
+    Write(io::Error),
+}
+
+impl BestIndex {
+    /// Loads the index from `filename`, a missing file gives an empty index.
+    pub fn load<P>(filename: P) -> Result<BestIndex, LoadError> where P: AsRef<Path> {
+        let file = match fs::File::open(filename) {
+            Ok(file) =>
+                file,
+            Err(error) if error.kind() == io::ErrorKind::NotFound =>
+                return Ok(BestIndex::default()),
+            Err(error) =>
+                return Err(LoadError::OpenFile(error)),
+        };
+        serde_json::from_reader(io::BufReader::new(file))
+            .map_err(LoadError::Deserialize)
+    }
+
+    /// Writes to a temporary file first so an interrupted write never corrupts the previous index.
+    pub fn save<P>(&self, filename: P) -> Result<(), SaveError> where P: AsRef<Path> {
+        let tmp_filename = filename.as_ref().with_extension("tmp");
+        let file = fs::File::create(&tmp_filename)
+            .map_err(SaveError::CreateFile)?;
+        serde_json::to_writer(io::BufWriter::new(file), self)
+            .map_err(SaveError::Serialize)?;
+        fs::rename(&tmp_filename, filename)
+            .map_err(SaveError::Rename)
+    }
+
+    /// Records the result of a solver run for `task_id`: a better score resets the stale runs counter, anything else increments it.
+    /// Returns true if the score is a new best for the task (including the first recorded one).
+    pub fn record(&mut self, task_id: &str, score: i64) -> bool {
+        match self.tasks.get_mut(task_id) {
+            Some(progress) if score < progress.best_score => {
+                progress.best_score = score;
+                progress.stale_runs = 0;
+                true
+            },
+            Some(progress) => {
+                progress.stale_runs += 1;
+                false
+            },
+            None => {
+                self.tasks.insert(task_id.to_string(), TaskProgress { best_score: score, stale_runs: 0, });
+                true
+            },
+        }
+    }
+
+    /// Sum of best scores over all recorded tasks.
+    pub fn total_dislikes(&self) -> i64 {
+        self.tasks.values().map(|progress| progress.best_score).sum()
+    }
+
+    /// True if `task_id` has not improved for `max_stale_runs` runs in a row, so its best is probably the local minimum.
+    /// Zero `max_stale_runs` means that no task is ever settled.
+    pub fn is_settled(&self, task_id: &str, max_stale_runs: usize) -> bool {
+        max_stale_runs > 0 && matches!(self.tasks.get(task_id), Some(progress) if progress.stale_runs >= max_stale_runs)
+    }
+}
+
+impl PassSummary {
+    /// True if the pass has not improved any task, so another pass over the same directory is unlikely to help.
+    pub fn is_stable(&self) -> bool {
+        self.tasks_improved == 0
+    }
+}
+
+impl DislikesLadder {
+    pub fn new(thresholds: &[i64]) -> DislikesLadder {
+        let mut thresholds = thresholds.to_vec();
+        thresholds.sort_unstable_by(|a, b| b.cmp(a));
+        thresholds.dedup();
+        DislikesLadder { thresholds, last_crossed: None, }
+    }
+
+    /// Marks thresholds already crossed by a `score` reached before without reporting them.
+    pub fn with_score(mut self, score: i64) -> DislikesLadder {
+        self.observe(score);
+        self
+    }
+
+    /// The lowest threshold crossed so far.
+    pub fn last_crossed(&self) -> Option<i64> {
+        self.last_crossed
+    }
+
+    /// Returns the lowest threshold newly crossed by `score`, if any: a jump over several thresholds reports only the last one.
+    pub fn observe(&mut self, score: i64) -> Option<i64> {
+        let crossed = self.thresholds.iter()
+            .rev()
+            .cloned()
+            .find(|&threshold| score <= threshold)?;
+        match self.last_crossed {
+            Some(last_crossed) if last_crossed <= crossed =>
+                None,
+            _ => {
+                self.last_crossed = Some(crossed);
+                Some(crossed)
+            },
+        }
+    }
+}
+
+impl ProgressStream {
+    pub fn open<P>(log_file: P) -> Result<ProgressStream, StreamError> where P: AsRef<Path> {
+        let file = fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(log_file)
+            .map_err(StreamError::OpenFile)?;
+        Ok(ProgressStream { writer: io::LineWriter::new(file), })
+    }
+
+    /// Writes `record` as a json line.
+    pub fn write(&mut self, record: &ProgressRecord) -> Result<(), StreamError> {
+        let mut line = serde_json::to_string(record)
+            .map_err(StreamError::Serialize)?;
+        line.push('\n');
+        self.writer.write_all(line.as_bytes())
+            .map_err(StreamError::Write)
+    }
+}
+
+/// Appends `summary` to `log_file` as a json line.
+pub fn append_pass_summary<P>(log_file: P, summary: &PassSummary) -> Result<(), AppendError> where P: AsRef<Path> {
+    let mut line = serde_json::to_string(summary)
+        .map_err(AppendError::Serialize)?;
+    line.push('\n');
+    let mut file = fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(log_file)
+        .map_err(AppendError::OpenFile)?;
+    file.write_all(line.as_bytes())
+        .map_err(AppendError::Write)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip_and_settle() {
+        let temp_directory = tempfile::tempdir().unwrap();
+        let index_file = temp_directory.path().join("best-index.json");
+        assert_eq!(BestIndex::load(&index_file).unwrap(), BestIndex::default());
+
+        let mut index = BestIndex::default();
+        index.record("12", 300);
+        index.record("12", 250);
+        index.record("7", 40);
+        index.record("7", 40);
+        index.record("7", 55);
+        assert_eq!(index.tasks["12"], TaskProgress { best_score: 250, stale_runs: 0, });
+        assert_eq!(index.tasks["7"], TaskProgress { best_score: 40, stale_runs: 2, });
+
+        index.save(&index_file).unwrap();
+        assert_eq!(BestIndex::load(&index_file).unwrap(), index);
+
+        // the task stuck at its recorded best goes behind the others
+        assert!(index.is_settled("7", 2));
+        assert!(!index.is_settled("12", 2));
+        assert!(!index.is_settled("3", 2));
+        assert!(!index.is_settled("7", 0));
+        let mut task_ids = vec!["7", "3", "12"];
+        task_ids.sort_by_key(|task_id| index.is_settled(task_id, 2));
+        assert_eq!(task_ids, vec!["3", "12", "7"]);
+    }
+
+    #[test]
+    fn pass_without_improvement_is_stable() {
+        let mut index = BestIndex::default();
+        assert!(index.record("12", 300));
+        assert!(index.record("7", 40));
+        assert_eq!(index.total_dislikes(), 340);
+
+        // second pass: one task improves
+        let improved = vec![index.record("12", 250), index.record("7", 40)].into_iter().filter(|&improved| improved).count();
+        let summary = PassSummary { pass: 2, tasks_done: 2, tasks_improved: improved, total_dislikes: index.total_dislikes(), };
+        assert_eq!(summary.tasks_improved, 1);
+        assert_eq!(summary.total_dislikes, 290);
+        assert!(!summary.is_stable());
+
+        // third pass: nothing improves, equal scores included
+        let improved = vec![index.record("12", 250), index.record("7", 45)].into_iter().filter(|&improved| improved).count();
+        let summary = PassSummary { pass: 3, tasks_done: 2, tasks_improved: improved, total_dislikes: index.total_dislikes(), };
+        assert_eq!(summary.total_dislikes, 290);
+        assert!(summary.is_stable());
+
+        let temp_directory = tempfile::tempdir().unwrap();
+        let log_file = temp_directory.path().join("passes.jsonl");
+        append_pass_summary(&log_file, &summary).unwrap();
+        append_pass_summary(&log_file, &summary).unwrap();
+        let content = fs::read_to_string(&log_file).unwrap();
+        let summaries: Vec<PassSummary> = content.lines().map(|line| serde_json::from_str(line).unwrap()).collect();
+        assert_eq!(summaries, vec![summary, summary]);
+    }
+
+    #[test]
+    fn ladder_reports_each_threshold_once() {
+        let mut ladder = DislikesLadder::new(&[10, 100, 0, 50]);
+        let crossed: Vec<_> = vec![250, 120, 99, 98, 60, 50, 50, 12, 3, 5, 0, 0]
+            .into_iter()
+            .map(|score| ladder.observe(score))
+            .collect();
+        assert_eq!(
+            crossed,
+            vec![None, None, Some(100), None, None, Some(50), None, None, Some(10), None, Some(0), None],
+        );
+        assert_eq!(ladder.last_crossed(), Some(0));
+
+        // a big jump reports only the lowest threshold, a known score from a previous run is not reported at all
+        assert_eq!(DislikesLadder::new(&[100, 50, 10, 0]).observe(7), Some(10));
+        let mut ladder = DislikesLadder::new(&[100, 50, 10, 0]).with_score(70);
+        assert_eq!(ladder.last_crossed(), Some(100));
+        assert_eq!(ladder.observe(80), None);
+        assert_eq!(ladder.observe(45), Some(50));
+        assert_eq!(DislikesLadder::new(&[]).observe(0), None);
+    }
+
+    #[test]
+    fn progress_stream_lines_are_json() {
+        let temp_directory = tempfile::tempdir().unwrap();
+        let log_file = temp_directory.path().join("progress.jsonl");
+
+        let mut stream = ProgressStream::open(&log_file).unwrap();
+        let cooling_step = ProgressRecord { task: "12".to_string(), step: 1, temp: 99.0, score: None, energy: 4.5, };
+        stream.write(&cooling_step).unwrap();
+        // the line is on disk right away even though the stream is still open
+        assert_eq!(fs::read_to_string(&log_file).unwrap().lines().count(), 1);
+        let improvement = ProgressRecord { task: "12".to_string(), step: 2, temp: 98.0, score: Some(250), energy: 1.996, };
+        stream.write(&improvement).unwrap();
+        let content = fs::read_to_string(&log_file).unwrap();
+        drop(stream);
+
+        let lines: Vec<_> = content.lines().collect();
+        assert_eq!(lines.len(), 2);
+        for line in &lines {
+            let value: serde_json::Value = serde_json::from_str(line).unwrap();
+            let mut keys: Vec<_> = value.as_object().unwrap().keys().cloned().collect();
+            keys.sort();
+            assert_eq!(keys, vec!["energy", "score", "step", "task", "temp"]);
+        }
+        assert_eq!(serde_json::from_str::<ProgressRecord>(lines[0]).unwrap(), cooling_step);
+        assert_eq!(serde_json::from_str::<ProgressRecord>(lines[1]).unwrap(), improvement);
+    }
+}
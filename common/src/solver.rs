@@ -1,3 +1,7 @@
+use std::{
+    sync::Arc,
+};
+
 use geo::algorithm::contains::Contains;
 
 use crate::{
@@ -7,6 +11,11 @@ use crate::{
 pub mod simulated_annealing;
 pub mod bruteforce;
 pub mod bruteforce_hole;
+pub mod config;
+pub mod polish;
+pub mod assignment;
+pub mod rigid;
+pub mod components;
 
 #[allow(dead_code)]
 pub struct Solver {
@@ -16,16 +25,42 @@ pub struct Solver {
     field_area: usize,
     field_width: i64,
     field_height: i64,
-    problem: problem::Problem,
+    problem: Arc<problem::Problem>,
+    solver_problem: Arc<SolverProblem>,
     pose: problem::Pose,
     pose_score: i64,
     use_bonus: Option<problem::ProblemBonusType>,
+    edge_checker: Arc<dyn problem::InvalidEdge + Send + Sync>,
+}
+
+/// Figure data solvers check their moves against: edges, original vertices and epsilon,
+/// with squared lengths of the original edges computed once.
+#[derive(Clone, PartialEq, Debug)]
+pub struct SolverProblem {
+    pub edges: Vec<problem::Edge>,
+    pub vertices: Vec<problem::Point>,
+    pub epsilon: u64,
+    /// squared lengths of `edges` in the original figure
+    pub edge_sq_lengths: Vec<i64>,
+}
+
+/// Problem data built once per task and shared by all of its solvers, see `Solver::with_shared_problem`.
+#[derive(Clone)]
+pub struct SharedProblem {
+    pub problem: Arc<problem::Problem>,
+    pub solver_problem: Arc<SolverProblem>,
+    /// plain polygon `InvalidEdge` backend solvers start with
+    pub edge_checker: Arc<dyn problem::InvalidEdge + Send + Sync>,
 }
 
+/// Upper bound for the hole mask size in bits (128 MiB of memory).
+pub const MAX_FIELD_AREA: i64 = 1 << 30;
+
 #[derive(Debug)]
 pub enum CreateError {
     NoPointsInHole,
     NoPointsInFigure,
+    FieldTooLarge { field_width: i64, field_height: i64, },
 }
 
 impl Solver {
@@ -45,6 +80,33 @@ impl Solver {
     )
         -> Result<Solver, CreateError>
     {
+        Solver::with_field_margin(problem, pose, use_bonus, 0)
+    }
+
+    /// Same as `with_bonus` but with wallhack the field is expanded by `field_margin` cells in every direction,
+    /// so the vertex outside of the hole may go beyond the hole and figure bounds. Without wallhack the margin is ignored.
+    pub fn with_field_margin(
+        problem: &problem::Problem,
+        pose: Option<problem::Pose>,
+        use_bonus: Option<problem::ProblemBonusType>,
+        field_margin: i64,
+    )
+        -> Result<Solver, CreateError>
+    {
+        Solver::with_shared_problem(&SharedProblem::new(problem.clone()), pose, use_bonus, field_margin)
+    }
+
+    /// Same as `with_field_margin` but neither the problem is cloned nor `SolverProblem` is rebuilt,
+    /// e.g. for several solvers of the same task in one worker.
+    pub fn with_shared_problem(
+        shared_problem: &SharedProblem,
+        pose: Option<problem::Pose>,
+        use_bonus: Option<problem::ProblemBonusType>,
+        field_margin: i64,
+    )
+        -> Result<Solver, CreateError>
+    {
+        let problem = shared_problem.problem.clone();
         if problem.hole.is_empty() {
             return Err(CreateError::NoPointsInHole);
         }
@@ -76,9 +138,17 @@ impl Solver {
                 .max()
                 .unwrap(),
         );
+        let field_margin = if use_bonus == Some(problem::ProblemBonusType::Wallhack) { field_margin.max(0) } else { 0 };
+        let field_min = problem::Point(field_min.0 - field_margin, field_min.1 - field_margin);
+        let field_max = problem::Point(field_max.0 + field_margin, field_max.1 + field_margin);
         let field_width = field_max.0 - field_min.0 + 1;
         let field_height = field_max.1 - field_min.1 + 1;
-        let field_area = (field_width * field_height) as usize;
+        let field_area = match field_width.checked_mul(field_height) {
+            Some(field_area) if field_area <= MAX_FIELD_AREA =>
+                field_area as usize,
+            _ =>
+                return Err(CreateError::FieldTooLarge { field_width, field_height, }),
+        };
         let mut hole_mask = bit_vec::BitVec::from_elem(field_area, false);
 
         let hole_poly = problem.hole_polygon();
@@ -91,14 +161,27 @@ impl Solver {
             }
         }
 
+        let figure_pose = || problem::Pose {
+            vertices: problem.figure.vertices.clone(),
+            bonuses: None,
+        };
         let pose = match pose {
-            None => problem::Pose {
-                vertices: problem.figure.vertices.clone(),
-                bonuses: None,
+            None =>
+                figure_pose(),
+            // a pose for another figure would index out of vertices in scoring
+            Some(pose) if pose.vertices.len() != problem.figure.vertices.len() => {
+                log::warn!(
+                    "seed pose has {} vertices while the figure has {}, starting from the figure instead",
+                    pose.vertices.len(),
+                    problem.figure.vertices.len(),
+                );
+                figure_pose()
             },
-            Some(pose) => pose,
+            Some(pose) =>
+                pose,
         };
-        let pose_score = match problem.score_pose(&pose) {
+        let edge_checker = shared_problem.edge_checker.clone();
+        let pose_score = match problem.score_pose_in(edge_checker.as_ref(), &pose) {
             Ok(score) => score,
             _ => i64::MAX,
         };
@@ -110,21 +193,106 @@ impl Solver {
             field_area,
             field_width,
             field_height,
-            problem: problem.clone(),
+            edge_checker,
+            solver_problem: shared_problem.solver_problem.clone(),
+            problem,
             pose,
             pose_score,
             use_bonus,
         })
     }
 
+    /// The whole problem, scoring needs the hole and bonuses as well.
+    pub fn problem(&self) -> &problem::Problem {
+        &self.problem
+    }
+
+    pub fn solver_problem(&self) -> &SolverProblem {
+        &self.solver_problem
+    }
+
+    /// Replaces the `InvalidEdge` backend used for scoring by solvers, plain polygon by default.
+    pub fn set_edge_checker(&mut self, edge_checker: Box<dyn problem::InvalidEdge + Send + Sync>) {
+        self.edge_checker = edge_checker.into();
+    }
+
+    pub fn edge_checker(&self) -> &dyn problem::InvalidEdge {
+        self.edge_checker.as_ref()
+    }
+
+    /// True if `point` lies within the searchable field (hole and figure bounds, expanded by the wallhack margin).
+    pub fn is_in_field(&self, point: &problem::Point) -> bool {
+        point.0 >= self.field_min.0 && point.0 <= self.field_max.0 && point.1 >= self.field_min.1 && point.1 <= self.field_max.1
+    }
+
     pub fn is_hole(&self, point: &problem::Point) -> bool {
-        if point.0 < self.field_min.0 || point.0 > self.field_max.0 || point.1 < self.field_min.1 || point.1 > self.field_max.1 {
+        if !self.is_in_field(point) {
             return false;
         }
         let mask_index = (point.1 - self.field_min.1) * self.field_width + (point.0 - self.field_min.0);
         self.hole_mask.get(mask_index as usize)
             .unwrap_or(false)
     }
+
+    /// All in-hole cells of the rasterized hole mask, row by row.
+    pub fn in_hole_points(&self) -> impl Iterator<Item = problem::Point> + '_ {
+        self.hole_mask
+            .iter()
+            .enumerate()
+            .filter(|&(_, in_hole)| in_hole)
+            .map(move |(mask_index, _)| problem::Point(
+                self.field_min.0 + mask_index as i64 % self.field_width,
+                self.field_min.1 + mask_index as i64 / self.field_width,
+            ))
+    }
+
+    /// In-hole cells among 8 neighbours of `point`, i.e. positions a vertex can be moved to in one step.
+    pub fn reachable_cells(&self, point: &problem::Point) -> Vec<problem::Point> {
+        let mut cells = Vec::with_capacity(8);
+        for dy in -1 ..= 1 {
+            for dx in -1 ..= 1 {
+                let cell = problem::Point(point.0 + dx, point.1 + dy);
+                if (dx != 0 || dy != 0) && self.is_hole(&cell) {
+                    cells.push(cell);
+                }
+            }
+        }
+        cells
+    }
+}
+
+impl SharedProblem {
+    pub fn new(problem: problem::Problem) -> SharedProblem {
+        let solver_problem = Arc::new(SolverProblem::new(&problem));
+        let edge_checker = problem::EdgeChecker::Polygon.build(&problem).into();
+        SharedProblem { problem: Arc::new(problem), solver_problem, edge_checker, }
+    }
+}
+
+impl SolverProblem {
+    pub fn new(problem: &problem::Problem) -> SolverProblem {
+        let vertices = &problem.figure.vertices;
+        SolverProblem {
+            edges: problem.figure.edges.clone(),
+            vertices: vertices.clone(),
+            epsilon: problem.epsilon,
+            edge_sq_lengths: problem.figure.edges.iter()
+                .map(|edge| problem::distance(&vertices[edge.0], &vertices[edge.1]))
+                .collect(),
+        }
+    }
+
+    pub fn epsilon_fraction(&self) -> f64 {
+        self.epsilon as f64 / 1000000.0
+    }
+
+    /// Same as `is_edge_ratio_valid` for the edge number `edge_index` without recomputing its original length.
+    pub fn is_edge_ratio_valid(&self, edge_index: usize, vertices: &[problem::Point]) -> (bool, f64) {
+        let edge = self.edges[edge_index];
+        let try_sq_dist = problem::distance(&vertices[edge.0], &vertices[edge.1]);
+        let ratio = problem::stretch_ratio(self.edge_sq_lengths[edge_index], try_sq_dist);
+        (ratio <= self.epsilon_fraction(), ratio)
+    }
 }
 
 pub fn is_edge_ratio_valid(
@@ -143,18 +311,26 @@ pub fn is_edge_ratio_valid(
     let sample_sq_dist = problem::distance(&sample_vertex_a, &sample_vertex_b);
     let try_sq_dist = problem::distance(&try_vertex_a, &try_vertex_b);
 
-    let ratio = ((try_sq_dist as f64 / sample_sq_dist as f64) - 1.0).abs();
-    (ratio <= problem.epsilon as f64 / 1000000.0, ratio)
+    let ratio = problem::stretch_ratio(sample_sq_dist, try_sq_dist);
+    (ratio <= problem.epsilon_fraction(), ratio)
 }
 
 #[cfg(test)]
 mod tests {
+    use std::{
+        sync::Arc,
+    };
+
     use geo::algorithm::contains::Contains;
 
     use crate::{
         problem,
         solver::{
+            self,
             Solver,
+            SolverProblem,
+            SharedProblem,
+            CreateError,
         },
     };
 
@@ -188,4 +364,89 @@ mod tests {
         assert_eq!(hole_poly.contains(&problem::Point(0, 20)), true);
         assert_eq!(hole_poly.contains(&problem::Point(20, 40)), true);
     }
+
+    #[test]
+    fn in_hole_points() {
+        let problem_data = r#"{"bonuses":[],"hole":[[20,0],[40,20],[20,40],[0,20]],"epsilon":0,"figure":{"edges":[[0,1]],"vertices":[[15,21],[34,0]]}}"#;
+        let problem: problem::Problem = serde_json::from_str(problem_data).unwrap();
+        let solver = Solver::new(&problem, None).unwrap();
+        let points: Vec<_> = solver.in_hole_points().collect();
+        assert_eq!(points.len(), solver.hole_mask.iter().filter(|&in_hole| in_hole).count());
+        assert!(points.iter().all(|point| solver.is_hole(point)));
+        assert_eq!(points[0], problem::Point(20, 0));
+    }
+
+    #[test]
+    fn wallhack_field_margin() {
+        let problem_data = r#"{"bonuses":[],"hole":[[0,0],[10,0],[10,10],[0,10]],"epsilon":0,"figure":{"edges":[[0,1]],"vertices":[[0,0],[5,5]]}}"#;
+        let problem: problem::Problem = serde_json::from_str(problem_data).unwrap();
+        let beyond = problem::Point(12, 5);
+
+        let solver = Solver::with_bonus(&problem, None, Some(problem::ProblemBonusType::Wallhack)).unwrap();
+        assert_eq!(solver.field_max, problem::Point(10, 10));
+        assert!(!solver.is_in_field(&beyond));
+
+        let solver = Solver::with_field_margin(&problem, None, Some(problem::ProblemBonusType::Wallhack), 3).unwrap();
+        assert_eq!(solver.field_max, problem::Point(13, 13));
+        assert!(solver.is_in_field(&beyond));
+        assert!(!solver.is_hole(&beyond));
+        assert!(solver.is_hole(&problem::Point(10, 10)));
+
+        let solver = Solver::with_field_margin(&problem, None, None, 3).unwrap();
+        assert!(!solver.is_in_field(&beyond));
+    }
+
+    #[test]
+    fn mismatched_seed_pose_falls_back_to_figure() {
+        let problem_data = r#"{"bonuses":[],"hole":[[0,0],[10,0],[10,10],[0,10]],"epsilon":0,"figure":{"edges":[[0,1],[1,2]],"vertices":[[0,0],[5,0],[5,5]]}}"#;
+        let problem: problem::Problem = serde_json::from_str(problem_data).unwrap();
+
+        let short_pose = problem::Pose { vertices: vec![problem::Point(1, 1), problem::Point(6, 1)], bonuses: None, };
+        let solver = Solver::new(&problem, Some(short_pose)).unwrap();
+        assert_eq!(solver.pose.vertices, problem.figure.vertices);
+        assert_eq!(solver.pose_score, problem.score_pose(&solver.pose).unwrap());
+
+        let seed_pose = problem::Pose { vertices: vec![problem::Point(1, 1), problem::Point(6, 1), problem::Point(6, 6)], bonuses: None, };
+        let solver = Solver::new(&problem, Some(seed_pose.clone())).unwrap();
+        assert_eq!(solver.pose, seed_pose);
+    }
+
+    #[test]
+    fn large_coordinates() {
+        let problem_data = r#"{"bonuses":[],"hole":[[0,0],[4000000000,0],[4000000000,4000000000],[0,4000000000]],"epsilon":0,"figure":{"edges":[[0,1]],"vertices":[[0,0],[4000000000,4000000000]]}}"#;
+        let problem: problem::Problem = serde_json::from_str(problem_data).unwrap();
+        assert!(matches!(
+            Solver::new(&problem, None),
+            Err(CreateError::FieldTooLarge { field_width: 4000000001, field_height: 4000000001, }),
+        ));
+        assert_eq!(problem::distance(&problem::Point(0, 0), &problem::Point(4000000000, 4000000000)), i64::MAX);
+        assert_eq!(problem::distance(&problem::Point(0, 0), &problem::Point(100000, 100000)), 20000000000);
+    }
+
+    #[test]
+    fn solver_problem_keeps_edges_and_epsilon() {
+        let problem_data = r#"{"bonuses":[{"bonus":"GLOBALIST","problem":72,"position":[17,10]}],"hole":[[34,0],[17,30],[10,62],[13,30],[0,0]],"epsilon":6731,"figure":{"edges":[[0,1],[0,3],[1,2],[1,3],[2,4],[3,4]],"vertices":[[0,0],[0,34],[17,62],[30,17],[45,46]]}}"#;
+        let problem: problem::Problem = serde_json::from_str(problem_data).unwrap();
+        let shared_problem = SharedProblem::new(problem);
+        let problem = &shared_problem.problem;
+        let solver = Solver::with_shared_problem(&shared_problem, None, None, 0).unwrap();
+        let other_solver = Solver::with_shared_problem(&shared_problem, None, Some(problem::ProblemBonusType::Globalist), 0).unwrap();
+        assert!(std::ptr::eq(solver.problem(), &**problem));
+        // built once per task, not per solver
+        assert!(std::ptr::eq(solver.solver_problem(), other_solver.solver_problem()));
+        assert!(Arc::ptr_eq(&solver.edge_checker, &other_solver.edge_checker));
+
+        let solver_problem = solver.solver_problem();
+        assert_eq!(solver_problem, &SolverProblem::new(problem));
+        assert_eq!(solver_problem.edges, problem.figure.edges);
+        assert_eq!(solver_problem.vertices, problem.figure.vertices);
+        assert_eq!(solver_problem.epsilon, 6731);
+        assert_eq!(solver_problem.epsilon_fraction(), problem.epsilon_fraction());
+        assert_eq!(solver_problem.edge_sq_lengths, vec![1156, 1189, 1073, 1189, 1040, 1066]);
+
+        let moved = vec![problem::Point(0, 0), problem::Point(0, 35), problem::Point(17, 62), problem::Point(30, 17), problem::Point(45, 46)];
+        for (edge_index, edge) in problem.figure.edges.iter().enumerate() {
+            assert_eq!(solver_problem.is_edge_ratio_valid(edge_index, &moved), solver::is_edge_ratio_valid(edge, &moved, problem));
+        }
+    }
 }
@@ -0,0 +1,102 @@
+use crate::{
+    problem,
+};
+
+/// Backtracking over all hole corner permutations grows factorially, so larger problems are left to other solvers.
+pub const MAX_ASSIGNMENT_VERTICES: usize = 12;
+
+/// Solver for problems where the hole has exactly as many vertices as the figure: every figure vertex is placed
+/// onto its own hole corner, so a valid assignment always scores zero dislikes. Corners are assigned one figure
+/// vertex at a time, trying the cheapest feasible corner first (sum of stretch ratios of edges to already placed
+/// vertices) and pruning corners that break an edge. Complete assignments are validated with `score_vertices`.
+pub fn solve_by_assignment(problem: &problem::Problem) -> Option<problem::Pose> {
+    let vertices_count = problem.figure.vertices.len();
+    if vertices_count == 0 || vertices_count != problem.hole.len() || vertices_count > MAX_ASSIGNMENT_VERTICES {
+        return None;
+    }
+
+    let mut neighbours = vec![Vec::new(); vertices_count];
+    for edge in &problem.figure.edges {
+        neighbours[edge.0].push(edge.1);
+        neighbours[edge.1].push(edge.0);
+    }
+
+    let mut assignment = vec![None; vertices_count];
+    let mut used = vec![false; vertices_count];
+    let vertices = assign(problem, &neighbours, 0, &mut assignment, &mut used)?;
+    Some(problem::Pose { vertices, bonuses: None, })
+}
+
+fn assign(
+    problem: &problem::Problem,
+    neighbours: &[Vec<usize>],
+    vertex_index: usize,
+    assignment: &mut [Option<usize>],
+    used: &mut [bool],
+)
+    -> Option<Vec<problem::Point>>
+{
+    if vertex_index == assignment.len() {
+        let vertices: Vec<_> = assignment.iter()
+            .map(|corner| problem.hole[corner.unwrap()])
+            .collect();
+        return match problem.score_vertices(&vertices, None) {
+            Ok(0) =>
+                Some(vertices),
+            _ =>
+                None,
+        };
+    }
+
+    let epsilon_ratio = problem.epsilon_fraction();
+    let mut candidates = Vec::new();
+    'corners: for (corner, corner_point) in problem.hole.iter().enumerate() {
+        if used[corner] {
+            continue;
+        }
+        let mut cost = 0.0;
+        for &neighbour in &neighbours[vertex_index] {
+            if let Some(neighbour_corner) = assignment[neighbour] {
+                let ratio = problem::stretch_ratio(
+                    problem::distance(&problem.figure.vertices[vertex_index], &problem.figure.vertices[neighbour]),
+                    problem::distance(corner_point, &problem.hole[neighbour_corner]),
+                );
+                if ratio > epsilon_ratio {
+                    continue 'corners;
+                }
+                cost += ratio;
+            }
+        }
+        candidates.push((cost, corner));
+    }
+    candidates.sort_by(|a, b| a.partial_cmp(b).unwrap());
+
+    for (_cost, corner) in candidates {
+        assignment[vertex_index] = Some(corner);
+        used[corner] = true;
+        if let Some(vertices) = assign(problem, neighbours, vertex_index + 1, assignment, used) {
+            return Some(vertices);
+        }
+        used[corner] = false;
+        assignment[vertex_index] = None;
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn problem_13_corners() {
+        let problem_data = r#"{"bonuses":[{"bonus":"GLOBALIST","problem":46,"position":[20,20]},{"bonus":"BREAK_A_LEG","problem":88,"position":[30,30]}],"hole":[[20,0],[40,20],[20,40],[0,20]],"epsilon":2494,"figure":{"edges":[[0,1],[0,2],[1,3],[2,3]],"vertices":[[15,21],[34,0],[0,45],[19,24]]}}"#;
+        let problem: problem::Problem = serde_json::from_str(problem_data).unwrap();
+
+        let pose = solve_by_assignment(&problem).unwrap();
+        assert_eq!(problem.score_pose(&pose).unwrap(), 0);
+
+        let mut problem = problem;
+        problem.hole.push(problem::Point(10, 10));
+        assert_eq!(solve_by_assignment(&problem), None);
+    }
+}
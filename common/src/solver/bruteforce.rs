@@ -15,6 +15,10 @@ impl BruteforceSolver {
         }
     }
 
+    pub fn edge_checker(&self) -> &dyn problem::InvalidEdge {
+        self.solver.edge_checker()
+    }
+
 
     fn run(&self,
            start: problem::Point, vert_idx: usize, last_best: i64,
@@ -59,7 +63,7 @@ impl BruteforceSolver {
                     }
                     let distance = problem::distance(&vertice, &vertices[idx]);
 
-                    if ((distance as f64 / edge_distance as f64) - 1_f64).abs() > self.solver.problem.epsilon as f64 / 1000000_f64 {
+                    if problem::stretch_ratio(edge_distance, distance) > self.solver.problem.epsilon as f64 / 1000000_f64 {
                         continue 'loop_x;
                     }
                 }
@@ -69,7 +73,7 @@ impl BruteforceSolver {
                 if vert_idx == vertices.len() - 1 {
                     // log::debug!("scoring candidate... {:?}", vertices);
 
-                    match self.solver.problem.score_vertices(vertices, None) {
+                    match self.solver.problem.score_vertices_in(self.solver.edge_checker(), vertices, None) {
                         Ok(score) => {
                             // log::debug!("Found solution with score {:?}: {:?}", score, vertices);
                             if score == 0 { // perfect solution found
@@ -126,3 +130,25 @@ impl BruteforceSolver {
         pose
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn same_score_for_every_edge_checker() {
+        let problem_data = r#"{"bonuses":[],"hole":[[0,0],[4,0],[4,4],[2,2],[0,4]],"epsilon":0,"figure":{"edges":[[0,1],[1,2],[2,0]],"vertices":[[0,0],[3,0],[0,3]]}}"#;
+        let problem: problem::Problem = serde_json::from_str(problem_data).unwrap();
+
+        let mut scores = Vec::new();
+        for edge_checker in &["polygon", "quadtree"] {
+            let edge_checker: problem::EdgeChecker = edge_checker.parse().unwrap();
+            let mut solver = solver::Solver::new(&problem, None).unwrap();
+            solver.set_edge_checker(edge_checker.build(&problem));
+            let pose = BruteforceSolver::new(solver).solve().unwrap();
+            scores.push(problem.score_pose(&pose).unwrap());
+        }
+        assert_eq!(scores[0], scores[1]);
+        assert!("bloom".parse::<problem::EdgeChecker>().is_err());
+    }
+}
@@ -1,21 +1,148 @@
-// use std::{
-//     path::PathBuf,
-// };
-
 use std::{
+    fs,
+    io,
     cmp,
-    collections::HashSet,
+    path::{
+        Path,
+        PathBuf,
+    },
+    collections::{
+        HashMap,
+        HashSet,
+    },
     iter::FromIterator,
+    sync::atomic::{
+        Ordering,
+        AtomicI64,
+        AtomicBool,
+        AtomicUsize,
+    },
+};
+
+use rand::{
+    SeedableRng,
+    seq::SliceRandom,
+};
+
+use rayon::prelude::*;
+
+use serde_derive::{
+    Serialize,
+    Deserialize,
 };
 
 use crate::{
+    math,
     solver,
     problem,
+    PartialPose,
 };
 
 #[allow(dead_code)]
 pub struct BruteforceHoleSolver {
     solver: solver::Solver,
+    max_candidates: usize,
+    hole_centroid: problem::Point,
+    heuristic_reported: AtomicBool,
+    checkpoint_file: Option<PathBuf>,
+    resume_from: Option<Checkpoint>,
+    fixed: HashMap<usize, problem::Point>,
+    candidate_order: CandidateOrder,
+    placements_tried: AtomicUsize,
+}
+
+/// Order in which hole points are tried for a vertex.
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize, Debug)]
+pub enum CandidateOrder {
+    /// closest to the hole centroid first
+    HoleCentroid,
+    /// points keeping the most edges to already placed neighbours within epsilon first, then `HoleCentroid` order
+    MostConstrained,
+}
+
+pub const CHECKPOINT_VERSION: u32 = 2;
+
+/// Search frontier of the sequential search: first vertex placements (hole points sorted by coordinates)
+/// before `next_start_idx` are done, `best_score` and `best_pose` is the best found so far.
+#[derive(Clone, PartialEq, Serialize, Deserialize, Debug)]
+pub struct Checkpoint {
+    pub version: u32,
+    pub search: SearchIdentity,
+    pub next_start_idx: usize,
+    pub best_score: i64,
+    pub best_pose: Option<problem::Pose>,
+}
+
+/// Everything the search frontier depends on: a checkpoint is only valid for the very same search.
+#[derive(Clone, PartialEq, Serialize, Deserialize, Debug)]
+pub struct SearchIdentity {
+    /// `Problem::fingerprint` of the searched problem
+    pub problem: u64,
+    pub bonus: Option<problem::PoseBonus>,
+    /// pinned vertices sorted by index
+    pub fixed: Vec<(usize, problem::Point)>,
+    pub candidate_order: CandidateOrder,
+    pub max_candidates: usize,
+}
+
+#[derive(Debug)]
+pub enum CheckpointError {
+    OpenFile(io::Error),
+    Deserialize(serde_json::Error),
+    UnsupportedVersion { version: u32, },
+    SearchMismatch { checkpoint: Box<SearchIdentity>, search: Box<SearchIdentity>, },
+    CreateFile(io::Error),
+    Serialize(serde_json::Error),
+    Rename(io::Error),
+}
+
+/// Search state shared between all branches (and threads) of the single `solve` run.
+struct SearchState {
+    best_score: AtomicI64,
+    perfect_found: AtomicBool,
+}
+
+impl SearchState {
+    fn new() -> SearchState {
+        SearchState {
+            best_score: AtomicI64::new(i64::MAX),
+            perfect_found: AtomicBool::new(false),
+        }
+    }
+}
+
+impl Checkpoint {
+    pub fn new(search: SearchIdentity) -> Checkpoint {
+        Checkpoint {
+            version: CHECKPOINT_VERSION,
+            search,
+            next_start_idx: 0,
+            best_score: i64::MAX,
+            best_pose: None,
+        }
+    }
+
+    pub fn from_file<P>(filename: P) -> Result<Checkpoint, CheckpointError> where P: AsRef<Path> {
+        let file = fs::File::open(filename)
+            .map_err(CheckpointError::OpenFile)?;
+        let checkpoint: Checkpoint = serde_json::from_reader(io::BufReader::new(file))
+            .map_err(CheckpointError::Deserialize)?;
+        if checkpoint.version != CHECKPOINT_VERSION {
+            return Err(CheckpointError::UnsupportedVersion { version: checkpoint.version, });
+        }
+        Ok(checkpoint)
+    }
+
+    /// Writes to a temporary file first so an interrupted write never corrupts the previous checkpoint.
+    pub fn write_to_file<P>(&self, filename: P) -> Result<(), CheckpointError> where P: AsRef<Path> {
+        let tmp_filename = filename.as_ref().with_extension("tmp");
+        let file = fs::File::create(&tmp_filename)
+            .map_err(CheckpointError::CreateFile)?;
+        serde_json::to_writer(io::BufWriter::new(file), self)
+            .map_err(CheckpointError::Serialize)?;
+        fs::rename(&tmp_filename, filename)
+            .map_err(CheckpointError::Rename)
+    }
 }
 
 #[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
@@ -64,15 +191,193 @@ impl BoundingRingBox {
 }
 
 impl BruteforceHoleSolver {
-    pub fn new(solver: solver::Solver) -> BruteforceHoleSolver {
+    /// `max_candidates` limits every candidate point set of the search: bigger sets are replaced with a deterministic random subset.
+    pub fn new(solver: solver::Solver, max_candidates: usize) -> BruteforceHoleSolver {
+        let hole = &solver.problem.hole;
+        let hole_centroid = problem::Point(
+            hole.iter().map(|point| point.0).sum::<i64>() / hole.len() as i64,
+            hole.iter().map(|point| point.1).sum::<i64>() / hole.len() as i64,
+        );
         BruteforceHoleSolver {
             solver,
+            max_candidates,
+            hole_centroid,
+            heuristic_reported: AtomicBool::new(false),
+            checkpoint_file: None,
+            resume_from: None,
+            fixed: HashMap::new(),
+            candidate_order: CandidateOrder::HoleCentroid,
+            placements_tried: AtomicUsize::new(0),
         }
     }
 
-    pub fn solve(&self) -> Option<problem::Pose> {
-        let mut vertices = self.solver.problem.figure.vertices.clone();
-        let hole = HashSet::from_iter(self.solver.problem.hole.iter().cloned());
+    /// Continues the search saved in `checkpoint_file`, progress is saved back to the same file.
+    pub fn resume<P>(solver: solver::Solver, max_candidates: usize, checkpoint_file: P) -> Result<BruteforceHoleSolver, CheckpointError> where P: AsRef<Path> {
+        let checkpoint = Checkpoint::from_file(&checkpoint_file)?;
+        log::info!("resuming bruteforce from {:?}: {:?}", checkpoint_file.as_ref(), checkpoint);
+        Ok(BruteforceHoleSolver {
+            resume_from: Some(checkpoint),
+            ..BruteforceHoleSolver::new(solver, max_candidates).with_checkpoint_file(checkpoint_file)
+        })
+    }
+
+    /// Saves search progress to `checkpoint_file` after each first vertex placement (`solve` runs the sequential search then).
+    pub fn with_checkpoint_file<P>(self, checkpoint_file: P) -> BruteforceHoleSolver where P: AsRef<Path> {
+        BruteforceHoleSolver {
+            checkpoint_file: Some(checkpoint_file.as_ref().to_path_buf()),
+            ..self
+        }
+    }
+
+    /// Pins figure vertices to known points (e.g. from a partial solution): they are placed up front and
+    /// skipped by the search, which only validates edges against them.
+    pub fn with_fixed(self, fixed: HashMap<usize, problem::Point>) -> BruteforceHoleSolver {
+        BruteforceHoleSolver {
+            fixed,
+            ..self
+        }
+    }
+
+    pub fn with_candidate_order(self, candidate_order: CandidateOrder) -> BruteforceHoleSolver {
+        BruteforceHoleSolver {
+            candidate_order,
+            ..self
+        }
+    }
+
+    /// Number of candidate points checked for vertex placement by the last search.
+    pub fn placements_tried(&self) -> usize {
+        self.placements_tried.load(Ordering::Relaxed)
+    }
+
+    pub fn edge_checker(&self) -> &dyn problem::InvalidEdge {
+        self.solver.edge_checker()
+    }
+
+    /// Identity of the search this solver runs, saved into checkpoints and checked on resume.
+    pub fn search_identity(&self) -> SearchIdentity {
+        let mut fixed: Vec<_> = self.fixed.iter()
+            .map(|(&vertex_index, &point)| (vertex_index, point))
+            .collect();
+        fixed.sort_unstable_by_key(|&(vertex_index, _)| vertex_index);
+        SearchIdentity {
+            problem: self.solver.problem.fingerprint(),
+            bonus: self.solver.pose.bonus(),
+            fixed,
+            candidate_order: self.candidate_order,
+            max_candidates: self.max_candidates,
+        }
+    }
+
+    /// Runs the search in parallel: every placement of the first vertex onto the hole is processed by a separate rayon task.
+    /// Checkpoints need the order of the sequential search, so with a checkpoint set this is `solve_sequential`.
+    pub fn solve(&self) -> Result<Option<problem::Pose>, CheckpointError> {
+        if self.checkpoint_file.is_some() || self.resume_from.is_some() {
+            return self.solve_sequential();
+        }
+        let (vertices, hole, distances) = self.prepare();
+        if vertices.len() < 2 {
+            return self.solve_sequential();
+        }
+
+        let bonus = self.solver.pose.bonus();
+        let shared = SearchState::new();
+        let starts = self.vertex_candidates(0, &hole, &vertices);
+        let (score, pose) = starts
+            .par_iter()
+            .map(|start| {
+                if shared.perfect_found.load(Ordering::Relaxed) {
+                    return (i64::MAX, None);
+                }
+                let mut vertices = vertices.clone();
+                vertices.place(0, *start);
+                let mut new_hole = hole.clone();
+                new_hole.remove(start);
+                self.run(1, i64::MAX, &mut vertices, new_hole, &distances, bonus, &shared)
+            })
+            .filter(|(_, pose)| pose.is_some())
+            .min_by_key(|(score, _)| *score)
+            .unwrap_or((i64::MAX, None));
+        Ok(self.report(score, pose))
+    }
+
+    /// Fails with `CheckpointError::SearchMismatch` if the resumed checkpoint was saved for a different search.
+    pub fn solve_sequential(&self) -> Result<Option<problem::Pose>, CheckpointError> {
+        let checkpoint = self.start_checkpoint()?;
+        if self.solver.problem.figure.vertices.len() < 2 {
+            let (mut vertices, hole, distances) = self.prepare();
+            let shared = SearchState::new();
+            let (score, pose) = self.run(0, i64::MAX, &mut vertices, hole, &distances, self.solver.pose.bonus(), &shared);
+            return Ok(self.report(score, pose));
+        }
+
+        let checkpoint = self.run_checkpointed(checkpoint, None);
+        Ok(self.report(checkpoint.best_score, checkpoint.best_pose))
+    }
+
+    /// The resumed checkpoint if it belongs to this search, a fresh one if there is nothing to resume.
+    fn start_checkpoint(&self) -> Result<Checkpoint, CheckpointError> {
+        let search = self.search_identity();
+        match &self.resume_from {
+            Some(checkpoint) if checkpoint.search != search =>
+                Err(CheckpointError::SearchMismatch { checkpoint: Box::new(checkpoint.search.clone()), search: Box::new(search), }),
+            Some(checkpoint) =>
+                Ok(checkpoint.clone()),
+            None =>
+                Ok(Checkpoint::new(search)),
+        }
+    }
+
+    /// Processes first vertex placements starting from `checkpoint`, at most `max_starts` of them if provided.
+    fn run_checkpointed(&self, mut checkpoint: Checkpoint, max_starts: Option<usize>) -> Checkpoint {
+        let (vertices, hole, distances) = self.prepare();
+        let bonus = self.solver.pose.bonus();
+        let shared = SearchState::new();
+        shared.best_score.store(checkpoint.best_score, Ordering::Relaxed);
+
+        let mut starts: Vec<_> = match self.fixed.get(&0) {
+            Some(&point) =>
+                vec![point],
+            None =>
+                hole.iter().cloned().collect(),
+        };
+        starts.sort_by_key(|point| (point.0, point.1));
+        let mut processed = 0;
+        while checkpoint.next_start_idx < starts.len() && checkpoint.best_score != 0 {
+            if Some(processed) == max_starts {
+                break;
+            }
+            let start = starts[checkpoint.next_start_idx];
+            let mut vertices = vertices.clone();
+            vertices.place(0, start);
+            let mut new_hole = hole.clone();
+            new_hole.remove(&start);
+            let (score, pose) = self.run(1, checkpoint.best_score, &mut vertices, new_hole, &distances, bonus, &shared);
+            if pose.is_some() && score < checkpoint.best_score {
+                checkpoint.best_score = score;
+                checkpoint.best_pose = pose;
+            }
+            checkpoint.next_start_idx += 1;
+            processed += 1;
+
+            if let Some(checkpoint_file) = &self.checkpoint_file {
+                if let Err(error) = checkpoint.write_to_file(checkpoint_file) {
+                    log::error!("failed to save checkpoint to {:?}: {:?}", checkpoint_file, error);
+                }
+            }
+        }
+        checkpoint
+    }
+
+    fn prepare(&self) -> (PartialPose, HashSet<problem::Point>, Vec<i64>) {
+        self.placements_tried.store(0, Ordering::Relaxed);
+        let mut vertices = PartialPose::new(self.solver.problem.figure.vertices.clone());
+        let mut hole = HashSet::from_iter(self.solver.problem.hole.iter().cloned());
+        for (&vertex_index, &point) in &self.fixed {
+            vertices.place(vertex_index, point);
+            hole.remove(&point);
+        }
+        let hole = self.limit_candidates(hole, 0);
         let mut distances = vec![-1; vertices.len() * vertices.len()];
 
         for &problem::Edge(from_idx, to_idx) in self.solver.problem.figure.edges.iter() {
@@ -89,7 +394,76 @@ impl BruteforceHoleSolver {
             _ => {},
         };
 
-        let (score, pose) = self.run(0, i64::MAX, &mut vertices, hole, &distances, self.solver.pose.bonus());
+        (vertices, hole, distances)
+    }
+
+    /// Keeps at most `max_candidates` points of `candidates`, the subset only depends on the set contents and `seed`.
+    fn limit_candidates(&self, candidates: HashSet<problem::Point>, seed: u64) -> HashSet<problem::Point> {
+        if candidates.len() <= self.max_candidates {
+            return candidates;
+        }
+        self.report_heuristic(candidates.len());
+
+        let mut points: Vec<_> = candidates.into_iter().collect();
+        points.sort_by_key(|point| (point.0, point.1));
+        let mut rng = rand::rngs::StdRng::seed_from_u64(seed);
+        points.shuffle(&mut rng);
+        points.truncate(self.max_candidates);
+        points.into_iter().collect()
+    }
+
+    /// Warns once per solver that a candidate set of (at least) `candidates_len` points was cut down to `max_candidates`.
+    fn report_heuristic(&self, candidates_len: usize) {
+        if !self.heuristic_reported.swap(true, Ordering::Relaxed) {
+            log::warn!(
+                "candidate set of {} points exceeds the limit of {}, search is heuristic from now on",
+                candidates_len,
+                self.max_candidates,
+            );
+        }
+    }
+
+    /// Candidates for `vert_idx` in a reproducible `CandidateOrder` (ties are broken by coordinates).
+    fn ordered_candidates(&self, vert_idx: usize, candidates: &HashSet<problem::Point>, vertices: &PartialPose) -> Vec<problem::Point> {
+        let mut points: Vec<_> = candidates.iter().cloned().collect();
+        match self.candidate_order {
+            CandidateOrder::HoleCentroid =>
+                points.sort_by_key(|point| (problem::distance(point, &self.hole_centroid), point.0, point.1)),
+            CandidateOrder::MostConstrained =>
+                points.sort_by_cached_key(|point| (
+                    cmp::Reverse(self.satisfied_neighbours(vert_idx, point, vertices)),
+                    problem::distance(point, &self.hole_centroid),
+                    point.0,
+                    point.1,
+                )),
+        }
+        points
+    }
+
+    /// Count of edges from `vert_idx` placed at `point` to already placed neighbours which stay within epsilon.
+    fn satisfied_neighbours(&self, vert_idx: usize, point: &problem::Point, vertices: &PartialPose) -> usize {
+        self.solver.problem.figure.edges
+            .iter()
+            .flat_map(|&edge| self.placed_neighbour(edge, vert_idx, vertices))
+            .filter(|&other_idx| {
+                let d_before = problem::distance(&self.solver.problem.figure.vertices[vert_idx], &self.solver.problem.figure.vertices[other_idx]);
+                let d_after = problem::distance(point, &vertices[other_idx]);
+                problem::stretch_ratio(d_before, d_after) <= self.solver.problem.epsilon_fraction()
+            })
+            .count()
+    }
+
+    /// Candidates for `vert_idx`: the pinned point only if the vertex is fixed, `ordered_candidates` otherwise.
+    fn vertex_candidates(&self, vert_idx: usize, candidates: &HashSet<problem::Point>, vertices: &PartialPose) -> Vec<problem::Point> {
+        match self.fixed.get(&vert_idx) {
+            Some(&point) =>
+                vec![point],
+            None =>
+                self.ordered_candidates(vert_idx, candidates, vertices),
+        }
+    }
+
+    fn report(&self, score: i64, pose: Option<problem::Pose>) -> Option<problem::Pose> {
         match pose {
             None => println!("Solution not found..."),
             Some(ref pose) => println!("Found solution with score {:?}: {:?}", score, pose),
@@ -97,16 +471,23 @@ impl BruteforceHoleSolver {
         pose
     }
 
+    #[allow(clippy::too_many_arguments)]
     fn run(&self, vert_idx: usize,
            last_best_score: i64,
-           vertices: &mut Vec<problem::Point>,
+           vertices: &mut PartialPose,
            hole: HashSet<problem::Point>,
            distances: &[i64],
-           bonus: Option<problem::PoseBonus>) -> (i64, Option<problem::Pose>) {
+           bonus: Option<problem::PoseBonus>,
+           shared: &SearchState) -> (i64, Option<problem::Pose>) {
         let mut best_pose_score = last_best_score;
         let mut best_pose = None;
         let mut progress = 1;
-        'next_hole_vertice: for hole_vertice in &hole {
+        let candidates = self.vertex_candidates(vert_idx, &hole, vertices);
+        'next_hole_vertice: for hole_vertice in &candidates {
+            if shared.perfect_found.load(Ordering::Relaxed) {
+                break;
+            }
+            self.placements_tried.fetch_add(1, Ordering::Relaxed);
             if vert_idx == 0 {
                 println!("Starting {} of {}...", progress, hole.len());
             }
@@ -121,17 +502,17 @@ impl BruteforceHoleSolver {
             match bonus {
                 Some(problem::PoseBonus::Globalist {..}) => {
                     let mut eps = 0_f64;
-                    for &problem::Edge(from_idx, to_idx) in self.solver.problem.figure.edges.iter() {
-                        if from_idx != vert_idx {
-                            continue;
-                        }
-                        if to_idx >= vert_idx {
-                            continue;
-                        }
+                    for &edge in self.solver.problem.figure.edges.iter() {
+                        let other_idx = match self.placed_neighbour(edge, vert_idx, vertices) {
+                            Some(other_idx) =>
+                                other_idx,
+                            None =>
+                                continue,
+                        };
 
-                        let d_before = problem::distance(&self.solver.problem.figure.vertices[from_idx], &self.solver.problem.figure.vertices[to_idx]);
-                        let d_after = problem::distance(&hole_vertice, &vertices[to_idx]);
-                        eps += ((d_after as f64 / d_before as f64) - 1_f64).abs();
+                        let d_before = problem::distance(&self.solver.problem.figure.vertices[vert_idx], &self.solver.problem.figure.vertices[other_idx]);
+                        let d_after = problem::distance(&hole_vertice, &vertices[other_idx]);
+                        eps += problem::stretch_ratio(d_before, d_after);
                     }
 
                     let max_eps = self.solver.problem.figure.edges.len() as f64 * self.solver.problem.epsilon as f64 / 1000000_f64 ;
@@ -156,18 +537,18 @@ impl BruteforceHoleSolver {
                         Some(problem::PoseBonus::Superflex {..}) => 1,
                         _ => 0,
                     };
-                    for &problem::Edge(from_idx, to_idx) in self.solver.problem.figure.edges.iter() {
-                        if from_idx != vert_idx {
-                            continue;
-                        }
-                        if to_idx >= vert_idx {
-                            continue;
-                        }
+                    for &edge in self.solver.problem.figure.edges.iter() {
+                        let other_idx = match self.placed_neighbour(edge, vert_idx, vertices) {
+                            Some(other_idx) =>
+                                other_idx,
+                            None =>
+                                continue,
+                        };
 
-                        let d_before = problem::distance(&self.solver.problem.figure.vertices[from_idx], &self.solver.problem.figure.vertices[to_idx]);
-                        let d_after = problem::distance(&hole_vertice, &vertices[to_idx]);
+                        let d_before = problem::distance(&self.solver.problem.figure.vertices[vert_idx], &self.solver.problem.figure.vertices[other_idx]);
+                        let d_after = problem::distance(&hole_vertice, &vertices[other_idx]);
 
-                        if ((d_after as f64 / d_before as f64) - 1_f64).abs() > self.solver.problem.epsilon as f64 / 1000000_f64 {
+                        if problem::stretch_ratio(d_before, d_after) > self.solver.problem.epsilon as f64 / 1000000_f64 {
                             if superstretch_allow > 0 {
                                 superstretch_allow = 0;
                                 continue;
@@ -192,31 +573,31 @@ impl BruteforceHoleSolver {
                 }
             }
 
-            vertices[vert_idx] = *hole_vertice;
+            vertices.place(vert_idx, *hole_vertice);
             let (new_score, new_pose) = if vert_idx == vertices.len() - 1 {
-                match self.solver.problem.score_vertices(vertices, bonus) {
-                    Ok(score) => (score, Some(problem::Pose {
-                        vertices: vertices.clone(),
-                        bonuses: bonus.map(|b| vec![b]),
-                    })),
-                    Err(_) => (i64::MAX, None),
+                match self.solver.problem.score_vertices_in(self.solver.edge_checker(), &vertices.vertices, bonus) {
+                    // only improvements of the global best are kept
+                    Ok(score) if score < shared.best_score.fetch_min(score, Ordering::Relaxed) =>
+                        (score, vertices.to_pose(bonus.map(|b| vec![b]))),
+                    Ok(_) | Err(_) => (i64::MAX, None),
                     // Err(e) => {println!("Got error {:?}", e); (i64::MAX, None)},
                 }
             }
             else {
                 let mut new_hole = hole.clone();
                 new_hole.remove(hole_vertice);
-                self.run(vert_idx + 1, best_pose_score, vertices, new_hole, distances, bonus)
+                self.run(vert_idx + 1, best_pose_score, vertices, new_hole, distances, bonus, shared)
             };
+            if !self.fixed.contains_key(&vert_idx) {
+                vertices.unplace(vert_idx);
+            }
 
-            if new_score == 0 {
+            if new_score == 0 && new_pose.is_some() {
                 // perfect match
-                return (0, Some(problem::Pose {
-                    vertices: vertices.clone(),
-                    bonuses: bonus.map(|b| vec![b]),
-                }))
+                shared.perfect_found.store(true, Ordering::Relaxed);
+                return (0, new_pose)
             }
-            else if new_score < self.solver.pose_score {
+            else if new_score < self.solver.pose_score && new_score < best_pose_score {
                 // improvement match
                 best_pose_score = new_score;
                 best_pose = new_pose;
@@ -236,13 +617,12 @@ impl BruteforceHoleSolver {
 
         }
 
-        if hole.is_empty() {
+        if hole.is_empty() && !self.fixed.contains_key(&vert_idx) {
             // // println!("Hole bruteforce reached its end. We have {} vertices left: {:?}",
             // //          vertices.len() - vert_idx, &vertices[vert_idx..]);
 
             // // println!("Running plain bruteforce to complete the task");
-            return self.run_plain_bruteforce(self.solver.field_min, vert_idx, best_pose_score,
-                                             vertices, distances, bonus);
+            return self.run_plain_bruteforce(vert_idx, best_pose_score, vertices, distances, bonus, shared);
             // return self.run_bounding_box(vert_idx,
             //                              best_pose_score,
             //                              vertices, distances, bonus);
@@ -251,301 +631,188 @@ impl BruteforceHoleSolver {
         (best_pose_score, best_pose)
     }
 
-    #[allow(dead_code)]
-    fn run_bounding_box(&self,
-                        vert_idx: usize,
-                        last_best_score: i64,
-                        vertices: &mut Vec<problem::Point>,
-                        distances: &[i64],
-                        bonus: Option<problem::PoseBonus>) -> (i64, Option<problem::Pose>) {
-        let mut best_pose_score = last_best_score;
-        let mut best_pose = None;
-
-        for point in self.point_set_for_vertice(vert_idx, vertices, distances, bonus) {
-            vertices[vert_idx] = point;
-            let (new_score, new_pose) = if vert_idx == vertices.len() - 1 {
-                match self.solver.problem.score_vertices(vertices, bonus) {
-                    Ok(score) => (score, Some(problem::Pose {
-                        vertices: vertices.clone(),
-                        bonuses: bonus.map(|b| vec![b]),
-                    })),
-                    Err(_) => (i64::MAX, None),
-                    // Err(e) => {println!("Got error {:?}", e); (i64::MAX, None)},
-                }
-            }
-            else {
-                self.run_bounding_box(vert_idx + 1, best_pose_score, vertices, distances, bonus)
-            };
-
-            if new_score == 0 {
-                // perfect match
-                return (0, Some(problem::Pose {
-                    vertices: vertices.clone(),
-                    bonuses: bonus.map(|b| vec![b]),
-                }))
-            }
-            else if new_score < self.solver.pose_score {
-                // improvement match
-                best_pose_score = new_score;
-                best_pose = new_pose;
-            }
+    /// The other end of `edge` if it is incident to `vert_idx` and already placed (earlier in the search or fixed).
+    fn placed_neighbour(&self, edge: problem::Edge, vert_idx: usize, vertices: &PartialPose) -> Option<usize> {
+        let problem::Edge(from_idx, to_idx) = edge;
+        let other_idx = if from_idx == vert_idx {
+            to_idx
+        } else if to_idx == vert_idx {
+            from_idx
+        } else {
+            return None;
+        };
+        if vertices.is_placed(other_idx) {
+            Some(other_idx)
+        } else {
+            None
         }
-
-        (best_pose_score, best_pose)
     }
 
-    fn point_set_for_vertice(&self,
-                             vert_idx: usize,
-                             vertices: &mut Vec<problem::Point>,
-                             distances: &[i64],
-                             bonus: Option<problem::PoseBonus>) -> HashSet<problem::Point>{
-        let mut pointset: HashSet<problem::Point> = HashSet::new();
-        let mut pointset_ready = false;
-        let total_factor = (self.solver.problem.figure.edges.len() as f64 * self.solver.problem.epsilon as f64) / 1000000_f64;
-        let mut used_factor = 0_f64;
-
-        // ...find all edges...
-        for &problem::Edge(from_idx, to_idx) in &self.solver.problem.figure.edges {
-            // println!("processing Edge: {} => {} for vert_idx: {}", from_idx, to_idx, vert_idx);
-            let idx: usize;
-            if from_idx == vert_idx {
-                idx = to_idx;
-            }
-            else if to_idx == vert_idx {
-                idx = from_idx;
-            }
-            else {
-                let d_before = distances[from_idx * vertices.len() + to_idx];
-                let d_after = problem::distance(&vertices[from_idx], &vertices[to_idx]);
-                used_factor += (d_after as f64 / d_before as f64 - 1_f64).abs();
-                continue;
-            }
-
-            // ...starting from it.
-            if idx > vert_idx {
-                /* This point not yet placed, ignore it */
-                continue;
-            }
+    /// Points of the square ring around `point` covering square distances `[distance_min, distance_max]`, yielded lazily.
+    fn points_within_distance_iter(&self, point: problem::Point, distance_min: i64, distance_max: i64) -> impl Iterator<Item = problem::Point> {
+        // IMPORTANT: `distance` is SQUARE distance
+        let (length_min, length_max) = ring_lengths(distance_min, distance_max);
 
-            // TODO: here we need support for globalist, superflex, and other shit like that
-            // let edge_budget = distances[vert_idx * vertices.len() + idx] * 2;
-            let edge_distance = distances[vert_idx * vertices.len() + idx];
+        let outer_box = problem::BoundingBox(problem::Point(point.0 - length_max, point.1 - length_max),
+                                             problem::Point(point.0 + length_max, point.1 + length_max));
 
-            let (edge_distance_min, edge_distance_max) = match bonus {
-                Some(problem::PoseBonus::Globalist {..}) => {
-                    let left_factor = total_factor - used_factor;
-                    let min = (edge_distance as f64 - edge_distance as f64 * left_factor).floor() as i64;
-                    let max = (edge_distance as f64 + edge_distance as f64 * left_factor).ceil() as i64;
-                    (cmp::min(0, min), max)
-                },
-                _ => {
-                    // let eps_factor = self.solver.problem.epsilon as f64 / 1000000_f64;
-                    let min = (edge_distance as f64 - (edge_distance as f64 * self.solver.problem.epsilon as f64) / 1000000_f64).floor() as i64;
-                    let max = (edge_distance as f64 + (edge_distance as f64 * self.solver.problem.epsilon as f64) / 1000000_f64).ceil() as i64;
-                    (cmp::min(0, min), max)
-                },
-            };
+        let inner_box = problem::BoundingBox(problem::Point(point.0 - length_min, point.1 - length_min),
+                                             problem::Point(point.0 + length_min, point.1 + length_min));
 
-            // let edge_distance_min = distances[vert_idx * vertices.len() + idx] - (distances[vert_idx * vertices.len() + idx] as f64 * 0.2).floor() as i64;
-            // let edge_distance_max = distances[vert_idx * vertices.len() + idx] + (distances[vert_idx * vertices.len() + idx] as f64 * 0.2).ceil() as i64;
+        problem::SquareRing(outer_box, inner_box).points_iter()
+    }
 
-            let next_pointset = self.points_within_distance(vertices[idx], edge_distance_min, edge_distance_max);
-            if !pointset_ready {
-                pointset = next_pointset;
-                pointset_ready = true;
-            }
-            else {
-                pointset = pointset
-                    .intersection(&next_pointset)
-                    .cloned()
-                    .collect();
-            }
+    /// Whether `point` for `vert_idx` keeps its edges to placed neighbours within epsilon, honouring the `bonus` relaxations.
+    fn fits_placed_neighbours(&self, vert_idx: usize, point: &problem::Point, vertices: &PartialPose, bonus: Option<problem::PoseBonus>) -> bool {
+        let mut stretches = self.solver.problem.figure.edges
+            .iter()
+            .flat_map(|&edge| self.placed_neighbour(edge, vert_idx, vertices))
+            .map(|other_idx| {
+                let d_before = problem::distance(&self.solver.problem.figure.vertices[vert_idx], &self.solver.problem.figure.vertices[other_idx]);
+                let d_after = problem::distance(point, &vertices[other_idx]);
+                problem::stretch_ratio(d_before, d_after)
+            });
+
+        match bonus {
+            Some(problem::PoseBonus::Globalist {..}) =>
+                stretches.sum::<f64>() <= self.solver.problem.figure.edges.len() as f64 * self.solver.problem.epsilon_fraction(),
+            Some(problem::PoseBonus::Superflex {..}) =>
+                stretches.filter(|&ratio| ratio > self.solver.problem.epsilon_fraction()).count() <= 1,
+            _ =>
+                stretches.all(|ratio| ratio <= self.solver.problem.epsilon_fraction()),
         }
-
-        // println!("point_set_for_verticle: {:?}", pointset);
-        pointset
     }
 
-    fn points_within_distance(&self, point: problem::Point, distance_min: i64, distance_max: i64) -> HashSet<problem::Point>{
-        // IMPORTANT: `distance` is SQUARE distance
-        let length_min = (distance_min as f64).sqrt() as i64 - 1; // -1 just to be sure :)
-        let length_max = (distance_max as f64).sqrt() as i64 + 1; // +1 just to be sure :)
-
-        let outer_box = BoundingBox(problem::Point(point.0 - length_max, point.1 - length_max),
-                                    problem::Point(point.0 + length_max, point.1 + length_max));
-
-        let inner_box = BoundingBox(problem::Point(point.0 - length_min, point.1 - length_min),
-                                    problem::Point(point.0 + length_min, point.1 + length_min));
-
-
-        let pointset = BoundingRingBox(outer_box, inner_box)
-            .point_set();
+    /// Hole points the plain bruteforce tries for `vert_idx`.
+    ///
+    /// With a placed neighbour only the ring around the closest one (by figure distance) is streamed through,
+    /// the whole field is scanned otherwise. Superflex lets any single edge break, so it always scans the field.
+    fn plain_candidates(&self, vert_idx: usize, vertices: &PartialPose, distances: &[i64], bonus: Option<problem::PoseBonus>) -> Vec<problem::Point> {
+        let stretch_budget = match bonus {
+            Some(problem::PoseBonus::Superflex {..}) =>
+                None,
+            Some(problem::PoseBonus::Globalist {..}) =>
+                Some(self.solver.problem.figure.edges.len() as u64 * self.solver.problem.epsilon),
+            _ =>
+                Some(self.solver.problem.epsilon),
+        };
+        let closest_neighbour = self.solver.problem.figure.edges
+            .iter()
+            .flat_map(|&edge| self.placed_neighbour(edge, vert_idx, vertices))
+            .min_by_key(|&other_idx| distances[vert_idx * vertices.len() + other_idx]);
+
+        let points: Box<dyn Iterator<Item = problem::Point>> = match (stretch_budget, closest_neighbour) {
+            (Some(stretch_budget), Some(other_idx)) => {
+                let (distance_min, distance_max) = stretched_distances(distances[vert_idx * vertices.len() + other_idx], stretch_budget);
+                Box::new(self.points_within_distance_iter(vertices[other_idx], distance_min, distance_max))
+            },
+            _ => {
+                let (field_min, field_max) = (self.solver.field_min, self.solver.field_max);
+                Box::new((field_min.1 ..= field_max.1).flat_map(move |y| (field_min.0 ..= field_max.0).map(move |x| problem::Point(x, y))))
+            },
+        };
 
-        pointset
+        // one extra point tells whether the limit was hit, without collecting the rest
+        let mut candidates: Vec<_> = points
+            .filter(|point| self.solver.is_hole(point))
+            .filter(|point| self.fits_placed_neighbours(vert_idx, point, vertices, bonus))
+            .take(self.max_candidates.saturating_add(1))
+            .collect();
+        if candidates.len() > self.max_candidates {
+            self.report_heuristic(candidates.len());
+            candidates.truncate(self.max_candidates);
+        }
+        candidates
     }
 
-    #[allow(dead_code)]
+    #[allow(clippy::too_many_arguments)]
     fn run_plain_bruteforce(&self,
-                            start: problem::Point, vert_idx: usize, last_best: i64,
-                            vertices: &mut Vec<problem::Point>,
+                            vert_idx: usize, last_best: i64,
+                            vertices: &mut PartialPose,
                             distances: &[i64],
-                            bonus: Option<problem::PoseBonus>) -> (i64, Option<problem::Pose>) {
+                            bonus: Option<problem::PoseBonus>,
+                            shared: &SearchState) -> (i64, Option<problem::Pose>) {
+
+        if self.fixed.contains_key(&vert_idx) {
+            // pinned vertex is already placed
+            if vert_idx < vertices.len() - 1 {
+                return self.run_plain_bruteforce(vert_idx + 1, last_best, vertices, distances, bonus, shared);
+            }
+            return match self.solver.problem.score_vertices_in(self.solver.edge_checker(), &vertices.vertices, bonus) {
+                Ok(score) if score < last_best => {
+                    shared.best_score.fetch_min(score, Ordering::Relaxed);
+                    (score, vertices.to_pose(bonus.map(|b| vec![b])))
+                },
+                Ok(_) | Err(_) =>
+                    (last_best, None),
+            };
+        }
 
         let mut new_pose = None;
         let mut best_score = last_best;
-        let mut next_y = start.1;
-        let mut next_x = start.0;
-        while next_y <= self.solver.field_max.1 {
-            if vert_idx < 2 {
-                // log::debug!("Starting Y-step {} for idx: {}...", next_y, vert_idx);
+        for vertice in self.plain_candidates(vert_idx, vertices, distances, bonus) {
+            if shared.perfect_found.load(Ordering::Relaxed) {
+                break;
             }
-            'loop_x: while next_x <= self.solver.field_max.0 {
-
-                if vert_idx < 2 {
-                    // log::debug!("Starting X-step {} for idx: {}...", next_x, vert_idx);
-                }
-                if next_y > self.solver.field_max.1 {
-                    break;
-                }
-
-                let vertice = problem::Point(next_x, next_y);
-                // log::debug!("checking {} vertice: {:?}, vertices: {:?}, is_hole: {}", vert_idx, vertice, vertices, self.solver.is_hole(&vertice));
+            self.placements_tried.fetch_add(1, Ordering::Relaxed);
 
-                next_x += 1;
-                if next_x > self.solver.field_max.0 {
-                    next_x = 0;
-                    next_y += 1;
-                }
+            vertices.place(vert_idx, vertice);
 
-                // match bonus {
-                //     Some(problem::PoseBonus::Wallhack {..}) => 1,
-                //     _ => 0,
-                // }
-                if !self.solver.is_hole(&vertice) {
-                    continue;
-                }
+            let (rec_best_score, rec_new_pose) = if vert_idx == vertices.len() - 1 {
+                // log::debug!("scoring candidate... {:?}", vertices);
 
-                match bonus {
-                    Some(problem::PoseBonus::Globalist {..}) => {
-                        let mut eps = 0_f64;
-                        for &problem::Edge(from_idx, to_idx) in self.solver.problem.figure.edges.iter() {
-                            if from_idx != vert_idx {
-                                continue;
-                            }
-                            if to_idx >= vert_idx {
-                                continue;
-                            }
-
-                            let d_before = problem::distance(&self.solver.problem.figure.vertices[from_idx], &self.solver.problem.figure.vertices[to_idx]);
-                            let d_after = problem::distance(&vertice, &vertices[to_idx]);
-                            eps += ((d_after as f64 / d_before as f64) - 1_f64).abs();
-                        }
-
-                        let max_eps = self.solver.problem.figure.edges.len() as f64 * self.solver.problem.epsilon as f64 / 1000000_f64 ;
-                        if eps > max_eps{
-                            // if vert_idx == 0 {
-                            //     println!("skipped {}..., eps: {}, max_eps: {}, orig_eps: {}", progress, eps, max_eps, self.solver.problem.epsilon as f64 / 1000000_f64);
-                            //     progress += 1;
-                            // }
-                            // if vert_idx == 1 {
-                            //     println!(" + skipped {}..., eps: {}, max_eps: {}, orig_eps: {}", progress, eps, max_eps, self.solver.problem.epsilon as f64 / 1000000_f64);
-                            //     progress += 1;
-                            // }
-                            // if vert_idx == 2 {
-                            //     println!("  ++  skipped {}..., eps: {}, max_eps: {}", progress, eps, max_eps);
-                            //     progress += 1;
-                            // }
-                            continue 'loop_x;
-                        }
+                match self.solver.problem.score_vertices_in(self.solver.edge_checker(), &vertices.vertices, bonus) {
+                    Ok(score) => {
+                        // log::debug!("Found solution with score {:?}: {:?}", score, vertices);
+                        shared.best_score.fetch_min(score, Ordering::Relaxed);
+                        (score, vertices.to_pose(bonus.map(|b| vec![b])))
                     },
-                    _ => {
-                        let mut superstretch_allow = match bonus {
-                            Some(problem::PoseBonus::Superflex {..}) => 1,
-                            _ => 0,
-                        };
-                        for &problem::Edge(from_idx, to_idx) in self.solver.problem.figure.edges.iter() {
-                            if from_idx != vert_idx {
-                                continue;
-                            }
-                            if to_idx >= vert_idx {
-                                continue;
-                            }
-
-                            let d_before = problem::distance(&self.solver.problem.figure.vertices[from_idx], &self.solver.problem.figure.vertices[to_idx]);
-                            let d_after = problem::distance(&vertice, &vertices[to_idx]);
-
-                            if ((d_after as f64 / d_before as f64) - 1_f64).abs() > self.solver.problem.epsilon as f64 / 1000000_f64 {
-                                if superstretch_allow > 0 {
-                                    superstretch_allow = 0;
-                                    continue;
-                                }
-
-                                // self.track_progress((hole.len() as u128).pow((vertices.len() - vert_idx) as u32));
-                                // if vert_idx == 0 {
-                                //     println!("skipped {}...", progress);
-                                //     progress += 1;
-                                // }
-                                // if vert_idx == 1 {
-                                //     println!(" + skipped {}...", progress);
-                                //     progress += 1;
-                                // }
-                                // if vert_idx == 3 {
-                                //     println!("  ++  skipped {}...", progress);
-                                //     progress += 1;
-                                // }
-                                continue 'loop_x;
-                            }
-                        }
-                    }
+                    Err(_) =>
+                        (i64::MAX, None),
                 }
+            }
+            else {
+                self.run_plain_bruteforce(vert_idx + 1, best_score, vertices, distances, bonus, shared)
+            };
+            // fixed vertices return early above, so this placement is always ours to undo
+            vertices.unplace(vert_idx);
 
-                vertices[vert_idx] = vertice;
-
-                if vert_idx == vertices.len() - 1 {
-                    // log::debug!("scoring candidate... {:?}", vertices);
-
-                    match self.solver.problem.score_vertices(vertices, bonus) {
-                        Ok(score) => {
-                            // log::debug!("Found solution with score {:?}: {:?}", score, vertices);
-                            if score == 0 { // perfect solution found
-                                return (0, Some(problem::Pose {
-                                    vertices: vertices.clone(),
-                                    bonuses: bonus.map(|b| vec![b]),
-                                }))
-                            }
-                            if score < best_score {
-                                best_score = score;
-                                new_pose = Some(problem::Pose {
-                                    vertices: vertices.clone(),
-                                    bonuses: bonus.map(|b| vec![b]),
-                                })
-                            }
-                        },
-                        _ => continue,
-                    }
-                }
-                else {
-                    let (rec_best_score, rec_new_pose) = self.run_plain_bruteforce(self.solver.field_min, vert_idx + 1, best_score, vertices,
-                                                                                   distances, bonus);
-                    if rec_best_score == 0 {
-                        return (0, rec_new_pose);
-                    }
-                    if rec_best_score < best_score {
-                        best_score = rec_best_score;
-                        new_pose = rec_new_pose;
-                    }
-                }
+            if rec_best_score == 0 && rec_new_pose.is_some() {
+                // perfect solution found
+                shared.perfect_found.store(true, Ordering::Relaxed);
+                return (0, rec_new_pose);
             }
-            if vert_idx < 2 {
-                // log::debug!("Passed Y-step {} for idx: {}", next_y, vert_idx);
+            if rec_best_score < best_score && rec_new_pose.is_some() {
+                best_score = rec_best_score;
+                new_pose = rec_new_pose;
             }
-
         }
 
         (best_score, new_pose)
     }
 }
 
+/// Square distances an edge of square length `edge_distance` may take when stretched by at most `stretch_budget`
+/// millionths, computed in integers so the ring never misses a point right on the epsilon boundary.
+fn stretched_distances(edge_distance: i64, stretch_budget: u64) -> (i64, i64) {
+    let delta = (edge_distance as i128 * stretch_budget as i128 / 1000000) as i64;
+    (cmp::max(0, edge_distance - delta), edge_distance + delta)
+}
+
+/// Half sides of the square ring around a point holding every point with square distance within `[distance_min, distance_max]`.
+/// The outer box is inclusive and the inner one is exclusive, so the inner box is the largest square
+/// strictly closer than `distance_min` (its corners included).
+fn ring_lengths(distance_min: i64, distance_max: i64) -> (i64, i64) {
+    let length_min = if distance_min <= 0 {
+        0
+    } else {
+        // excluded points have |dx|, |dy| <= length_min - 1, so 2 * (length_min - 1)^2 < distance_min
+        math::isqrt((distance_min - 1) / 2) + 1
+    };
+    let length_max = math::isqrt_ceil(distance_max);
+    (length_min, length_max)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -566,4 +833,298 @@ mod tests {
             ];
         assert_eq!(ring.point_set(), right.iter().cloned().collect());
     }
+
+    #[test]
+    fn stretched_distances_cover_epsilon() {
+        for &edge_distance in &[1, 7, 100, 1156, 4000, 123457] {
+            for &epsilon in &[0, 1, 150000, 999999, 1000000, 2500000] {
+                let (distance_min, distance_max) = stretched_distances(edge_distance, epsilon);
+                for distance in 0 ..= edge_distance * 4 + 8 {
+                    if problem::stretch_ratio(edge_distance, distance) <= epsilon as f64 / 1000000_f64 {
+                        assert!(distance >= distance_min && distance <= distance_max, "{} for {} with epsilon {} is outside [{}, {}]", distance, edge_distance, epsilon, distance_min, distance_max);
+                    }
+                }
+            }
+        }
+        // the bounds are exact, not rounded outwards
+        assert_eq!(stretched_distances(100, 150000), (85, 115));
+        assert_eq!(stretched_distances(1156, 0), (1156, 1156));
+    }
+
+    #[test]
+    fn ring_lengths_cover_exact_distances() {
+        let center = problem::Point(60, 60);
+        for &(distance_min, distance_max) in &[(0, 0), (0, 1), (1, 2), (24, 26), (25, 25), (49, 50), (50, 98), (1000, 1200), (3599, 3600)] {
+            let (length_min, length_max) = ring_lengths(distance_min, distance_max);
+            let ring = BoundingRingBox(
+                BoundingBox(problem::Point(center.0 - length_max, center.1 - length_max), problem::Point(center.0 + length_max, center.1 + length_max)),
+                BoundingBox(problem::Point(center.0 - length_min, center.1 - length_min), problem::Point(center.0 + length_min, center.1 + length_min)),
+            ).point_set();
+            for x in 0 ..= 120 {
+                for y in 0 ..= 120 {
+                    let point = problem::Point(x, y);
+                    let distance = problem::distance(&center, &point);
+                    if distance >= distance_min && distance <= distance_max {
+                        assert!(ring.contains(&point), "{:?} at {} is missing for [{}, {}]", point, distance, distance_min, distance_max);
+                    }
+                }
+            }
+        }
+        // large distances do not lose precision
+        assert_eq!(ring_lengths(0, 3_037_000_499 * 3_037_000_499 - 1).1, 3_037_000_499);
+    }
+
+    fn sample_problem() -> problem::Problem {
+        problem::Problem {
+            hole: vec![problem::Point(20, 0), problem::Point(40, 20), problem::Point(20, 40), problem::Point(0, 20), problem::Point(10, 10)],
+            figure: problem::Figure {
+                edges: vec![problem::Edge(0, 1), problem::Edge(1, 2), problem::Edge(2, 3)],
+                vertices: vec![problem::Point(0, 0), problem::Point(20, 20), problem::Point(40, 0), problem::Point(60, 20)],
+            },
+            epsilon: 150000,
+            bonuses: None,
+        }
+    }
+
+    #[test]
+    fn parallel_matches_sequential() {
+        let problem = sample_problem();
+
+        let sequential_solver = BruteforceHoleSolver::new(solver::Solver::new(&problem, None).unwrap(), usize::MAX);
+        let sequential_pose = sequential_solver.solve_sequential().unwrap().unwrap();
+        let parallel_solver = BruteforceHoleSolver::new(solver::Solver::new(&problem, None).unwrap(), usize::MAX);
+        let parallel_pose = parallel_solver.solve().unwrap().unwrap();
+
+        assert_eq!(problem.score_pose(&parallel_pose), problem.score_pose(&sequential_pose));
+        assert!(problem.score_pose(&parallel_pose).is_ok());
+    }
+
+    #[test]
+    fn quad_tree_edge_checker_matches_polygon() {
+        let problem = sample_problem();
+
+        let polygon_pose = BruteforceHoleSolver::new(solver::Solver::new(&problem, None).unwrap(), usize::MAX)
+            .solve_sequential()
+            .unwrap()
+            .unwrap();
+        let mut quad_tree_solver = solver::Solver::new(&problem, None).unwrap();
+        quad_tree_solver.set_edge_checker(problem::EdgeChecker::QuadTree.build(&problem));
+        let quad_tree_solver = BruteforceHoleSolver::new(quad_tree_solver, usize::MAX);
+        let quad_tree_pose = quad_tree_solver.solve_sequential().unwrap().unwrap();
+
+        assert_eq!(problem.score_pose(&quad_tree_pose), problem.score_pose(&polygon_pose));
+        assert!(quad_tree_solver.edge_checker().quad_tree_stats().unwrap().hits_total > 0);
+    }
+
+    #[test]
+    fn reproducible_output() {
+        let problem = sample_problem();
+
+        let first = BruteforceHoleSolver::new(solver::Solver::new(&problem, None).unwrap(), usize::MAX)
+            .solve_sequential()
+            .unwrap()
+            .unwrap();
+        let second = BruteforceHoleSolver::new(solver::Solver::new(&problem, None).unwrap(), usize::MAX)
+            .solve_sequential()
+            .unwrap()
+            .unwrap();
+        assert_eq!(first, second);
+    }
+
+    #[test]
+    fn resume_from_checkpoint() {
+        let problem = sample_problem();
+        let temp_directory = tempfile::tempdir().unwrap();
+        let checkpoint_file = temp_directory.path().join("checkpoint.json");
+
+        let uninterrupted = BruteforceHoleSolver::new(solver::Solver::new(&problem, None).unwrap(), usize::MAX)
+            .solve_sequential()
+            .unwrap()
+            .unwrap();
+
+        let interrupted = BruteforceHoleSolver::new(solver::Solver::new(&problem, None).unwrap(), usize::MAX)
+            .with_checkpoint_file(&checkpoint_file);
+        let checkpoint = interrupted.run_checkpointed(Checkpoint::new(interrupted.search_identity()), Some(2));
+        assert_eq!(checkpoint.next_start_idx, 2);
+        assert_eq!(Checkpoint::from_file(&checkpoint_file).unwrap(), checkpoint);
+
+        // a checkpoint of another search is rejected
+        let other_limit = BruteforceHoleSolver::resume(solver::Solver::new(&problem, None).unwrap(), 1000, &checkpoint_file)
+            .unwrap()
+            .solve();
+        assert!(matches!(other_limit, Err(CheckpointError::SearchMismatch { .. })), "{:?}", other_limit);
+        let other_fixed = BruteforceHoleSolver::resume(solver::Solver::new(&problem, None).unwrap(), usize::MAX, &checkpoint_file)
+            .unwrap()
+            .with_fixed(vec![(0, problem::Point(20, 0))].into_iter().collect())
+            .solve_sequential();
+        assert!(matches!(other_fixed, Err(CheckpointError::SearchMismatch { .. })), "{:?}", other_fixed);
+
+        // parallel `solve` continues from the checkpoint as well
+        let resumed = BruteforceHoleSolver::resume(solver::Solver::new(&problem, None).unwrap(), usize::MAX, &checkpoint_file)
+            .unwrap()
+            .solve()
+            .unwrap()
+            .unwrap();
+        assert_eq!(problem.score_pose(&resumed), problem.score_pose(&uninterrupted));
+        assert!(Checkpoint::from_file(&checkpoint_file).unwrap().next_start_idx > 2);
+    }
+
+    #[test]
+    fn more_vertices_than_hole() {
+        let hole = vec![problem::Point(0, 0), problem::Point(6, 0), problem::Point(6, 4), problem::Point(0, 4)];
+        let problem = problem::Problem {
+            hole,
+            figure: problem::Figure {
+                edges: vec![problem::Edge(0, 4), problem::Edge(1, 4)],
+                vertices: vec![
+                    problem::Point(10, 14), problem::Point(16, 10), problem::Point(10, 10), problem::Point(16, 14),
+                    problem::Point(12, 11),
+                ],
+            },
+            epsilon: 0,
+            bonuses: None,
+        };
+
+        // vertex 4 is left over once every hole corner is taken, so it goes through the plain bruteforce,
+        // and only fits when vertex 1 lands in the corner opposite to vertex 0, which is not the first one tried
+        let solver = BruteforceHoleSolver::new(solver::Solver::new(&problem, None).unwrap(), usize::MAX)
+            .with_fixed(vec![(0, problem::Point(0, 4))].into_iter().collect());
+        let pose = solver.solve_sequential().unwrap().unwrap();
+        assert_eq!(problem.score_pose(&pose), Ok(0));
+        assert_eq!(pose.vertices[1], problem::Point(6, 0));
+        assert_eq!(pose.vertices[4], problem::Point(2, 1));
+    }
+
+    #[test]
+    fn plain_candidates_match_field_scan() {
+        let problem = sample_problem();
+        let solver = BruteforceHoleSolver::new(solver::Solver::new(&problem, None).unwrap(), usize::MAX);
+        let distances = problem.distance_cache();
+        let mut vertices = PartialPose::new(problem.figure.vertices.clone());
+        vertices.place(0, problem::Point(20, 0));
+        vertices.place(2, problem::Point(20, 40));
+
+        let mut field_scan = Vec::new();
+        for y in solver.solver.field_min.1 ..= solver.solver.field_max.1 {
+            for x in solver.solver.field_min.0 ..= solver.solver.field_max.0 {
+                let point = problem::Point(x, y);
+                if solver.solver.is_hole(&point) && solver.fits_placed_neighbours(1, &point, &vertices, None) {
+                    field_scan.push(point);
+                }
+            }
+        }
+        let mut candidates = solver.plain_candidates(1, &vertices, &distances, None);
+        candidates.sort_by_key(|point| (point.1, point.0));
+        assert!(!candidates.is_empty());
+        assert_eq!(candidates, field_scan);
+    }
+
+    #[test]
+    fn fixed_vertices_shrink_search() {
+        let hole = vec![
+            problem::Point(0, 0), problem::Point(10, 0), problem::Point(20, 5), problem::Point(30, 0),
+            problem::Point(30, 20), problem::Point(20, 15), problem::Point(10, 20), problem::Point(0, 20),
+        ];
+        let problem = problem::Problem {
+            hole: hole.clone(),
+            figure: problem::Figure {
+                edges: (0 .. hole.len()).map(|index| problem::Edge(index, (index + 1) % hole.len())).collect(),
+                vertices: hole.iter().map(|point| problem::Point(point.0 + 5, point.1 + 40)).collect(),
+            },
+            epsilon: 0,
+            bonuses: None,
+        };
+
+        let free = BruteforceHoleSolver::new(solver::Solver::new(&problem, None).unwrap(), usize::MAX);
+        let free_pose = free.solve_sequential().unwrap().unwrap();
+        assert_eq!(problem.score_pose(&free_pose), Ok(0));
+
+        let fixed = BruteforceHoleSolver::new(solver::Solver::new(&problem, None).unwrap(), usize::MAX)
+            .with_fixed(vec![(2, hole[2]), (5, hole[5])].into_iter().collect());
+        let fixed_pose = fixed.solve_sequential().unwrap().unwrap();
+        assert_eq!(problem.score_pose(&fixed_pose), Ok(0));
+        assert_eq!(fixed_pose.vertices[2], hole[2]);
+        assert_eq!(fixed_pose.vertices[5], hole[5]);
+        assert!(
+            fixed.placements_tried() < free.placements_tried(),
+            "fixed search tried {} placements, free one {}",
+            fixed.placements_tried(),
+            free.placements_tried(),
+        );
+    }
+
+    #[test]
+    fn most_constrained_first() {
+        let hole = vec![
+            problem::Point(0, 0), problem::Point(10, 0), problem::Point(20, 5), problem::Point(30, 0),
+            problem::Point(30, 20), problem::Point(20, 15), problem::Point(10, 20), problem::Point(0, 20),
+        ];
+        let problem = problem::Problem {
+            hole: hole.clone(),
+            figure: problem::Figure {
+                edges: (0 .. hole.len()).map(|index| problem::Edge(index, (index + 1) % hole.len())).collect(),
+                vertices: hole.iter().map(|point| problem::Point(point.0 + 5, point.1 + 40)).collect(),
+            },
+            epsilon: 0,
+            bonuses: None,
+        };
+
+        let centroid = BruteforceHoleSolver::new(solver::Solver::new(&problem, None).unwrap(), usize::MAX);
+        assert_eq!(problem.score_pose(&centroid.solve_sequential().unwrap().unwrap()), Ok(0));
+        let constrained = BruteforceHoleSolver::new(solver::Solver::new(&problem, None).unwrap(), usize::MAX)
+            .with_candidate_order(CandidateOrder::MostConstrained);
+        assert_eq!(problem.score_pose(&constrained.solve_sequential().unwrap().unwrap()), Ok(0));
+        assert!(
+            constrained.placements_tried() < centroid.placements_tried(),
+            "most constrained first tried {} placements, centroid order {}",
+            constrained.placements_tried(),
+            centroid.placements_tried(),
+        );
+    }
+
+    #[test]
+    fn limited_candidates() {
+        let problem = problem::Problem {
+            hole: vec![problem::Point(0, 0), problem::Point(40, 0), problem::Point(40, 40), problem::Point(0, 40)],
+            figure: problem::Figure {
+                edges: vec![problem::Edge(0, 1)],
+                vertices: vec![problem::Point(0, 0), problem::Point(0, 10)],
+            },
+            epsilon: 0,
+            bonuses: None,
+        };
+
+        // one start and one point on the ring around it
+        let limited = BruteforceHoleSolver::new(solver::Solver::new(&problem, None).unwrap(), 1);
+        let pose = limited.solve().unwrap().unwrap();
+        assert!(problem.score_pose(&pose).is_ok());
+        assert_eq!(limited.placements_tried(), 1);
+        assert_eq!(Some(pose), BruteforceHoleSolver::new(solver::Solver::new(&problem, None).unwrap(), 1).solve().unwrap());
+    }
+
+    #[test]
+    fn limited_plain_candidates() {
+        let problem = problem::Problem {
+            hole: vec![problem::Point(0, 0), problem::Point(40, 0), problem::Point(40, 40), problem::Point(0, 40)],
+            figure: problem::Figure {
+                edges: vec![
+                    problem::Edge(0, 1), problem::Edge(1, 2), problem::Edge(2, 3), problem::Edge(3, 0), problem::Edge(0, 2),
+                    problem::Edge(0, 4), problem::Edge(4, 5),
+                ],
+                vertices: vec![
+                    problem::Point(0, 0), problem::Point(40, 0), problem::Point(40, 40), problem::Point(0, 40),
+                    problem::Point(15, 20), problem::Point(15, 400),
+                ],
+            },
+            epsilon: 0,
+            bonuses: None,
+        };
+
+        // vertex 5 never fits, so vertex 4 goes through all of its 6 ring points after each of the 8 square placements
+        let unlimited = BruteforceHoleSolver::new(solver::Solver::new(&problem, None).unwrap(), usize::MAX);
+        assert_eq!(unlimited.solve().unwrap(), None);
+        let limited = BruteforceHoleSolver::new(solver::Solver::new(&problem, None).unwrap(), 4);
+        assert_eq!(limited.solve().unwrap(), None);
+        assert_eq!(unlimited.placements_tried() - limited.placements_tried(), 8 * (6 - 4));
+    }
 }
@@ -0,0 +1,197 @@
+use std::{
+    cmp,
+    collections::HashMap,
+};
+
+use crate::{
+    solver::{
+        self,
+        simulated_annealing::{
+            self,
+            Fitness,
+            FloorBehavior,
+            SimulatedAnnealingSolver,
+        },
+    },
+    problem,
+};
+
+#[derive(Debug)]
+pub enum Error {
+    NoEdgesInFigure,
+    SolverCreate(solver::CreateError),
+    SimulatedAnnealingCreate(simulated_annealing::CreateError),
+    GenerateVertices(simulated_annealing::GenerateVerticesError),
+    PinVertex(simulated_annealing::PinVertexError),
+    Step(simulated_annealing::StepError),
+    ComponentNotPlaced { component: Vec<problem::Edge>, },
+    PoseInvalid(problem::PoseValidationError),
+}
+
+/// Places the figure one biconnected component (see `Figure::biconnected_components`) at a time with simulated annealing
+/// seeded with `seed`, articulation points shared with already placed components are pinned.
+/// The rest of placed vertices are pinned too (without edges), so annealing accounts dislikes they already cover.
+///
+/// Components are taken in turn starting from the largest one, the next is the one sharing most vertices with the placed part.
+/// Each component keeps the best valid placement seen until the temperature drops below `params.minimum_temp`
+/// (`params.below_floor` is ignored), so the result is not necessary the best pose overall.
+/// Figure vertices without edges stay where they are in the figure.
+pub fn solve(problem: &problem::Problem, params: simulated_annealing::Params, seed: u64) -> Result<(problem::Pose, i64), Error> {
+    let params = simulated_annealing::Params {
+        below_floor: FloorBehavior::Stop,
+        ..params
+    };
+    let figure = &problem.figure;
+    if figure.edges.is_empty() {
+        return Err(Error::NoEdgesInFigure);
+    }
+
+    let mut vertices = figure.vertices.clone();
+    let mut placed = vec![false; vertices.len()];
+    let mut components = figure.biconnected_components();
+    while !components.is_empty() {
+        let next_index = (0 .. components.len())
+            .max_by_key(|&index| {
+                let shared = component_vertices(&components[index]).iter().filter(|&&vertex| placed[vertex]).count();
+                (shared, components[index].len(), cmp::Reverse(index))
+            })
+            .unwrap();
+        let component = components.remove(next_index);
+        let component_vertices = component_vertices(&component);
+
+        // the component as a separate problem: its vertices renumbered in `component_vertices` order
+        // followed by placed vertices from other components
+        let local_vertices: Vec<_> = component_vertices.iter()
+            .cloned()
+            .chain((0 .. vertices.len()).filter(|vertex| placed[*vertex] && component_vertices.binary_search(vertex).is_err()))
+            .collect();
+        let local_index: HashMap<_, _> = local_vertices.iter()
+            .enumerate()
+            .map(|(local, &vertex)| (vertex, local))
+            .collect();
+        let sub_problem = problem::Problem {
+            hole: problem.hole.clone(),
+            figure: problem::Figure {
+                edges: component.iter()
+                    .map(|edge| problem::Edge(local_index[&edge.0], local_index[&edge.1]))
+                    .collect(),
+                vertices: local_vertices.iter().map(|&vertex| figure.vertices[vertex]).collect(),
+            },
+            epsilon: problem.epsilon,
+            bonuses: None,
+        };
+        let pinned: Vec<_> = local_vertices.iter()
+            .enumerate()
+            .filter(|&(_, &vertex)| placed[vertex])
+            .map(|(local, &vertex)| (local, vertices[vertex]))
+            .collect();
+        log::debug!(
+            "placing component of {} edges, {} vertices with {} of them pinned",
+            component.len(),
+            component_vertices.len(),
+            pinned.len(),
+        );
+
+        let sub_solver = solver::Solver::new(&sub_problem, None)
+            .map_err(Error::SolverCreate)?;
+        let mut annealing = SimulatedAnnealingSolver::new(sub_solver, params)
+            .map_err(Error::SimulatedAnnealingCreate)?
+            .with_seed(seed)
+            .map_err(Error::GenerateVertices)?;
+        for &(local, position) in &pinned {
+            annealing.pin_vertex(local, position)
+                .map_err(Error::PinVertex)?;
+        }
+        let mut best: Option<(i64, Vec<problem::Point>)> = None;
+        loop {
+            match annealing.step() {
+                Ok(()) =>
+                    (),
+                Err(simulated_annealing::StepError::TempTooLow) =>
+                    break,
+                Err(error) =>
+                    return Err(Error::Step(error)),
+            }
+            if let Fitness::FigureScored { score, } = annealing.fitness() {
+                let improved = match best {
+                    Some((best_score, _)) =>
+                        score < best_score,
+                    None =>
+                        true,
+                };
+                if improved {
+                    best = Some((score, annealing.vertices().to_vec()));
+                }
+            }
+        }
+        let (_, sub_vertices) = best
+            .ok_or_else(|| Error::ComponentNotPlaced { component: component.clone(), })?;
+        for (local, &vertex) in component_vertices.iter().enumerate() {
+            vertices[vertex] = sub_vertices[local];
+            placed[vertex] = true;
+        }
+    }
+
+    let pose = problem::Pose { vertices, bonuses: None, };
+    let score = problem.score_pose(&pose)
+        .map_err(Error::PoseInvalid)?;
+    Ok((pose, score))
+}
+
+/// Distinct vertices of the component edges, sorted.
+fn component_vertices(component: &[problem::Edge]) -> Vec<usize> {
+    let mut vertices: Vec<_> = component.iter()
+        .flat_map(|edge| vec![edge.0, edge.1])
+        .collect();
+    vertices.sort_unstable();
+    vertices.dedup();
+    vertices
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn two_triangles_sharing_vertex() {
+        let problem = problem::Problem {
+            hole: vec![problem::Point(0, 0), problem::Point(12, 0), problem::Point(12, 12), problem::Point(0, 12)],
+            figure: problem::Figure {
+                edges: vec![
+                    problem::Edge(0, 1), problem::Edge(1, 2), problem::Edge(2, 0),
+                    problem::Edge(2, 3), problem::Edge(3, 4), problem::Edge(4, 2),
+                ],
+                vertices: vec![
+                    problem::Point(30, 30), problem::Point(36, 30), problem::Point(33, 33),
+                    problem::Point(36, 36), problem::Point(30, 36),
+                ],
+            },
+            epsilon: 150000,
+            bonuses: None,
+        };
+        assert_eq!(problem.figure.articulation_points(), vec![2]);
+        assert_eq!(problem.figure.biconnected_components().len(), 2);
+
+        let params = simulated_annealing::Params {
+            max_temp: 100.0,
+            cooling_step_temp: 1.0,
+            minimum_temp: 2.0,
+            below_floor: FloorBehavior::Greedy,
+            edge_bias: simulated_annealing::EdgeBias::Off,
+            frozen_swap_prob: 0.0,
+            frozen_swap_bias: 0.0,
+            vertex_swap_prob: 0.0,
+            reachable_moves: false,
+            wallhack_allowed_outside: problem::WALLHACK_ALLOWED_OUTSIDE,
+            coarsen_factor: 1,
+            min_move_radius: 1,
+            max_move_radius: 1,
+            dislikes_hint_weight: 0.0,
+            iterations_per_cooling_step: 256,
+            operating_mode: simulated_annealing::OperatingMode::ScoreMaximizer,
+        };
+        let (pose, score) = solve(&problem, params, 1194).unwrap();
+        // both triangles are valid together, the second one is annealed around the pinned shared vertex
+        assert_eq!(problem.score_pose(&pose), Ok(score));
+    }
+}
@@ -0,0 +1,247 @@
+use std::{
+    fs,
+    io,
+    path::{
+        Path,
+        PathBuf,
+    },
+};
+
+use serde_derive::{
+    Serialize,
+    Deserialize,
+};
+
+use crate::{
+    solver::simulated_annealing::{
+        Params,
+        EdgeBias,
+        FloorBehavior,
+    },
+};
+
+#[derive(Clone, Copy, PartialEq, Default, Serialize, Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct ParamsOverrides {
+    pub max_temp: Option<f64>,
+    pub cooling_step_temp: Option<f64>,
+    pub minimum_temp: Option<f64>,
+    pub below_floor: Option<FloorBehavior>,
+    pub valid_edge_accept_prob: Option<f64>,
+    pub focus_broken_edges: Option<bool>,
+    pub frozen_swap_prob: Option<f64>,
+    pub frozen_swap_bias: Option<f64>,
+    pub vertex_swap_prob: Option<f64>,
+    pub reachable_moves: Option<bool>,
+    pub wallhack_allowed_outside: Option<usize>,
+    pub coarsen_factor: Option<usize>,
+    pub min_move_radius: Option<usize>,
+    pub max_move_radius: Option<usize>,
+    pub dislikes_hint_weight: Option<f64>,
+    pub iterations_per_cooling_step: Option<usize>,
+}
+
+#[derive(Debug)]
+pub enum LoadError {
+    OpenFile { config_file: PathBuf, error: io::Error, },
+    Deserialize { config_file: PathBuf, error: serde_json::Error, },
+    Validate { config_file: PathBuf, error: ValidateError, },
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum ValidateError {
+    ProbabilityOutOfRange { field: &'static str, value: f64, },
+    NonPositiveTemperature { field: &'static str, value: f64, },
+    MinimumTempAboveMaxTemp { minimum_temp: f64, max_temp: f64, },
+    ZeroIterationsPerCoolingStep,
+    ZeroCoarsenFactor,
+    MoveRadiusOutOfRange { min_move_radius: usize, max_move_radius: usize, },
+    /// hint above 1.0 could outweigh the difference between feasibility levels of the energy
+    DislikesHintWeightOutOfRange(f64),
+}
+
+/// Path of the optional solver config for the task, e.g. `./tasks/12.solver.json`.
+pub fn config_file<P>(problems_directory: P, task_id: &str) -> PathBuf where P: AsRef<Path> {
+    problems_directory.as_ref().join(format!("{}.solver.json", task_id))
+}
+
+/// Loads `Params` overrides for the task on top of `defaults`. A missing config file is not an error: `defaults` are returned as is.
+pub fn load_params<P>(problems_directory: P, task_id: &str, defaults: Params) -> Result<Params, LoadError> where P: AsRef<Path> {
+    let config_file = config_file(problems_directory, task_id);
+    match load_params_file(&config_file, defaults) {
+        Err(LoadError::OpenFile { ref error, .. }) if error.kind() == io::ErrorKind::NotFound =>
+            Ok(defaults),
+        Ok(params) => {
+            log::debug!("solver params for task {} loaded from {:?}: {:?}", task_id, config_file, params);
+            Ok(params)
+        },
+        Err(error) =>
+            Err(error),
+    }
+}
+
+/// Loads `Params` overrides from `config_file` on top of `defaults`.
+pub fn load_params_file<P>(config_file: P, defaults: Params) -> Result<Params, LoadError> where P: AsRef<Path> {
+    let config_file = config_file.as_ref().to_path_buf();
+    let file = match fs::File::open(&config_file) {
+        Ok(file) =>
+            file,
+        Err(error) =>
+            return Err(LoadError::OpenFile { config_file, error, }),
+    };
+    let overrides: ParamsOverrides = match serde_json::from_reader(io::BufReader::new(file)) {
+        Ok(overrides) =>
+            overrides,
+        Err(error) =>
+            return Err(LoadError::Deserialize { config_file, error, }),
+    };
+    let params = overrides.apply(defaults);
+    if let Err(error) = validate(&params) {
+        return Err(LoadError::Validate { config_file, error, });
+    }
+    Ok(params)
+}
+
+impl ParamsOverrides {
+    pub fn apply(&self, defaults: Params) -> Params {
+        Params {
+            max_temp: self.max_temp.unwrap_or(defaults.max_temp),
+            cooling_step_temp: self.cooling_step_temp.unwrap_or(defaults.cooling_step_temp),
+            minimum_temp: self.minimum_temp.unwrap_or(defaults.minimum_temp),
+            below_floor: self.below_floor.unwrap_or(defaults.below_floor),
+            edge_bias: match (self.focus_broken_edges, self.valid_edge_accept_prob) {
+                (Some(true), _) =>
+                    EdgeBias::FocusBroken,
+                (_, Some(valid_edge_accept_prob)) =>
+                    EdgeBias::from_valid_edge_accept_prob(valid_edge_accept_prob),
+                (_, None) =>
+                    defaults.edge_bias,
+            },
+            frozen_swap_prob: self.frozen_swap_prob.unwrap_or(defaults.frozen_swap_prob),
+            frozen_swap_bias: self.frozen_swap_bias.unwrap_or(defaults.frozen_swap_bias),
+            vertex_swap_prob: self.vertex_swap_prob.unwrap_or(defaults.vertex_swap_prob),
+            reachable_moves: self.reachable_moves.unwrap_or(defaults.reachable_moves),
+            wallhack_allowed_outside: self.wallhack_allowed_outside.unwrap_or(defaults.wallhack_allowed_outside),
+            coarsen_factor: self.coarsen_factor.unwrap_or(defaults.coarsen_factor),
+            min_move_radius: self.min_move_radius.unwrap_or(defaults.min_move_radius),
+            max_move_radius: self.max_move_radius.unwrap_or(defaults.max_move_radius),
+            dislikes_hint_weight: self.dislikes_hint_weight.unwrap_or(defaults.dislikes_hint_weight),
+            iterations_per_cooling_step: self.iterations_per_cooling_step.unwrap_or(defaults.iterations_per_cooling_step),
+            ..defaults
+        }
+    }
+}
+
+pub fn validate(params: &Params) -> Result<(), ValidateError> {
+    let valid_edge_accept_prob = match params.edge_bias {
+        EdgeBias::ProbSkip(valid_edge_accept_prob) =>
+            valid_edge_accept_prob,
+        EdgeBias::Off | EdgeBias::FocusBroken =>
+            1.0,
+    };
+    let probabilities = [
+        ("valid_edge_accept_prob", valid_edge_accept_prob),
+        ("frozen_swap_prob", params.frozen_swap_prob),
+        ("frozen_swap_bias", params.frozen_swap_bias),
+        ("vertex_swap_prob", params.vertex_swap_prob),
+    ];
+    for &(field, value) in &probabilities {
+        if !(0.0 ..= 1.0).contains(&value) {
+            return Err(ValidateError::ProbabilityOutOfRange { field, value, });
+        }
+    }
+    for &(field, value) in &[("max_temp", params.max_temp), ("cooling_step_temp", params.cooling_step_temp), ("minimum_temp", params.minimum_temp)] {
+        if value.is_nan() || value <= 0.0 {
+            return Err(ValidateError::NonPositiveTemperature { field, value, });
+        }
+    }
+    if params.minimum_temp > params.max_temp {
+        return Err(ValidateError::MinimumTempAboveMaxTemp { minimum_temp: params.minimum_temp, max_temp: params.max_temp, });
+    }
+    if params.iterations_per_cooling_step == 0 {
+        return Err(ValidateError::ZeroIterationsPerCoolingStep);
+    }
+    if params.coarsen_factor == 0 {
+        return Err(ValidateError::ZeroCoarsenFactor);
+    }
+    if params.min_move_radius == 0 || params.min_move_radius > params.max_move_radius {
+        return Err(ValidateError::MoveRadiusOutOfRange { min_move_radius: params.min_move_radius, max_move_radius: params.max_move_radius, });
+    }
+    if !(0.0 ..= 1.0).contains(&params.dislikes_hint_weight) {
+        return Err(ValidateError::DislikesHintWeightOutOfRange(params.dislikes_hint_weight));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::{
+        problem,
+        solver::simulated_annealing::OperatingMode,
+    };
+
+    fn defaults() -> Params {
+        Params {
+            max_temp: 100.0,
+            cooling_step_temp: 1.0,
+            minimum_temp: 2.0,
+            below_floor: FloorBehavior::Stop,
+            edge_bias: EdgeBias::ProbSkip(0.5),
+            frozen_swap_prob: 0.15,
+            frozen_swap_bias: 0.0,
+            vertex_swap_prob: 0.0,
+            reachable_moves: false,
+            wallhack_allowed_outside: problem::WALLHACK_ALLOWED_OUTSIDE,
+            coarsen_factor: 1,
+            min_move_radius: 1,
+            max_move_radius: 1,
+            dislikes_hint_weight: 0.0,
+            iterations_per_cooling_step: 32768,
+            operating_mode: OperatingMode::ScoreMaximizer,
+        }
+    }
+
+    #[test]
+    fn missing_config_keeps_defaults() {
+        let temp_directory = tempfile::tempdir().unwrap();
+        let directory = temp_directory.path();
+        let params = load_params(directory, "999", defaults()).unwrap();
+        assert_eq!(params.iterations_per_cooling_step, 32768);
+        assert_eq!(params.frozen_swap_prob, 0.15);
+    }
+
+    #[test]
+    fn override_iterations_per_cooling_step() {
+        let temp_directory = tempfile::tempdir().unwrap();
+        let directory = temp_directory.path();
+        fs::write(config_file(directory, "12"), r#"{"iterations_per_cooling_step":1024,"below_floor":"greedy"}"#).unwrap();
+
+        let params = load_params(directory, "12", defaults()).unwrap();
+        assert_eq!(params.iterations_per_cooling_step, 1024);
+        assert_eq!(params.max_temp, 100.0);
+        assert_eq!(params.cooling_step_temp, 1.0);
+        assert_eq!(params.minimum_temp, 2.0);
+        assert_eq!(params.below_floor, FloorBehavior::Greedy);
+        assert_eq!(params.edge_bias, EdgeBias::ProbSkip(0.5));
+        assert_eq!(params.frozen_swap_prob, 0.15);
+    }
+
+    #[test]
+    fn override_validation() {
+        let temp_directory = tempfile::tempdir().unwrap();
+        let directory = temp_directory.path();
+        fs::write(config_file(directory, "13"), r#"{"frozen_swap_prob":1.5}"#).unwrap();
+        assert!(matches!(
+            load_params(directory, "13", defaults()),
+            Err(LoadError::Validate { error: ValidateError::ProbabilityOutOfRange { field: "frozen_swap_prob", .. }, .. }),
+        ));
+
+        fs::write(config_file(directory, "14"), r#"{"max_temp":-1.0}"#).unwrap();
+        assert!(matches!(
+            load_params(directory, "14", defaults()),
+            Err(LoadError::Validate { error: ValidateError::NonPositiveTemperature { field: "max_temp", .. }, .. }),
+        ));
+    }
+}
@@ -0,0 +1,89 @@
+use crate::{
+    problem,
+};
+
+/// Hill climbing for low dislikes poses: for every uncovered hole corner tries to move its nearest pose vertex
+/// onto the corner or one step towards it, keeping only moves that produce a valid pose with fewer dislikes.
+/// Stops on zero dislikes, after `max_iters` rounds or when a round brings no improvement.
+/// An invalid input pose is returned as is.
+pub fn wiggle_to_zero(problem: &problem::Problem, pose: &problem::Pose, max_iters: usize) -> problem::Pose {
+    let mut current = pose.clone();
+    let bonus = pose.bonus();
+    let mut score = match problem.score_vertices(&current.vertices, bonus) {
+        Ok(score) =>
+            score,
+        Err(..) =>
+            return current,
+    };
+
+    for _ in 0 .. max_iters {
+        if score == 0 {
+            break;
+        }
+
+        let mut improved = false;
+        let assignment = problem.dislikes_assignment(&current.vertices);
+        for (corner, &(pose_index, sq_dist)) in problem.hole.iter().zip(assignment.iter()) {
+            if sq_dist == 0 {
+                continue;
+            }
+            let vertex = current.vertices[pose_index];
+            let step_x = (corner.0 - vertex.0).signum();
+            let step_y = (corner.1 - vertex.1).signum();
+            let candidates = [
+                *corner,
+                problem::Point(vertex.0 + step_x, vertex.1 + step_y),
+                problem::Point(vertex.0 + step_x, vertex.1),
+                problem::Point(vertex.0, vertex.1 + step_y),
+            ];
+            for &candidate in &candidates {
+                if candidate == vertex {
+                    continue;
+                }
+                current.vertices[pose_index] = candidate;
+                match problem.score_vertices(&current.vertices, bonus) {
+                    Ok(candidate_score) if candidate_score < score => {
+                        score = candidate_score;
+                        improved = true;
+                        break;
+                    },
+                    _ =>
+                        current.vertices[pose_index] = vertex,
+                }
+            }
+        }
+
+        if !improved {
+            break;
+        }
+    }
+
+    current
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn one_move_from_perfect() {
+        let problem = problem::Problem {
+            hole: vec![problem::Point(0, 0), problem::Point(10, 0), problem::Point(10, 10), problem::Point(0, 10)],
+            figure: problem::Figure {
+                edges: vec![problem::Edge(0, 1), problem::Edge(1, 2), problem::Edge(2, 3), problem::Edge(3, 0)],
+                vertices: vec![problem::Point(0, 0), problem::Point(10, 0), problem::Point(10, 10), problem::Point(0, 10)],
+            },
+            epsilon: 200000,
+            bonuses: None,
+        };
+        let pose = problem::Pose {
+            vertices: vec![problem::Point(0, 0), problem::Point(10, 0), problem::Point(10, 10), problem::Point(0, 9)],
+            bonuses: None,
+        };
+        assert_eq!(problem.score_pose(&pose).unwrap(), 1);
+
+        let polished = wiggle_to_zero(&problem, &pose, 8);
+        assert_eq!(problem.score_pose(&polished).unwrap(), 0);
+        assert_eq!(wiggle_to_zero(&problem, &pose, 0), pose);
+    }
+}
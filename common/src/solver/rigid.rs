@@ -0,0 +1,132 @@
+use std::collections::HashSet;
+
+use crate::{
+    problem,
+};
+
+#[derive(Debug)]
+pub enum Error {
+    NotRigid,
+    NoEdgesInFigure,
+}
+
+/// Valid poses of a rigid figure (see `Figure::is_rigid`) sorted by dislikes, best first.
+///
+/// Every figure edge is put onto every ordered pair of hole vertices with a compatible distance,
+/// the rest of the figure follows by rotating (and reflecting) the original vertices around the edge,
+/// rounded to the grid. Only the placements which pass validation are returned.
+pub fn enumerate_placements(problem: &problem::Problem) -> Result<Vec<(problem::Pose, i64)>, Error> {
+    let figure = &problem.figure;
+    if figure.edges.is_empty() {
+        return Err(Error::NoEdgesInFigure);
+    }
+    if !figure.is_rigid() {
+        return Err(Error::NotRigid);
+    }
+
+    let mut visited = HashSet::new();
+    let mut placements = Vec::new();
+    for edge in &figure.edges {
+        let from = figure.vertices[edge.0];
+        let to = figure.vertices[edge.1];
+        let edge_sq_length = problem::distance(&from, &to);
+        if edge_sq_length == 0 {
+            continue;
+        }
+        for &hole_from in &problem.hole {
+            for &hole_to in &problem.hole {
+                if hole_from == hole_to {
+                    continue;
+                }
+                let ratio = problem::distance(&hole_from, &hole_to) as f64 / edge_sq_length as f64;
+                if (ratio - 1.0).abs() > problem.epsilon_fraction() {
+                    continue;
+                }
+                for &reflect in &[false, true] {
+                    let vertices = transform(&figure.vertices, (from, to), (hole_from, hole_to), reflect);
+                    if !visited.insert(vertices.clone()) {
+                        continue;
+                    }
+                    let pose = problem::Pose { vertices, bonuses: None, };
+                    if let Ok(score) = problem.score_pose(&pose) {
+                        placements.push((pose, score));
+                    }
+                }
+            }
+        }
+    }
+    placements.sort_by_key(|&(_, score)| score);
+    Ok(placements)
+}
+
+/// Rigid transform of `vertices` taking `source.0` onto `target.0` with the `source` direction turned along `target`,
+/// `reflect` mirrors the figure over the `source` line first.
+fn transform(
+    vertices: &[problem::Point],
+    source: (problem::Point, problem::Point),
+    target: (problem::Point, problem::Point),
+    reflect: bool,
+)
+    -> Vec<problem::Point>
+{
+    let source_angle = ((source.1).1 as f64 - (source.0).1 as f64).atan2((source.1).0 as f64 - (source.0).0 as f64);
+    let target_angle = ((target.1).1 as f64 - (target.0).1 as f64).atan2((target.1).0 as f64 - (target.0).0 as f64);
+    let (sin, cos) = (target_angle - source_angle).sin_cos();
+    let (source_sin, source_cos) = source_angle.sin_cos();
+
+    vertices.iter()
+        .map(|vertex| {
+            let mut x = (vertex.0 - (source.0).0) as f64;
+            let mut y = (vertex.1 - (source.0).1) as f64;
+            if reflect {
+                // mirror over the source edge line: rotate it onto the x axis, flip y and rotate back
+                let along = x * source_cos + y * source_sin;
+                let across = y * source_cos - x * source_sin;
+                x = along * source_cos + across * source_sin;
+                y = along * source_sin - across * source_cos;
+            }
+            problem::Point(
+                (target.0).0 + (x * cos - y * sin).round() as i64,
+                (target.0).1 + (x * sin + y * cos).round() as i64,
+            )
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rigid_triangle() {
+        // right triangle fits the hole corners exactly only after rotation
+        let problem = problem::Problem {
+            hole: vec![problem::Point(10, 10), problem::Point(10, 0), problem::Point(20, 10)],
+            figure: problem::Figure {
+                edges: vec![problem::Edge(0, 1), problem::Edge(1, 2), problem::Edge(2, 0)],
+                vertices: vec![problem::Point(0, 0), problem::Point(10, 0), problem::Point(0, 10)],
+            },
+            epsilon: 0,
+            bonuses: None,
+        };
+        let placements = enumerate_placements(&problem).unwrap();
+        assert!(!placements.is_empty());
+        let (best_pose, best_score) = &placements[0];
+        assert_eq!(*best_score, 0);
+        let mut vertices = best_pose.vertices.clone();
+        vertices.sort_unstable_by_key(|point| (point.0, point.1));
+        assert_eq!(vertices, vec![problem::Point(10, 0), problem::Point(10, 10), problem::Point(20, 10)]);
+        assert!(placements.windows(2).all(|pair| pair[0].1 <= pair[1].1));
+
+        let square = problem::Problem {
+            hole: problem.hole.clone(),
+            figure: problem::Figure {
+                edges: vec![problem::Edge(0, 1), problem::Edge(1, 2), problem::Edge(2, 3), problem::Edge(3, 0)],
+                vertices: vec![problem::Point(0, 0), problem::Point(5, 0), problem::Point(5, 5), problem::Point(0, 5)],
+            },
+            epsilon: 0,
+            bonuses: None,
+        };
+        assert!(matches!(enumerate_placements(&square), Err(Error::NotRigid)));
+    }
+}
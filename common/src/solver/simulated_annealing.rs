@@ -1,4 +1,17 @@
-use rand::Rng;
+use std::{
+    cmp,
+};
+
+use rand::{
+    Rng,
+    SeedableRng,
+    rngs::StdRng,
+};
+
+use serde_derive::{
+    Serialize,
+    Deserialize,
+};
 
 use crate::{
     solver,
@@ -10,12 +23,61 @@ pub struct Params {
     pub max_temp: f64,
     pub cooling_step_temp: f64,
     pub minimum_temp: f64,
-    pub valid_edge_accept_prob: f64,
+    /// what `step` does once temperature drops below `minimum_temp`
+    pub below_floor: FloorBehavior,
+    pub edge_bias: EdgeBias,
     pub frozen_swap_prob: f64,
+    pub frozen_swap_bias: f64,
+    /// probability of swapping positions of two non frozen vertices instead of a vertex move, 0.0 disables
+    pub vertex_swap_prob: f64,
+    /// sample vertex moves from `Solver::reachable_cells` instead of rejecting proposals outside of the hole
+    pub reachable_moves: bool,
+    /// how many vertices may stay outside of the hole when solving with wallhack, `problem::WALLHACK_ALLOWED_OUTSIDE` by the rules
+    pub wallhack_allowed_outside: usize,
+    /// initial lattice step for random placement and vertex moves, shrinks down to 1 as temperature drops (1 disables coarsening)
+    pub coarsen_factor: usize,
+    /// bounds of the vertex move radius in lattice steps, adapted after every cooling step (see `Params::adapt_move_radius`),
+    /// both set to 1 keep plain neighbour moves
+    pub min_move_radius: usize,
+    pub max_move_radius: usize,
+    /// weight of the dislikes the pose would have mixed into energy of infeasible states, 0.0 disables
+    pub dislikes_hint_weight: f64,
     pub iterations_per_cooling_step: usize,
     pub operating_mode: OperatingMode,
 }
 
+/// Solver behavior below `Params::minimum_temp`.
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum FloorBehavior {
+    /// `step` fails with `StepError::TempTooLow`
+    Stop,
+    /// `step` keeps polishing accepting only improving moves
+    Greedy,
+}
+
+/// Which figure edges are picked for vertex moves (applies only when solving without bonuses).
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum EdgeBias {
+    /// every edge is considered
+    Off,
+    /// an edge which is already valid is considered only with the given probability: 0.0 never moves valid edges
+    ProbSkip(f64),
+    /// only currently broken edges are considered, any edge if there are none
+    FocusBroken,
+}
+
+impl EdgeBias {
+    /// Legacy `valid_edge_accept_prob` setting: 1.0 and above means that valid edges are always considered.
+    pub fn from_valid_edge_accept_prob(valid_edge_accept_prob: f64) -> EdgeBias {
+        if valid_edge_accept_prob >= 1.0 {
+            EdgeBias::Off
+        } else {
+            EdgeBias::ProbSkip(valid_edge_accept_prob)
+        }
+    }
+}
+
 #[derive(Clone, Copy, Debug)]
 pub enum OperatingMode {
     ScoreMaximizer,
@@ -24,23 +86,83 @@ pub enum OperatingMode {
     },
     BonusHunter,
     ZeroHunter,
+    /// starts from the solver pose and moves only endpoints of broken edges while there are any
+    EdgeRepair,
 }
 
+impl Params {
+    /// Probability of frozen swap instead of a vertex move: `frozen_swap_prob` raised towards 1.0 by `frozen_swap_bias`
+    /// times the frozen vertices ratio, so with bias 1.0 and almost all vertices frozen the solver only swaps.
+    pub fn frozen_swap_prob(&self, frozen_count: usize, vertices_count: usize) -> f64 {
+        if vertices_count == 0 {
+            return self.frozen_swap_prob;
+        }
+        let frozen_ratio = frozen_count as f64 / vertices_count as f64;
+        self.frozen_swap_prob + (1.0 - self.frozen_swap_prob) * self.frozen_swap_bias * frozen_ratio
+    }
+
+    /// Lattice step at `temp`: `coarsen_factor` at `max_temp` decreasing linearly down to 1.
+    pub fn lattice_step(&self, temp: f64) -> i64 {
+        let coarsen_factor = self.coarsen_factor.max(1) as f64;
+        (coarsen_factor * temp / self.max_temp).ceil().max(1.0).min(coarsen_factor) as i64
+    }
+
+    /// Move radius for the next cooling step: one more while most of moves are accepted at `move_radius`,
+    /// one less while few of them are. It never exceeds the cap falling linearly from `max_move_radius` at `max_temp`
+    /// down to `min_move_radius` at `minimum_temp`, so moves become local again as the solver cools.
+    pub fn adapt_move_radius(&self, move_radius: usize, acceptance_ratio: f64, temp: f64) -> usize {
+        let min_radius = self.min_move_radius.max(1);
+        let max_radius = self.max_move_radius.max(min_radius);
+        let temp_span = self.max_temp - self.minimum_temp;
+        let temp_share = if temp_span > 0.0 {
+            ((temp - self.minimum_temp) / temp_span).clamp(0.0, 1.0)
+        } else {
+            0.0
+        };
+        let cap = min_radius + ((max_radius - min_radius) as f64 * temp_share).round() as usize;
+
+        let move_radius = if acceptance_ratio > MOVE_RADIUS_GROW_ACCEPTANCE {
+            move_radius + 1
+        } else if acceptance_ratio < MOVE_RADIUS_SHRINK_ACCEPTANCE {
+            move_radius.saturating_sub(1)
+        } else {
+            move_radius
+        };
+        move_radius.clamp(min_radius, cap)
+    }
+}
+
+/// Acceptance ratio of a cooling step above which `Params::adapt_move_radius` grows the radius.
+const MOVE_RADIUS_GROW_ACCEPTANCE: f64 = 0.5;
+/// Acceptance ratio of a cooling step below which `Params::adapt_move_radius` shrinks the radius.
+const MOVE_RADIUS_SHRINK_ACCEPTANCE: f64 = 0.2;
+
 pub struct SimulatedAnnealingSolver {
     solver: solver::Solver,
     params: Params,
     vertices_cur: Vec<problem::Point>,
     vertices_tmp: Vec<problem::Point>,
     frozen_vertices_indices: Vec<usize>,
+    /// vertices kept in place by `pin_vertex`, never moved or swapped
+    pinned_vertices: Vec<(usize, problem::Point)>,
+    rng: StdRng,
     fitness_cur: Fitness,
     temp: f64,
     steps: usize,
+    /// inner iterations already done within the current cooling step (see `step_budget`)
+    iterations_done: usize,
+    proposed_last_step: usize,
+    accepted_last_step: usize,
+    /// frozen swaps among `proposed_last_step`
+    frozen_swaps_last_step: usize,
+    /// current vertex move radius in lattice steps, see `Params::adapt_move_radius`
+    move_radius: usize,
 }
 
 #[derive(Clone, Copy, PartialEq, Debug)]
 pub enum Fitness {
-    FigureCorrupted { ratio_avg: f64, },
-    NotFitHole { bad_edges_count: usize, ratio_avg: f64, },
+    FigureCorrupted { ratio_avg: f64, dislikes_hint: f64, },
+    NotFitHole { bad_edges_count: usize, ratio_avg: f64, dislikes_hint: f64, },
     FigureScored { score: i64, },
 }
 
@@ -53,20 +175,48 @@ pub enum StepError {
     GenerateVertices(GenerateVerticesError),
 }
 
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum StepProgress {
+    /// iterations budget is exhausted before the end of the current cooling step
+    InProgress { iterations_left: usize, },
+    CoolingStepDone,
+}
+
 #[derive(Debug)]
 pub enum CreateError {
+    NoEdgesInFigure,
     GenerateVertices(GenerateVerticesError),
+    /// break a leg needs the edge to break, which annealing does not choose
+    UnsupportedBonus(problem::ProblemBonusType),
+}
+
+#[derive(Debug)]
+pub enum WarmStartError {
+    VerticesCountMismatch { expected: usize, provided: usize, },
+}
+
+#[derive(Debug)]
+pub enum PinVertexError {
+    VertexIndexOutOfRange { vertex_index: usize, vertices_count: usize, },
 }
 
 impl SimulatedAnnealingSolver {
     pub fn new(solver: solver::Solver, params: Params) -> Result<SimulatedAnnealingSolver, CreateError> {
+        if solver.problem.figure.edges.is_empty() {
+            return Err(CreateError::NoEdgesInFigure);
+        }
+        if let Some(bonus @ problem::ProblemBonusType::BreakALeg) = solver.use_bonus {
+            return Err(CreateError::UnsupportedBonus(bonus));
+        }
+
+        let mut rng = StdRng::from_entropy();
         let mut vertices_cur = Vec::new();
         let mut frozen_vertices_indices = Vec::new();
-        generate_vertices(&solver, &mut vertices_cur, &mut frozen_vertices_indices, params.operating_mode)
+        generate_vertices(&mut rng, &solver, &mut vertices_cur, &mut frozen_vertices_indices, params.operating_mode, params.lattice_step(params.max_temp))
             .map_err(CreateError::GenerateVertices)?;
 
         let temp = params.max_temp;
-        let fitness_cur = Fitness::calc(&solver.problem, &vertices_cur, &solver.use_bonus);
+        let fitness_cur = Fitness::calc(&solver.problem, solver.edge_checker(), &vertices_cur, &solver.use_bonus, &params);
 
         Ok(SimulatedAnnealingSolver {
             solver,
@@ -74,20 +224,80 @@ impl SimulatedAnnealingSolver {
             vertices_cur,
             vertices_tmp: Vec::new(),
             frozen_vertices_indices,
+            pinned_vertices: Vec::new(),
+            rng,
             fitness_cur,
             temp,
             steps: 0,
+            iterations_done: 0,
+            proposed_last_step: 0,
+            accepted_last_step: 0,
+            frozen_swaps_last_step: 0,
+            move_radius: params.min_move_radius.max(1),
         })
     }
 
+    /// Reseeds the solver random generator and regenerates initial vertices with it, so runs with the same seed
+    /// and params are reproducible (e.g. for comparing configurations).
+    pub fn with_seed(mut self, seed: u64) -> Result<SimulatedAnnealingSolver, GenerateVerticesError> {
+        self.rng = StdRng::seed_from_u64(seed);
+        self.reset()?;
+        Ok(self)
+    }
+
     pub fn reset(&mut self) -> Result<(), GenerateVerticesError> {
-        generate_vertices(&self.solver, &mut self.vertices_cur, &mut self.frozen_vertices_indices, self.params.operating_mode)?;
+        generate_vertices(
+            &mut self.rng,
+            &self.solver,
+            &mut self.vertices_cur,
+            &mut self.frozen_vertices_indices,
+            self.params.operating_mode,
+            self.params.lattice_step(self.params.max_temp),
+        )?;
         self.temp = self.params.max_temp;
         self.steps = 0;
-        self.fitness_cur = Fitness::calc(&self.solver.problem, &self.vertices_cur, &self.solver.use_bonus);
+        self.iterations_done = 0;
+        self.move_radius = self.params.min_move_radius.max(1);
+        self.apply_pins();
+        Ok(())
+    }
+
+    /// Continues annealing from `vertices` (e.g. a known pose) instead of the randomly generated ones.
+    pub fn warm_start(&mut self, vertices: &[problem::Point]) -> Result<(), WarmStartError> {
+        if vertices.len() != self.vertices_cur.len() {
+            return Err(WarmStartError::VerticesCountMismatch { expected: self.vertices_cur.len(), provided: vertices.len(), });
+        }
+        self.vertices_cur.clear();
+        self.vertices_cur.extend(vertices.iter().cloned());
+        self.apply_pins();
+        Ok(())
+    }
+
+    /// Keeps vertex `vertex_index` at `position` for the rest of annealing (also after `reset` or `warm_start`),
+    /// e.g. a vertex placed by hand in the editor. A frozen vertex stops being frozen when pinned.
+    pub fn pin_vertex(&mut self, vertex_index: usize, position: problem::Point) -> Result<(), PinVertexError> {
+        if vertex_index >= self.vertices_cur.len() {
+            return Err(PinVertexError::VertexIndexOutOfRange { vertex_index, vertices_count: self.vertices_cur.len(), });
+        }
+        self.pinned_vertices.retain(|&(pinned_index, _)| pinned_index != vertex_index);
+        self.pinned_vertices.push((vertex_index, position));
+        self.apply_pins();
         Ok(())
     }
 
+    fn is_pinned(&self, vertex_index: usize) -> bool {
+        self.pinned_vertices.iter().any(|&(pinned_index, _)| pinned_index == vertex_index)
+    }
+
+    fn apply_pins(&mut self) {
+        for &(vertex_index, position) in &self.pinned_vertices {
+            self.vertices_cur[vertex_index] = position;
+        }
+        let pinned_vertices = &self.pinned_vertices;
+        self.frozen_vertices_indices.retain(|index| pinned_vertices.iter().all(|&(pinned_index, _)| pinned_index != *index));
+        self.fitness_cur = Fitness::calc(&self.solver.problem, self.solver.edge_checker(), &self.vertices_cur, &self.solver.use_bonus, &self.params);
+    }
+
     pub fn reheat(&mut self, temp_factor: f64) {
         self.temp = self.params.max_temp * temp_factor;
     }
@@ -100,21 +310,70 @@ impl SimulatedAnnealingSolver {
         self.fitness_cur
     }
 
+    pub fn edge_checker(&self) -> &dyn problem::InvalidEdge {
+        self.solver.edge_checker()
+    }
+
     pub fn vertices(&self) -> &[problem::Point] {
         &self.vertices_cur
     }
 
+    /// Share of proposed moves accepted during the last `step`: close to 1.0 means the temperature is too high,
+    /// close to 0.0 means it is too low.
+    pub fn acceptance_ratio_last_step(&self) -> f64 {
+        if self.proposed_last_step == 0 {
+            0.0
+        } else {
+            self.accepted_last_step as f64 / self.proposed_last_step as f64
+        }
+    }
+
+    /// Count of frozen swaps proposed during the last `step` (instead of vertex moves or swaps).
+    pub fn frozen_swaps_last_step(&self) -> usize {
+        self.frozen_swaps_last_step
+    }
+
+    /// Vertex move radius (in lattice steps) used by the current cooling step.
+    pub fn move_radius(&self) -> usize {
+        self.move_radius
+    }
+
+    /// Performs the whole cooling step (or finishes the one started with `step_budget`).
     pub fn step(&mut self) -> Result<(), StepError> {
-        if self.temp < self.params.minimum_temp {
-            return Err(StepError::TempTooLow);
+        self.step_budget(usize::MAX)
+            .map(|_progress| ())
+    }
+
+    /// Performs at most `max_iterations` inner iterations of the current cooling step, so a caller with
+    /// a frame budget (e.g. the gui) can spread one cooling step with many `iterations_per_cooling_step` across several calls.
+    pub fn step_budget(&mut self, max_iterations: usize) -> Result<StepProgress, StepError> {
+        // rng is moved out for the step so it can be borrowed together with the rest of the solver
+        let mut rng = self.rng.clone();
+        let result = self.step_budget_with(&mut rng, max_iterations);
+        self.rng = rng;
+        result
+    }
+
+    fn step_budget_with<R>(&mut self, rng: &mut R, max_iterations: usize) -> Result<StepProgress, StepError> where R: Rng {
+        if self.iterations_done == 0 {
+            if self.temp < self.params.minimum_temp && self.params.below_floor == FloorBehavior::Stop {
+                return Err(StepError::TempTooLow);
+            }
+            self.proposed_last_step = self.params.iterations_per_cooling_step;
+            self.accepted_last_step = 0;
+            self.frozen_swaps_last_step = 0;
         }
 
+        // vertices could be changed between calls (e.g. with `warm_start`)
         self.vertices_tmp.clear();
         self.vertices_tmp.extend(self.vertices_cur.iter().cloned());
 
-        let mut rng = rand::thread_rng();
-        for _ in 0 .. self.params.iterations_per_cooling_step {
-            if !self.frozen_vertices_indices.is_empty() && rng.gen_range(0.0 .. 1.0) < self.params.frozen_swap_prob {
+        let swap_prob = self.params.frozen_swap_prob(self.frozen_vertices_indices.len(), self.vertices_tmp.len());
+        let lattice_step = self.params.lattice_step(self.temp);
+        let iterations = cmp::min(max_iterations, self.params.iterations_per_cooling_step - self.iterations_done);
+        for _ in 0 .. iterations {
+            if !self.frozen_vertices_indices.is_empty() && rng.gen_range(0.0 .. 1.0) < swap_prob {
+                self.frozen_swaps_last_step += 1;
                 let frozen_index = rng.gen_range(0 .. self.frozen_vertices_indices.len());
                 let mut counter = 0;
                 let pose_vertices_index = loop {
@@ -124,7 +383,7 @@ impl SimulatedAnnealingSolver {
                     }
 
                     let index = rng.gen_range(0 .. self.vertices_tmp.len());
-                    if !self.frozen_vertices_indices.contains(&index) {
+                    if !self.frozen_vertices_indices.contains(&index) && !self.is_pinned(index) {
                         break index;
                     }
                 };
@@ -133,20 +392,17 @@ impl SimulatedAnnealingSolver {
                 let curr_index = pose_vertices_index;
 
                 self.vertices_tmp.swap(prev_index, curr_index);
-                let fitness_tmp = Fitness::calc(&self.solver.problem, &self.vertices_tmp, &self.solver.use_bonus);
+                let fitness_tmp = Fitness::calc(&self.solver.problem, self.solver.edge_checker(), &self.vertices_tmp, &self.solver.use_bonus, &self.params);
 
                 let energy_cur = self.fitness_cur.energy();
-                let q_cur = energy_cur * self.params.max_temp * self.solver.problem.figure.edges.len() as f64;
+                let q_cur = energy_cur * self.params.max_temp * edges_factor(&self.solver.problem);
                 let energy_tmp = fitness_tmp.energy();
-                let q_tmp = energy_tmp * self.params.max_temp * self.solver.problem.figure.edges.len() as f64;
+                let q_tmp = energy_tmp * self.params.max_temp * edges_factor(&self.solver.problem);
 
-                let accept_prob = if q_tmp < q_cur {
-                    1.0
-                } else {
-                    (-(q_tmp - q_cur) / self.temp).exp()
-                };
+                let accept_prob = self.accept_prob(q_cur, q_tmp);
                 if rng.gen_range(0.0 .. 1.0) < accept_prob {
                     // accept
+                    self.accepted_last_step += 1;
                     self.frozen_vertices_indices[frozen_index] = pose_vertices_index;
                     self.vertices_cur.swap(prev_index, curr_index);
                     self.fitness_cur = fitness_tmp;
@@ -154,6 +410,25 @@ impl SimulatedAnnealingSolver {
                     // reject
                     self.vertices_tmp.swap(prev_index, curr_index);
                 }
+            } else if let Some((index_a, index_b)) = self.propose_vertex_swap(rng) {
+                self.vertices_tmp.swap(index_a, index_b);
+                let fitness_tmp = Fitness::calc(&self.solver.problem, self.solver.edge_checker(), &self.vertices_tmp, &self.solver.use_bonus, &self.params);
+
+                let energy_cur = self.fitness_cur.energy();
+                let q_cur = energy_cur * self.params.max_temp * edges_factor(&self.solver.problem);
+                let energy_tmp = fitness_tmp.energy();
+                let q_tmp = energy_tmp * self.params.max_temp * edges_factor(&self.solver.problem);
+
+                let accept_prob = self.accept_prob(q_cur, q_tmp);
+                if rng.gen_range(0.0 .. 1.0) < accept_prob {
+                    // accept
+                    self.accepted_last_step += 1;
+                    self.vertices_cur.swap(index_a, index_b);
+                    self.fitness_cur = fitness_tmp;
+                } else {
+                    // reject
+                    self.vertices_tmp.swap(index_a, index_b);
+                }
             } else {
                 let mut counter = 0;
                 let vertex_index = loop {
@@ -162,42 +437,45 @@ impl SimulatedAnnealingSolver {
                         return Err(StepError::ProbablyInfiniteLoopInVertexIndex);
                     }
 
-                    let edge_index = rng.gen_range(0 .. self.solver.problem.figure.edges.len());
-                    let edge = &self.solver.problem.figure.edges[edge_index];
-                    if self.solver.use_bonus.is_none() {
-                        let (is_valid, _ratio) = solver::is_edge_ratio_valid(
-                            edge,
-                            &self.vertices_tmp,
-                            &self.solver.problem,
-                        );
-                        if is_valid {
-                            let accept_prob = rng.gen_range(0.0 .. 1.0);
-                            if accept_prob >= self.params.valid_edge_accept_prob {
-                                continue;
-                            }
-                        }
-                    }
+                    let edge_index = match self.propose_edge(&self.vertices_tmp, rng) {
+                        Some(edge_index) =>
+                            edge_index,
+                        None =>
+                            continue,
+                    };
+                    let edge = &self.solver.solver_problem.edges[edge_index];
                     let try_index = if rng.gen_range(0.0 .. 1.0) < 0.5 {
                         edge.0
                     } else {
                         edge.1
                     };
-                    if !self.frozen_vertices_indices.contains(&try_index) {
+                    if !self.frozen_vertices_indices.contains(&try_index) && !self.is_pinned(try_index) {
                         break try_index;
                     }
                 };
                 // let vertex_index = rng.gen_range(0 .. self.vertices_tmp.len());
                 let vertex = self.vertices_tmp[vertex_index];
 
+                let reachable_move = if lattice_step > 1 || self.move_radius > 1 {
+                    None
+                } else {
+                    self.propose_reachable_move(&vertex, rng)
+                };
                 let mut counter = 0;
                 let moved_vertex = loop {
+                    if let Some(moved_vertex) = reachable_move {
+                        break moved_vertex;
+                    }
+
                     counter += 1;
                     if counter > 10000000 {
                         return Err(StepError::ProbablyInfiniteLoopInMovedVertex);
                     }
 
-                    let x = vertex.0 + rng.gen_range(-1 ..= 1);
-                    let y = vertex.1 + rng.gen_range(-1 ..= 1);
+                    // vertex stuck on the coarse lattice falls back to unit moves
+                    let (move_step, move_radius) = if counter > 1000 { (1, 1) } else { (lattice_step, self.move_radius as i64) };
+                    let x = vertex.0 + rng.gen_range(-move_radius ..= move_radius) * move_step;
+                    let y = vertex.1 + rng.gen_range(-move_radius ..= move_radius) * move_step;
                     let try_vertex = problem::Point(x, y);
 
                     let allow_hole = match self.solver.use_bonus {
@@ -207,17 +485,13 @@ impl SimulatedAnnealingSolver {
                         Some(problem::ProblemBonusType::Superflex) =>
                             self.solver.is_hole(&try_vertex),
                         Some(problem::ProblemBonusType::Wallhack) => {
-                            let mut already_has_outside = false;
-                            for vertex in &self.vertices_tmp {
-                                if !self.solver.is_hole(vertex) {
-                                    already_has_outside = true;
-                                    break;
-                                }
-                            }
-                            if already_has_outside {
+                            let outside_count = self.vertices_tmp.iter()
+                                .filter(|vertex| !self.solver.is_hole(vertex))
+                                .count();
+                            if outside_count >= self.params.wallhack_allowed_outside {
                                 self.solver.is_hole(&try_vertex)
                             } else {
-                                true
+                                self.solver.is_in_field(&try_vertex)
                             }
                         },
                     };
@@ -227,17 +501,18 @@ impl SimulatedAnnealingSolver {
                     }
                 };
                 self.vertices_tmp[vertex_index] = moved_vertex;
-                let fitness_tmp = Fitness::calc(&self.solver.problem, &self.vertices_tmp, &self.solver.use_bonus);
+                let fitness_tmp = Fitness::calc(&self.solver.problem, self.solver.edge_checker(), &self.vertices_tmp, &self.solver.use_bonus, &self.params);
 
                 let energy_cur = self.fitness_cur.energy();
-                let q_cur = energy_cur * self.params.max_temp * self.solver.problem.figure.edges.len() as f64;
+                let q_cur = energy_cur * self.params.max_temp * edges_factor(&self.solver.problem);
                 let energy_tmp = fitness_tmp.energy();
-                let q_tmp = energy_tmp * self.params.max_temp * self.solver.problem.figure.edges.len() as f64;
+                let q_tmp = energy_tmp * self.params.max_temp * edges_factor(&self.solver.problem);
 
-                let accept_prob = if q_tmp < q_cur {
-                    1.0
+                let accept_prob = if self.breaks_wallhack_invariant(&self.vertices_tmp) {
+                    // wallhack allows only `wallhack_allowed_outside` vertices outside of the hole
+                    0.0
                 } else {
-                    (-(q_tmp - q_cur) / self.temp).exp()
+                    self.accept_prob(q_cur, q_tmp)
                 };
                 if rng.gen_range(0.0 .. 1.0) < accept_prob {
                     // accept
@@ -253,6 +528,7 @@ impl SimulatedAnnealingSolver {
                     //     accept_prob,
                     // );
 
+                    self.accepted_last_step += 1;
                     self.vertices_cur[vertex_index] =
                         self.vertices_tmp[vertex_index];
                     self.fitness_cur = fitness_tmp;
@@ -264,43 +540,158 @@ impl SimulatedAnnealingSolver {
             }
         }
 
-        let temp_delta = (self.temp * 2.0 / self.params.max_temp) * self.params.cooling_step_temp;
+        self.iterations_done += iterations;
+        if self.iterations_done < self.params.iterations_per_cooling_step {
+            return Ok(StepProgress::InProgress {
+                iterations_left: self.params.iterations_per_cooling_step - self.iterations_done,
+            });
+        }
+        self.iterations_done = 0;
 
-        self.temp -= temp_delta;
+        if self.temp >= self.params.minimum_temp {
+            let temp_delta = (self.temp * 2.0 / self.params.max_temp) * self.params.cooling_step_temp;
+            self.temp -= temp_delta;
+        }
+        self.move_radius = self.params.adapt_move_radius(self.move_radius, self.acceptance_ratio_last_step(), self.temp);
         self.steps += 1;
-        Ok(())
+        Ok(StepProgress::CoolingStepDone)
+    }
+
+    /// Metropolis acceptance probability of the move from energy `q_cur` to `q_tmp`,
+    /// only improving moves are accepted below the temperature floor (see `FloorBehavior::Greedy`).
+    fn accept_prob(&self, q_cur: f64, q_tmp: f64) -> f64 {
+        if q_tmp < q_cur {
+            1.0
+        } else if self.temp < self.params.minimum_temp {
+            0.0
+        } else {
+            (-(q_tmp - q_cur) / self.temp).exp()
+        }
+    }
+
+    /// Pair of distinct non frozen and non pinned vertices to swap positions with probability `Params::vertex_swap_prob`,
+    /// `None` means a usual vertex move (also when there are less than two non frozen vertices).
+    fn propose_vertex_swap<R>(&self, rng: &mut R) -> Option<(usize, usize)> where R: Rng {
+        if self.params.vertex_swap_prob <= 0.0 || rng.gen_range(0.0 .. 1.0) >= self.params.vertex_swap_prob {
+            return None;
+        }
+        let frozen_vertices_indices = &self.frozen_vertices_indices;
+        let free_indices: Vec<_> = (0 .. self.vertices_tmp.len())
+            .filter(|index| !frozen_vertices_indices.contains(index) && !self.is_pinned(*index))
+            .collect();
+        if free_indices.len() < 2 {
+            return None;
+        }
+        let index_a = rng.gen_range(0 .. free_indices.len());
+        let mut index_b = rng.gen_range(0 .. free_indices.len() - 1);
+        if index_b >= index_a {
+            index_b += 1;
+        }
+        Some((free_indices[index_a], free_indices[index_b]))
+    }
+
+    /// Index of the edge to move one of its vertices according to `Params::edge_bias`, `None` if the proposal is skipped.
+    /// `OperatingMode::EdgeRepair` always picks a broken edge if there is one.
+    fn propose_edge<R>(&self, vertices: &[problem::Point], rng: &mut R) -> Option<usize> where R: Rng {
+        let solver_problem = &self.solver.solver_problem;
+        let edges = &solver_problem.edges;
+        let is_edge_valid = |edge_index| solver_problem.is_edge_ratio_valid(edge_index, vertices).0;
+        let broken_or_any = |rng: &mut R| {
+            let broken: Vec<_> = (0 .. edges.len())
+                .filter(|&edge_index| !is_edge_valid(edge_index))
+                .collect();
+            if broken.is_empty() {
+                rng.gen_range(0 .. edges.len())
+            } else {
+                broken[rng.gen_range(0 .. broken.len())]
+            }
+        };
+        if let OperatingMode::EdgeRepair = self.params.operating_mode {
+            return Some(broken_or_any(rng));
+        }
+        if self.solver.use_bonus.is_some() {
+            return Some(rng.gen_range(0 .. edges.len()));
+        }
+        match self.params.edge_bias {
+            EdgeBias::Off =>
+                Some(rng.gen_range(0 .. edges.len())),
+            EdgeBias::ProbSkip(valid_edge_accept_prob) => {
+                let edge_index = rng.gen_range(0 .. edges.len());
+                if is_edge_valid(edge_index) && rng.gen_range(0.0 .. 1.0) >= valid_edge_accept_prob {
+                    None
+                } else {
+                    Some(edge_index)
+                }
+            },
+            EdgeBias::FocusBroken =>
+                Some(broken_or_any(rng)),
+        }
+    }
+
+    /// Move of `vertex` sampled directly from its reachable in-hole cells when `Params::reachable_moves` is set.
+    /// `None` means falling back to rejection sampling: for wallhack (which may allow moving outside),
+    /// for a vertex without reachable cells and for a vertex with all neighbours in hole (nothing to reject there).
+    fn propose_reachable_move<R>(&self, vertex: &problem::Point, rng: &mut R) -> Option<problem::Point> where R: Rng {
+        if !self.params.reachable_moves || self.solver.use_bonus == Some(problem::ProblemBonusType::Wallhack) {
+            return None;
+        }
+        let cells = self.solver.reachable_cells(vertex);
+        if cells.is_empty() || cells.len() == 8 {
+            return None;
+        }
+        Some(cells[rng.gen_range(0 .. cells.len())])
+    }
+
+    fn breaks_wallhack_invariant(&self, vertices: &[problem::Point]) -> bool {
+        match self.solver.use_bonus {
+            Some(problem::ProblemBonusType::Wallhack) =>
+                vertices.iter().filter(|vertex| !self.solver.is_hole(vertex)).count() > self.params.wallhack_allowed_outside,
+            _ =>
+                false,
+        }
     }
 }
 
 #[derive(Debug)]
 pub enum GenerateVerticesError {
-    ProbablyInfiniteLoopInFrozenIndexInBonusCollector,
-    ProbablyInfiniteLoopInFrozenIndexInBonusHunter,
-    ProbablyInfiniteLoopInFrozenIndexInZeroHunter,
+    MoreFrozenTargetsThanVertices { targets: usize, vertices: usize, },
 }
 
-fn generate_vertices(
+/// Random picks of a free vertex to freeze before falling back to the first free one.
+const FREEZE_RANDOM_TRIES: usize = 1000;
+
+fn generate_vertices<R>(
+    rng: &mut R,
     solver: &solver::Solver,
     vertices: &mut Vec<problem::Point>,
     frozen_vertices_indices: &mut Vec<usize>,
     operating_mode: OperatingMode,
+    lattice_step: i64,
 )
     -> Result<(), GenerateVerticesError>
+    where R: Rng
 {
     let figure_vertices_iter = solver
         .problem
         .figure
         .vertices
         .iter();
-    let mut rng = rand::thread_rng();
     vertices.clear();
+    frozen_vertices_indices.clear();
     vertices.extend(
         figure_vertices_iter
             .map(|_vertex| {
+                let mut count = 0;
                 loop {
+                    count += 1;
                     let x = rng.gen_range(solver.field_min.0 ..= solver.field_max.1);
                     let y = rng.gen_range(solver.field_min.1 ..= solver.field_max.1);
-                    let point = problem::Point(x, y);
+                    // snap to the coarse lattice unless the hole seems to have no lattice cells at all
+                    let point = if count > 10000 {
+                        problem::Point(x, y)
+                    } else {
+                        problem::Point(x - x.rem_euclid(lattice_step), y - y.rem_euclid(lattice_step))
+                    };
                     if solver.is_hole(&point) {
                         break point;
                     }
@@ -310,28 +701,18 @@ fn generate_vertices(
     match operating_mode {
         OperatingMode::ScoreMaximizer =>
             (),
+        OperatingMode::EdgeRepair =>
+            if solver.pose.vertices.len() == vertices.len() {
+                vertices.clone_from(&solver.pose.vertices);
+            },
         OperatingMode::BonusCollector { target_problem, } =>
             match &solver.problem.bonuses {
                 Some(bonuses) if bonuses.is_empty() => {
-                    for bonus in bonuses {
-                        if bonus.problem != target_problem {
-                            continue;
-                        }
-                        let mut count = 0;
-                        let frozen_vertex_index = loop {
-                            count += 1;
-                            if count > 10000000 {
-                                return Err(GenerateVerticesError::ProbablyInfiniteLoopInFrozenIndexInBonusCollector);
-                            }
-
-                            let index = rng.gen_range(0 .. vertices.len());
-                            if !frozen_vertices_indices.contains(&index) {
-                                break index;
-                            }
-                        };
-                        frozen_vertices_indices.push(frozen_vertex_index);
-                        vertices[frozen_vertex_index] = bonus.position;
-                    }
+                    let targets: Vec<_> = bonuses.iter()
+                        .filter(|bonus| bonus.problem == target_problem)
+                        .map(|bonus| bonus.position)
+                        .collect();
+                    freeze_targets(rng, vertices, frozen_vertices_indices, &targets)?;
                 },
                 Some(..) | None =>
                     (),
@@ -339,52 +720,62 @@ fn generate_vertices(
         OperatingMode::BonusHunter =>
             match &solver.problem.bonuses {
                 Some(bonuses) if !bonuses.is_empty() => {
-                    for bonus in bonuses {
-                        let mut count = 0;
-                        let frozen_vertex_index = loop {
-                            count += 1;
-                            if count > 10000000 {
-                                return Err(GenerateVerticesError::ProbablyInfiniteLoopInFrozenIndexInBonusHunter);
-                            }
-                            let index = rng.gen_range(0 .. vertices.len());
-                            if !frozen_vertices_indices.contains(&index) {
-                                break index;
-                            }
-                        };
-                        frozen_vertices_indices.push(frozen_vertex_index);
-                        vertices[frozen_vertex_index] = bonus.position;
-                    }
+                    let targets: Vec<_> = bonuses.iter()
+                        .map(|bonus| bonus.position)
+                        .collect();
+                    freeze_targets(rng, vertices, frozen_vertices_indices, &targets)?;
                 },
                 Some(..) | None =>
                     (),
             },
         OperatingMode::ZeroHunter =>
-            for &hole_vertex in &solver.problem.hole {
-                let mut count = 0;
-                let frozen_vertex_index = loop {
-                    count += 1;
-                    if count > 10000000 {
-                        return Err(GenerateVerticesError::ProbablyInfiniteLoopInFrozenIndexInZeroHunter);
-                    }
-                    let index = rng.gen_range(0 .. vertices.len());
-                    if !frozen_vertices_indices.contains(&index) {
-                        break index;
-                    }
-                };
-                frozen_vertices_indices.push(frozen_vertex_index);
-                vertices[frozen_vertex_index] = hole_vertex;
-            },
+            freeze_targets(rng, vertices, frozen_vertices_indices, &solver.problem.hole)?,
+    }
+    Ok(())
+}
+
+/// Moves a random free vertex onto every target and freezes it. When random picks keep hitting frozen vertices
+/// (almost all of them are frozen) the first free vertex is taken, so this fails only if there are more targets than vertices.
+fn freeze_targets<R>(
+    rng: &mut R,
+    vertices: &mut [problem::Point],
+    frozen_vertices_indices: &mut Vec<usize>,
+    targets: &[problem::Point],
+)
+    -> Result<(), GenerateVerticesError>
+    where R: Rng
+{
+    for &target in targets {
+        let frozen_vertex_index = (0 .. FREEZE_RANDOM_TRIES)
+            .map(|_| rng.gen_range(0 .. vertices.len()))
+            .find(|index| !frozen_vertices_indices.contains(index))
+            .or_else(|| (0 .. vertices.len()).find(|index| !frozen_vertices_indices.contains(index)))
+            .ok_or(GenerateVerticesError::MoreFrozenTargetsThanVertices {
+                targets: frozen_vertices_indices.len() + targets.len(),
+                vertices: vertices.len(),
+            })?;
+        frozen_vertices_indices.push(frozen_vertex_index);
+        vertices[frozen_vertex_index] = target;
     }
     Ok(())
 }
 
 impl Fitness {
-    fn calc(problem: &problem::Problem, vertices: &[problem::Point], use_bonus: &Option<problem::ProblemBonusType>) -> Self {
+    fn calc(
+        problem: &problem::Problem,
+        geo_hole: &dyn problem::InvalidEdge,
+        vertices: &[problem::Point],
+        use_bonus: &Option<problem::ProblemBonusType>,
+        params: &Params,
+    )
+        -> Self
+    {
         let maybe_pose_bonus = match use_bonus {
             None =>
                 None,
+            // rejected by `SimulatedAnnealingSolver::new`, plain rules are the strictest fallback anyway
             Some(problem::ProblemBonusType::BreakALeg) =>
-                todo!(),
+                None,
             Some(problem::ProblemBonusType::Globalist) =>
                 Some(problem::PoseBonus::Globalist { problem: problem::ProblemId(0), }),
             Some(problem::ProblemBonusType::Wallhack) =>
@@ -405,9 +796,15 @@ impl Fitness {
                 unreachable!(),
         };
 
-        let ratio_avg = ratio_sum / problem.figure.edges.len() as f64;
+        let ratio_avg = ratio_sum / edges_factor(problem);
+        let dislikes_hint = || if params.dislikes_hint_weight > 0.0 {
+            let dislikes = problem.dislikes_assignment(vertices).iter().map(|&(_, sq_dist)| sq_dist).sum();
+            Fitness::dislikes_hint(dislikes, params.dislikes_hint_weight)
+        } else {
+            0.0
+        };
         if is_ok {
-            match problem.score_vertices(vertices, maybe_pose_bonus) {
+            match problem.score_vertices_in_allowing(geo_hole, vertices, maybe_pose_bonus, params.wallhack_allowed_outside) {
                 Ok(score) =>
                     Fitness::FigureScored { score, },
                 Err(problem::PoseValidationError::VerticeCountMismatch) =>
@@ -415,10 +812,29 @@ impl Fitness {
                 Err(problem::PoseValidationError::BrokenEdgesFound { broken_edges, .. }) =>
                     panic!("unexpected PoseValidationError::Broken_Edges on broken_edges = {:?}", broken_edges),
                 Err(problem::PoseValidationError::EdgesNotFitHole(not_fit_edges)) =>
-                    Fitness::NotFitHole { bad_edges_count: not_fit_edges.len(), ratio_avg, },
+                    Fitness::NotFitHole { bad_edges_count: not_fit_edges.len(), ratio_avg, dislikes_hint: dislikes_hint(), },
+                Err(problem::PoseValidationError::UnsupportedBonus(..)) =>
+                    Fitness::FigureCorrupted { ratio_avg, dislikes_hint: dislikes_hint(), },
             }
         } else {
-            Fitness::FigureCorrupted { ratio_avg, }
+            Fitness::FigureCorrupted { ratio_avg, dislikes_hint: dislikes_hint(), }
+        }
+    }
+
+    /// Energy penalty of an infeasible state for the `dislikes` it would have, as a fraction of the gap left
+    /// up to the next energy level: grows from 0.0 towards `weight` (clamped to 1.0), so the levels are never reordered.
+    pub fn dislikes_hint(dislikes: i64, weight: f64) -> f64 {
+        weight.min(1.0) * (1.0 - 1.0 / (1.0 + dislikes as f64))
+    }
+
+    /// Dislikes of a valid pose, `None` otherwise.
+    pub fn score(&self) -> Option<i64> {
+        match self {
+            &Fitness::FigureScored { score, } =>
+                Some(score),
+            &Fitness::FigureCorrupted { .. } |
+            &Fitness::NotFitHole { .. } =>
+                None,
         }
     }
 
@@ -428,14 +844,418 @@ impl Fitness {
                 0.0,
             &Fitness::FigureScored { score, } =>
                 2.0 - (1.0 / score as f64),
-            &Fitness::FigureCorrupted { ratio_avg, } =>
+            &Fitness::FigureCorrupted { ratio_avg, dislikes_hint, } =>
                 if ratio_avg < 1.0 {
-                    2.0 + ratio_avg
+                    let base = 2.0 + ratio_avg;
+                    base + dislikes_hint * (3.0 - base)
                 } else {
-                    4.0 - (1.0 / ratio_avg)
+                    let base = 4.0 - (1.0 / ratio_avg);
+                    base + dislikes_hint * (4.0 - base)
                 },
-            &Fitness::NotFitHole { bad_edges_count, ratio_avg, } =>
-                5.0 - (1.0 / bad_edges_count as f64) + ratio_avg,
+            &Fitness::NotFitHole { bad_edges_count, ratio_avg, dislikes_hint, } =>
+                5.0 - (1.0 / bad_edges_count as f64) + ratio_avg + dislikes_hint,
+        }
+    }
+}
+
+/// Edges count as energy multiplier, never zero so that edgeless figures do not turn energies into zeros and NaNs.
+fn edges_factor(problem: &problem::Problem) -> f64 {
+    problem.figure.edges.len().max(1) as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+
+    use super::*;
+
+    fn params(frozen_swap_prob: f64, frozen_swap_bias: f64) -> Params {
+        Params {
+            max_temp: 100.0,
+            cooling_step_temp: 1.0,
+            minimum_temp: 2.0,
+            below_floor: FloorBehavior::Stop,
+            edge_bias: EdgeBias::ProbSkip(0.5),
+            frozen_swap_prob,
+            frozen_swap_bias,
+            vertex_swap_prob: 0.0,
+            reachable_moves: false,
+            wallhack_allowed_outside: problem::WALLHACK_ALLOWED_OUTSIDE,
+            coarsen_factor: 1,
+            min_move_radius: 1,
+            max_move_radius: 1,
+            dislikes_hint_weight: 0.0,
+            iterations_per_cooling_step: 16,
+            operating_mode: OperatingMode::ZeroHunter,
+        }
+    }
+
+    #[test]
+    fn frozen_swap_bias() {
+        assert_eq!(params(0.15, 0.0).frozen_swap_prob(3, 4), 0.15);
+        assert_eq!(params(0.15, 1.0).frozen_swap_prob(0, 4), 0.15);
+        assert_eq!(params(0.0, 1.0).frozen_swap_prob(4, 4), 1.0);
+
+        let swap_prob = params(0.2, 0.5).frozen_swap_prob(3, 4);
+        assert!((swap_prob - 0.5).abs() < 1e-9);
+    }
+
+    #[test]
+    fn frozen_swap_bias_drives_step() {
+        let problem_data = r#"{"bonuses":[],"hole":[[0,0],[20,0],[20,20],[0,20]],"epsilon":150000,"figure":{"edges":[[0,3],[1,3],[2,3]],"vertices":[[0,0],[10,0],[0,10],[5,5]]}}"#;
+        let problem: problem::Problem = serde_json::from_str(problem_data).unwrap();
+        for &(frozen_swap_bias, expected_ratio) in &[(0.0, 0.2), (0.5, 0.5), (1.0, 0.8)] {
+            let mut params = params(0.2, frozen_swap_bias);
+            params.iterations_per_cooling_step = 20000;
+            let mut solver = SimulatedAnnealingSolver::new(solver::Solver::new(&problem, None).unwrap(), params)
+                .unwrap()
+                .with_seed(1129)
+                .unwrap();
+            solver.frozen_vertices_indices = vec![0, 1, 2];
+
+            solver.step().unwrap();
+            let swaps_ratio = solver.frozen_swaps_last_step() as f64 / params.iterations_per_cooling_step as f64;
+            assert!((swaps_ratio - expected_ratio).abs() < 0.02, "bias {}: {} frozen swaps ratio, {} expected", frozen_swap_bias, swaps_ratio, expected_ratio);
+        }
+    }
+
+    #[test]
+    fn wallhack_keeps_single_vertex_outside() {
+        let problem_data = r#"{"bonuses":[],"hole":[[0,0],[6,0],[6,6],[0,6]],"epsilon":150000,"figure":{"edges":[[0,1],[1,2],[2,0],[2,3]],"vertices":[[0,0],[5,0],[0,5],[6,6]]}}"#;
+        let problem: problem::Problem = serde_json::from_str(problem_data).unwrap();
+        let solver = solver::Solver::with_bonus(&problem, None, Some(problem::ProblemBonusType::Wallhack)).unwrap();
+        let mut params = params(0.0, 0.0);
+        params.iterations_per_cooling_step = 1;
+        params.cooling_step_temp = 0.01;
+        params.operating_mode = OperatingMode::ScoreMaximizer;
+        let mut solver = SimulatedAnnealingSolver::new(solver, params).unwrap();
+
+        for _ in 0 .. 5000 {
+            match solver.step() {
+                Ok(()) =>
+                    (),
+                Err(StepError::TempTooLow) =>
+                    break,
+                Err(error) =>
+                    panic!("unexpected step error: {:?}", error),
+            }
+            let outside = solver.vertices().iter().filter(|vertex| !solver.solver.is_hole(vertex)).count();
+            assert!(outside <= 1, "vertices {:?} have {} outside of the hole", solver.vertices(), outside);
+        }
+    }
+
+    #[test]
+    fn break_a_leg_is_rejected() {
+        let problem_data = r#"{"bonuses":[],"hole":[[0,0],[6,0],[6,6],[0,6]],"epsilon":150000,"figure":{"edges":[[0,1],[1,2],[2,0]],"vertices":[[0,0],[5,0],[0,5]]}}"#;
+        let problem: problem::Problem = serde_json::from_str(problem_data).unwrap();
+        let solver = solver::Solver::with_bonus(&problem, None, Some(problem::ProblemBonusType::BreakALeg)).unwrap();
+        assert!(matches!(
+            SimulatedAnnealingSolver::new(solver, params(0.0, 0.0)),
+            Err(CreateError::UnsupportedBonus(problem::ProblemBonusType::BreakALeg)),
+        ));
+    }
+
+    #[test]
+    fn edge_repair_moves_broken_edges_only() {
+        let problem_data = r#"{"bonuses":[],"hole":[[0,0],[20,0],[20,20],[0,20]],"epsilon":0,"figure":{"edges":[[0,1],[1,2],[2,3]],"vertices":[[0,0],[5,0],[5,5],[10,5]]}}"#;
+        let problem: problem::Problem = serde_json::from_str(problem_data).unwrap();
+        // only edge (1, 2) is stretched
+        let pose = problem::Pose {
+            vertices: vec![problem::Point(0, 0), problem::Point(5, 0), problem::Point(5, 7), problem::Point(10, 7)],
+            bonuses: None,
+        };
+        let solver = solver::Solver::with_bonus(&problem, Some(pose.clone()), Some(problem::ProblemBonusType::Globalist)).unwrap();
+        let mut params = params(0.0, 0.0);
+        params.operating_mode = OperatingMode::EdgeRepair;
+        let solver = SimulatedAnnealingSolver::new(solver, params).unwrap();
+        assert_eq!(solver.vertices(), &pose.vertices[..]);
+
+        let mut rng = rand::rngs::StdRng::seed_from_u64(1162);
+        for _ in 0 .. 1000 {
+            assert_eq!(solver.propose_edge(&pose.vertices, &mut rng), Some(1));
+        }
+        // nothing is broken: any edge may be picked
+        let proposed: Vec<_> = (0 .. 1000)
+            .flat_map(|_| solver.propose_edge(&problem.figure.vertices, &mut rng))
+            .collect();
+        assert!(proposed.contains(&0) && proposed.contains(&2));
+    }
+
+    #[test]
+    fn focus_broken_edges() {
+        let problem_data = r#"{"bonuses":[],"hole":[[0,0],[20,0],[20,20],[0,20]],"epsilon":0,"figure":{"edges":[[0,1],[1,2],[2,3]],"vertices":[[0,0],[5,0],[5,5],[10,5]]}}"#;
+        let problem: problem::Problem = serde_json::from_str(problem_data).unwrap();
+        let solver = solver::Solver::new(&problem, None).unwrap();
+        let mut params = params(0.15, 0.0);
+        params.edge_bias = EdgeBias::FocusBroken;
+        params.operating_mode = OperatingMode::ScoreMaximizer;
+        let solver = SimulatedAnnealingSolver::new(solver, params).unwrap();
+
+        // only edge (1, 2) is stretched
+        let vertices = vec![problem::Point(0, 0), problem::Point(5, 0), problem::Point(5, 7), problem::Point(10, 7)];
+        let mut rng = rand::rngs::StdRng::seed_from_u64(1140);
+        for _ in 0 .. 1000 {
+            assert_eq!(solver.propose_edge(&vertices, &mut rng), Some(1));
+        }
+
+        assert_eq!(EdgeBias::from_valid_edge_accept_prob(1.0), EdgeBias::Off);
+        assert_eq!(EdgeBias::from_valid_edge_accept_prob(0.5), EdgeBias::ProbSkip(0.5));
+    }
+
+    #[test]
+    fn reachable_moves_stay_in_hole() {
+        // thin corridor
+        let problem_data = r#"{"bonuses":[],"hole":[[0,0],[20,0],[20,2],[0,2]],"epsilon":0,"figure":{"edges":[[0,1]],"vertices":[[5,1],[6,1]]}}"#;
+        let problem: problem::Problem = serde_json::from_str(problem_data).unwrap();
+        let solver = solver::Solver::new(&problem, None).unwrap();
+        let mut params = params(0.0, 0.0);
+        params.reachable_moves = true;
+        params.operating_mode = OperatingMode::ScoreMaximizer;
+        let solver = SimulatedAnnealingSolver::new(solver, params).unwrap();
+
+        let vertex = problem::Point(10, 0);
+        assert!(solver.solver.reachable_cells(&vertex).len() < 8);
+        let mut rng = rand::rngs::StdRng::seed_from_u64(1148);
+        for _ in 0 .. 1000 {
+            let moved_vertex = solver.propose_reachable_move(&vertex, &mut rng).unwrap();
+            assert_ne!(moved_vertex, vertex);
+            assert!(solver.solver.is_hole(&moved_vertex), "{:?} is out of hole", moved_vertex);
+        }
+    }
+
+    #[test]
+    fn acceptance_ratio_follows_temp() {
+        let problem_data = r#"{"bonuses":[],"hole":[[0,0],[20,0],[20,20],[0,20]],"epsilon":0,"figure":{"edges":[[0,1],[1,2],[2,3],[3,0],[0,2]],"vertices":[[2,2],[12,2],[12,12],[2,12]]}}"#;
+        let problem: problem::Problem = serde_json::from_str(problem_data).unwrap();
+        let mut params = params(0.0, 0.0);
+        params.edge_bias = EdgeBias::Off;
+        params.minimum_temp = 1e-12;
+        params.iterations_per_cooling_step = 2000;
+        params.operating_mode = OperatingMode::ScoreMaximizer;
+
+        let mut hot = SimulatedAnnealingSolver::new(solver::Solver::new(&problem, None).unwrap(), params).unwrap();
+        assert_eq!(hot.acceptance_ratio_last_step(), 0.0);
+        hot.reheat(1e6);
+        hot.step().unwrap();
+        assert!(hot.acceptance_ratio_last_step() > 0.9, "hot acceptance ratio {}", hot.acceptance_ratio_last_step());
+
+        let mut cold = SimulatedAnnealingSolver::new(solver::Solver::new(&problem, None).unwrap(), params).unwrap();
+        cold.reheat(1e-9);
+        cold.step().unwrap();
+        assert!(cold.acceptance_ratio_last_step() < 0.2, "cold acceptance ratio {}", cold.acceptance_ratio_last_step());
+    }
+
+    #[test]
+    fn coarsen_factor_lattice_moves() {
+        let problem_data = r#"{"bonuses":[],"hole":[[0,0],[40,0],[40,40],[0,40]],"epsilon":0,"figure":{"edges":[[0,1],[1,2],[2,0]],"vertices":[[0,0],[10,0],[0,10]]}}"#;
+        let problem: problem::Problem = serde_json::from_str(problem_data).unwrap();
+        let mut params = params(0.0, 0.0);
+        params.edge_bias = EdgeBias::Off;
+        params.coarsen_factor = 4;
+        params.operating_mode = OperatingMode::ScoreMaximizer;
+        assert_eq!(params.lattice_step(params.max_temp), 4);
+        assert_eq!(params.lattice_step(params.max_temp * 0.5), 2);
+        assert_eq!(params.lattice_step(params.minimum_temp), 1);
+
+        let mut solver = SimulatedAnnealingSolver::new(solver::Solver::new(&problem, None).unwrap(), params).unwrap();
+        for _ in 0 .. 5 {
+            assert!(
+                solver.vertices().iter().all(|vertex| vertex.0 % 4 == 0 && vertex.1 % 4 == 0),
+                "vertices {:?} are off the lattice at temp {}",
+                solver.vertices(),
+                solver.temp(),
+            );
+            solver.step().unwrap();
+        }
+
+        params.coarsen_factor = 1;
+        assert_eq!(params.lattice_step(params.max_temp), 1);
+    }
+
+    #[test]
+    fn move_radius_follows_acceptance_and_temp() {
+        let problem_data = r#"{"bonuses":[],"hole":[[0,0],[40,0],[40,40],[0,40]],"epsilon":0,"figure":{"edges":[[0,1],[1,2],[2,3],[3,0],[0,2]],"vertices":[[2,2],[12,2],[12,12],[2,12]]}}"#;
+        let problem: problem::Problem = serde_json::from_str(problem_data).unwrap();
+        let mut params = params(0.0, 0.0);
+        params.edge_bias = EdgeBias::Off;
+        params.minimum_temp = 1e-12;
+        params.iterations_per_cooling_step = 2000;
+        params.min_move_radius = 1;
+        params.max_move_radius = 6;
+        params.operating_mode = OperatingMode::ScoreMaximizer;
+
+        // the cap follows temperature, acceptance only moves the radius within it
+        assert_eq!(params.adapt_move_radius(6, 1.0, params.max_temp), 6);
+        assert_eq!(params.adapt_move_radius(3, 0.3, params.max_temp), 3);
+        assert_eq!(params.adapt_move_radius(3, 0.0, params.max_temp), 2);
+        assert_eq!(params.adapt_move_radius(6, 1.0, params.minimum_temp), 1);
+
+        let mut hot = SimulatedAnnealingSolver::new(solver::Solver::new(&problem, None).unwrap(), params).unwrap();
+        assert_eq!(hot.move_radius(), 1);
+        hot.reheat(1e6);
+        for _ in 0 .. 3 {
+            hot.step().unwrap();
+        }
+        assert_eq!(hot.move_radius(), 4, "acceptance ratio {}", hot.acceptance_ratio_last_step());
+
+        hot.reheat(1e-9);
+        hot.step().unwrap();
+        assert_eq!(hot.move_radius(), params.min_move_radius);
+
+        hot.reset().unwrap();
+        assert_eq!(hot.move_radius(), params.min_move_radius);
+    }
+
+    #[test]
+    fn dislikes_hint_energy() {
+        let far = Fitness::NotFitHole { bad_edges_count: 2, ratio_avg: 0.1, dislikes_hint: Fitness::dislikes_hint(400, 0.0), };
+        let near = Fitness::NotFitHole { bad_edges_count: 2, ratio_avg: 0.1, dislikes_hint: Fitness::dislikes_hint(25, 0.0), };
+        assert_eq!(far.energy(), near.energy());
+
+        let far = Fitness::NotFitHole { bad_edges_count: 2, ratio_avg: 0.1, dislikes_hint: Fitness::dislikes_hint(400, 0.1), };
+        let near = Fitness::NotFitHole { bad_edges_count: 2, ratio_avg: 0.1, dislikes_hint: Fitness::dislikes_hint(25, 0.1), };
+        assert!(near.energy() < far.energy());
+        // still worse than any feasible state
+        assert!(Fitness::FigureScored { score: 1000000, }.energy() < near.energy());
+
+        // even at max weight a corrupted pose never outranks a less corrupted one
+        for &weight in &[1.0, 10.0] {
+            let hint = Fitness::dislikes_hint(1_000_000_000, weight);
+            let below_one = Fitness::FigureCorrupted { ratio_avg: 0.99, dislikes_hint: hint, };
+            let above_one = Fitness::FigureCorrupted { ratio_avg: 1.0, dislikes_hint: 0.0, };
+            let far_above_one = Fitness::FigureCorrupted { ratio_avg: 1000.0, dislikes_hint: hint, };
+            let not_fit = Fitness::NotFitHole { bad_edges_count: 1, ratio_avg: 0.0, dislikes_hint: 0.0, };
+            assert!(below_one.energy() < above_one.energy(), "{:?} vs {:?}", below_one, above_one);
+            assert!(far_above_one.energy() < not_fit.energy(), "{:?} vs {:?}", far_above_one, not_fit);
+        }
+
+        let problem_data = r#"{"bonuses":[],"hole":[[0,0],[20,0],[20,20],[0,20]],"epsilon":0,"figure":{"edges":[[0,1]],"vertices":[[0,0],[10,0]]}}"#;
+        let problem: problem::Problem = serde_json::from_str(problem_data).unwrap();
+        let solver = solver::Solver::new(&problem, None).unwrap();
+        let mut params = params(0.0, 0.0);
+        params.dislikes_hint_weight = 0.1;
+        // both edges are stretched the same, the second one is closer to the hole corners
+        let far = Fitness::calc(&problem, solver.edge_checker(), &[problem::Point(0, 0), problem::Point(20, 0)], &None, &params);
+        let near = Fitness::calc(&problem, solver.edge_checker(), &[problem::Point(0, 10), problem::Point(20, 10)], &None, &params);
+        assert!(matches!(far, Fitness::FigureCorrupted { .. }), "{:?}", far);
+        assert!(near.energy() < far.energy(), "{:?} vs {:?}", near, far);
+    }
+
+    #[test]
+    fn greedy_below_floor() {
+        let problem_data = r#"{"bonuses":[],"hole":[[0,0],[20,0],[20,20],[0,20]],"epsilon":0,"figure":{"edges":[[0,1],[1,2],[2,3],[3,0],[0,2]],"vertices":[[2,2],[12,2],[12,12],[2,12]]}}"#;
+        let problem: problem::Problem = serde_json::from_str(problem_data).unwrap();
+        let mut params = params(0.0, 0.0);
+        params.cooling_step_temp = 20.0;
+        params.minimum_temp = 50.0;
+        params.below_floor = FloorBehavior::Greedy;
+        params.operating_mode = OperatingMode::ScoreMaximizer;
+        let mut solver = SimulatedAnnealingSolver::new(solver::Solver::new(&problem, None).unwrap(), params).unwrap();
+        while solver.temp() >= params.minimum_temp {
+            solver.step().unwrap();
+        }
+
+        let floor_temp = solver.temp();
+        let mut energy = solver.fitness().energy();
+        for _ in 0 .. 200 {
+            solver.step().unwrap();
+            assert!(solver.fitness().energy() <= energy, "{:?} is worse than energy {}", solver.fitness(), energy);
+            energy = solver.fitness().energy();
         }
+        assert_eq!(solver.temp(), floor_temp);
+
+        params.below_floor = FloorBehavior::Stop;
+        let mut solver = SimulatedAnnealingSolver::new(solver::Solver::new(&problem, None).unwrap(), params).unwrap();
+        while solver.temp() >= params.minimum_temp {
+            solver.step().unwrap();
+        }
+        assert!(matches!(solver.step(), Err(StepError::TempTooLow)));
+    }
+
+    #[test]
+    fn vertex_swap_keeps_positions() {
+        let problem_data = r#"{"bonuses":[],"hole":[[0,0],[20,0],[20,20],[0,20]],"epsilon":0,"figure":{"edges":[[0,1],[1,2],[2,3]],"vertices":[[0,0],[5,0],[5,5],[10,5]]}}"#;
+        let problem: problem::Problem = serde_json::from_str(problem_data).unwrap();
+        let mut params = params(0.0, 0.0);
+        params.vertex_swap_prob = 1.0;
+        params.operating_mode = OperatingMode::ScoreMaximizer;
+        let mut solver = SimulatedAnnealingSolver::new(solver::Solver::new(&problem, None).unwrap(), params).unwrap();
+        let initial = vec![problem::Point(2, 2), problem::Point(7, 2), problem::Point(7, 7), problem::Point(12, 7)];
+        solver.warm_start(&initial).unwrap();
+
+        let mut sorted_initial = initial.clone();
+        sorted_initial.sort_unstable_by_key(|point| (point.0, point.1));
+        let mut reassigned = false;
+        for _ in 0 .. 10 {
+            solver.step().unwrap();
+            let mut sorted_vertices = solver.vertices().to_vec();
+            reassigned |= sorted_vertices != initial;
+            sorted_vertices.sort_unstable_by_key(|point| (point.0, point.1));
+            assert_eq!(sorted_vertices, sorted_initial);
+        }
+        assert!(reassigned);
+    }
+
+    #[test]
+    fn step_budget_halves() {
+        let problem_data = r#"{"bonuses":[],"hole":[[0,0],[30,0],[30,30],[0,30]],"epsilon":0,"figure":{"edges":[[0,1],[1,2],[2,3],[3,0],[0,2]],"vertices":[[2,2],[12,2],[12,12],[2,12]]}}"#;
+        let problem: problem::Problem = serde_json::from_str(problem_data).unwrap();
+        let mut params = params(0.15, 0.0);
+        params.iterations_per_cooling_step = 64;
+        params.operating_mode = OperatingMode::ScoreMaximizer;
+        let solver_new = || SimulatedAnnealingSolver::new(solver::Solver::new(&problem, None).unwrap(), params)
+            .unwrap()
+            .with_seed(1185)
+            .unwrap();
+        let mut whole = solver_new();
+        let mut sliced = solver_new();
+        for _ in 0 .. 20 {
+            let temp = sliced.temp();
+            whole.step().unwrap();
+            assert_eq!(sliced.step_budget(32).unwrap(), StepProgress::InProgress { iterations_left: 32, });
+            // cooling happens only when the step is complete
+            assert_eq!(sliced.temp(), temp);
+            assert_eq!(sliced.step_budget(32).unwrap(), StepProgress::CoolingStepDone);
+            assert_eq!(sliced.vertices(), whole.vertices());
+            assert_eq!(sliced.fitness(), whole.fitness());
+            assert_eq!(sliced.temp(), whole.temp());
+            assert_eq!(sliced.acceptance_ratio_last_step(), whole.acceptance_ratio_last_step());
+        }
+    }
+
+    #[test]
+    fn nearly_all_frozen_vertices() {
+        // every figure vertex is frozen on a hole vertex by zero hunter
+        let problem_data = r#"{"bonuses":[],"hole":[[0,0],[10,0],[10,10],[0,10]],"epsilon":0,"figure":{"edges":[[0,1],[1,2],[2,3],[3,0]],"vertices":[[0,0],[10,0],[10,10],[0,10]]}}"#;
+        let problem: problem::Problem = serde_json::from_str(problem_data).unwrap();
+        let mut solver = SimulatedAnnealingSolver::new(solver::Solver::new(&problem, None).unwrap(), params(0.15, 0.0)).unwrap();
+        let mut frozen = solver.frozen_vertices_indices.clone();
+        frozen.sort_unstable();
+        assert_eq!(frozen, vec![0, 1, 2, 3]);
+        // regenerating used to run out of free vertices
+        for _ in 0 .. 3 {
+            solver.reset().unwrap();
+            assert_eq!(solver.frozen_vertices_indices.len(), 4);
+        }
+
+        let mut vertices = vec![problem::Point(0, 0); 3];
+        let mut frozen_vertices_indices = vec![1];
+        let mut rng = rand::rngs::StdRng::seed_from_u64(1172);
+        freeze_targets(&mut rng, &mut vertices, &mut frozen_vertices_indices, &[problem::Point(5, 5), problem::Point(6, 6)]).unwrap();
+        frozen_vertices_indices.sort_unstable();
+        assert_eq!(frozen_vertices_indices, vec![0, 1, 2]);
+        assert!(matches!(
+            freeze_targets(&mut rng, &mut vertices, &mut frozen_vertices_indices, &[problem::Point(7, 7)]),
+            Err(GenerateVerticesError::MoreFrozenTargetsThanVertices { targets: 4, vertices: 3, }),
+        ));
+    }
+
+    #[test]
+    fn edgeless_figure() {
+        let problem_data = r#"{"bonuses":[],"hole":[[0,0],[10,0],[10,10],[0,10]],"epsilon":0,"figure":{"edges":[],"vertices":[[1,1],[5,5]]}}"#;
+        let problem: problem::Problem = serde_json::from_str(problem_data).unwrap();
+        let solver = solver::Solver::new(&problem, None).unwrap();
+        assert!(matches!(SimulatedAnnealingSolver::new(solver, params(0.15, 0.0)), Err(CreateError::NoEdgesInFigure)));
+        assert!(matches!(Fitness::calc(&problem, &problem.hole_polygon_f64(), &problem.figure.vertices, &None, &params(0.15, 0.0)), Fitness::FigureScored { .. }));
     }
 }
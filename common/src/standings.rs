@@ -0,0 +1,71 @@
+#[derive(Clone, PartialEq, Debug)]
+pub struct Standing {
+    pub task_id: usize,
+    pub our_dislikes: Option<i64>,
+    pub best_dislikes: Option<i64>,
+}
+
+/// Extracts `(task id, our dislikes, minimal dislikes)` rows from the poses.live problems page table.
+pub fn parse_standings(page: &str) -> Vec<Standing> {
+    let mut standings = Vec::new();
+    for row in page.split("<tr").skip(1) {
+        let row = row.split("</tr>").next().unwrap_or("");
+        let cells: Vec<_> = row
+            .split("<td")
+            .skip(1)
+            .map(|cell| strip_tags(cell.split("</td>").next().unwrap_or("")))
+            .collect();
+        if cells.len() < 3 {
+            continue;
+        }
+        if let Ok(task_id) = cells[0].parse() {
+            standings.push(Standing {
+                task_id,
+                our_dislikes: cells[1].parse().ok(),
+                best_dislikes: cells[2].parse().ok(),
+            });
+        }
+    }
+    standings
+}
+
+fn strip_tags(html: &str) -> String {
+    let mut text = String::new();
+    let mut in_tag = true; // cell content starts right after the `<td` prefix
+    for c in html.chars() {
+        match c {
+            '<' => in_tag = true,
+            '>' => in_tag = false,
+            c if !in_tag => text.push(c),
+            _ => (),
+        }
+    }
+    text.trim().to_string()
+}
+
+impl Standing {
+    /// How many dislikes could still be gained on this task, `None` if the task is unsolved.
+    pub fn gap(&self) -> Option<i64> {
+        match (self.our_dislikes, self.best_dislikes) {
+            (Some(ours), Some(best)) => Some(ours - best),
+            (Some(ours), None) => Some(ours),
+            (None, _) => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_problems_page() {
+        let page = r#"<table><tr><th>Problem</th><th>Your dislikes</th><th>Minimal dislikes</th></tr>
+            <tr><td><a href="/problems/1">1</a></td><td>25</td><td>0</td></tr>
+            <tr><td><a href="/problems/2">2</a></td><td>-</td><td>1344</td></tr></table>"#;
+        assert_eq!(parse_standings(page), vec![
+            Standing { task_id: 1, our_dislikes: Some(25), best_dislikes: Some(0), },
+            Standing { task_id: 2, our_dislikes: None, best_dislikes: Some(1344), },
+        ]);
+    }
+}
@@ -0,0 +1,117 @@
+use std::{
+    fs,
+    io::{
+        self,
+        Write,
+        BufRead,
+    },
+    time,
+    path::Path,
+};
+
+use serde_derive::{
+    Serialize,
+    Deserialize,
+};
+
+use crate::{
+    problem,
+};
+
+/// One line of `submissions.jsonl` log: a pose that has been saved and submitted for the task.
+#[derive(Clone, PartialEq, Eq, Serialize, Deserialize, Debug)]
+pub struct Submission {
+    pub task_id: String,
+    pub dislikes: i64,
+    pub bonuses: Vec<problem::PoseBonus>,
+    /// seconds since unix epoch
+    pub timestamp: u64,
+    pub pose_hash: u64,
+}
+
+#[derive(Debug)]
+pub enum AppendError {
+    OpenFile(io::Error),
+    Serialize(serde_json::Error),
+    Write(io::Error),
+}
+
+#[derive(Debug)]
+pub enum LoadError {
+    OpenFile(io::Error),
+    Read(io::Error),
+    Deserialize { line: usize, error: serde_json::Error, },
+}
+
+impl Submission {
+    pub fn new(task_id: &str, pose: &problem::Pose, dislikes: i64) -> Submission {
+        let timestamp = time::SystemTime::now()
+            .duration_since(time::UNIX_EPOCH)
+            .map_or(0, |since_epoch| since_epoch.as_secs());
+        Submission {
+            task_id: task_id.to_string(),
+            dislikes,
+            bonuses: pose.bonuses.clone().unwrap_or_default(),
+            timestamp,
+            pose_hash: pose.fingerprint(),
+        }
+    }
+}
+
+/// Appends `submission` as a json line to `log_file`, creating the file if needed.
+pub fn append<P>(log_file: P, submission: &Submission) -> Result<(), AppendError> where P: AsRef<Path> {
+    let mut line = serde_json::to_string(submission)
+        .map_err(AppendError::Serialize)?;
+    line.push('\n');
+    let mut file = fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(log_file)
+        .map_err(AppendError::OpenFile)?;
+    file.write_all(line.as_bytes())
+        .map_err(AppendError::Write)
+}
+
+/// Reads all submissions from `log_file` in the order they were appended, skipping empty lines.
+pub fn load<P>(log_file: P) -> Result<Vec<Submission>, LoadError> where P: AsRef<Path> {
+    let file = fs::File::open(log_file)
+        .map_err(LoadError::OpenFile)?;
+    let mut submissions = Vec::new();
+    for (index, line) in io::BufReader::new(file).lines().enumerate() {
+        let line = line.map_err(LoadError::Read)?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let submission = serde_json::from_str(&line)
+            .map_err(|error| LoadError::Deserialize { line: index + 1, error, })?;
+        submissions.push(submission);
+    }
+    Ok(submissions)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn append_and_load() {
+        let temp_directory = tempfile::tempdir().unwrap();
+        let log_file = temp_directory.path().join("submissions.jsonl");
+
+        let pose_a = problem::Pose {
+            vertices: vec![problem::Point(1, 2), problem::Point(3, 4)],
+            bonuses: None,
+        };
+        let pose_b = problem::Pose {
+            vertices: vec![problem::Point(1, 2), problem::Point(3, 5)],
+            bonuses: Some(vec![problem::PoseBonus::Globalist { problem: problem::ProblemId(7), }]),
+        };
+        let submission_a = Submission::new("12", &pose_a, 100);
+        let submission_b = Submission::new("12", &pose_b, 25);
+        assert_ne!(submission_a.pose_hash, submission_b.pose_hash);
+
+        append(&log_file, &submission_a).unwrap();
+        append(&log_file, &submission_b).unwrap();
+        assert_eq!(load(&log_file).unwrap(), vec![submission_a, submission_b]);
+    }
+}
@@ -0,0 +1,145 @@
+use std::fmt::Write;
+
+use crate::{
+    problem,
+    solver,
+};
+
+const MARGIN: i64 = 2;
+
+/// Renders the hole and the pose figure as an svg document. Every figure edge is stroked with `stretch_color`
+/// of its stretch ratio relative to the original figure edge, thinner the closer it is to the epsilon limit.
+pub fn export(problem: &problem::Problem, pose_vertices: &[problem::Point]) -> String {
+    let mut min = problem::Point(i64::MAX, i64::MAX);
+    let mut max = problem::Point(i64::MIN, i64::MIN);
+    for point in problem.hole.iter().chain(pose_vertices.iter()) {
+        min = problem::Point(min.0.min(point.0), min.1.min(point.1));
+        max = problem::Point(max.0.max(point.0), max.1.max(point.1));
+    }
+    if min.0 > max.0 {
+        min = problem::Point(0, 0);
+        max = problem::Point(0, 0);
+    }
+
+    let mut svg = String::new();
+    writeln!(
+        svg,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="{} {} {} {}">"#,
+        min.0 - MARGIN,
+        min.1 - MARGIN,
+        max.0 - min.0 + MARGIN * 2,
+        max.1 - min.1 + MARGIN * 2,
+    ).unwrap();
+
+    let hole_points: Vec<_> = problem.hole
+        .iter()
+        .map(|point| format!("{},{}", point.0, point.1))
+        .collect();
+    writeln!(
+        svg,
+        r#"  <polygon points="{}" fill="none" stroke="black" stroke-width="0.5"/>"#,
+        hole_points.join(" "),
+    ).unwrap();
+
+    let epsilon_ratio = problem.epsilon_fraction();
+    for edge in &problem.figure.edges {
+        let (_is_valid, ratio) = solver::is_edge_ratio_valid(edge, pose_vertices, problem);
+        let (r, g, b) = stretch_color(ratio, epsilon_ratio);
+        let from = pose_vertices[edge.0];
+        let to = pose_vertices[edge.1];
+        writeln!(
+            svg,
+            r#"  <line x1="{}" y1="{}" x2="{}" y2="{}" stroke="rgb({},{},{})" stroke-width="{:.2}"/>"#,
+            from.0,
+            from.1,
+            to.0,
+            to.1,
+            r,
+            g,
+            b,
+            headroom_width(ratio, epsilon_ratio, 0.2, 0.8),
+        ).unwrap();
+    }
+
+    svg.push_str("</svg>\n");
+    svg
+}
+
+/// Green for an unstretched edge shading to red as `ratio` approaches `epsilon_ratio`, red beyond it.
+pub fn stretch_color(ratio: f64, epsilon_ratio: f64) -> (u8, u8, u8) {
+    let shade = if ratio <= 0.0 {
+        0.0
+    } else if ratio >= epsilon_ratio {
+        1.0
+    } else {
+        ratio / epsilon_ratio
+    };
+    ((255.0 * shade).round() as u8, (255.0 * (1.0 - shade)).round() as u8, 0)
+}
+
+/// Stroke width by the epsilon headroom left to the edge: `max_width` for an unstretched edge
+/// narrowing down to `min_width` as `ratio` approaches `epsilon_ratio` and beyond it.
+pub fn headroom_width(ratio: f64, epsilon_ratio: f64, min_width: f64, max_width: f64) -> f64 {
+    let headroom = if ratio <= 0.0 {
+        1.0
+    } else if ratio >= epsilon_ratio {
+        0.0
+    } else {
+        1.0 - ratio / epsilon_ratio
+    };
+    min_width + (max_width - min_width) * headroom
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn edges_colored_by_stretch() {
+        let problem = problem::Problem {
+            hole: vec![ problem::Point(0, 0), problem::Point(20, 0), problem::Point(20, 20), problem::Point(0, 20) ],
+            figure: problem::Figure {
+                edges: vec![ problem::Edge(0, 1), problem::Edge(1, 2) ],
+                vertices: vec![ problem::Point(0, 0), problem::Point(10, 0), problem::Point(10, 10) ],
+            },
+            epsilon: 10000,
+            bonuses: None,
+        };
+        let pose_vertices = vec![ problem::Point(2, 2), problem::Point(12, 2), problem::Point(12, 18) ];
+
+        let svg = export(&problem, &pose_vertices);
+        assert!(svg.contains(r#"<line x1="2" y1="2" x2="12" y2="2" stroke="rgb(0,255,0)""#), "{}", svg);
+        assert!(svg.contains(r#"<line x1="12" y1="2" x2="12" y2="18" stroke="rgb(255,0,0)""#), "{}", svg);
+
+        assert_eq!(stretch_color(0.005, 0.01), (128, 128, 0));
+    }
+
+    #[test]
+    fn fragile_edges_are_thinner() {
+        let near_limit = headroom_width(0.009, 0.01, 0.2, 0.8);
+        let slack = headroom_width(0.002, 0.01, 0.2, 0.8);
+        assert!(near_limit < slack, "{} vs {}", near_limit, slack);
+        assert_eq!(headroom_width(0.0, 0.01, 0.2, 0.8), 0.8);
+        assert_eq!(headroom_width(0.02, 0.01, 0.2, 0.8), 0.2);
+        assert_eq!(headroom_width(0.0, 0.0, 0.2, 0.8), 0.8);
+
+        let problem = problem::Problem {
+            hole: vec![ problem::Point(0, 0), problem::Point(40, 0), problem::Point(40, 40), problem::Point(0, 40) ],
+            figure: problem::Figure {
+                edges: vec![ problem::Edge(0, 1), problem::Edge(1, 2) ],
+                vertices: vec![ problem::Point(0, 0), problem::Point(20, 0), problem::Point(20, 20) ],
+            },
+            epsilon: 20000,
+            bonuses: None,
+        };
+        // 400 -> 401 leaves most of the 2% headroom, 400 -> 405 uses more than a half of it
+        let pose_vertices = vec![ problem::Point(1, 1), problem::Point(21, 2), problem::Point(30, 20) ];
+        let svg = export(&problem, &pose_vertices);
+        let widths: Vec<f64> = svg.lines()
+            .filter(|line| line.trim_start().starts_with("<line"))
+            .map(|line| line.split("stroke-width=\"").nth(1).unwrap().split('"').next().unwrap().parse().unwrap())
+            .collect();
+        assert_eq!(widths.len(), 2);
+        assert!(widths[1] < widths[0], "{}", svg);
+    }
+}
@@ -0,0 +1,131 @@
+use common::{
+    problem,
+};
+
+/// Expected outcome of `Problem::score_pose`, errors are compared by kind and edges only (not by stretching sums).
+#[derive(Debug)]
+enum Expected {
+    Score(i64),
+    BrokenEdges(&'static [problem::Edge]),
+    NotFitHole(&'static [problem::Edge]),
+    UnsupportedBonus,
+    VerticeCountMismatch,
+}
+
+// square hole, chain figure of two perpendicular edges of length 10, epsilon is 15%
+const CHAIN_PROBLEM: &str = r#"{"bonuses":[],"hole":[[0,0],[20,0],[20,20],[0,20]],"epsilon":150000,"figure":{"edges":[[0,1],[1,2]],"vertices":[[0,0],[10,0],[10,10]]}}"#;
+
+// the same square hole, triangle figure without any stretching allowed
+const TRIANGLE_PROBLEM: &str = r#"{"bonuses":[],"hole":[[0,0],[20,0],[20,20],[0,20]],"epsilon":0,"figure":{"edges":[[0,1],[1,2],[2,0]],"vertices":[[0,0],[10,0],[0,10]]}}"#;
+
+const GOLDEN: &[(&str, &str, &str, Expected)] = &[
+    (
+        "triangle as is",
+        TRIANGLE_PROBLEM,
+        r#"{"vertices":[[0,0],[10,0],[0,10]]}"#,
+        Expected::Score(700),
+    ),
+    (
+        "triangle shifted inside",
+        TRIANGLE_PROBLEM,
+        r#"{"vertices":[[5,5],[15,5],[5,15]]}"#,
+        Expected::Score(400),
+    ),
+    (
+        "triangle with a stretched vertex",
+        TRIANGLE_PROBLEM,
+        r#"{"vertices":[[0,0],[11,0],[0,10]]}"#,
+        Expected::BrokenEdges(&[problem::Edge(0, 1), problem::Edge(1, 2)]),
+    ),
+    (
+        "triangle missing a vertex",
+        TRIANGLE_PROBLEM,
+        r#"{"vertices":[[0,0],[10,0]]}"#,
+        Expected::VerticeCountMismatch,
+    ),
+    (
+        "chain stretched within epsilon",
+        CHAIN_PROBLEM,
+        r#"{"vertices":[[0,0],[10,0],[10,10]]}"#,
+        Expected::Score(500),
+    ),
+    (
+        "chain with one edge over epsilon",
+        CHAIN_PROBLEM,
+        r#"{"vertices":[[0,0],[10,0],[10,11]]}"#,
+        Expected::BrokenEdges(&[problem::Edge(1, 2)]),
+    ),
+    (
+        "globalist: one edge over epsilon, total within budget",
+        CHAIN_PROBLEM,
+        r#"{"vertices":[[0,0],[10,0],[10,11]],"bonuses":[{"bonus":"GLOBALIST","problem":7}]}"#,
+        Expected::Score(462),
+    ),
+    (
+        "globalist: total over budget",
+        CHAIN_PROBLEM,
+        r#"{"vertices":[[0,0],[10,0],[10,12]],"bonuses":[{"bonus":"GLOBALIST","problem":7}]}"#,
+        Expected::BrokenEdges(&[]),
+    ),
+    (
+        "superflex: one edge over epsilon",
+        CHAIN_PROBLEM,
+        r#"{"vertices":[[0,0],[10,0],[10,11]],"bonuses":[{"bonus":"SUPERFLEX","problem":7}]}"#,
+        Expected::Score(462),
+    ),
+    (
+        "superflex: two edges over epsilon",
+        CHAIN_PROBLEM,
+        r#"{"vertices":[[0,0],[12,0],[12,12]],"bonuses":[{"bonus":"SUPERFLEX","problem":7}]}"#,
+        Expected::BrokenEdges(&[problem::Edge(1, 2)]),
+    ),
+    (
+        "one vertex out of the hole",
+        CHAIN_PROBLEM,
+        r#"{"vertices":[[12,5],[22,5],[16,13]]}"#,
+        Expected::NotFitHole(&[problem::Edge(0, 1), problem::Edge(1, 2)]),
+    ),
+    (
+        "wallhack: one vertex out of the hole",
+        CHAIN_PROBLEM,
+        r#"{"vertices":[[12,5],[22,5],[16,13]],"bonuses":[{"bonus":"WALLHACK","problem":7}]}"#,
+        Expected::Score(568),
+    ),
+    (
+        "wallhack: two vertices out of the hole",
+        CHAIN_PROBLEM,
+        r#"{"vertices":[[12,5],[22,5],[22,15]],"bonuses":[{"bonus":"WALLHACK","problem":7}]}"#,
+        // the first edge out of the hole takes the only allowed outer vertex
+        Expected::NotFitHole(&[problem::Edge(1, 2)]),
+    ),
+    (
+        "break a leg",
+        CHAIN_PROBLEM,
+        r#"{"vertices":[[0,0],[10,0],[10,10]],"bonuses":[{"bonus":"BREAK_A_LEG","problem":7,"edge":[0,1]}]}"#,
+        Expected::UnsupportedBonus,
+    ),
+];
+
+#[test]
+fn golden_scores() {
+    for (name, problem_json, pose_json, expected) in GOLDEN {
+        let problem: problem::Problem = serde_json::from_str(problem_json).unwrap();
+        let pose: problem::Pose = serde_json::from_str(pose_json).unwrap();
+        let result = problem.score_pose(&pose);
+        let matches = match (expected, &result) {
+            (Expected::Score(expected), Ok(score)) =>
+                score == expected,
+            (Expected::BrokenEdges(expected), Err(problem::PoseValidationError::BrokenEdgesFound { broken_edges, .. })) =>
+                broken_edges == expected,
+            (Expected::NotFitHole(expected), Err(problem::PoseValidationError::EdgesNotFitHole(edges))) =>
+                edges == expected,
+            (Expected::UnsupportedBonus, Err(problem::PoseValidationError::UnsupportedBonus(..))) =>
+                true,
+            (Expected::VerticeCountMismatch, Err(problem::PoseValidationError::VerticeCountMismatch)) =>
+                true,
+            _ =>
+                false,
+        };
+        assert!(matches, "{}: expected {:?}, got {:?}", name, expected, result);
+    }
+}
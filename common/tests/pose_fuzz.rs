@@ -0,0 +1,130 @@
+use rand::{
+    Rng,
+    SeedableRng,
+    rngs::StdRng,
+};
+
+use common::{
+    problem::{
+        self,
+        InvalidEdge,
+    },
+};
+
+const PROBLEMS_JSON: &[&str] = &[
+    // square hole, square figure with a diagonal
+    r#"{"bonuses":[],"hole":[[0,0],[20,0],[20,20],[0,20]],"epsilon":50000,"figure":{"edges":[[0,1],[1,2],[2,3],[3,0],[0,2]],"vertices":[[2,2],[12,2],[12,12],[2,12]]}}"#,
+    // concave hole, chain figure with leaf vertices
+    r#"{"bonuses":[],"hole":[[0,0],[30,0],[30,30],[20,30],[20,10],[10,10],[10,30],[0,30]],"epsilon":10000,"figure":{"edges":[[0,1],[1,2],[2,3],[1,4]],"vertices":[[2,2],[8,2],[14,2],[20,2],[8,8]]}}"#,
+    // rhombus hole from the first problems
+    r#"{"bonuses":[],"hole":[[20,0],[40,20],[20,40],[0,20]],"epsilon":2494,"figure":{"edges":[[0,1],[0,2],[1,3],[2,3]],"vertices":[[15,21],[34,0],[0,45],[19,24]]}}"#,
+];
+
+const POSES_PER_PROBLEM: usize = 400;
+
+fn random_bonus(rng: &mut StdRng, edges: &[problem::Edge]) -> problem::PoseBonus {
+    let problem = problem::ProblemId(rng.gen_range(1 ..= 100));
+    match rng.gen_range(0 .. 4) {
+        0 =>
+            problem::PoseBonus::Globalist { problem, },
+        1 =>
+            problem::PoseBonus::Wallhack { problem, },
+        2 =>
+            problem::PoseBonus::Superflex { problem, },
+        _ =>
+            problem::PoseBonus::BreakALeg { problem, edge: edges[rng.gen_range(0 .. edges.len())], },
+    }
+}
+
+/// Either the figure slightly shaken (so a part of poses is valid) or vertices scattered over the hole bounding box.
+fn random_vertices(rng: &mut StdRng, problem: &problem::Problem) -> Vec<problem::Point> {
+    let max_x = problem.hole.iter().map(|point| point.0).max().unwrap();
+    let max_y = problem.hole.iter().map(|point| point.1).max().unwrap();
+    if rng.gen_range(0.0 .. 1.0) < 0.7 {
+        let shift = problem::Point(rng.gen_range(-2 ..= 8), rng.gen_range(-2 ..= 8));
+        problem.figure.vertices.iter()
+            .map(|vertex| {
+                let jitter = if rng.gen_range(0.0 .. 1.0) < 0.3 { rng.gen_range(-2 ..= 2) } else { 0 };
+                problem::Point(vertex.0 + shift.0 + jitter, vertex.1 + shift.1 - jitter)
+            })
+            .collect()
+    } else {
+        (0 .. problem.figure.vertices.len())
+            .map(|_| problem::Point(rng.gen_range(-2 ..= max_x + 2), rng.gen_range(-2 ..= max_y + 2)))
+            .collect()
+    }
+}
+
+fn over_epsilon_edges(problem: &problem::Problem, vertices: &[problem::Point]) -> usize {
+    problem.figure.edges.iter()
+        .filter(|edge| {
+            let d_before = problem::distance(&problem.figure.vertices[edge.0], &problem.figure.vertices[edge.1]);
+            let d_after = problem::distance(&vertices[edge.0], &vertices[edge.1]);
+            problem::stretch_ratio(d_before, d_after) > problem.epsilon_fraction()
+        })
+        .count()
+}
+
+#[test]
+fn pose_legality_fuzz() {
+    let mut rng = StdRng::seed_from_u64(1186);
+    let mut valid_count = 0;
+    for problem_json in PROBLEMS_JSON {
+        let problem: problem::Problem = serde_json::from_str(problem_json).unwrap();
+        let geo_hole = problem.hole_polygon_f64();
+        let edges_count = problem.figure.edges.len();
+
+        for _ in 0 .. POSES_PER_PROBLEM {
+            let vertices = random_vertices(&mut rng, &problem);
+            let bonuses: Vec<_> = (0 .. rng.gen_range(0 ..= 2))
+                .map(|_| random_bonus(&mut rng, &problem.figure.edges))
+                .collect();
+            let bonus = bonuses.first().cloned();
+            let case = format!("vertices {:?} with bonuses {:?} in {}", vertices, bonuses, problem_json);
+
+            let result = problem.score_vertices(&vertices, bonus);
+            // only the first pose bonus is applied
+            let pose = problem::Pose { vertices: vertices.clone(), bonuses: if bonuses.is_empty() { None } else { Some(bonuses.clone()) }, };
+            assert_eq!(problem.score_pose(&pose), result, "{}", case);
+
+            let outside_count = vertices.iter().filter(|&&vertex| geo_hole.is_point_outside(vertex)).count();
+            match &result {
+                Ok(dislikes) => {
+                    valid_count += 1;
+                    assert_eq!(problem.score_vertices_check_count(&vertices, bonus), Ok(()), "{}", case);
+                    assert!(problem.score_vertices_check_stretching(&vertices, bonus).is_ok(), "{}", case);
+                    assert_eq!(problem.score_vertices_check_hole(&vertices, bonus), Ok(()), "{}", case);
+                    let assigned: i64 = problem.dislikes_assignment(&vertices).iter().map(|&(_, sq_dist)| sq_dist).sum();
+                    assert_eq!(*dislikes, assigned, "{}", case);
+                },
+                Err(problem::PoseValidationError::BrokenEdgesFound { broken_edges, .. }) => {
+                    assert!(broken_edges.len() <= edges_count, "{}", case);
+                    if let Some(problem::PoseBonus::Superflex { .. }) = bonus {
+                        assert_eq!(broken_edges.len(), over_epsilon_edges(&problem, &vertices) - 1, "{}", case);
+                    }
+                },
+                Err(problem::PoseValidationError::EdgesNotFitHole(edges_out_of_hole)) => {
+                    assert!(edges_out_of_hole.len() <= edges_count, "{}", case);
+                    assert!(problem.score_vertices_check_stretching(&vertices, bonus).is_ok(), "{}", case);
+                },
+                Err(problem::PoseValidationError::UnsupportedBonus(unsupported)) =>
+                    assert!(matches!(unsupported, problem::PoseBonus::BreakALeg { .. }), "{}", case),
+                Err(problem::PoseValidationError::VerticeCountMismatch) =>
+                    panic!("vertices count mismatch for {}", case),
+            }
+
+            // every figure vertex belongs to some edge, so the hole check sees all of them
+            if problem.score_vertices_check_hole(&vertices, bonus).is_ok() {
+                let allowed_outside = match bonus {
+                    Some(problem::PoseBonus::Wallhack { .. }) =>
+                        problem::WALLHACK_ALLOWED_OUTSIDE,
+                    _ =>
+                        0,
+                };
+                assert!(outside_count <= allowed_outside, "{} vertices outside for {}", outside_count, case);
+            }
+        }
+    }
+    // poses are not all trivially rejected
+    assert!(valid_count > 0);
+}
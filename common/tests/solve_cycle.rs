@@ -0,0 +1,61 @@
+use common::{
+    problem,
+    solver,
+};
+
+const PROBLEM_JSON: &str = r#"{"bonuses":[],"hole":[[0,0],[20,0],[20,20],[0,20]],"epsilon":50000,"figure":{"edges":[[0,1],[1,2],[2,3],[3,0],[0,2]],"vertices":[[2,2],[12,2],[12,12],[2,12]]}}"#;
+
+#[test]
+fn solve_write_reload_score() {
+    let problem: problem::Problem = serde_json::from_str(PROBLEM_JSON).unwrap();
+    let mut solver = solver::simulated_annealing::SimulatedAnnealingSolver::new(
+        solver::Solver::new(&problem, None).unwrap(),
+        solver::simulated_annealing::Params {
+            max_temp: 100.0,
+            cooling_step_temp: 10.0,
+            minimum_temp: 2.0,
+            below_floor: solver::simulated_annealing::FloorBehavior::Stop,
+            edge_bias: solver::simulated_annealing::EdgeBias::ProbSkip(0.5),
+            frozen_swap_prob: 0.15,
+            frozen_swap_bias: 0.0,
+            vertex_swap_prob: 0.0,
+            reachable_moves: false,
+            wallhack_allowed_outside: problem::WALLHACK_ALLOWED_OUTSIDE,
+            coarsen_factor: 1,
+            min_move_radius: 1,
+            max_move_radius: 1,
+            dislikes_hint_weight: 0.0,
+            iterations_per_cooling_step: 256,
+            operating_mode: solver::simulated_annealing::OperatingMode::ScoreMaximizer,
+        },
+    ).unwrap();
+
+    let mut best_solution = None;
+    loop {
+        match solver.step() {
+            Ok(()) =>
+                (),
+            Err(solver::simulated_annealing::StepError::TempTooLow) =>
+                break,
+            Err(error) =>
+                panic!("unexpected step error: {:?}", error),
+        }
+        if let solver::simulated_annealing::Fitness::FigureScored { score, } = solver.fitness() {
+            if best_solution.as_ref().map_or(true, |&(_, best_score)| score < best_score) {
+                best_solution = Some((problem::Pose { vertices: solver.vertices().to_vec(), bonuses: None, }, score));
+            }
+        }
+    }
+    // annealing budget is tiny, so fall back to the original figure which fits the hole as is
+    let (pose, score) = best_solution
+        .unwrap_or_else(|| (problem.export_pose(), problem.score_pose(&problem.export_pose()).unwrap()));
+    assert_eq!(problem.score_pose(&pose).unwrap(), score);
+
+    let temp_directory = tempfile::tempdir().unwrap();
+    let pose_file = temp_directory.path().join("solve-cycle.pose");
+    pose.write_to_file(&pose_file).unwrap();
+    let reloaded = problem::Pose::from_file(&pose_file).unwrap();
+
+    assert_eq!(reloaded, pose);
+    assert_eq!(problem.score_pose(&reloaded).unwrap(), score);
+}
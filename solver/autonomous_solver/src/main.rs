@@ -1,11 +1,13 @@
 use std::{
     fs,
     io,
+    cmp,
     time,
     thread,
     sync::{
         mpsc,
     },
+    collections::HashMap,
     path::{
         PathBuf,
         Component,
@@ -21,6 +23,8 @@ use rand::prelude::SliceRandom;
 use common::{
     problem,
     solver,
+    progress,
+    submission,
 };
 
 #[derive(Clone, StructOpt, Debug)]
@@ -32,6 +36,22 @@ pub struct CliArgs {
     #[structopt(long = "poses-directory", default_value = "./poses")]
     pub poses_directory: PathBuf,
 
+    /// submissions log file, one json line per submitted pose
+    #[structopt(long = "submissions-log", default_value = "./submissions.jsonl")]
+    pub submissions_log: PathBuf,
+    /// best scores index, kept between restarts to run tasks stuck at their best last
+    #[structopt(long = "best-index", default_value = "./best_index.json")]
+    pub best_index: PathBuf,
+    /// runs without improvement after which a task is considered settled and goes last (0 disables)
+    #[structopt(long = "max-stale-runs", default_value = "3")]
+    pub max_stale_runs: usize,
+    /// pass summaries log file, one json line per pass over the problems directory
+    #[structopt(long = "pass-log", default_value = "./passes.jsonl")]
+    pub pass_log: PathBuf,
+    /// stop after a pass over the problems directory which has not improved any task
+    #[structopt(long = "until-stable")]
+    pub until_stable: bool,
+
     /// api token for submit authorization
     #[structopt(long = "api-token", default_value = "29a3adf2-b0d3-4166-8891-9c990df11546")]
     pub api_token: String,
@@ -52,15 +72,42 @@ pub struct CliArgs {
     /// iterations count per one cooling step
     #[structopt(long = "iterations-per-cooling-step", default_value = "32768")]
     pub iterations_per_cooling_step: usize,
-    /// addition probability of valid edge mutation
+    /// addition probability of valid edge mutation, 1.0 and above means that valid edges are always mutated
     #[structopt(long = "valid-edge-accept-prob", default_value = "0.5")]
     pub valid_edge_accept_prob: f64,
+    /// mutate only currently broken edges (overrides valid edge accept probability)
+    #[structopt(long = "focus-broken-edges")]
+    pub focus_broken_edges: bool,
     /// frozen edges swap probability
     #[structopt(long = "frozen-swap-prob", default_value = "0.15")]
     pub frozen_swap_prob: f64,
+    /// bias frozen edges swap probability towards 1.0 by frozen vertices ratio [0.0 - 1.0]
+    #[structopt(long = "frozen-swap-bias", default_value = "0.0")]
+    pub frozen_swap_bias: f64,
+    /// sample vertex moves only from reachable in-hole cells instead of rejecting moves out of the hole
+    #[structopt(long = "reachable-moves")]
+    pub reachable_moves: bool,
     /// cooling step base temperature
     #[structopt(long = "cooling-step-temp", default_value = "1.0")]
     pub cooling_step_temp: f64,
+    /// maximum rounds of wiggle to zero polishing before a pose is written
+    #[structopt(long = "wiggle-max-iters", default_value = "16")]
+    pub wiggle_max_iters: usize,
+
+    /// how many dislikes one unlocked bonus is worth when choosing the best solution
+    #[structopt(long = "unlocked-bonus-weight", default_value = "0.0")]
+    pub unlocked_bonus_weight: f64,
+    /// comma separated dislikes thresholds logged once a task score reaches them
+    #[structopt(long = "dislikes-ladder", default_value = "100,50,10,0", use_delimiter = true)]
+    pub dislikes_ladder: Vec<i64>,
+    /// append a json line per cooling step and per new best solution of every task to this file for external monitoring
+    #[structopt(long = "progress-jsonl")]
+    pub progress_jsonl: Option<PathBuf>,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct SolutionWeights {
+    pub unlocked_bonus_weight: f64,
 }
 
 
@@ -73,7 +120,13 @@ pub enum Error {
     LoadPoseInvalidContent { pose_file: PathBuf, error: problem::PoseValidationError, },
     SolverCreate(solver::CreateError),
     SimulatedAnnealingSolverCreate(solver::simulated_annealing::CreateError),
+    SolverConfigLoad(solver::config::LoadError),
     PoseExport(problem::WriteFileError),
+    SubmissionLog(submission::AppendError),
+    BestIndexLoad(progress::LoadError),
+    BestIndexSave(progress::SaveError),
+    PassLogAppend(progress::AppendError),
+    ProgressStream(progress::StreamError),
     PoseSerialize(serde_json::Error),
     WorkerSpawn(io::Error),
     WebClientBuilder(reqwest::Error),
@@ -87,25 +140,52 @@ fn main() -> Result<(), Error> {
     let cli_args = CliArgs::from_args();
     log::info!("program starts as: {:?}", cli_args);
 
+    let mut best_index = progress::BestIndex::load(&cli_args.best_index)
+        .map_err(Error::BestIndexLoad)?;
+    // milestones reached before the restart are not reported again
+    let mut ladders: HashMap<String, progress::DislikesLadder> = best_index.tasks.iter()
+        .map(|(task_id, task_progress)| {
+            let ladder = progress::DislikesLadder::new(&cli_args.dislikes_ladder)
+                .with_score(task_progress.best_score);
+            (task_id.clone(), ladder)
+        })
+        .collect();
+    let mut pass = 0;
     loop {
+        pass += 1;
         let mut available_problems = sync_problems_directory(&cli_args)?;
         available_problems.problems.shuffle(&mut rand::thread_rng());
+        // tasks are popped from the end, so settled ones go first to be solved last
+        available_problems.problems.sort_by_key(|problem| !best_index.is_settled(&problem.task_id, cli_args.max_stale_runs));
 
         gather_unlocked_bonuses(&mut available_problems.problems)?;
 
         let (slaves_tx, slaves_rx) = mpsc::channel();
         let mut current_workers_count = 0;
         let mut tasks_done = 0;
+        let mut tasks_improved = 0;
 
         loop {
             if current_workers_count == 0 && available_problems.problems.is_empty() {
                 break;
             }
             if current_workers_count >= cli_args.worker_slaves_count || available_problems.problems.is_empty() {
-                let task_id = slaves_rx.recv().unwrap()?;
+                let (task_id, maybe_score): (String, Option<i64>) = slaves_rx.recv().unwrap()?;
                 current_workers_count -= 1;
                 tasks_done += 1;
                 log::info!("slave done with task = {}; current_workers_count = {}, tasks_done = {}", task_id, current_workers_count, tasks_done);
+                if let Some(score) = maybe_score {
+                    if best_index.record(&task_id, score) {
+                        tasks_improved += 1;
+                    }
+                    let ladder = ladders.entry(task_id.clone())
+                        .or_insert_with(|| progress::DislikesLadder::new(&cli_args.dislikes_ladder));
+                    if let Some(threshold) = ladder.observe(score) {
+                        log::info!("MILESTONE: task {} reached {} dislikes (threshold {})", task_id, score, threshold);
+                    }
+                    best_index.save(&cli_args.best_index)
+                        .map_err(Error::BestIndexSave)?;
+                }
                 continue;
             }
 
@@ -119,7 +199,25 @@ fn main() -> Result<(), Error> {
             current_workers_count += 1;
         }
 
-        log::info!("directory processing finished, {} tasks done", tasks_done);
+        let summary = progress::PassSummary {
+            pass,
+            tasks_done,
+            tasks_improved,
+            total_dislikes: best_index.total_dislikes(),
+        };
+        log::info!(
+            "directory pass {} finished: {} tasks done, {} improved, total dislikes {}",
+            summary.pass,
+            summary.tasks_done,
+            summary.tasks_improved,
+            summary.total_dislikes,
+        );
+        progress::append_pass_summary(&cli_args.pass_log, &summary)
+            .map_err(Error::PassLogAppend)?;
+        if cli_args.until_stable && summary.is_stable() {
+            log::info!("no task improved during pass {}, stopping", summary.pass);
+            return Ok(());
+        }
     }
 }
 
@@ -129,17 +227,19 @@ struct ProblemDesc {
     pose_file: PathBuf,
     task_id: String,
     unlocked_bonuses: Vec<(problem::ProblemBonusType, problem::ProblemId)>,
+    /// pose files of other tasks with the same figure signature
+    seed_pose_files: Vec<PathBuf>,
 }
 
 struct AvailableProblems {
     problems: Vec<ProblemDesc>,
 }
 
-fn slave_run(slaves_tx: mpsc::Sender<Result<String, Error>>, problem: ProblemDesc, cli_args: CliArgs) {
+fn slave_run(slaves_tx: mpsc::Sender<Result<(String, Option<i64>), Error>>, problem: ProblemDesc, cli_args: CliArgs) {
     slaves_tx.send(
         match slave_run_task(&problem, &cli_args) {
-            Ok(()) =>
-                Ok(problem.task_id),
+            Ok(maybe_score) =>
+                Ok((problem.task_id, maybe_score)),
             Err(error) => {
                 log::error!("task {} raised error: {:?}", problem.task_id, error);
                 Err(error)
@@ -148,17 +248,29 @@ fn slave_run(slaves_tx: mpsc::Sender<Result<String, Error>>, problem: ProblemDes
     ).ok();
 }
 
-fn slave_run_task(problem_desc: &ProblemDesc, cli_args: &CliArgs) -> Result<(), Error> {
+/// Solves the task and returns the score of the written pose, if any.
+fn slave_run_task(problem_desc: &ProblemDesc, cli_args: &CliArgs) -> Result<Option<i64>, Error> {
 
-    let problem = problem::Problem::from_file(&problem_desc.problem_file)
-        .map_err(Error::ProblemLoad)?;
+    // shared by all solvers of the task instead of cloning the problem for each of them
+    let shared_problem = solver::SharedProblem::new(
+        problem::Problem::from_file(&problem_desc.problem_file)
+            .map_err(Error::ProblemLoad)?,
+    );
+    let problem = &shared_problem.problem;
+    if !problem.validate_structure() {
+        log::warn!("task {} has a self touching hole, containment checks near pinch points may be off", problem_desc.task_id);
+    }
+    if !problem.figure_might_fit() {
+        log::info!("skipping task {} because figure cannot fit the hole", problem_desc.task_id);
+        return Ok(None);
+    }
 
     let mut unlocked_bonuses_here = Vec::new();
     let maybe_pose_score = match problem::Pose::from_file(&problem_desc.pose_file) {
         Ok(pose) => {
             if let Some(ref bonuses) = problem.bonuses {
                 for bonus in bonuses {
-                    if pose.vertices.iter().find(|v| v == &&bonus.position).is_some() {
+                    if bonus.is_claimed_by(&pose.vertices) {
                         unlocked_bonuses_here.push(bonus.problem);
                         log::debug!("task {} has unlocked bonus: {:?}", problem_desc.task_id, bonus);
                     }
@@ -183,6 +295,28 @@ fn slave_run_task(problem_desc: &ProblemDesc, cli_args: &CliArgs) -> Result<(),
 
     let mut best_solution = maybe_pose_score;
 
+    let seed = seed_solution(problem, problem_desc);
+    let weights = SolutionWeights {
+        unlocked_bonus_weight: cli_args.unlocked_bonus_weight,
+    };
+    if let Some(candidate) = &seed {
+        log::info!("task {} has a seed pose from a task with the same figure, score: {}", problem_desc.task_id, candidate.1);
+        if is_better_solution(problem, candidate, &best_solution, &weights) {
+            best_solution = Some(candidate.clone());
+        }
+    }
+    let seed_pose = seed.as_ref().map(|seed| &seed.0);
+
+    // rigid figures have only a few discrete placements, the best of them is a cheap candidate
+    if let Ok(placements) = solver::rigid::enumerate_placements(problem) {
+        if let Some(candidate) = placements.first() {
+            log::info!("task {} has a rigid figure, best of {} placements scores {}", problem_desc.task_id, placements.len(), candidate.1);
+            if is_better_solution(problem, candidate, &best_solution, &weights) {
+                best_solution = Some(candidate.clone());
+            }
+        }
+    }
+
     let allowed_unlocked_bonuses: Vec<_> = problem_desc
         .unlocked_bonuses
         .iter()
@@ -196,63 +330,82 @@ fn slave_run_task(problem_desc: &ProblemDesc, cli_args: &CliArgs) -> Result<(),
         })
         .collect();
 
+    // bonuses on hole vertices are claimed by any zero score pose, so they do not prevent zero hunting
+    let lucky_bonuses = problem.bonus_on_hole_vertices();
+    if !lucky_bonuses.is_empty() {
+        log::info!("task {} has bonuses on hole vertices: {:?}", problem_desc.task_id, lucky_bonuses);
+    }
+    let zero_keeps_unlocked_bonuses = unlocked_bonuses_here.iter()
+        .all(|unlocked| lucky_bonuses.iter().any(|bonus| bonus.problem == *unlocked));
+    // plausibility check does not know about bonuses which could stretch the figure further
+    let zero_plausible = problem.zero_dislikes_plausible() || !allowed_unlocked_bonuses.is_empty();
+    if !zero_plausible {
+        log::info!("task {}: zero dislikes are not reachable with this figure, skipping zero hunting", problem_desc.task_id);
+    }
+
     // try gather zero score, maybe we are lucky
     let mut temporary_best_solution = None;
-    if unlocked_bonuses_here.is_empty() {
+    if zero_keeps_unlocked_bonuses && zero_plausible {
         if allowed_unlocked_bonuses.is_empty() {
             slave_run_task_with(
                 problem_desc,
-                &problem,
+                &shared_problem,
                 &mut temporary_best_solution,
                 cli_args,
                 None,
+                seed_pose,
                 solver::simulated_annealing::OperatingMode::ZeroHunter,
             )?;
         } else {
             for &&unlocked_bonus in &allowed_unlocked_bonuses {
                 slave_run_task_with(
                     problem_desc,
-                    &problem,
+                    &shared_problem,
                     &mut temporary_best_solution,
                     cli_args,
                     Some(unlocked_bonus),
+                    seed_pose,
                     solver::simulated_annealing::OperatingMode::ZeroHunter,
                 )?;
             }
         }
     }
-    if temporary_best_solution.is_some() {
+    if let Some(candidate) = temporary_best_solution {
         // we are lucky
-        best_solution = temporary_best_solution;
+        if is_better_solution(problem, &candidate, &best_solution, &weights) {
+            best_solution = Some(candidate);
+        }
     } else {
         // try unlock all bonuses, maybe we are lucky
         let mut temporary_best_solution = None;
         if allowed_unlocked_bonuses.is_empty() {
             slave_run_task_with(
                 problem_desc,
-                &problem,
+                &shared_problem,
                 &mut temporary_best_solution,
                 cli_args,
                 None,
+                seed_pose,
                 solver::simulated_annealing::OperatingMode::BonusHunter,
             )?;
         } else {
             for &&unlocked_bonus in &allowed_unlocked_bonuses {
                 slave_run_task_with(
                     problem_desc,
-                    &problem,
+                    &shared_problem,
                     &mut temporary_best_solution,
                     cli_args,
                     Some(unlocked_bonus),
+                    seed_pose,
                     solver::simulated_annealing::OperatingMode::BonusHunter,
                 )?;
             }
-        };
-        match (&temporary_best_solution, &best_solution) {
+        }
+        match (temporary_best_solution, &best_solution) {
             // (&Some((_, score)), &None) {  // TODO as well as below
-            (&Some((_, score)), &Some((_, best_score))) if score < best_score => {
+            (Some(candidate), &Some(..)) if is_better_solution(problem, &candidate, &best_solution, &weights) => {
                 // we are lucky
-                best_solution = temporary_best_solution;
+                best_solution = Some(candidate);
             },
             _ => {
                 // not this time, proceed with regular stuff
@@ -265,35 +418,47 @@ fn slave_run_task(problem_desc: &ProblemDesc, cli_args: &CliArgs) -> Result<(),
                     }
                 } else {
                     log::info!("skipping task {} because of many bonuses already unlocked", problem_desc.task_id);
-                    return Ok(());
+                    return Ok(None);
                 };
 
                 if allowed_unlocked_bonuses.is_empty() {
                     slave_run_task_with(
                         problem_desc,
-                        &problem,
+                        &shared_problem,
                         &mut best_solution,
                         cli_args,
                         None,
+                        seed_pose,
                         operating_mode,
                     )?;
                 } else {
                     for &&unlocked_bonus in &allowed_unlocked_bonuses {
                         slave_run_task_with(
                             problem_desc,
-                            &problem,
+                            &shared_problem,
                             &mut best_solution,
                             cli_args,
                             Some(unlocked_bonus),
+                            seed_pose,
                             operating_mode,
                         )?;
                     }
-                };
+                }
             },
         }
     }
 
     if let Some((pose, score)) = best_solution {
+        let mut pose = solver::polish::wiggle_to_zero(problem, &pose, cli_args.wiggle_max_iters);
+        pose.canonicalize_bonuses();
+        let score = match problem.score_pose(&pose) {
+            Ok(score) =>
+                score,
+            Err(error) => {
+                log::error!("pose with solver score {} for task {} is rejected by scoring: {:?}, skipping it", score, problem_desc.task_id, error);
+                return Ok(None);
+            },
+        };
         log::info!("pose with score {} for task {} has been written to {:?}", score, problem_desc.task_id, problem_desc.problem_file);
         pose.write_to_file(&problem_desc.pose_file)
             .map_err(Error::PoseExport)?;
@@ -322,21 +487,27 @@ fn slave_run_task(problem_desc: &ProblemDesc, cli_args: &CliArgs) -> Result<(),
             .body(body)
             .send().map_err(Error::WebClientSend)?;
         log::info!("solution submitted for task = {}, result = {:?}", problem_desc.task_id, send_result);
+
+        submission::append(&cli_args.submissions_log, &submission::Submission::new(&problem_desc.task_id, &pose, score))
+            .map_err(Error::SubmissionLog)?;
+        return Ok(Some(score));
     }
 
-    Ok(())
+    Ok(None)
 }
 
 fn slave_run_task_with(
     problem_desc: &ProblemDesc,
-    problem: &problem::Problem,
+    shared_problem: &solver::SharedProblem,
     best_solution: &mut Option<(problem::Pose, i64)>,
     cli_args: &CliArgs,
     use_bonus: Option<(problem::ProblemBonusType, problem::ProblemId)>,
+    seed_pose: Option<&problem::Pose>,
     operating_mode: solver::simulated_annealing::OperatingMode,
 )
     -> Result<(), Error>
 {
+    let problem = &shared_problem.problem;
     log::info!(
         "slave started task {}, current pose score: {:?}, use_bonus: {:?}, operating_mode = {:?}",
         problem_desc.task_id,
@@ -345,45 +516,77 @@ fn slave_run_task_with(
         operating_mode,
     );
 
-    let maybe_solver = solver::simulated_annealing::SimulatedAnnealingSolver::new(
-        solver::Solver::with_bonus(problem, best_solution.as_ref().map(|best| best.0.clone()), use_bonus.map(|ub| ub.0))
-            .map_err(Error::SolverCreate)?,
+    let params = solver::config::load_params(
+        &cli_args.problems_directory,
+        &problem_desc.task_id,
         solver::simulated_annealing::Params {
             max_temp: 100.0,
             cooling_step_temp: cli_args.cooling_step_temp,
             minimum_temp: 2.0,
-            valid_edge_accept_prob: cli_args.valid_edge_accept_prob,
+            below_floor: solver::simulated_annealing::FloorBehavior::Stop,
+            edge_bias: if cli_args.focus_broken_edges {
+                solver::simulated_annealing::EdgeBias::FocusBroken
+            } else {
+                solver::simulated_annealing::EdgeBias::from_valid_edge_accept_prob(cli_args.valid_edge_accept_prob)
+            },
             frozen_swap_prob: cli_args.frozen_swap_prob,
+            frozen_swap_bias: cli_args.frozen_swap_bias,
+            vertex_swap_prob: 0.0,
+            reachable_moves: cli_args.reachable_moves,
+            wallhack_allowed_outside: problem::WALLHACK_ALLOWED_OUTSIDE,
+            coarsen_factor: 1,
+            min_move_radius: 1,
+            max_move_radius: 1,
+            dislikes_hint_weight: 0.0,
             iterations_per_cooling_step: cli_args.iterations_per_cooling_step,
             operating_mode,
         },
+    ).map_err(Error::SolverConfigLoad)?;
+
+    let maybe_solver = solver::simulated_annealing::SimulatedAnnealingSolver::new(
+        solver::Solver::with_shared_problem(shared_problem, best_solution.as_ref().map(|best| best.0.clone()), use_bonus.map(|ub| ub.0), 0)
+            .map_err(Error::SolverCreate)?,
+        params,
     );
 
     let mut solver = match maybe_solver {
         Ok(solver) =>
             solver,
-        Err(solver::simulated_annealing::CreateError::GenerateVertices(
-            solver::simulated_annealing::GenerateVerticesError::ProbablyInfiniteLoopInFrozenIndexInBonusCollector,
-        )) => {
-            log::error!("probably infinite loop in generate vertices for bonus collector for task {}, stopping", problem_desc.task_id);
-            return Ok(());
-        },
-        Err(solver::simulated_annealing::CreateError::GenerateVertices(
-            solver::simulated_annealing::GenerateVerticesError::ProbablyInfiniteLoopInFrozenIndexInBonusHunter,
-        )) => {
-            log::error!("probably infinite loop in generate vertices for bonus hunter for task {}, stopping", problem_desc.task_id);
+        Err(solver::simulated_annealing::CreateError::NoEdgesInFigure) => {
+            log::error!("figure has no edges for task {}, stopping", problem_desc.task_id);
             return Ok(());
         },
         Err(solver::simulated_annealing::CreateError::GenerateVertices(
-            solver::simulated_annealing::GenerateVerticesError::ProbablyInfiniteLoopInFrozenIndexInZeroHunter,
+            solver::simulated_annealing::GenerateVerticesError::MoreFrozenTargetsThanVertices { targets, vertices, },
         )) => {
-            log::error!("probably infinite loop in generate vertices for zero hunter for task {}, stopping", problem_desc.task_id);
+            log::error!("{} frozen targets for {} vertices in generate vertices for task {}, stopping", targets, vertices, problem_desc.task_id);
             return Ok(());
         },
+        Err(error @ solver::simulated_annealing::CreateError::UnsupportedBonus(..)) =>
+            return Err(Error::SimulatedAnnealingSolverCreate(error)),
     };
 
+    // bonus modes freeze vertices on their own, so the seed is only a warm start for plain score maximizing
+    if let (Some(seed_pose), solver::simulated_annealing::OperatingMode::ScoreMaximizer) = (seed_pose, operating_mode) {
+        if let Err(error) = solver.warm_start(&seed_pose.vertices) {
+            log::warn!("failed to warm start task {} from seed pose: {:?}", problem_desc.task_id, error);
+        }
+    }
+
+    let weights = SolutionWeights {
+        unlocked_bonus_weight: cli_args.unlocked_bonus_weight,
+    };
     let solving_start_time = time::Instant::now();
 
+    // every worker appends to the file with its own stream, lines stay whole
+    let mut progress_stream = match &cli_args.progress_jsonl {
+        None =>
+            None,
+        Some(progress_jsonl) =>
+            Some(progress::ProgressStream::open(progress_jsonl).map_err(Error::ProgressStream)?),
+    };
+    let mut cooling_steps = 0;
+
     let mut reheats_count = 0;
     loop {
         if solving_start_time.elapsed().as_secs() > cli_args.worker_solving_timeout_s {
@@ -392,8 +595,27 @@ fn slave_run_task_with(
         }
 
         match solver.step() {
-            Ok(()) =>
-                (),
+            Ok(()) => {
+                log::trace!(
+                    "task {} temp: {}, acceptance ratio: {:.3}",
+                    problem_desc.task_id,
+                    solver.temp(),
+                    solver.acceptance_ratio_last_step(),
+                );
+                cooling_steps += 1;
+                if let Some(progress_stream) = &mut progress_stream {
+                    let fitness = solver.fitness();
+                    let record = progress::ProgressRecord {
+                        task: problem_desc.task_id.clone(),
+                        step: cooling_steps,
+                        temp: solver.temp(),
+                        score: fitness.score(),
+                        energy: fitness.energy(),
+                    };
+                    progress_stream.write(&record)
+                        .map_err(Error::ProgressStream)?;
+                }
+            },
             Err(solver::simulated_annealing::StepError::TempTooLow) if reheats_count < cli_args.max_reheats_count => {
                 // log::debug!(
                 //     "temperature is too low for task {}: performing reheat ({} left)",
@@ -420,49 +642,50 @@ fn slave_run_task_with(
                 break;
             },
             Err(solver::simulated_annealing::StepError::GenerateVertices(
-                solver::simulated_annealing::GenerateVerticesError::ProbablyInfiniteLoopInFrozenIndexInBonusCollector,
+                solver::simulated_annealing::GenerateVerticesError::MoreFrozenTargetsThanVertices { targets, vertices, },
             )) => {
-                log::error!("probably infinite loop in generate vertices for bonus collector for task {}, stopping", problem_desc.task_id);
-                break;
-            },
-            Err(solver::simulated_annealing::StepError::GenerateVertices(
-                solver::simulated_annealing::GenerateVerticesError::ProbablyInfiniteLoopInFrozenIndexInBonusHunter,
-            )) => {
-                log::error!("probably infinite loop in generate vertices for bonus hunter for task {}, stopping", problem_desc.task_id);
-                break;
-            },
-            Err(solver::simulated_annealing::StepError::GenerateVertices(
-                solver::simulated_annealing::GenerateVerticesError::ProbablyInfiniteLoopInFrozenIndexInZeroHunter,
-            )) => {
-                log::error!("probably infinite loop in generate vertices for zero hunter for task {}, stopping", problem_desc.task_id);
+                log::error!("{} frozen targets for {} vertices in generate vertices for task {}, stopping", targets, vertices, problem_desc.task_id);
                 break;
             },
         }
         match solver.fitness() {
-            solver::simulated_annealing::Fitness::FigureScored { score, } =>
-                if best_solution.as_ref().map_or(true, |best| score < best.1) {
-                    let pose = problem::Pose {
-                        vertices: solver.vertices().to_vec(),
-                        bonuses: match use_bonus {
-                            None =>
-                                None,
-                            Some((problem::ProblemBonusType::BreakALeg, _source_problem)) =>
-                                unreachable!(),
-                            Some((problem::ProblemBonusType::Globalist, source_problem)) =>
-                                Some(vec![problem::PoseBonus::Globalist { problem: source_problem, }]),
-                            Some((problem::ProblemBonusType::Wallhack, source_problem)) =>
-                                Some(vec![problem::PoseBonus::Wallhack { problem: source_problem, }]),
-                            Some((problem::ProblemBonusType::Superflex, source_problem)) =>
-                                Some(vec![problem::PoseBonus::Superflex { problem: source_problem, }]),
-                        },
-                    };
-                    log::info!(
+            solver::simulated_annealing::Fitness::FigureScored { score, } => {
+                let pose = problem::Pose {
+                    vertices: solver.vertices().to_vec(),
+                    bonuses: match use_bonus {
+                        None =>
+                            None,
+                        Some((problem::ProblemBonusType::BreakALeg, _source_problem)) =>
+                            unreachable!(),
+                        Some((problem::ProblemBonusType::Globalist, source_problem)) =>
+                            Some(vec![problem::PoseBonus::Globalist { problem: source_problem, }]),
+                        Some((problem::ProblemBonusType::Wallhack, source_problem)) =>
+                            Some(vec![problem::PoseBonus::Wallhack { problem: source_problem, }]),
+                        Some((problem::ProblemBonusType::Superflex, source_problem)) =>
+                            Some(vec![problem::PoseBonus::Superflex { problem: source_problem, }]),
+                    },
+                };
+                let candidate = (pose, score);
+                if is_better_solution(problem, &candidate, best_solution, &weights) {
+                    log::debug!(
                         "SCORE: {} | new best solution found for task {}",
                         score,
                         problem_desc.task_id,
                     );
-                    *best_solution = Some((pose, score));
-                },
+                    if let Some(progress_stream) = &mut progress_stream {
+                        let record = progress::ProgressRecord {
+                            task: problem_desc.task_id.clone(),
+                            step: cooling_steps,
+                            temp: solver.temp(),
+                            score: Some(score),
+                            energy: solver.fitness().energy(),
+                        };
+                        progress_stream.write(&record)
+                            .map_err(Error::ProgressStream)?;
+                    }
+                    *best_solution = Some(candidate);
+                }
+            },
             solver::simulated_annealing::Fitness::FigureCorrupted { .. } |
             solver::simulated_annealing::Fitness::NotFitHole { .. } =>
                 (),
@@ -472,6 +695,42 @@ fn slave_run_task_with(
     Ok(())
 }
 
+/// Orders solutions by dislikes where every bonus unlocked by the pose reduces dislikes by `unlocked_bonus_weight`:
+/// `Ordering::Less` means that `a` is better than `b`.
+fn compare_solutions(
+    problem: &problem::Problem,
+    a: &(problem::Pose, i64),
+    b: &(problem::Pose, i64),
+    weights: &SolutionWeights,
+)
+    -> cmp::Ordering
+{
+    let weighted_score = |solution: &(problem::Pose, i64)| {
+        solution.1 as f64 - unlocked_bonuses_count(problem, &solution.0) as f64 * weights.unlocked_bonus_weight
+    };
+    weighted_score(a)
+        .partial_cmp(&weighted_score(b))
+        .unwrap_or(cmp::Ordering::Equal)
+        .then(a.1.cmp(&b.1))
+}
+
+fn is_better_solution(
+    problem: &problem::Problem,
+    candidate: &(problem::Pose, i64),
+    best_solution: &Option<(problem::Pose, i64)>,
+    weights: &SolutionWeights,
+)
+    -> bool
+{
+    best_solution.as_ref()
+        .map_or(true, |best| compare_solutions(problem, candidate, best, weights) == cmp::Ordering::Less)
+}
+
+fn unlocked_bonuses_count(problem: &problem::Problem, pose: &problem::Pose) -> usize {
+    problem.bonuses.as_ref()
+        .map_or(0, |bonuses| bonuses.iter().filter(|bonus| bonus.is_claimed_by(&pose.vertices)).count())
+}
+
 fn sync_problems_directory(cli_args: &CliArgs) -> Result<AvailableProblems, Error> {
     let mut problems = Vec::new();
 
@@ -494,6 +753,7 @@ fn sync_problems_directory(cli_args: &CliArgs) -> Result<AvailableProblems, Erro
                                 problem_file: problem_path,
                                 pose_file,
                                 unlocked_bonuses: vec![],
+                                seed_pose_files: vec![],
                             });
                         }
                     }
@@ -502,9 +762,82 @@ fn sync_problems_directory(cli_args: &CliArgs) -> Result<AvailableProblems, Erro
         }
     }
 
+    let mut signatures = Vec::with_capacity(problems.len());
+    for problem_desc in &problems {
+        let problem = problem::Problem::from_file(&problem_desc.problem_file)
+            .map_err(Error::ProblemLoad)?;
+        signatures.push(problem.figure.signature());
+    }
+    assign_seed_poses(&mut problems, &signatures);
+
     Ok(AvailableProblems { problems, })
 }
 
+/// Offers pose files of every task to all other tasks with the same figure signature (`signatures` are parallel to `problems`).
+fn assign_seed_poses(problems: &mut [ProblemDesc], signatures: &[problem::FigureSignature]) {
+    for index in 0 .. problems.len() {
+        let seed_pose_files: Vec<_> = problems.iter()
+            .zip(signatures.iter())
+            .enumerate()
+            .filter(|&(other_index, (_, signature))| other_index != index && signature == &signatures[index])
+            .map(|(_, (other, _))| other.pose_file.clone())
+            .collect();
+        if !seed_pose_files.is_empty() {
+            log::debug!("task {} has the same figure as {:?}", problems[index].task_id, seed_pose_files);
+        }
+        problems[index].seed_pose_files = seed_pose_files;
+    }
+}
+
+/// Best valid placement of sibling task poses into the hole: every pose is tried as is and moved to the hole centroid
+/// in all four right angle rotations. Missing or unreadable pose files are skipped.
+fn seed_solution(problem: &problem::Problem, problem_desc: &ProblemDesc) -> Option<(problem::Pose, i64)> {
+    let mut best_seed: Option<(problem::Pose, i64)> = None;
+    for seed_pose_file in &problem_desc.seed_pose_files {
+        let seed_pose = match problem::Pose::from_file(seed_pose_file) {
+            Ok(seed_pose) =>
+                seed_pose,
+            Err(..) =>
+                continue,
+        };
+        for placement in seed_placements(problem, &seed_pose) {
+            if let Ok(score) = problem.score_pose(&placement) {
+                if best_seed.as_ref().map_or(true, |best| score < best.1) {
+                    best_seed = Some((placement, score));
+                }
+            }
+        }
+    }
+    best_seed
+}
+
+fn seed_placements(problem: &problem::Problem, seed_pose: &problem::Pose) -> Vec<problem::Pose> {
+    let centroid = |points: &[problem::Point]| {
+        let count = points.len().max(1) as i64;
+        problem::Point(
+            points.iter().map(|point| point.0).sum::<i64>() / count,
+            points.iter().map(|point| point.1).sum::<i64>() / count,
+        )
+    };
+    let hole_centroid = centroid(&problem.hole);
+
+    let mut placements = vec![problem::Pose { vertices: seed_pose.vertices.clone(), bonuses: None, }];
+    let mut vertices = seed_pose.vertices.clone();
+    for _ in 0 .. 4 {
+        let vertices_centroid = centroid(&vertices);
+        placements.push(problem::Pose {
+            vertices: vertices.iter()
+                .map(|point| problem::Point(point.0 - vertices_centroid.0 + hole_centroid.0, point.1 - vertices_centroid.1 + hole_centroid.1))
+                .collect(),
+            bonuses: None,
+        });
+        vertices = vertices.iter()
+            .map(|point| problem::Point(-point.1, point.0))
+            .collect();
+    }
+    placements
+}
+
 fn gather_unlocked_bonuses(problems: &mut [ProblemDesc]) -> Result<(), Error> {
     for problem_index in 0 .. problems.len() {
         let problem_desc = &problems[problem_index];
@@ -527,7 +860,7 @@ fn gather_unlocked_bonuses(problems: &mut [ProblemDesc]) -> Result<(), Error> {
         };
 
         for bonus in available_bonuses {
-            if pose.vertices.iter().find(|v| v == &&bonus.position).is_some() {
+            if bonus.is_claimed_by(&pose.vertices) {
                 let target_task_id = format!("{}", bonus.problem.0);
                 let source_task_id: usize = problems[problem_index].task_id.parse()
                     .map_err(Error::TaskIdParse)?;
@@ -541,3 +874,78 @@ fn gather_unlocked_bonuses(problems: &mut [ProblemDesc]) -> Result<(), Error> {
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bonus_weight_beats_dislikes() {
+        let problem = problem::Problem {
+            hole: vec![problem::Point(0, 0), problem::Point(10, 0), problem::Point(10, 10), problem::Point(0, 10)],
+            figure: problem::Figure {
+                edges: vec![problem::Edge(0, 1)],
+                vertices: vec![problem::Point(0, 0), problem::Point(5, 0)],
+            },
+            epsilon: 0,
+            bonuses: Some(vec![problem::ProblemBonus {
+                position: problem::Point(5, 5),
+                bonus: problem::ProblemBonusType::Globalist,
+                problem: problem::ProblemId(7),
+            }]),
+        };
+        let bonus_solution = (
+            problem::Pose { vertices: vec![problem::Point(0, 5), problem::Point(5, 5)], bonuses: None, },
+            110,
+        );
+        let plain_solution = (
+            problem::Pose { vertices: vec![problem::Point(0, 0), problem::Point(5, 0)], bonuses: None, },
+            100,
+        );
+
+        let no_weight = SolutionWeights { unlocked_bonus_weight: 0.0, };
+        assert_eq!(compare_solutions(&problem, &bonus_solution, &plain_solution, &no_weight), cmp::Ordering::Greater);
+        assert!(!is_better_solution(&problem, &bonus_solution, &Some(plain_solution.clone()), &no_weight));
+
+        let high_weight = SolutionWeights { unlocked_bonus_weight: 1000.0, };
+        assert_eq!(compare_solutions(&problem, &bonus_solution, &plain_solution, &high_weight), cmp::Ordering::Less);
+        assert!(is_better_solution(&problem, &bonus_solution, &Some(plain_solution), &high_weight));
+        assert!(is_better_solution(&problem, &bonus_solution, &None, &high_weight));
+    }
+
+    #[test]
+    fn seed_offered_for_same_figure() {
+        let problem_desc = |task_id: &str| ProblemDesc {
+            problem_file: PathBuf::from(format!("./tasks/{}.problem", task_id)),
+            pose_file: PathBuf::from(format!("./poses/{}.pose", task_id)),
+            task_id: task_id.to_string(),
+            unlocked_bonuses: vec![],
+            seed_pose_files: vec![],
+        };
+        let figure = |tail: i64| problem::Figure {
+            edges: vec![problem::Edge(0, 1), problem::Edge(1, 2)],
+            vertices: vec![problem::Point(0, 0), problem::Point(5, 0), problem::Point(5, tail)],
+        };
+        let mut problems = vec![problem_desc("1"), problem_desc("2"), problem_desc("3")];
+        let signatures = vec![figure(5).signature(), figure(7).signature(), figure(5).signature()];
+        assign_seed_poses(&mut problems, &signatures);
+        assert_eq!(problems[0].seed_pose_files, vec![PathBuf::from("./poses/3.pose")]);
+        assert!(problems[1].seed_pose_files.is_empty());
+        assert_eq!(problems[2].seed_pose_files, vec![PathBuf::from("./poses/1.pose")]);
+
+        // a sibling pose is moved into the hole of the target task
+        let problem = problem::Problem {
+            hole: vec![problem::Point(100, 100), problem::Point(120, 100), problem::Point(120, 120), problem::Point(100, 120)],
+            figure: figure(5),
+            epsilon: 0,
+            bonuses: None,
+        };
+        let sibling_pose = problem::Pose {
+            vertices: vec![problem::Point(0, 0), problem::Point(5, 0), problem::Point(5, 5)],
+            bonuses: None,
+        };
+        let placements = seed_placements(&problem, &sibling_pose);
+        assert!(placements.iter().any(|placement| problem.score_pose(placement).is_ok()));
+        assert!(problem.score_pose(&placements[0]).is_err());
+    }
+}
@@ -1,3 +1,7 @@
+use std::{
+    time::Instant,
+};
+
 use structopt::{
     StructOpt,
 };
@@ -14,6 +18,10 @@ use common::{
 pub struct CliArgs {
     #[structopt(flatten)]
     pub common: cli::CommonCliArgs,
+
+    /// hole containment backend used for scoring: polygon or quadtree
+    #[structopt(long = "edge-checker", default_value = "polygon")]
+    pub edge_checker: problem::EdgeChecker,
 }
 
 
@@ -36,12 +44,18 @@ fn main() -> Result<(), Error> {
     let pose = problem::Pose::from_file(&cli_args.common.pose_file).ok();
     log::debug!(" ;; pose loaded: {:?}", pose);
 
-    let solver = solver::bruteforce::BruteforceSolver::new(
-        solver::Solver::new(&problem, pose)
-            .map_err(Error::SolverCreate)?,
-        );
+    let mut base_solver = solver::Solver::new(&problem, pose)
+        .map_err(Error::SolverCreate)?;
+    let edge_checker_build_start = Instant::now();
+    base_solver.set_edge_checker(cli_args.edge_checker.build(&problem));
+    log::info!("{:?} edge checker built in {:?}", cli_args.edge_checker, edge_checker_build_start.elapsed());
+
+    let solver = solver::bruteforce::BruteforceSolver::new(base_solver);
 
     let pose = solver.solve().unwrap();
+    if let Some(stats) = solver.edge_checker().quad_tree_stats() {
+        log::info!("quad tree: {} queries, slow path ratio {:.3}", stats.hits_total, stats.slow_ratio());
+    }
     pose.write_to_file(&cli_args.common.pose_file)
         .map_err(Error::PoseExport)?;
     log::info!("pose {:?} has been written to {:?}", pose, cli_args.common.pose_file);
@@ -1,3 +1,9 @@
+use std::{
+    time::Instant,
+    path::PathBuf,
+    collections::HashMap,
+};
+
 use structopt::{
     StructOpt,
 };
@@ -16,6 +22,24 @@ pub struct CliArgs {
     pub common: cli::CommonCliArgs,
     #[structopt(long = "use-bonus")]
     pub use_bonus: Option<String>,
+    /// maximum size of a candidate points set, bigger sets are sampled down (search becomes heuristic)
+    #[structopt(long = "max-candidates", default_value = "65536")]
+    pub max_candidates: usize,
+    /// save search progress into this file (search runs single-threaded then)
+    #[structopt(long = "checkpoint-file")]
+    pub checkpoint_file: Option<PathBuf>,
+    /// continue the search from `--checkpoint-file` if it exists
+    #[structopt(long = "resume")]
+    pub resume: bool,
+    /// pin a figure vertex to a point, e.g. "3:20,40" (may be repeated)
+    #[structopt(long = "fixed", parse(try_from_str = parse_fixed))]
+    pub fixed: Vec<(usize, problem::Point)>,
+    /// try hole points keeping the most edges to placed vertices first instead of the ones closest to the hole centroid
+    #[structopt(long = "most-constrained-first")]
+    pub most_constrained_first: bool,
+    /// hole containment backend used for scoring: polygon or quadtree
+    #[structopt(long = "edge-checker", default_value = "polygon")]
+    pub edge_checker: problem::EdgeChecker,
 }
 
 
@@ -23,7 +47,9 @@ pub struct CliArgs {
 pub enum Error {
     ProblemLoad(problem::FromFileError),
     SolverCreate(solver::CreateError),
+    Checkpoint(solver::bruteforce_hole::CheckpointError),
     PoseExport(problem::WriteFileError),
+    UnknownBonus(String),
 }
 
 fn main() -> Result<(), Error> {
@@ -59,15 +85,40 @@ fn main() -> Result<(), Error> {
         None => {
             pose.bonuses = None;
         }
-        Some(a) => unimplemented!("Unknown bonus type '{}'", a),
+        Some(a) =>
+            return Err(Error::UnknownBonus(a)),
     };
 
-    let solver = solver::bruteforce_hole::BruteforceHoleSolver::new(
-        solver::Solver::new(&problem, Some(pose))
-            .map_err(Error::SolverCreate)?,
-        );
+    let mut solver = solver::Solver::new(&problem, Some(pose))
+        .map_err(Error::SolverCreate)?;
+    let edge_checker_build_start = Instant::now();
+    solver.set_edge_checker(cli_args.edge_checker.build(&problem));
+    log::info!("{:?} edge checker built in {:?}", cli_args.edge_checker, edge_checker_build_start.elapsed());
+    let fixed: HashMap<_, _> = cli_args.fixed.iter().cloned().collect();
+    let candidate_order = if cli_args.most_constrained_first {
+        solver::bruteforce_hole::CandidateOrder::MostConstrained
+    } else {
+        solver::bruteforce_hole::CandidateOrder::HoleCentroid
+    };
+    let bruteforce_solver = match cli_args.checkpoint_file {
+        Some(ref checkpoint_file) if cli_args.resume && checkpoint_file.exists() =>
+            solver::bruteforce_hole::BruteforceHoleSolver::resume(solver, cli_args.max_candidates, checkpoint_file)
+                .map_err(Error::Checkpoint)?,
+        Some(ref checkpoint_file) =>
+            solver::bruteforce_hole::BruteforceHoleSolver::new(solver, cli_args.max_candidates)
+                .with_checkpoint_file(checkpoint_file),
+        None =>
+            solver::bruteforce_hole::BruteforceHoleSolver::new(solver, cli_args.max_candidates),
+    };
+    let bruteforce_solver = bruteforce_solver
+        .with_fixed(fixed)
+        .with_candidate_order(candidate_order);
+    let pose = bruteforce_solver.solve()
+        .map_err(Error::Checkpoint)?;
+    if let Some(stats) = bruteforce_solver.edge_checker().quad_tree_stats() {
+        log::info!("quad tree: {} queries, slow path ratio {:.3}", stats.hits_total, stats.slow_ratio());
+    }
 
-    let pose = solver.solve();
     match pose {
         None => {},
         Some(pose) => {
@@ -79,3 +130,17 @@ fn main() -> Result<(), Error> {
 
     Ok(())
 }
+
+fn parse_fixed(value: &str) -> Result<(usize, problem::Point), String> {
+    let mut parts = value.splitn(2, ':');
+    let vertex_index = parts.next()
+        .and_then(|vertex_index| vertex_index.parse().ok())
+        .ok_or_else(|| format!("expected vertex index in {:?}", value))?;
+    let mut coords = parts.next().unwrap_or("").splitn(2, ',');
+    let mut coord = || coords.next()
+        .and_then(|coord| coord.trim().parse().ok())
+        .ok_or_else(|| format!("expected point coordinates in {:?}", value));
+    let x = coord()?;
+    let y = coord()?;
+    Ok((vertex_index, problem::Point(x, y)))
+}
@@ -1,3 +1,13 @@
+use std::{
+    fs,
+    io,
+    time::Instant,
+    path::{
+        Path,
+        PathBuf,
+    },
+};
+
 use structopt::{
     StructOpt,
 };
@@ -6,6 +16,7 @@ use common::{
     cli,
     problem,
     solver,
+    progress,
 };
 
 #[derive(Clone, StructOpt, Debug)]
@@ -29,15 +40,66 @@ pub struct CliArgs {
     /// iterations count per one cooling step
     #[structopt(long = "iterations-per-cooling-step", default_value = "32768")]
     pub iterations_per_cooling_step: usize,
-    /// addition probability of valid edge mutation
+    /// addition probability of valid edge mutation, 1.0 and above means that valid edges are always mutated
     #[structopt(long = "valid-edge-accept-prob", default_value = "0.5")]
     pub valid_edge_accept_prob: f64,
+    /// mutate only currently broken edges (overrides valid edge accept probability)
+    #[structopt(long = "focus-broken-edges")]
+    pub focus_broken_edges: bool,
     /// frozen edges swap probability
     #[structopt(long = "frozen-swap-prob", default_value = "0.15")]
     pub frozen_swap_prob: f64,
+    /// bias frozen edges swap probability towards 1.0 by frozen vertices ratio [0.0 - 1.0]
+    #[structopt(long = "frozen-swap-bias", default_value = "0.0")]
+    pub frozen_swap_bias: f64,
+    /// probability of swapping positions of two non frozen vertices instead of a vertex move
+    #[structopt(long = "vertex-swap-prob", default_value = "0.0")]
+    pub vertex_swap_prob: f64,
+    /// sample vertex moves only from reachable in-hole cells instead of rejecting moves out of the hole
+    #[structopt(long = "reachable-moves")]
+    pub reachable_moves: bool,
+    /// start from the loaded pose and move only endpoints of broken edges while there are any
+    #[structopt(long = "edge-repair")]
+    pub edge_repair: bool,
+    /// expand the search field by this many cells around hole and figure when solving with wallhack
+    #[structopt(long = "field-margin", default_value = "0")]
+    pub field_margin: i64,
+    /// start annealing on a lattice of every k-th cell, refining down to single cells as temperature drops
+    #[structopt(long = "coarsen-factor", default_value = "1")]
+    pub coarsen_factor: usize,
+    /// smallest vertex move radius in lattice steps, the radius adapts to acceptance between the bounds
+    #[structopt(long = "min-move-radius", default_value = "1")]
+    pub min_move_radius: usize,
+    /// largest vertex move radius in lattice steps, reachable only while the temperature is high
+    #[structopt(long = "max-move-radius", default_value = "1")]
+    pub max_move_radius: usize,
+    /// mix this weight of the would be dislikes into energy of infeasible states (0.0 .. 1.0, 0.0 disables)
+    #[structopt(long = "dislikes-hint-weight", default_value = "0.0")]
+    pub dislikes_hint_weight: f64,
+    /// keep polishing with improving moves only below minimum temperature instead of finishing (runs until interrupted)
+    #[structopt(long = "greedy-below-floor")]
+    pub greedy_below_floor: bool,
     /// cooling step base temperature
     #[structopt(long = "cooling-step-temp", default_value = "1.0")]
     pub cooling_step_temp: f64,
+    /// maximum rounds of wiggle to zero polishing before a pose is written
+    #[structopt(long = "wiggle-max-iters", default_value = "16")]
+    pub wiggle_max_iters: usize,
+    /// hole containment backend used for scoring: polygon or quadtree
+    #[structopt(long = "edge-checker", default_value = "polygon")]
+    pub edge_checker: problem::EdgeChecker,
+    /// log time spent in every scoring check (count, stretching, hole, dislikes) for new best poses
+    #[structopt(long = "profile-scoring")]
+    pub profile_scoring: bool,
+    /// directory to dump intermediate poses into for animation, nothing is dumped if not set
+    #[structopt(long = "frame-dir")]
+    pub frame_dir: Option<PathBuf>,
+    /// dump a frame every this many cooling steps
+    #[structopt(long = "frame-every", default_value = "10")]
+    pub frame_every: usize,
+    /// append a json line per cooling step and per new best solution to this file for external monitoring
+    #[structopt(long = "progress-jsonl")]
+    pub progress_jsonl: Option<PathBuf>,
 }
 
 
@@ -46,7 +108,11 @@ pub enum Error {
     ProblemLoad(problem::FromFileError),
     SolverCreate(solver::CreateError),
     SimulatedAnnealingSolverCreate(solver::simulated_annealing::CreateError),
+    SolverConfigLoad(solver::config::LoadError),
     PoseExport(problem::WriteFileError),
+    FrameDirCreate(io::Error),
+    FrameExport(problem::WriteFileError),
+    ProgressStream(progress::StreamError),
     IncorrectBonus(serde_json::Error),
 }
 
@@ -59,57 +125,137 @@ fn main() -> Result<(), Error> {
         .map_err(Error::ProblemLoad)?;
     log::debug!(" ;; problem loaded: {:?}", problem);
 
-    let pose = problem::Pose::from_file(&cli_args.common.pose_file).ok();
+    let pose = match problem::Pose::from_file(&cli_args.common.pose_file) {
+        Ok(pose) if pose.vertices.len() != problem.figure.vertices.len() => {
+            log::error!(
+                "seed pose {:?} has {} vertices while the figure has {}, ignoring it and starting from the figure",
+                cli_args.common.pose_file,
+                pose.vertices.len(),
+                problem.figure.vertices.len(),
+            );
+            None
+        },
+        Ok(pose) =>
+            Some(pose),
+        Err(..) =>
+            None,
+    };
     log::debug!(" ;; pose loaded: {:?}", pose);
 
-    let unlocked_bonus: Option<problem::PoseBonus> = if let Some(bonus) = cli_args.unlocked_bonus {
-        Some(serde_json::from_str(&bonus).map_err(Error::IncorrectBonus)?)
+    let unlocked_bonus: Option<problem::PoseBonus> = if let Some(ref bonus) = cli_args.unlocked_bonus {
+        Some(serde_json::from_str(bonus).map_err(Error::IncorrectBonus)?)
     } else {
         None
     };
 
-    let mut solver = solver::simulated_annealing::SimulatedAnnealingSolver::new(
-        solver::Solver::with_bonus(
-            &problem,
-            pose,
-            match unlocked_bonus {
-                None =>
-                    None,
-                Some(problem::PoseBonus::BreakALeg { .. }) =>
-                    Some(problem::ProblemBonusType::BreakALeg),
-                Some(problem::PoseBonus::Globalist { .. }) =>
-                    Some(problem::ProblemBonusType::Globalist),
-                Some(problem::PoseBonus::Wallhack { .. }) =>
-                    Some(problem::ProblemBonusType::Wallhack),
-                Some(problem::PoseBonus::Superflex { .. }) =>
-                    Some(problem::ProblemBonusType::Superflex),
-            },
-        ).map_err(Error::SolverCreate)?,
-        solver::simulated_annealing::Params {
-            max_temp: 100.0,
-            cooling_step_temp: cli_args.cooling_step_temp,
-            minimum_temp: 2.0,
-            valid_edge_accept_prob: cli_args.valid_edge_accept_prob,
-            frozen_swap_prob: cli_args.frozen_swap_prob,
-            iterations_per_cooling_step: cli_args.iterations_per_cooling_step,
-            operating_mode: match cli_args.collect_bonus_problem {
-                Some(problem_id) =>
-                    solver::simulated_annealing::OperatingMode::BonusCollector {
-                        target_problem: problem::ProblemId(problem_id),
-                    },
-                None =>
-                    // solver::simulated_annealing::OperatingMode::ZeroHunter,
-                    solver::simulated_annealing::OperatingMode::ScoreMaximizer,
-            },
+    let params = load_params(&cli_args, solver::simulated_annealing::Params {
+        max_temp: 100.0,
+        cooling_step_temp: cli_args.cooling_step_temp,
+        minimum_temp: 2.0,
+        below_floor: if cli_args.greedy_below_floor {
+            solver::simulated_annealing::FloorBehavior::Greedy
+        } else {
+            solver::simulated_annealing::FloorBehavior::Stop
+        },
+        edge_bias: if cli_args.focus_broken_edges {
+            solver::simulated_annealing::EdgeBias::FocusBroken
+        } else {
+            solver::simulated_annealing::EdgeBias::from_valid_edge_accept_prob(cli_args.valid_edge_accept_prob)
         },
-    ).map_err(Error::SimulatedAnnealingSolverCreate)?;
+        frozen_swap_prob: cli_args.frozen_swap_prob,
+        frozen_swap_bias: cli_args.frozen_swap_bias,
+        vertex_swap_prob: cli_args.vertex_swap_prob,
+        reachable_moves: cli_args.reachable_moves,
+        wallhack_allowed_outside: problem::WALLHACK_ALLOWED_OUTSIDE,
+        coarsen_factor: cli_args.coarsen_factor,
+        min_move_radius: cli_args.min_move_radius,
+        max_move_radius: cli_args.max_move_radius,
+        dislikes_hint_weight: cli_args.dislikes_hint_weight,
+        iterations_per_cooling_step: cli_args.iterations_per_cooling_step,
+        operating_mode: match cli_args.collect_bonus_problem {
+            Some(problem_id) =>
+                solver::simulated_annealing::OperatingMode::BonusCollector {
+                    target_problem: problem::ProblemId(problem_id),
+                },
+            None if cli_args.edge_repair =>
+                solver::simulated_annealing::OperatingMode::EdgeRepair,
+            None =>
+                // solver::simulated_annealing::OperatingMode::ZeroHunter,
+                solver::simulated_annealing::OperatingMode::ScoreMaximizer,
+        },
+    })?;
+    log::debug!(" ;; solver params: {:?}", params);
+
+    let mut base_solver = solver::Solver::with_field_margin(
+        &problem,
+        pose,
+        match unlocked_bonus {
+            None =>
+                None,
+            Some(problem::PoseBonus::BreakALeg { .. }) =>
+                Some(problem::ProblemBonusType::BreakALeg),
+            Some(problem::PoseBonus::Globalist { .. }) =>
+                Some(problem::ProblemBonusType::Globalist),
+            Some(problem::PoseBonus::Wallhack { .. }) =>
+                Some(problem::ProblemBonusType::Wallhack),
+            Some(problem::PoseBonus::Superflex { .. }) =>
+                Some(problem::ProblemBonusType::Superflex),
+        },
+        cli_args.field_margin,
+    ).map_err(Error::SolverCreate)?;
+    let edge_checker_build_start = Instant::now();
+    base_solver.set_edge_checker(cli_args.edge_checker.build(&problem));
+    log::info!("{:?} edge checker built in {:?}", cli_args.edge_checker, edge_checker_build_start.elapsed());
+
+    let mut solver = solver::simulated_annealing::SimulatedAnnealingSolver::new(base_solver, params)
+        .map_err(Error::SimulatedAnnealingSolverCreate)?;
+
+    let frame_writer = match &cli_args.frame_dir {
+        None =>
+            None,
+        Some(frame_dir) =>
+            Some(FrameWriter::new(frame_dir, cli_args.frame_every)?),
+    };
+    let mut progress_stream = match &cli_args.progress_jsonl {
+        None =>
+            None,
+        Some(progress_jsonl) =>
+            Some(progress::ProgressStream::open(progress_jsonl).map_err(Error::ProgressStream)?),
+    };
+    let task = cli_args.common.problem_file.file_stem()
+        .and_then(|stem| stem.to_str())
+        .unwrap_or_default()
+        .to_string();
 
     let mut reheats_count = 0;
+    let mut cooling_steps = 0;
     let mut best_solution = None;
     loop {
         match solver.step() {
-            Ok(()) =>
-                (),
+            Ok(()) => {
+                log::debug!(
+                    "temp: {}, acceptance ratio: {:.3}, move radius: {}",
+                    solver.temp(),
+                    solver.acceptance_ratio_last_step(),
+                    solver.move_radius(),
+                );
+                if let Some(frame_writer) = &frame_writer {
+                    frame_writer.write(cooling_steps, solver.vertices())?;
+                }
+                cooling_steps += 1;
+                if let Some(progress_stream) = &mut progress_stream {
+                    let fitness = solver.fitness();
+                    let record = progress::ProgressRecord {
+                        task: task.clone(),
+                        step: cooling_steps,
+                        temp: solver.temp(),
+                        score: fitness.score(),
+                        energy: fitness.energy(),
+                    };
+                    progress_stream.write(&record)
+                        .map_err(Error::ProgressStream)?;
+                }
+            },
             Err(solver::simulated_annealing::StepError::TempTooLow) if reheats_count < cli_args.max_reheats_count => {
                 log::info!(
                     "temperature is too low: performing reheat ({} left), fitness = {:?}",
@@ -121,6 +267,9 @@ fn main() -> Result<(), Error> {
             },
             Err(solver::simulated_annealing::StepError::TempTooLow) => {
                 log::info!("annealing done");
+                if let Some(stats) = solver.edge_checker().quad_tree_stats() {
+                    log::info!("quad tree: {} queries, slow path ratio {:.3}", stats.hits_total, stats.slow_ratio());
+                }
                 return Ok(());
             },
             Err(solver::simulated_annealing::StepError::ProbablyInfiniteLoopInVertexIndex) => {
@@ -136,28 +285,15 @@ fn main() -> Result<(), Error> {
                 return Ok(());
             },
             Err(solver::simulated_annealing::StepError::GenerateVertices(
-                solver::simulated_annealing::GenerateVerticesError::ProbablyInfiniteLoopInFrozenIndexInBonusCollector,
-            )) => {
-                log::error!("probably infinite loop in generate vertices for bonus collector, stopping");
-                return Ok(());
-            },
-            Err(solver::simulated_annealing::StepError::GenerateVertices(
-                solver::simulated_annealing::GenerateVerticesError::ProbablyInfiniteLoopInFrozenIndexInBonusHunter,
-            )) => {
-                log::error!("probably infinite loop in generate vertices for bonus hunter, stopping");
-                return Ok(());
-            },
-            Err(solver::simulated_annealing::StepError::GenerateVertices(
-                solver::simulated_annealing::GenerateVerticesError::ProbablyInfiniteLoopInFrozenIndexInZeroHunter,
+                solver::simulated_annealing::GenerateVerticesError::MoreFrozenTargetsThanVertices { targets, vertices, },
             )) => {
-                log::error!("probably infinite loop in generate vertices for zero hunter, stopping");
+                log::error!("{} frozen targets for {} vertices in generate vertices, stopping", targets, vertices);
                 return Ok(());
             },
         }
         match solver.fitness() {
             solver::simulated_annealing::Fitness::FigureScored { score, } =>
                 if best_solution.map_or(true, |best_score| score < best_score) {
-                    best_solution = Some(score);
                     let pose = problem::Pose {
                         vertices: solver.vertices().to_vec(),
                         bonuses: if let Some(bonus) = unlocked_bonus {
@@ -166,8 +302,43 @@ fn main() -> Result<(), Error> {
                             None
                         },
                     };
+                    let pose = solver::polish::wiggle_to_zero(&problem, &pose, cli_args.wiggle_max_iters);
+                    let result = if cli_args.profile_scoring {
+                        let (result, timings) = problem.score_pose_timed(solver.edge_checker(), &pose);
+                        log::info!(
+                            "scoring took {} ns: count {} ns, stretching {} ns, hole {} ns, dislikes {} ns",
+                            timings.total_ns(),
+                            timings.count_ns,
+                            timings.stretching_ns,
+                            timings.hole_ns,
+                            timings.dislikes_ns,
+                        );
+                        result
+                    } else {
+                        problem.score_pose(&pose)
+                    };
+                    let score = match result {
+                        Ok(score) =>
+                            score,
+                        Err(error) => {
+                            log::error!("pose with solver score {} is rejected by scoring: {:?}, skipping it", score, error);
+                            continue;
+                        },
+                    };
+                    best_solution = Some(score);
                     pose.write_to_file(&cli_args.common.pose_file)
                         .map_err(Error::PoseExport)?;
+                    if let Some(progress_stream) = &mut progress_stream {
+                        let record = progress::ProgressRecord {
+                            task: task.clone(),
+                            step: cooling_steps,
+                            temp: solver.temp(),
+                            score: Some(score),
+                            energy: solver.fitness().energy(),
+                        };
+                        progress_stream.write(&record)
+                            .map_err(Error::ProgressStream)?;
+                    }
                     log::info!("SCORE: {} | new best solution found, pose has been written to {:?}", score, cli_args.common.pose_file);
                 },
             solver::simulated_annealing::Fitness::FigureCorrupted { .. } |
@@ -176,3 +347,63 @@ fn main() -> Result<(), Error> {
         }
     }
 }
+
+/// Dumps the current vertices as `<step>.pose` files (step zero padded, so frames sort by name) every `every` cooling steps.
+struct FrameWriter {
+    frame_dir: PathBuf,
+    every: usize,
+}
+
+impl FrameWriter {
+    fn new(frame_dir: &Path, every: usize) -> Result<FrameWriter, Error> {
+        fs::create_dir_all(frame_dir)
+            .map_err(Error::FrameDirCreate)?;
+        Ok(FrameWriter { frame_dir: frame_dir.to_path_buf(), every: every.max(1), })
+    }
+
+    fn write(&self, cooling_step: usize, vertices: &[problem::Point]) -> Result<(), Error> {
+        if cooling_step % self.every != 0 {
+            return Ok(());
+        }
+        let pose = problem::Pose { vertices: vertices.to_vec(), bonuses: None, };
+        pose.write_to_file(self.frame_dir.join(format!("{:06}.pose", cooling_step)))
+            .map_err(Error::FrameExport)
+    }
+}
+
+fn load_params(cli_args: &CliArgs, defaults: solver::simulated_annealing::Params) -> Result<solver::simulated_annealing::Params, Error> {
+    let problem_file = &cli_args.common.problem_file;
+    let task_id = match problem_file.file_stem().and_then(|stem| stem.to_str()) {
+        Some(task_id) =>
+            task_id,
+        None =>
+            return Ok(defaults),
+    };
+    let problems_directory = problem_file.parent()
+        .unwrap_or_else(|| Path::new("."));
+    solver::config::load_params(problems_directory, task_id, defaults)
+        .map_err(Error::SolverConfigLoad)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn frames_every_k_steps() {
+        let temp_directory = tempfile::tempdir().unwrap();
+        let frame_dir = temp_directory.path().join("frames");
+
+        let frame_writer = FrameWriter::new(&frame_dir, 10).unwrap();
+        let vertices = vec![problem::Point(1, 2), problem::Point(3, 4)];
+        for cooling_step in 0 .. 25 {
+            frame_writer.write(cooling_step, &vertices).unwrap();
+        }
+
+        let mut frames: Vec<_> = fs::read_dir(&frame_dir).unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .collect();
+        frames.sort();
+        assert_eq!(frames, vec!["000000.pose", "000010.pose", "000020.pose"]);
+    }
+}
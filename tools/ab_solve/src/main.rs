@@ -0,0 +1,291 @@
+use std::{
+    time,
+    path::PathBuf,
+    collections::BTreeMap,
+};
+
+use structopt::{
+    StructOpt,
+};
+
+use common::{
+    solver,
+    problem,
+};
+
+#[derive(Clone, StructOpt, Debug)]
+pub struct CliArgs {
+    /// tasks directory with problems
+    #[structopt(long = "tasks-directory", default_value = "./tasks")]
+    pub tasks_directory: PathBuf,
+    /// task to solve with both configurations (may be repeated)
+    #[structopt(long = "task", required = true)]
+    pub tasks: Vec<u64>,
+    /// solver config A: json with simulated annealing params overrides, like `<task>.solver.json`
+    #[structopt(long = "config-a")]
+    pub config_a: PathBuf,
+    /// solver config B: json with simulated annealing params overrides, like `<task>.solver.json`
+    #[structopt(long = "config-b")]
+    pub config_b: PathBuf,
+    /// time budget for every task with every configuration
+    #[structopt(long = "time-budget-s", default_value = "60")]
+    pub time_budget_s: u64,
+    /// reheats performed within the time budget after temperature drops to minimum
+    #[structopt(long = "max-reheats-count", default_value = "2")]
+    pub max_reheats_count: usize,
+    /// reheat temperature factor
+    #[structopt(long = "reheat-factor", default_value = "0.33")]
+    pub reheat_factor: f64,
+    /// random seed shared by both configurations
+    #[structopt(long = "seed", default_value = "1180")]
+    pub seed: u64,
+}
+
+#[derive(Debug)]
+pub enum Error {
+    ProblemLoad { problem_file: PathBuf, error: problem::FromFileError, },
+    ConfigLoad(solver::config::LoadError),
+    SolverCreate { task_id: u64, error: solver::CreateError, },
+    SimulatedAnnealingSolverCreate { task_id: u64, error: solver::simulated_annealing::CreateError, },
+    SimulatedAnnealingSolverSeed { task_id: u64, error: solver::simulated_annealing::GenerateVerticesError, },
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Budget {
+    pub time: time::Duration,
+    pub max_reheats_count: usize,
+    pub reheat_factor: f64,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Winner {
+    A,
+    B,
+    Tie,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct TaskResult {
+    pub task_id: u64,
+    /// best dislikes of a valid pose found with config A, `None` if there was none
+    pub dislikes_a: Option<i64>,
+    pub dislikes_b: Option<i64>,
+}
+
+fn main() -> Result<(), Error> {
+    pretty_env_logger::init();
+    let cli_args = CliArgs::from_args();
+    log::info!("program starts as: {:?}", cli_args);
+
+    let params_a = solver::config::load_params_file(&cli_args.config_a, default_params())
+        .map_err(Error::ConfigLoad)?;
+    let params_b = solver::config::load_params_file(&cli_args.config_b, default_params())
+        .map_err(Error::ConfigLoad)?;
+
+    let mut problems = BTreeMap::new();
+    for &task_id in &cli_args.tasks {
+        let problem_file = cli_args.tasks_directory.join(format!("{}.problem", task_id));
+        let problem = match problem::Problem::from_file(&problem_file) {
+            Ok(problem) =>
+                problem,
+            Err(error) =>
+                return Err(Error::ProblemLoad { problem_file, error, }),
+        };
+        problems.insert(task_id, problem);
+    }
+
+    let budget = Budget {
+        time: time::Duration::from_secs(cli_args.time_budget_s),
+        max_reheats_count: cli_args.max_reheats_count,
+        reheat_factor: cli_args.reheat_factor,
+    };
+    let results = compare(&problems, params_a, params_b, cli_args.seed, budget)?;
+    print!("{}", format_summary(&results));
+    Ok(())
+}
+
+/// Same defaults as the simulated annealing binary has without any cli flags.
+fn default_params() -> solver::simulated_annealing::Params {
+    solver::simulated_annealing::Params {
+        max_temp: 100.0,
+        cooling_step_temp: 1.0,
+        minimum_temp: 2.0,
+        below_floor: solver::simulated_annealing::FloorBehavior::Stop,
+        edge_bias: solver::simulated_annealing::EdgeBias::ProbSkip(0.5),
+        frozen_swap_prob: 0.15,
+        frozen_swap_bias: 0.0,
+        vertex_swap_prob: 0.0,
+        reachable_moves: false,
+        wallhack_allowed_outside: problem::WALLHACK_ALLOWED_OUTSIDE,
+        coarsen_factor: 1,
+        min_move_radius: 1,
+        max_move_radius: 1,
+        dislikes_hint_weight: 0.0,
+        iterations_per_cooling_step: 32768,
+        operating_mode: solver::simulated_annealing::OperatingMode::ScoreMaximizer,
+    }
+}
+
+fn compare(
+    problems: &BTreeMap<u64, problem::Problem>,
+    params_a: solver::simulated_annealing::Params,
+    params_b: solver::simulated_annealing::Params,
+    seed: u64,
+    budget: Budget,
+)
+    -> Result<Vec<TaskResult>, Error>
+{
+    let mut results = Vec::with_capacity(problems.len());
+    for (&task_id, problem) in problems {
+        let dislikes_a = solve(task_id, problem, params_a, seed, budget)?;
+        let dislikes_b = solve(task_id, problem, params_b, seed, budget)?;
+        log::info!("task {}: config A {:?}, config B {:?}", task_id, dislikes_a, dislikes_b);
+        results.push(TaskResult { task_id, dislikes_a, dislikes_b, });
+    }
+    Ok(results)
+}
+
+/// Anneals `problem` with `params` from the `seed` initial state and returns the best valid pose dislikes found within `budget`.
+fn solve(
+    task_id: u64,
+    problem: &problem::Problem,
+    params: solver::simulated_annealing::Params,
+    seed: u64,
+    budget: Budget,
+)
+    -> Result<Option<i64>, Error>
+{
+    let solver = solver::Solver::new(problem, None)
+        .map_err(|error| Error::SolverCreate { task_id, error, })?;
+    let mut solver = solver::simulated_annealing::SimulatedAnnealingSolver::new(solver, params)
+        .map_err(|error| Error::SimulatedAnnealingSolverCreate { task_id, error, })?
+        .with_seed(seed)
+        .map_err(|error| Error::SimulatedAnnealingSolverSeed { task_id, error, })?;
+
+    let started_at = time::Instant::now();
+    let mut best_dislikes = None;
+    let mut reheats_count = 0;
+    while started_at.elapsed() < budget.time {
+        record_best(solver.fitness(), &mut best_dislikes);
+        match solver.step() {
+            Ok(()) =>
+                (),
+            Err(solver::simulated_annealing::StepError::TempTooLow) if reheats_count < budget.max_reheats_count => {
+                solver.reheat(budget.reheat_factor);
+                reheats_count += 1;
+            },
+            Err(solver::simulated_annealing::StepError::TempTooLow) =>
+                break,
+            Err(error) => {
+                log::warn!("task {}: annealing stopped early: {:?}", task_id, error);
+                break;
+            },
+        }
+    }
+    record_best(solver.fitness(), &mut best_dislikes);
+    Ok(best_dislikes)
+}
+
+fn record_best(fitness: solver::simulated_annealing::Fitness, best_dislikes: &mut Option<i64>) {
+    match (fitness, *best_dislikes) {
+        (solver::simulated_annealing::Fitness::FigureScored { score, }, Some(best)) if score < best =>
+            *best_dislikes = Some(score),
+        (solver::simulated_annealing::Fitness::FigureScored { score, }, None) =>
+            *best_dislikes = Some(score),
+        _ =>
+            (),
+    }
+}
+
+impl TaskResult {
+    /// Lower dislikes win, any valid pose wins over none.
+    pub fn winner(&self) -> Winner {
+        match (self.dislikes_a, self.dislikes_b) {
+            (Some(a), Some(b)) if a < b =>
+                Winner::A,
+            (Some(a), Some(b)) if a > b =>
+                Winner::B,
+            (Some(..), None) =>
+                Winner::A,
+            (None, Some(..)) =>
+                Winner::B,
+            (Some(..), Some(..)) | (None, None) =>
+                Winner::Tie,
+        }
+    }
+}
+
+fn format_summary(results: &[TaskResult]) -> String {
+    let show = |value: Option<i64>| match value {
+        Some(value) =>
+            value.to_string(),
+        None =>
+            "-".to_string(),
+    };
+    let mut table = String::new();
+    table.push_str("|----------|------------|------------|--------|\n");
+    table.push_str("| Task     | A          | B          | Winner |\n");
+    table.push_str("|----------|------------|------------|--------|\n");
+    for result in results {
+        table.push_str(&format!(
+            "| {:>8} | {:>10} | {:>10} | {:>6} |\n",
+            result.task_id,
+            show(result.dislikes_a),
+            show(result.dislikes_b),
+            match result.winner() {
+                Winner::A =>
+                    "A",
+                Winner::B =>
+                    "B",
+                Winner::Tie =>
+                    "-",
+            },
+        ));
+    }
+    table.push_str("|----------|------------|------------|--------|\n");
+
+    // totals are comparable only over tasks solved by both configurations
+    let both_solved = results.iter().filter(|result| result.dislikes_a.is_some() && result.dislikes_b.is_some());
+    let total_a: i64 = both_solved.clone().flat_map(|result| result.dislikes_a).sum();
+    let total_b: i64 = both_solved.flat_map(|result| result.dislikes_b).sum();
+    let wins = |winner| results.iter().filter(|result| result.winner() == winner).count();
+    table.push_str(&format!(
+        "total dislikes on tasks solved by both: A {}, B {}\nwins: A {}, B {}, ties {}\n",
+        total_a,
+        total_b,
+        wins(Winner::A),
+        wins(Winner::B),
+        wins(Winner::Tie),
+    ));
+    table
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn deterministic_winner() {
+        let mut problems = BTreeMap::new();
+        let triangle = r#"{"bonuses":[],"hole":[[0,0],[20,0],[20,20],[0,20]],"epsilon":150000,"figure":{"edges":[[0,1],[1,2],[2,0]],"vertices":[[0,0],[10,0],[0,10]]}}"#;
+        let square = r#"{"bonuses":[],"hole":[[0,0],[12,0],[12,12],[0,12]],"epsilon":150000,"figure":{"edges":[[0,1],[1,2],[2,3],[3,0]],"vertices":[[0,0],[10,0],[10,10],[0,10]]}}"#;
+        problems.insert(1, serde_json::from_str(triangle).unwrap());
+        problems.insert(2, serde_json::from_str(square).unwrap());
+
+        let mut params_a = default_params();
+        params_a.iterations_per_cooling_step = 256;
+        params_a.cooling_step_temp = 2.0;
+        // config B freezes right away and hardly moves anything
+        let mut params_b = params_a;
+        params_b.iterations_per_cooling_step = 1;
+        params_b.minimum_temp = 99.0;
+        let budget = Budget { time: time::Duration::from_secs(30), max_reheats_count: 0, reheat_factor: 0.33, };
+
+        let results = compare(&problems, params_a, params_b, 1180, budget).unwrap();
+        assert!(results.iter().all(|result| result.winner() == Winner::A), "results: {:?}", results);
+        assert_eq!(compare(&problems, params_a, params_b, 1180, budget).unwrap(), results);
+
+        let summary = format_summary(&results);
+        assert!(summary.ends_with("wins: A 2, B 0, ties 0\n"), "summary: {}", summary);
+    }
+}
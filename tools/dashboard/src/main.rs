@@ -0,0 +1,343 @@
+use std::{
+    fs,
+    io,
+    ffi::OsStr,
+    path::{
+        Path,
+        PathBuf,
+    },
+    collections::{
+        HashMap,
+        BTreeMap,
+    },
+};
+
+use structopt::{
+    StructOpt,
+};
+
+use rayon::prelude::*;
+
+use common::{
+    svg,
+    problem,
+    standings,
+};
+
+#[derive(Clone, StructOpt, Debug)]
+pub struct CliArgs {
+    /// tasks directory with problems
+    #[structopt(long = "tasks-directory", default_value = "./tasks")]
+    pub tasks_directory: PathBuf,
+    /// poses directory with current poses
+    #[structopt(long = "poses-directory", default_value = "./poses")]
+    pub poses_directory: PathBuf,
+    /// problems page cached by tools/standings for the best possible dislikes column (optional)
+    #[structopt(long = "standings-cache", default_value = "./standings.cache.html")]
+    pub standings_cache: PathBuf,
+    /// dashboard html file to write
+    #[structopt(long = "output", default_value = "./index.html")]
+    pub output: PathBuf,
+    /// worker threads loading and scoring tasks, 0 uses all cores
+    #[structopt(long = "jobs", default_value = "0")]
+    pub jobs: usize,
+}
+
+#[derive(Debug)]
+pub enum Error {
+    TasksDirectoryRead(io::Error),
+    ProblemLoad { problem_file: PathBuf, error: problem::FromFileError, },
+    StandingsCacheRead { cache_file: PathBuf, error: io::Error, },
+    OutputWrite { output_file: PathBuf, error: io::Error, },
+    ThreadPoolBuild(rayon::ThreadPoolBuildError),
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Status {
+    NoPose,
+    Invalid,
+    Solved,
+    /// dislikes are not worse than the best known
+    Optimal,
+}
+
+#[derive(Clone, Debug)]
+pub struct TaskRow {
+    pub task_id: u64,
+    pub dislikes: Option<i64>,
+    pub best_dislikes: Option<i64>,
+    pub status: Status,
+    pub bonuses: Vec<problem::ProblemBonus>,
+    /// figure can only be rotated, reflected or translated
+    pub rigid: bool,
+    pub thumbnail: String,
+}
+
+fn main() -> Result<(), Error> {
+    pretty_env_logger::init();
+    let cli_args = CliArgs::from_args();
+    log::info!("program starts as: {:?}", cli_args);
+
+    let best_dislikes = load_best_dislikes(&cli_args.standings_cache)?;
+    let thread_pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(cli_args.jobs)
+        .build()
+        .map_err(Error::ThreadPoolBuild)?;
+    let rows = thread_pool.install(|| load_task_rows(&cli_args.tasks_directory, &cli_args.poses_directory, &best_dislikes))?;
+    fs::write(&cli_args.output, render_dashboard(&rows))
+        .map_err(|error| Error::OutputWrite { output_file: cli_args.output.clone(), error, })?;
+    println!("dashboard for {} tasks written to {:?}", rows.len(), cli_args.output);
+    Ok(())
+}
+
+/// Best known dislikes per task from the cached problems page, empty if it has never been fetched.
+fn load_best_dislikes(cache_file: &Path) -> Result<HashMap<u64, i64>, Error> {
+    let page = match fs::read_to_string(cache_file) {
+        Ok(page) =>
+            page,
+        Err(error) if error.kind() == io::ErrorKind::NotFound => {
+            log::info!("no standings cache {:?}, best possible dislikes are not shown", cache_file);
+            return Ok(HashMap::new());
+        },
+        Err(error) =>
+            return Err(Error::StandingsCacheRead { cache_file: cache_file.to_path_buf(), error, }),
+    };
+    Ok(standings::parse_standings(&page)
+        .into_iter()
+        .flat_map(|standing| standing.best_dislikes.map(|best| (standing.task_id as u64, best)))
+        .collect())
+}
+
+/// Rows sorted by task id. Tasks are loaded and scored in parallel on the current rayon pool,
+/// the order does not depend on which task is done first.
+fn load_task_rows(tasks_directory: &Path, poses_directory: &Path, best_dislikes: &HashMap<u64, i64>) -> Result<Vec<TaskRow>, Error> {
+    let mut problem_files = BTreeMap::new();
+    for entry in fs::read_dir(tasks_directory).map_err(Error::TasksDirectoryRead)? {
+        let problem_file = entry.map_err(Error::TasksDirectoryRead)?.path();
+        if problem_file.extension() != Some(OsStr::new("problem")) {
+            continue;
+        }
+        let task_id = match problem_file.file_stem().and_then(OsStr::to_str).and_then(|stem| stem.parse().ok()) {
+            Some(task_id) =>
+                task_id,
+            None => {
+                log::warn!("skipping problem file with non numeric name: {:?}", problem_file);
+                continue;
+            },
+        };
+        problem_files.insert(task_id, problem_file);
+    }
+
+    problem_files.into_iter()
+        .collect::<Vec<_>>()
+        .into_par_iter()
+        .map(|(task_id, problem_file)| {
+            let problem = match problem::Problem::from_file(&problem_file) {
+                Ok(problem) =>
+                    problem,
+                Err(error) =>
+                    return Err(Error::ProblemLoad { problem_file, error, }),
+            };
+            let pose_file = poses_directory.join(format!("{}.pose", task_id));
+            Ok(task_row(task_id, &problem, &pose_file, best_dislikes.get(&task_id).cloned()))
+        })
+        .collect()
+}
+
+fn task_row(task_id: u64, problem: &problem::Problem, pose_file: &Path, best_dislikes: Option<i64>) -> TaskRow {
+    let pose = match problem::Pose::from_file(pose_file) {
+        Ok(pose) =>
+            Some(pose),
+        Err(problem::FromFileError::OpenFile(ref error)) if error.kind() == io::ErrorKind::NotFound =>
+            None,
+        Err(error) => {
+            log::warn!("failed to load pose {:?} for task {}: {:?}", pose_file, task_id, error);
+            None
+        },
+    };
+    let (dislikes, status) = match &pose {
+        None =>
+            (None, Status::NoPose),
+        Some(pose) =>
+            match problem.score_pose(pose) {
+                Ok(dislikes) if matches!(best_dislikes, Some(best) if dislikes <= best) =>
+                    (Some(dislikes), Status::Optimal),
+                Ok(dislikes) =>
+                    (Some(dislikes), Status::Solved),
+                Err(error) => {
+                    log::debug!("pose {:?} for task {} is invalid: {:?}", pose_file, task_id, error);
+                    (None, Status::Invalid)
+                },
+            },
+    };
+    // the original figure is drawn until there is a pose
+    let thumbnail_vertices = match &pose {
+        Some(pose) if pose.vertices.len() == problem.figure.vertices.len() =>
+            &pose.vertices,
+        Some(..) | None =>
+            &problem.figure.vertices,
+    };
+    TaskRow {
+        task_id,
+        dislikes,
+        best_dislikes,
+        status,
+        bonuses: problem.bonuses.clone().unwrap_or_default(),
+        rigid: problem.figure.is_rigid(),
+        thumbnail: svg::export(problem, thumbnail_vertices),
+    }
+}
+
+impl Status {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Status::NoPose =>
+                "no pose",
+            Status::Invalid =>
+                "invalid",
+            Status::Solved =>
+                "solved",
+            Status::Optimal =>
+                "optimal",
+        }
+    }
+}
+
+const HEADERS: &[&str] = &["Task", "Dislikes", "Best possible", "Status", "Bonuses", "Rigid", "Pose"];
+
+const SORT_SCRIPT: &str = r#"<script>
+document.querySelectorAll("th").forEach(function (th, column) {
+  th.addEventListener("click", function () {
+    var tbody = th.closest("table").tBodies[0];
+    var ascending = th.dataset.order !== "asc";
+    th.dataset.order = ascending ? "asc" : "desc";
+    var key = function (row) { return row.cells[column].dataset.sort || row.cells[column].textContent; };
+    Array.from(tbody.rows)
+      .sort(function (a, b) {
+        var x = key(a), y = key(b);
+        var order = isNaN(x) || isNaN(y) ? x.localeCompare(y) : Number(x) - Number(y);
+        return ascending ? order : -order;
+      })
+      .forEach(function (row) { tbody.appendChild(row); });
+  });
+});
+</script>
+"#;
+
+/// Renders all tasks as a single html page with a table sortable by clicking on column headers.
+fn render_dashboard(rows: &[TaskRow]) -> String {
+    // missing numbers go last in ascending order
+    let number = |value: Option<i64>| match value {
+        Some(value) =>
+            (value.to_string(), value.to_string()),
+        None =>
+            ("Infinity".to_string(), "-".to_string()),
+    };
+
+    let mut html = String::new();
+    html.push_str("<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>icfpc 2021 dashboard</title>\n");
+    html.push_str("<style>\n");
+    html.push_str("table { border-collapse: collapse; }\n");
+    html.push_str("th { cursor: pointer; }\n");
+    html.push_str("th, td { border: 1px solid #ccc; padding: 4px 8px; }\n");
+    html.push_str("td svg { width: 120px; height: 120px; }\n");
+    html.push_str("</style>\n</head>\n<body>\n<table>\n<thead>\n<tr>");
+    for header in HEADERS {
+        html.push_str(&format!("<th>{}</th>", header));
+    }
+    html.push_str("</tr>\n</thead>\n<tbody>\n");
+    for row in rows {
+        let (dislikes_sort, dislikes) = number(row.dislikes);
+        let (best_sort, best) = number(row.best_dislikes);
+        let bonuses: Vec<_> = row.bonuses
+            .iter()
+            .map(|bonus| format!("{:?} for task {}", bonus.bonus, bonus.problem.0))
+            .collect();
+        html.push_str(&format!(
+            "<tr data-task=\"{}\"><td>{}</td><td data-sort=\"{}\">{}</td><td data-sort=\"{}\">{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>\n",
+            row.task_id,
+            row.task_id,
+            dislikes_sort,
+            dislikes,
+            best_sort,
+            best,
+            row.status.as_str(),
+            bonuses.join("<br>"),
+            if row.rigid { "yes" } else { "no" },
+            row.thumbnail.trim_end(),
+        ));
+    }
+    html.push_str("</tbody>\n</table>\n");
+    html.push_str(SORT_SCRIPT);
+    html.push_str("</body>\n</html>\n");
+    html
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn row_per_task() {
+        let temp_directory = tempfile::tempdir().unwrap();
+        let directory = temp_directory.path();
+        let tasks_directory = directory.join("tasks");
+        let poses_directory = directory.join("poses");
+        fs::create_dir_all(&tasks_directory).unwrap();
+        fs::create_dir_all(&poses_directory).unwrap();
+        let problem_data = r#"{"bonuses":[{"bonus":"GLOBALIST","problem":7,"position":[20,0]}],"hole":[[20,0],[40,20],[20,40],[0,20]],"epsilon":2494,"figure":{"edges":[[0,1],[0,2],[1,3],[2,3]],"vertices":[[15,21],[34,0],[0,45],[19,24]]}}"#;
+        fs::write(tasks_directory.join("12.problem"), problem_data).unwrap();
+        fs::write(tasks_directory.join("7.problem"), problem_data).unwrap();
+        fs::write(poses_directory.join("12.pose"), r#"{"vertices":[[20,0],[40,20],[0,20],[20,40]]}"#).unwrap();
+
+        let best_dislikes = vec![(12, 0)].into_iter().collect();
+        let rows = load_task_rows(&tasks_directory, &poses_directory, &best_dislikes).unwrap();
+        assert_eq!(rows.iter().map(|row| row.task_id).collect::<Vec<_>>(), vec![7, 12]);
+        assert_eq!(rows[0].status, Status::NoPose);
+        assert_eq!(rows[1].status, Status::Optimal);
+        assert_eq!(rows[1].dislikes, Some(0));
+
+        let html = render_dashboard(&rows);
+        assert_eq!(html.matches("<tr data-task=").count(), 2);
+        assert!(html.contains("<tr data-task=\"12\"><td>12</td><td data-sort=\"0\">0</td><td data-sort=\"0\">0</td><td>optimal</td><td>Globalist for task 7</td><td>no</td><td><svg"));
+        for header in HEADERS {
+            assert!(html.contains(&format!("<th>{}</th>", header)), "no {:?} column", header);
+        }
+    }
+
+    #[test]
+    fn parallel_rows_match_sequential() {
+        let temp_directory = tempfile::tempdir().unwrap();
+        let directory = temp_directory.path();
+        let tasks_directory = directory.join("tasks");
+        let poses_directory = directory.join("poses");
+        fs::create_dir_all(&tasks_directory).unwrap();
+        fs::create_dir_all(&poses_directory).unwrap();
+        let problem_data = r#"{"bonuses":[],"hole":[[20,0],[40,20],[20,40],[0,20]],"epsilon":2494,"figure":{"edges":[[0,1],[0,2],[1,3],[2,3]],"vertices":[[15,21],[34,0],[0,45],[19,24]]}}"#;
+        for task_id in 1 ..= 24 {
+            fs::write(tasks_directory.join(format!("{}.problem", task_id)), problem_data).unwrap();
+            let pose_data = match task_id % 3 {
+                0 =>
+                    r#"{"vertices":[[20,0],[40,20],[0,20],[20,40]]}"#,
+                1 =>
+                    r#"{"vertices":[[0,0],[40,20],[0,20],[20,40]]}"#,
+                _ =>
+                    continue,
+            };
+            fs::write(poses_directory.join(format!("{}.pose", task_id)), pose_data).unwrap();
+        }
+
+        let best_dislikes = HashMap::new();
+        let rows_with = |threads| {
+            let thread_pool = rayon::ThreadPoolBuilder::new().num_threads(threads).build().unwrap();
+            thread_pool.install(|| load_task_rows(&tasks_directory, &poses_directory, &best_dislikes)).unwrap()
+        };
+        let sequential = rows_with(1);
+        let parallel = rows_with(4);
+
+        assert_eq!(sequential.iter().map(|row| row.task_id).collect::<Vec<_>>(), (1 ..= 24).collect::<Vec<_>>());
+        assert_eq!(render_dashboard(&parallel), render_dashboard(&sequential));
+        assert!(sequential.iter().any(|row| row.status == Status::Invalid));
+        assert!(sequential.iter().any(|row| row.status == Status::Solved));
+    }
+}
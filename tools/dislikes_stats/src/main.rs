@@ -0,0 +1,120 @@
+use structopt::{
+    StructOpt,
+};
+
+use common::{
+    cli,
+    problem,
+};
+
+#[derive(Clone, StructOpt, Debug)]
+pub struct CliArgs {
+    #[structopt(flatten)]
+    pub common: cli::CommonCliArgs,
+    /// print contributions as json instead of a table
+    #[structopt(long = "json")]
+    pub json: bool,
+}
+
+#[derive(Debug)]
+pub enum Error {
+    ProblemLoad(problem::FromFileError),
+    PoseLoad(problem::FromFileError),
+    PoseInvalid(problem::PoseValidationError),
+}
+
+fn main() -> Result<(), Error> {
+    pretty_env_logger::init();
+    let cli_args = CliArgs::from_args();
+    log::info!("program starts as: {:?}", cli_args);
+
+    let problem = problem::Problem::from_file(&cli_args.common.problem_file)
+        .map_err(Error::ProblemLoad)?;
+    let pose = problem::Pose::from_file(&cli_args.common.pose_file)
+        .map_err(Error::PoseLoad)?;
+    let dislikes = problem.score_pose(&pose)
+        .map_err(Error::PoseInvalid)?;
+    let contributions = problem.dislikes_contributions(&pose.vertices);
+    let unreachable = problem.unreachable_hole_vertices();
+
+    if cli_args.json {
+        println!("{}", format_json(dislikes, &contributions, &unreachable));
+    } else {
+        print!("{}", format_table(dislikes, &contributions, &unreachable));
+    }
+    Ok(())
+}
+
+/// Hole vertices with their dislikes and share of the total, as returned by `Problem::dislikes_contributions`,
+/// hole vertices no figure vertex can reach (see `Problem::unreachable_hole_vertices`) are marked with `*`.
+fn format_table(dislikes: i64, contributions: &[(problem::Point, i64)], unreachable: &[problem::Point]) -> String {
+    let mut table = String::new();
+    table.push_str("|----------------------|------------|--------|\n");
+    table.push_str("| Hole vertex          | Dislikes   | Share  |\n");
+    table.push_str("|----------------------|------------|--------|\n");
+    for &(hole_vertex, contribution) in contributions {
+        let share = if dislikes == 0 { 0.0 } else { contribution as f64 * 100.0 / dislikes as f64 };
+        table.push_str(&format!(
+            "| {:<20} | {:>10} | {:>5.1}% |\n",
+            format!("({}, {}){}", hole_vertex.0, hole_vertex.1, if unreachable.contains(&hole_vertex) { " *" } else { "" }),
+            contribution,
+            share,
+        ));
+    }
+    table.push_str("|----------------------|------------|--------|\n");
+    if !unreachable.is_empty() {
+        table.push_str(&format!("* unreachable by the figure: {} hole vertices, zero dislikes is impossible\n", unreachable.len()));
+    }
+    table.push_str(&format!("total dislikes: {}\n", dislikes));
+    table
+}
+
+fn format_json(dislikes: i64, contributions: &[(problem::Point, i64)], unreachable: &[problem::Point]) -> String {
+    let contributions: Vec<_> = contributions
+        .iter()
+        .map(|&(hole_vertex, contribution)| serde_json::json!({
+            "hole_vertex": [hole_vertex.0, hole_vertex.1],
+            "dislikes": contribution,
+        }))
+        .collect();
+    serde_json::json!({
+        "dislikes": dislikes,
+        "contributions": contributions,
+        "unreachable_hole_vertices": unreachable.iter()
+            .map(|hole_vertex| [hole_vertex.0, hole_vertex.1])
+            .collect::<Vec<_>>(),
+    }).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn contributions_sum_to_dislikes() {
+        let problem_data = r#"{"bonuses":[],"hole":[[0,0],[20,0],[20,20],[0,20]],"epsilon":150000,"figure":{"edges":[[0,1],[1,2],[2,0]],"vertices":[[0,0],[10,0],[0,10]]}}"#;
+        let problem: problem::Problem = serde_json::from_str(problem_data).unwrap();
+        let pose = problem::Pose { vertices: vec![problem::Point(1, 1), problem::Point(11, 1), problem::Point(1, 11)], bonuses: None, };
+        let dislikes = problem.score_pose(&pose).unwrap();
+        let contributions = problem.dislikes_contributions(&pose.vertices);
+
+        assert_eq!(contributions.len(), problem.hole.len());
+        assert_eq!(contributions.iter().map(|&(_, contribution)| contribution).sum::<i64>(), dislikes);
+        assert!(contributions.windows(2).all(|pair| pair[0].1 >= pair[1].1));
+        // the far corner gives the most
+        assert_eq!(contributions[0], (problem::Point(20, 20), 442));
+
+        let table = format_table(dislikes, &contributions, &[]);
+        assert_eq!(table.matches("| (").count(), problem.hole.len());
+        assert!(table.ends_with(&format!("total dislikes: {}\n", dislikes)));
+        let table = format_table(dislikes, &contributions, &[problem::Point(20, 20)]);
+        assert!(table.contains("| (20, 20) *"));
+        assert!(table.contains("unreachable by the figure: 1 hole vertices"));
+
+        let json: serde_json::Value = serde_json::from_str(&format_json(dislikes, &contributions, &[problem::Point(20, 20)])).unwrap();
+        assert_eq!(json["unreachable_hole_vertices"], serde_json::json!([[20, 20]]));
+        assert_eq!(json["dislikes"], dislikes);
+        let json_sum: i64 = json["contributions"].as_array().unwrap().iter().map(|entry| entry["dislikes"].as_i64().unwrap()).sum();
+        assert_eq!(json_sum, dislikes);
+    }
+}
@@ -0,0 +1,106 @@
+use std::{
+    path::PathBuf,
+};
+
+use structopt::{
+    StructOpt,
+};
+
+use common::{
+    pose,
+    problem,
+};
+
+#[derive(Clone, StructOpt, Debug)]
+pub struct CliArgs {
+    /// pose collection to import: json object keyed by task id or csv with "task_id,x0,y0,x1,y1,..." lines
+    #[structopt(long = "collection")]
+    pub collection: PathBuf,
+    /// tasks directory with problems to validate imported poses against
+    #[structopt(long = "tasks-directory", default_value = "./tasks")]
+    pub tasks_directory: PathBuf,
+    /// poses directory to write improving poses into
+    #[structopt(long = "poses-directory", default_value = "./poses")]
+    pub poses_directory: PathBuf,
+    /// try to fix invalid imported poses listing vertices in another order than the problem figure
+    #[structopt(long = "reorder")]
+    pub reorder: bool,
+}
+
+#[derive(Debug)]
+pub enum Error {
+    Import(pose::ImportError),
+    ProblemLoad { problem_file: PathBuf, error: problem::FromFileError, },
+    PoseWrite { pose_file: PathBuf, error: problem::WriteFileError, },
+}
+
+fn main() -> Result<(), Error> {
+    pretty_env_logger::init();
+    let cli_args = CliArgs::from_args();
+    log::info!("program starts as: {:?}", cli_args);
+
+    // invalid poses are kept on import when they may be rescued by reordering, they are checked below anyway
+    let validate_against = if cli_args.reorder {
+        None
+    } else {
+        Some(cli_args.tasks_directory.as_path())
+    };
+    let poses = pose::import_collection(&cli_args.collection, validate_against)
+        .map_err(Error::Import)?;
+    let mut task_ids: Vec<_> = poses.keys().cloned().collect();
+    task_ids.sort_unstable();
+
+    let mut improved = 0;
+    for task_id in task_ids {
+        let problem_file = cli_args.tasks_directory.join(format!("{}.problem", task_id));
+        let problem = match problem::Problem::from_file(&problem_file) {
+            Ok(problem) =>
+                problem,
+            Err(error) =>
+                return Err(Error::ProblemLoad { problem_file, error, }),
+        };
+        let mut imported_pose = poses[&task_id].clone();
+        if cli_args.reorder && problem.score_pose(&imported_pose).is_err() {
+            match imported_pose.reorder_to_match(&problem) {
+                Ok(()) =>
+                    log::info!("imported pose for task {} is reordered to match the figure", task_id),
+                Err(error) =>
+                    log::warn!("failed to reorder imported pose for task {}: {:?}", task_id, error),
+            }
+        }
+        let pose_file = cli_args.poses_directory.join(format!("{}.pose", task_id));
+        let current_pose = problem::Pose::from_file(&pose_file).ok();
+        if current_pose.as_ref().map(problem::Pose::fingerprint) == Some(imported_pose.fingerprint()) {
+            println!("task {}: imported pose is already in {:?}", task_id, pose_file);
+            continue;
+        }
+        let imported_score = match problem.score_pose(&imported_pose) {
+            Ok(score) =>
+                score,
+            Err(error) => {
+                log::warn!("skipping imported pose for task {}: {:?}", task_id, error);
+                continue;
+            },
+        };
+
+        let current_score = current_pose
+            .and_then(|current_pose| problem.score_pose(&current_pose).ok());
+        let improves = match current_score {
+            None =>
+                true,
+            Some(current_score) =>
+                imported_score < current_score,
+        };
+        if improves {
+            if let Err(error) = imported_pose.write_to_file(&pose_file) {
+                return Err(Error::PoseWrite { pose_file, error, });
+            }
+            improved += 1;
+            println!("task {}: {:?} -> {}, written to {:?}", task_id, current_score, imported_score, pose_file);
+        } else {
+            println!("task {}: imported {} does not improve {:?}", task_id, imported_score, current_score);
+        }
+    }
+    println!("{} of {} imported poses improved", improved, poses.len());
+    Ok(())
+}
@@ -0,0 +1,209 @@
+use std::{
+    fs,
+    io,
+    ffi::OsStr,
+    path::{
+        Path,
+        PathBuf,
+    },
+    collections::BTreeMap,
+};
+
+use structopt::{
+    StructOpt,
+};
+
+use common::{
+    problem,
+};
+
+#[derive(Clone, StructOpt, Debug)]
+pub struct CliArgs {
+    /// tasks directory with problems to score poses against
+    #[structopt(long = "tasks-directory", default_value = "./tasks")]
+    pub tasks_directory: PathBuf,
+    /// poses directory to re-score
+    #[structopt(long = "poses-directory", default_value = "./poses")]
+    pub poses_directory: PathBuf,
+    /// move invalid poses out of the poses directory
+    #[structopt(long = "quarantine")]
+    pub quarantine: bool,
+    /// directory invalid poses are moved into with --quarantine
+    #[structopt(long = "quarantine-directory", default_value = "./poses/invalid")]
+    pub quarantine_directory: PathBuf,
+    /// only report which poses would be moved with --quarantine
+    #[structopt(long = "dry-run", requires = "quarantine")]
+    pub dry_run: bool,
+}
+
+#[derive(Debug)]
+pub enum Error {
+    PosesDirectoryRead(io::Error),
+    QuarantineDirectoryCreate { quarantine_directory: PathBuf, error: io::Error, },
+    PoseMove { pose_file: PathBuf, target_file: PathBuf, error: io::Error, },
+}
+
+#[derive(Debug)]
+pub enum Verdict {
+    Valid { dislikes: i64, },
+    Invalid(problem::PoseValidationError),
+    Unreadable(problem::FromFileError),
+    /// the problem of the task cannot be loaded, so the pose is not checked
+    NoProblem { problem_file: PathBuf, error: problem::FromFileError, },
+}
+
+#[derive(Debug)]
+pub struct Rescored {
+    pub task_id: u64,
+    pub pose_file: PathBuf,
+    pub verdict: Verdict,
+}
+
+fn main() -> Result<(), Error> {
+    pretty_env_logger::init();
+    let cli_args = CliArgs::from_args();
+    log::info!("program starts as: {:?}", cli_args);
+
+    let rescored = rescore_poses(&cli_args.tasks_directory, &cli_args.poses_directory)?;
+    let mut invalid = Vec::new();
+    let mut unchecked = 0;
+    for item in &rescored {
+        match &item.verdict {
+            Verdict::Valid { dislikes, } =>
+                log::debug!("task {}: {} dislikes", item.task_id, dislikes),
+            Verdict::Invalid(error) => {
+                println!("task {}: pose {:?} is invalid: {:?}", item.task_id, item.pose_file, error);
+                invalid.push(item);
+            },
+            Verdict::Unreadable(error) => {
+                println!("task {}: pose {:?} is unreadable: {:?}", item.task_id, item.pose_file, error);
+                invalid.push(item);
+            },
+            Verdict::NoProblem { problem_file, error, } => {
+                println!("task {}: pose {:?} is not checked, problem {:?} cannot be loaded: {:?}", item.task_id, item.pose_file, problem_file, error);
+                unchecked += 1;
+            },
+        }
+    }
+    println!("{} of {} poses are invalid, {} are not checked", invalid.len(), rescored.len(), unchecked);
+
+    if cli_args.quarantine {
+        for item in invalid {
+            let target_file = quarantine_target(&item.pose_file, &cli_args.quarantine_directory);
+            if cli_args.dry_run {
+                println!("would move {:?} to {:?}", item.pose_file, target_file);
+            } else {
+                quarantine_pose(&item.pose_file, &cli_args.quarantine_directory)?;
+                println!("moved {:?} to {:?}", item.pose_file, target_file);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Scores every `<task id>.pose` in `poses_directory` against its problem from `tasks_directory`, sorted by task id.
+/// A pose without a loadable problem gets `Verdict::NoProblem` and does not stop the run.
+pub fn rescore_poses(tasks_directory: &Path, poses_directory: &Path) -> Result<Vec<Rescored>, Error> {
+    let mut pose_files = BTreeMap::new();
+    for entry in fs::read_dir(poses_directory).map_err(Error::PosesDirectoryRead)? {
+        let pose_file = entry.map_err(Error::PosesDirectoryRead)?.path();
+        if pose_file.extension() != Some(OsStr::new("pose")) {
+            continue;
+        }
+        match pose_file.file_stem().and_then(OsStr::to_str).and_then(|stem| stem.parse().ok()) {
+            Some(task_id) => {
+                pose_files.insert(task_id, pose_file);
+            },
+            None =>
+                log::warn!("skipping pose file with non numeric name: {:?}", pose_file),
+        }
+    }
+
+    let mut rescored = Vec::new();
+    for (task_id, pose_file) in pose_files {
+        let problem_file = tasks_directory.join(format!("{}.problem", task_id));
+        let problem = match problem::Problem::from_file(&problem_file) {
+            Ok(problem) =>
+                problem,
+            Err(error) => {
+                rescored.push(Rescored { task_id, pose_file, verdict: Verdict::NoProblem { problem_file, error, }, });
+                continue;
+            },
+        };
+        let verdict = match problem::Pose::from_file(&pose_file) {
+            Ok(pose) =>
+                match problem.score_pose(&pose) {
+                    Ok(dislikes) =>
+                        Verdict::Valid { dislikes, },
+                    Err(error) =>
+                        Verdict::Invalid(error),
+                },
+            Err(error) =>
+                Verdict::Unreadable(error),
+        };
+        rescored.push(Rescored { task_id, pose_file, verdict, });
+    }
+    Ok(rescored)
+}
+
+fn quarantine_target(pose_file: &Path, quarantine_directory: &Path) -> PathBuf {
+    quarantine_directory.join(pose_file.file_name().unwrap_or_else(|| OsStr::new("unnamed.pose")))
+}
+
+/// Moves `pose_file` into `quarantine_directory` under the same name, replacing a pose quarantined before.
+pub fn quarantine_pose(pose_file: &Path, quarantine_directory: &Path) -> Result<PathBuf, Error> {
+    fs::create_dir_all(quarantine_directory)
+        .map_err(|error| Error::QuarantineDirectoryCreate { quarantine_directory: quarantine_directory.to_path_buf(), error, })?;
+    let target_file = quarantine_target(pose_file, quarantine_directory);
+    fs::rename(pose_file, &target_file)
+        .map_err(|error| Error::PoseMove { pose_file: pose_file.to_path_buf(), target_file: target_file.clone(), error, })?;
+    Ok(target_file)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn invalid_pose_is_quarantined() {
+        let temp_directory = tempfile::tempdir().unwrap();
+        let directory = temp_directory.path();
+        let tasks_directory = directory.join("tasks");
+        let poses_directory = directory.join("poses");
+        let quarantine_directory = poses_directory.join("invalid");
+        fs::create_dir_all(&tasks_directory).unwrap();
+        fs::create_dir_all(&poses_directory).unwrap();
+        let problem_data = r#"{"bonuses":[],"hole":[[20,0],[40,20],[20,40],[0,20]],"epsilon":2494,"figure":{"edges":[[0,1],[0,2],[1,3],[2,3]],"vertices":[[15,21],[34,0],[0,45],[19,24]]}}"#;
+        fs::write(tasks_directory.join("3.problem"), problem_data).unwrap();
+        fs::write(tasks_directory.join("5.problem"), problem_data).unwrap();
+        fs::write(poses_directory.join("3.pose"), r#"{"vertices":[[20,0],[40,20],[0,20],[20,40]]}"#).unwrap();
+        fs::write(poses_directory.join("5.pose"), r#"{"vertices":[[0,0],[40,20],[0,20],[20,40]]}"#).unwrap();
+        // no problem for this one, the rest is still checked
+        fs::write(poses_directory.join("4.pose"), r#"{"vertices":[[20,0],[40,20],[0,20],[20,40]]}"#).unwrap();
+
+        let rescored = rescore_poses(&tasks_directory, &poses_directory).unwrap();
+        assert_eq!(rescored.iter().map(|item| item.task_id).collect::<Vec<_>>(), vec![3, 4, 5]);
+        assert!(matches!(rescored[0].verdict, Verdict::Valid { dislikes: 0, }));
+        assert!(matches!(rescored[1].verdict, Verdict::NoProblem { .. }));
+        assert!(matches!(rescored[2].verdict, Verdict::Invalid(..)));
+
+        let target_file = quarantine_pose(&rescored[2].pose_file, &quarantine_directory).unwrap();
+        let valid_left = poses_directory.join("3.pose").exists();
+        let invalid_left = poses_directory.join("5.pose").exists();
+        let quarantined = target_file == quarantine_directory.join("5.pose") && target_file.exists();
+        // the quarantine directory is not a pose itself, so a second run finds only the valid pose
+        let rescored_again = rescore_poses(&tasks_directory, &poses_directory).unwrap();
+
+        assert!(valid_left);
+        assert!(!invalid_left);
+        assert!(quarantined);
+        assert_eq!(rescored_again.iter().map(|item| item.task_id).collect::<Vec<_>>(), vec![3, 4]);
+    }
+
+    #[test]
+    fn dry_run_requires_quarantine() {
+        assert!(CliArgs::from_iter_safe(&["rescore_poses", "--dry-run"]).is_err());
+        let cli_args = CliArgs::from_iter_safe(&["rescore_poses", "--quarantine", "--dry-run"]).unwrap();
+        assert!(cli_args.quarantine && cli_args.dry_run);
+    }
+}
@@ -0,0 +1,156 @@
+use std::{
+    fs,
+    io,
+    time,
+    path::PathBuf,
+};
+
+use structopt::{
+    StructOpt,
+};
+
+use common::{
+    standings::{
+        self,
+        Standing,
+    },
+};
+
+#[derive(Clone, StructOpt, Debug)]
+pub struct CliArgs {
+    /// api token for poses.live authorization
+    #[structopt(long = "api-token", default_value = "29a3adf2-b0d3-4166-8891-9c990df11546")]
+    pub api_token: String,
+    /// problems page url
+    #[structopt(long = "problems-url", default_value = "https://poses.live/problems")]
+    pub problems_url: String,
+    /// cached problems page
+    #[structopt(long = "cache-file", default_value = "./standings.cache.html")]
+    pub cache_file: PathBuf,
+    /// cached problems page time to live in seconds
+    #[structopt(long = "cache-ttl-s", default_value = "600")]
+    pub cache_ttl_s: u64,
+}
+
+#[derive(Debug)]
+pub enum Error {
+    CacheRead { cache_file: PathBuf, error: io::Error, },
+    CacheWrite { cache_file: PathBuf, error: io::Error, },
+    WebClientHeader(reqwest::header::InvalidHeaderValue),
+    WebClientBuilder(reqwest::Error),
+    WebClientSend(reqwest::Error),
+    WebClientBody(reqwest::Error),
+    Unauthorized { url: String, status: reqwest::StatusCode, },
+    UnexpectedStatus { url: String, status: reqwest::StatusCode, },
+    NoStandingsFound { url: String, },
+}
+
+fn main() -> Result<(), Error> {
+    pretty_env_logger::init();
+    let cli_args = CliArgs::from_args();
+    log::info!("program starts as: {:?}", cli_args);
+
+    let page = load_problems_page(&cli_args)?;
+    let standings = standings::parse_standings(&page);
+    if standings.is_empty() {
+        return Err(Error::NoStandingsFound { url: cli_args.problems_url.clone(), });
+    }
+
+    print!("{}", format_table(standings));
+    Ok(())
+}
+
+fn load_problems_page(cli_args: &CliArgs) -> Result<String, Error> {
+    let is_cache_fresh = fs::metadata(&cli_args.cache_file)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+        .and_then(|modified| modified.elapsed().ok())
+        .map_or(false, |elapsed| elapsed < time::Duration::from_secs(cli_args.cache_ttl_s));
+    if is_cache_fresh {
+        log::info!("using cached problems page {:?}", cli_args.cache_file);
+        return fs::read_to_string(&cli_args.cache_file)
+            .map_err(|error| Error::CacheRead { cache_file: cli_args.cache_file.clone(), error, });
+    }
+
+    let mut headers = reqwest::header::HeaderMap::new();
+    let auth_value = reqwest::header::HeaderValue::from_str(&format!("Bearer {}", cli_args.api_token))
+        .map_err(Error::WebClientHeader)?;
+    headers.insert("Authorization", auth_value);
+
+    log::info!("fetching problems page from {:?}", cli_args.problems_url);
+    let response = reqwest::blocking::Client::builder()
+        .default_headers(headers)
+        .build().map_err(Error::WebClientBuilder)?
+        .get(&cli_args.problems_url)
+        .send().map_err(Error::WebClientSend)?;
+
+    let status = response.status();
+    if status == reqwest::StatusCode::UNAUTHORIZED || status == reqwest::StatusCode::FORBIDDEN {
+        log::error!("poses.live rejected the api token (status {}), check --api-token", status);
+        return Err(Error::Unauthorized { url: cli_args.problems_url.clone(), status, });
+    }
+    if !status.is_success() {
+        return Err(Error::UnexpectedStatus { url: cli_args.problems_url.clone(), status, });
+    }
+
+    let page = response.text()
+        .map_err(Error::WebClientBody)?;
+    fs::write(&cli_args.cache_file, &page)
+        .map_err(|error| Error::CacheWrite { cache_file: cli_args.cache_file.clone(), error, })?;
+    Ok(page)
+}
+
+/// Formats standings sorted by the largest improvable gap first, unsolved tasks on top.
+fn format_table(mut standings: Vec<Standing>) -> String {
+    standings.sort_by(|a, b| {
+        match (a.gap(), b.gap()) {
+            (None, None) => a.task_id.cmp(&b.task_id),
+            (None, Some(..)) => std::cmp::Ordering::Less,
+            (Some(..), None) => std::cmp::Ordering::Greater,
+            (Some(gap_a), Some(gap_b)) => gap_b.cmp(&gap_a).then(a.task_id.cmp(&b.task_id)),
+        }
+    });
+
+    let show = |value: Option<i64>| value.map_or("-".to_string(), |value| value.to_string());
+    let mut table = String::new();
+    table.push_str("|----------|------------|------------|------------|\n");
+    table.push_str("| Task     | Our        | Best       | Gap        |\n");
+    table.push_str("|----------|------------|------------|------------|\n");
+    for standing in &standings {
+        table.push_str(&format!(
+            "| {:>8} | {:>10} | {:>10} | {:>10} |\n",
+            standing.task_id,
+            show(standing.our_dislikes),
+            show(standing.best_dislikes),
+            show(standing.gap()),
+        ));
+    }
+    table.push_str("|----------|------------|------------|------------|\n");
+    table
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn table_sorted_by_gap() {
+        let table = format_table(vec![
+            Standing { task_id: 1, our_dislikes: Some(25), best_dislikes: Some(0), },
+            Standing { task_id: 2, our_dislikes: Some(1400), best_dislikes: Some(1344), },
+            Standing { task_id: 3, our_dislikes: Some(0), best_dislikes: Some(0), },
+            Standing { task_id: 4, our_dislikes: None, best_dislikes: Some(7), },
+        ]);
+        assert_eq!(
+            table,
+            "|----------|------------|------------|------------|\n\
+             | Task     | Our        | Best       | Gap        |\n\
+             |----------|------------|------------|------------|\n\
+             |        4 |          - |          7 |          - |\n\
+             |        2 |       1400 |       1344 |         56 |\n\
+             |        1 |         25 |          0 |         25 |\n\
+             |        3 |          0 |          0 |          0 |\n\
+             |----------|------------|------------|------------|\n",
+        );
+    }
+}
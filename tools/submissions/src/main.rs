@@ -0,0 +1,114 @@
+use std::{
+    collections::{
+        BTreeMap,
+        HashSet,
+    },
+    path::PathBuf,
+};
+
+use structopt::{
+    StructOpt,
+};
+
+use common::{
+    submission,
+};
+
+#[derive(Clone, StructOpt, Debug)]
+pub struct CliArgs {
+    /// submissions log file written by autonomous solver
+    #[structopt(long = "submissions-log", default_value = "./submissions.jsonl")]
+    pub submissions_log: PathBuf,
+}
+
+#[derive(Debug)]
+pub enum Error {
+    SubmissionsLoad(submission::LoadError),
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct TaskSummary {
+    pub task_id: String,
+    pub submissions: usize,
+    pub unique_poses: usize,
+    pub best_dislikes: i64,
+    pub last_timestamp: u64,
+}
+
+fn main() -> Result<(), Error> {
+    pretty_env_logger::init();
+    let cli_args = CliArgs::from_args();
+    log::info!("program starts as: {:?}", cli_args);
+
+    let submissions = submission::load(&cli_args.submissions_log)
+        .map_err(Error::SubmissionsLoad)?;
+    print!("{}", format_table(&summarize(&submissions)));
+    Ok(())
+}
+
+/// Groups submissions by task, ordered by numeric task id when possible.
+fn summarize(submissions: &[submission::Submission]) -> Vec<TaskSummary> {
+    let mut by_task: BTreeMap<(u64, String), (TaskSummary, HashSet<u64>)> = BTreeMap::new();
+    for submission in submissions {
+        let key = (submission.task_id.parse().unwrap_or(u64::MAX), submission.task_id.clone());
+        let (summary, pose_hashes) = by_task.entry(key)
+            .or_insert_with(|| {
+                let summary = TaskSummary {
+                    task_id: submission.task_id.clone(),
+                    submissions: 0,
+                    unique_poses: 0,
+                    best_dislikes: i64::MAX,
+                    last_timestamp: 0,
+                };
+                (summary, HashSet::new())
+            });
+        summary.submissions += 1;
+        summary.best_dislikes = summary.best_dislikes.min(submission.dislikes);
+        summary.last_timestamp = summary.last_timestamp.max(submission.timestamp);
+        pose_hashes.insert(submission.pose_hash);
+        summary.unique_poses = pose_hashes.len();
+    }
+    by_task.into_iter().map(|(_key, (summary, _pose_hashes))| summary).collect()
+}
+
+fn format_table(summaries: &[TaskSummary]) -> String {
+    let mut table = String::new();
+    table.push_str("|----------|-------------|--------------|------------|-------------|\n");
+    table.push_str("| Task     | Submissions | Unique poses | Best       | Last (unix) |\n");
+    table.push_str("|----------|-------------|--------------|------------|-------------|\n");
+    for summary in summaries {
+        table.push_str(&format!(
+            "| {:>8} | {:>11} | {:>12} | {:>10} | {:>11} |\n",
+            summary.task_id,
+            summary.submissions,
+            summary.unique_poses,
+            summary.best_dislikes,
+            summary.last_timestamp,
+        ));
+    }
+    table.push_str("|----------|-------------|--------------|------------|-------------|\n");
+    table
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn submission(task_id: &str, dislikes: i64, timestamp: u64, pose_hash: u64) -> submission::Submission {
+        submission::Submission { task_id: task_id.to_string(), dislikes, bonuses: vec![], timestamp, pose_hash, }
+    }
+
+    #[test]
+    fn summarize_by_task() {
+        let summaries = summarize(&[
+            submission("12", 100, 10, 1),
+            submission("2", 7, 11, 5),
+            submission("12", 25, 12, 2),
+            submission("12", 25, 13, 2),
+        ]);
+        assert_eq!(summaries, vec![
+            TaskSummary { task_id: "2".to_string(), submissions: 1, unique_poses: 1, best_dislikes: 7, last_timestamp: 11, },
+            TaskSummary { task_id: "12".to_string(), submissions: 3, unique_poses: 2, best_dislikes: 25, last_timestamp: 13, },
+        ]);
+    }
+}
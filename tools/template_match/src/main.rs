@@ -0,0 +1,118 @@
+use std::{
+    io,
+    fs,
+    ffi::OsStr,
+    path::PathBuf,
+    collections::BTreeMap,
+};
+
+use structopt::{
+    StructOpt,
+};
+
+use common::{
+    problem,
+};
+
+#[derive(Clone, StructOpt, Debug)]
+pub struct CliArgs {
+    /// tasks directory with problems to match
+    #[structopt(long = "tasks-directory", default_value = "./tasks")]
+    pub tasks_directory: PathBuf,
+}
+
+#[derive(Debug)]
+pub enum Error {
+    TasksDirectoryRead(io::Error),
+    ProblemLoad { problem_file: PathBuf, error: problem::FromFileError, },
+}
+
+fn main() -> Result<(), Error> {
+    pretty_env_logger::init();
+    let cli_args = CliArgs::from_args();
+    log::info!("program starts as: {:?}", cli_args);
+
+    let problems = load_problems(&cli_args)?;
+    let groups = group_by_signature(&problems);
+    if groups.is_empty() {
+        println!("no recurring figures found among {} problems", problems.len());
+    }
+    for group in groups {
+        let figure = &problems[&group[0]].figure;
+        let task_ids: Vec<_> = group.iter().map(|task_id| task_id.to_string()).collect();
+        println!(
+            "{} vertices, {} edges: tasks {}",
+            figure.vertices.len(),
+            figure.edges.len(),
+            task_ids.join(", "),
+        );
+    }
+    Ok(())
+}
+
+fn load_problems(cli_args: &CliArgs) -> Result<BTreeMap<u64, problem::Problem>, Error> {
+    let mut problems = BTreeMap::new();
+    for entry in fs::read_dir(&cli_args.tasks_directory).map_err(Error::TasksDirectoryRead)? {
+        let problem_file = entry.map_err(Error::TasksDirectoryRead)?.path();
+        if problem_file.extension() != Some(OsStr::new("problem")) {
+            continue;
+        }
+        let task_id = match problem_file.file_stem().and_then(OsStr::to_str).and_then(|stem| stem.parse().ok()) {
+            Some(task_id) =>
+                task_id,
+            None => {
+                log::warn!("skipping problem file with non numeric name: {:?}", problem_file);
+                continue;
+            },
+        };
+        let problem = match problem::Problem::from_file(&problem_file) {
+            Ok(problem) =>
+                problem,
+            Err(error) =>
+                return Err(Error::ProblemLoad { problem_file, error, }),
+        };
+        problems.insert(task_id, problem);
+    }
+    Ok(problems)
+}
+
+/// Task ids grouped by figure signature, only groups with more than one member, each sorted by task id.
+fn group_by_signature(problems: &BTreeMap<u64, problem::Problem>) -> Vec<Vec<u64>> {
+    let mut groups: BTreeMap<problem::FigureSignature, Vec<u64>> = BTreeMap::new();
+    for (&task_id, problem) in problems {
+        groups.entry(problem.figure.signature())
+            .or_default()
+            .push(task_id);
+    }
+    let mut groups: Vec<_> = groups.into_values()
+        .filter(|task_ids| task_ids.len() > 1)
+        .collect();
+    groups.sort();
+    groups
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn problem(vertices: Vec<problem::Point>) -> problem::Problem {
+        problem::Problem {
+            hole: vec![problem::Point(0, 0), problem::Point(100, 0), problem::Point(100, 100), problem::Point(0, 100)],
+            figure: problem::Figure {
+                edges: vec![problem::Edge(0, 1), problem::Edge(1, 2)],
+                vertices,
+            },
+            epsilon: 0,
+            bonuses: None,
+        }
+    }
+
+    #[test]
+    fn recurring_figures_grouped() {
+        let mut problems = BTreeMap::new();
+        problems.insert(3, problem(vec![problem::Point(0, 0), problem::Point(5, 0), problem::Point(5, 5)]));
+        problems.insert(7, problem(vec![problem::Point(0, 0), problem::Point(5, 0), problem::Point(5, 7)]));
+        problems.insert(11, problem(vec![problem::Point(10, 10), problem::Point(15, 10), problem::Point(15, 15)]));
+        assert_eq!(group_by_signature(&problems), vec![vec![3, 11]]);
+    }
+}
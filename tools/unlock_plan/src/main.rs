@@ -0,0 +1,111 @@
+use std::{
+    io,
+    fs,
+    ffi::OsStr,
+    path::PathBuf,
+    collections::BTreeMap,
+};
+
+use structopt::{
+    StructOpt,
+};
+
+use common::{
+    bonus,
+    problem,
+};
+
+#[derive(Clone, StructOpt, Debug)]
+pub struct CliArgs {
+    /// tasks directory with problems
+    #[structopt(long = "tasks-directory", default_value = "./tasks")]
+    pub tasks_directory: PathBuf,
+    /// task to unlock the bonus on
+    #[structopt(long = "target-task")]
+    pub target_task: u64,
+    /// bonus to unlock: globalist, wallhack, break_a_leg or superflex
+    #[structopt(long = "target-bonus", parse(try_from_str = parse_bonus_type))]
+    pub target_bonus: problem::ProblemBonusType,
+    /// task which can only be solved with a bonus, e.g. "12:wallhack" (may be repeated)
+    #[structopt(long = "requires", parse(try_from_str = parse_requirement))]
+    pub requires: Vec<(u64, problem::ProblemBonusType)>,
+}
+
+#[derive(Debug)]
+pub enum Error {
+    TasksDirectoryRead(io::Error),
+    ProblemLoad { problem_file: PathBuf, error: problem::FromFileError, },
+}
+
+fn main() -> Result<(), Error> {
+    pretty_env_logger::init();
+    let cli_args = CliArgs::from_args();
+    log::info!("program starts as: {:?}", cli_args);
+
+    let problems = load_problems(&cli_args)?;
+    let mut graph = bonus::BonusGraph::from_problems(&problems);
+    graph.requires.extend(cli_args.requires.iter().cloned());
+
+    let plan = bonus::plan_unlock(&graph, cli_args.target_task, cli_args.target_bonus);
+    match plan.len() {
+        0 =>
+            println!("{:?} cannot be unlocked on task {}", cli_args.target_bonus, cli_args.target_task),
+        1 =>
+            println!("{:?} on task {} is directly available from task {}", cli_args.target_bonus, cli_args.target_task, plan[0]),
+        _ => {
+            let tasks: Vec<_> = plan.iter().map(|task_id| task_id.to_string()).collect();
+            println!("{:?} on task {}: solve tasks {}", cli_args.target_bonus, cli_args.target_task, tasks.join(" -> "));
+        },
+    }
+    Ok(())
+}
+
+fn parse_bonus_type(value: &str) -> Result<problem::ProblemBonusType, String> {
+    match value.to_lowercase().as_str() {
+        "globalist" =>
+            Ok(problem::ProblemBonusType::Globalist),
+        "wallhack" =>
+            Ok(problem::ProblemBonusType::Wallhack),
+        "break_a_leg" =>
+            Ok(problem::ProblemBonusType::BreakALeg),
+        "superflex" =>
+            Ok(problem::ProblemBonusType::Superflex),
+        _ =>
+            Err(format!("unknown bonus type {:?}", value)),
+    }
+}
+
+fn parse_requirement(value: &str) -> Result<(u64, problem::ProblemBonusType), String> {
+    let mut parts = value.splitn(2, ':');
+    let task_id = parts.next()
+        .and_then(|task_id| task_id.parse().ok())
+        .ok_or_else(|| format!("expected task id in {:?}", value))?;
+    let bonus = parse_bonus_type(parts.next().unwrap_or(""))?;
+    Ok((task_id, bonus))
+}
+
+fn load_problems(cli_args: &CliArgs) -> Result<BTreeMap<u64, problem::Problem>, Error> {
+    let mut problems = BTreeMap::new();
+    for entry in fs::read_dir(&cli_args.tasks_directory).map_err(Error::TasksDirectoryRead)? {
+        let problem_file = entry.map_err(Error::TasksDirectoryRead)?.path();
+        if problem_file.extension() != Some(OsStr::new("problem")) {
+            continue;
+        }
+        let task_id = match problem_file.file_stem().and_then(OsStr::to_str).and_then(|stem| stem.parse().ok()) {
+            Some(task_id) =>
+                task_id,
+            None => {
+                log::warn!("skipping problem file with non numeric name: {:?}", problem_file);
+                continue;
+            },
+        };
+        let problem = match problem::Problem::from_file(&problem_file) {
+            Ok(problem) =>
+                problem,
+            Err(error) =>
+                return Err(Error::ProblemLoad { problem_file, error, }),
+        };
+        problems.insert(task_id, problem);
+    }
+    Ok(problems)
+}